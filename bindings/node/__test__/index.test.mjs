@@ -0,0 +1,43 @@
+import assert from 'node:assert/strict'
+import { createRequire } from 'node:module'
+import { test } from 'node:test'
+
+const ads = createRequire(import.meta.url)('../ads-txt.node')
+
+test('parses files leniently', () => {
+  const { adsTxt, errors } = ads.parse('SSP.com, 1, direct, abc\ncontact=ops@example.com\nbroken')
+  assert.deepEqual(adsTxt.records, [
+    { domain: 'SSP.com', publisherId: '1', relation: 'DIRECT', certAuthority: 'abc' },
+  ])
+  assert.deepEqual(adsTxt.variables, [{ name: 'contact', value: 'ops@example.com' }])
+  assert.deepEqual(errors, ['Invalid ads.txt line: broken'])
+  assert.throws(() => ads.parseStrict('broken'), /Invalid ads.txt line: broken/)
+})
+
+test('validates files', () => {
+  const [diagnostic] = ads.validate('ssp.com, 1, DIRECT\nssp.com, 1, DIRECT')
+  assert.deepEqual(diagnostic, {
+    line: 2,
+    rule: 'duplicate-record',
+    severity: 'WARNING',
+    message: 'Duplicate record: ssp.com, 1, DIRECT',
+  })
+  assert.equal(ads.validate('subdomain=a.example.com', { app: true })[0].rule, 'unknown-variable')
+
+  const policy = '[policy]\nforbidden-domains = ["ssp.com"]'
+  assert.equal(ads.validate('ssp.com, 1, DIRECT', { policy })[0].rule, 'forbidden-domain')
+  assert.throws(() => ads.validate('', { policy: '[policy]\nseats = []' }), /Unknown policy setting/)
+})
+
+test('diffs files', () => {
+  const diff = ads.diff('ssp.com, 1, DIRECT\ncontact=a', 'ssp.com, 1, RESELLER\nssp.com, 2, DIRECT')
+  assert.deepEqual(
+    diff.seats.map((s) => [s.change, s.record?.publisherId, s.new?.relation]),
+    [
+      ['changed', undefined, 'RESELLER'],
+      ['added', '2', undefined],
+    ],
+  )
+  assert.deepEqual(diff.removedVariables, [{ name: 'contact', value: 'a' }])
+  assert.deepEqual(diff.addedVariables, [])
+})
@@ -0,0 +1,191 @@
+//! Node.js bindings of parsing, validation and diffing through N-API. Field
+//! names are camelCased in JavaScript, e.g. `publisherId`.
+
+use napi::{Error, Result};
+use napi_derive::napi;
+use rs_ads_txt as ads;
+use rs_ads_txt::validate::Validator;
+
+#[napi(object)]
+pub struct Record {
+    pub domain: String,
+    pub publisher_id: String,
+    /// `DIRECT` or `RESELLER`
+    pub relation: String,
+    pub cert_authority: Option<String>,
+}
+
+#[napi(object)]
+pub struct Variable {
+    pub name: String,
+    pub value: String,
+}
+
+#[napi(object)]
+pub struct AdsTxt {
+    pub records: Vec<Record>,
+    pub variables: Vec<Variable>,
+}
+
+#[napi(object)]
+pub struct ParseResult {
+    pub ads_txt: AdsTxt,
+    /// Messages of the lines which could not be parsed
+    pub errors: Vec<String>,
+}
+
+#[napi(object)]
+pub struct Diagnostic {
+    /// 1-based line number, 0 for diagnostics of the whole file
+    pub line: u32,
+    pub rule: String,
+    /// `ERROR`, `WARNING` or `INFO`
+    pub severity: String,
+    pub message: String,
+}
+
+#[napi(object)]
+pub struct ValidateOptions {
+    /// Validates as app-ads.txt, which has no SUBDOMAIN
+    pub app: Option<bool>,
+    /// TOML policy, as taken by the CLI's `validate --policy`
+    pub policy: Option<String>,
+}
+
+#[napi(object)]
+pub struct SeatChange {
+    /// `added`, `removed` or `changed`
+    pub change: String,
+    /// Record of an added or removed seat
+    pub record: Option<Record>,
+    /// Records of a changed seat
+    pub old: Option<Record>,
+    pub new: Option<Record>,
+}
+
+#[napi(object)]
+pub struct Diff {
+    pub seats: Vec<SeatChange>,
+    pub added_variables: Vec<Variable>,
+    pub removed_variables: Vec<Variable>,
+}
+
+impl From<&ads::DataRecord> for Record {
+    fn from(record: &ads::DataRecord) -> Self {
+        Record {
+            domain: record.domain().to_string(),
+            publisher_id: record.publisher_id().to_string(),
+            relation: record.acc_relation().as_str().to_string(),
+            cert_authority: record.cert_authority().map(str::to_string),
+        }
+    }
+}
+
+impl From<&ads::Variable> for Variable {
+    fn from(variable: &ads::Variable) -> Self {
+        Variable {
+            name: variable.name.clone(),
+            value: variable.value.clone(),
+        }
+    }
+}
+
+impl From<&ads::AdsTxt> for AdsTxt {
+    fn from(ads_txt: &ads::AdsTxt) -> Self {
+        AdsTxt {
+            records: ads_txt.records.iter().map(Record::from).collect(),
+            variables: ads_txt.variables.iter().map(Variable::from).collect(),
+        }
+    }
+}
+
+fn variables(variables: &[ads::Variable]) -> Vec<Variable> {
+    variables.iter().map(Variable::from).collect()
+}
+
+/// Parses a file leniently, collecting the lines which are not records or
+/// variables
+#[napi]
+pub fn parse(text: String) -> ParseResult {
+    let (ads_txt, errors) = ads::AdsTxt::parse_lenient(&text);
+    ParseResult {
+        ads_txt: AdsTxt::from(&ads_txt),
+        errors: errors.iter().map(|e| e.message().to_string()).collect(),
+    }
+}
+
+/// Parses a file, throwing on the first invalid line
+#[napi]
+pub fn parse_strict(text: String) -> Result<AdsTxt> {
+    ads::AdsTxt::parse(&text)
+        .map(|ads_txt| AdsTxt::from(&ads_txt))
+        .map_err(|e| Error::from_reason(e.message()))
+}
+
+/// Diagnostics of a file in line order, throwing if the policy is invalid
+#[napi]
+pub fn validate(text: String, options: Option<ValidateOptions>) -> Result<Vec<Diagnostic>> {
+    let (app, policy) = match options {
+        Some(options) => (options.app.unwrap_or(false), options.policy),
+        None => (false, None),
+    };
+    let validator = match policy {
+        Some(policy) => {
+            Validator::from_policy_str(&policy).map_err(|e| Error::from_reason(e.message()))?
+        }
+        None => Validator::new(),
+    };
+    let file = if app {
+        ads::FileKind::AppAdsTxt
+    } else {
+        ads::FileKind::AdsTxt
+    };
+
+    Ok(validator
+        .with_file_kind(file)
+        .validate(&text)
+        .iter()
+        .map(|d| Diagnostic {
+            line: d.line as u32,
+            rule: d.rule.as_str().to_string(),
+            severity: d.severity.as_str().to_string(),
+            message: d.message.clone(),
+        })
+        .collect())
+}
+
+/// Seats and variables added, removed or changed between two files, parsed
+/// leniently
+#[napi]
+pub fn diff(old: String, new: String) -> Diff {
+    use ads::diff::SeatChange as Change;
+
+    let (old, _) = ads::AdsTxt::parse_lenient(&old);
+    let (new, _) = ads::AdsTxt::parse_lenient(&new);
+    let diff = ads::diff::AdsTxtDiff::between(&old, &new);
+
+    let seat = |change: &str, record: Option<&ads::DataRecord>, old, new| SeatChange {
+        change: change.to_string(),
+        record: record.map(Record::from),
+        old,
+        new,
+    };
+    Diff {
+        seats: diff
+            .seats
+            .iter()
+            .map(|change| match change {
+                Change::Added(record) => seat("added", Some(record), None, None),
+                Change::Removed(record) => seat("removed", Some(record), None, None),
+                Change::Changed { old, new } => seat(
+                    "changed",
+                    None,
+                    Some(Record::from(old)),
+                    Some(Record::from(new)),
+                ),
+            })
+            .collect(),
+        added_variables: variables(&diff.added_variables),
+        removed_variables: variables(&diff.removed_variables),
+    }
+}
@@ -0,0 +1,13 @@
+//! Lenient parsing accepts any text, and what it keeps parses strictly
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rs_ads_txt::AdsTxt;
+
+fuzz_target!(|text: &str| {
+    let (ads_txt, _) = AdsTxt::parse_lenient(text);
+    let mut written = String::new();
+    ads_txt.write_into(&mut written).unwrap();
+    assert!(AdsTxt::parse(&written).is_ok());
+});
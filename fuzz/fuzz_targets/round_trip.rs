@@ -0,0 +1,12 @@
+//! Files written by `AdsTxt::write_into` parse back unchanged
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rs_ads_txt::AdsTxt;
+
+fuzz_target!(|ads_txt: AdsTxt| {
+    let mut text = String::new();
+    ads_txt.write_into(&mut text).unwrap();
+    assert_eq!(AdsTxt::parse(&text).unwrap(), ads_txt);
+});
@@ -0,0 +1,255 @@
+//! Database of ad system domain aliases, mapping the domains an ad system is
+//! listed under in ads.txt files to one canonical domain, so that
+//! aggregation per ad system does not split across its aliases
+//!
+//! `AliasDatabase::builtin` holds the known ad systems. A database is
+//! updated with entries parsed from text of the same format, one ad system
+//! per line: canonical domain, name, then the aliases, comma separated.
+
+use crate::graph::normalize_domain;
+use crate::{ads_txt_error, AdsTxt, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+const BUILTIN_ALIASES: &str = include_str!("aliases.txt");
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AdSystem {
+    /// Domain the ad system is canonicalized to
+    pub domain: String,
+    pub name: String,
+    /// Other domains of the ad system
+    pub aliases: Vec<String>,
+}
+
+impl AdSystem {
+    pub fn new(domain: &str, name: &str, aliases: &[&str]) -> Self {
+        AdSystem {
+            domain: normalize_domain(domain),
+            name: name.trim().to_string(),
+            aliases: aliases.iter().map(|a| normalize_domain(a)).collect(),
+        }
+    }
+
+    /// Parses a line of the database, e.g. `google.com, Google, doubleclick.net`
+    pub fn parse(line: &str) -> Result<AdSystem> {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        match fields.as_slice() {
+            [domain, name, aliases @ ..] if !domain.is_empty() && !name.is_empty() => {
+                Ok(AdSystem::new(
+                    domain,
+                    name,
+                    &aliases
+                        .iter()
+                        .copied()
+                        .filter(|a| !a.is_empty())
+                        .collect::<Vec<_>>(),
+                ))
+            }
+            _ => ads_txt_error(&format!("Invalid ad system: {}", line)),
+        }
+    }
+}
+
+impl std::fmt::Display for AdSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, {}", self.domain, self.name)?;
+        for alias in &self.aliases {
+            write!(f, ", {}", alias)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AliasDatabase {
+    /// Ad systems by canonical domain
+    systems: BTreeMap<String, AdSystem>,
+    /// Canonical domain of every domain and alias
+    canonical: HashMap<String, String>,
+}
+
+impl AliasDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Database of the ad systems known to the crate
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_ALIASES).expect("built-in aliases are valid")
+    }
+
+    /// Parses a database, ignoring blank lines and `#` comments
+    pub fn parse(text: &str) -> Result<AliasDatabase> {
+        let mut database = AliasDatabase::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match AdSystem::parse(line) {
+                Ok(system) => {
+                    database.insert(system);
+                }
+                Err(e) => return ads_txt_error(&format!("line {}: {}", i + 1, e)),
+            }
+        }
+
+        Ok(database)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<AliasDatabase> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Adds an ad system, returning the one it replaces with the same
+    /// canonical domain. An alias claimed by another ad system moves to the
+    /// new one.
+    pub fn insert(&mut self, system: AdSystem) -> Option<AdSystem> {
+        let replaced = self.systems.remove(&system.domain);
+        if let Some(replaced) = &replaced {
+            for alias in &replaced.aliases {
+                self.canonical.remove(alias);
+            }
+        }
+
+        for domain in std::iter::once(&system.domain).chain(&system.aliases) {
+            if let Some(previous) = self.canonical.insert(domain.clone(), system.domain.clone()) {
+                if previous != system.domain {
+                    if let Some(other) = self.systems.get_mut(&previous) {
+                        other.aliases.retain(|a| a != domain);
+                    }
+                }
+            }
+        }
+        self.systems.insert(system.domain.clone(), system);
+
+        replaced
+    }
+
+    /// Updates the database with the ad systems of another, which replace
+    /// those with the same canonical domain
+    pub fn update(&mut self, other: AliasDatabase) {
+        for system in other.systems.into_values() {
+            self.insert(system);
+        }
+    }
+
+    /// Ad system of a domain, which may be a subdomain of one of its domains
+    pub fn get(&self, domain: &str) -> Option<&AdSystem> {
+        let domain = normalize_domain(domain);
+        let mut candidate = domain.as_str();
+        loop {
+            if let Some(canonical) = self.canonical.get(candidate) {
+                return self.systems.get(canonical);
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => candidate = parent,
+                _ => return None,
+            }
+        }
+    }
+
+    /// Canonical domain of the ad system of a domain, or the normalized
+    /// domain itself when it is not known
+    pub fn canonical_domain(&self, domain: &str) -> String {
+        match self.get(domain) {
+            Some(system) => system.domain.clone(),
+            None => normalize_domain(domain),
+        }
+    }
+
+    /// Copy of the file with the domains of its records canonicalized
+    pub fn canonicalize(&self, ads_txt: &AdsTxt) -> AdsTxt {
+        let mut canonicalized = ads_txt.clone();
+        for record in &mut canonicalized.records {
+            if let Some(system) = self.get(&record.domain) {
+                record.domain = system.domain.as_str().into();
+            }
+        }
+        canonicalized
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AdSystem> {
+        self.systems.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    /// Renders the database in the format read by `parse`
+    pub fn to_text(&self) -> String {
+        self.iter().map(|s| format!("{}\n", s)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalizing_ad_systems() {
+        let database = AliasDatabase::builtin();
+        assert!(database.len() > 10);
+        assert_eq!(database.get("DoubleClick.net").unwrap().name, "Google");
+        assert_eq!(
+            database.canonical_domain("pubads.g.doubleclick.net"),
+            "google.com"
+        );
+        assert_eq!(database.canonical_domain("www.google.com"), "google.com");
+        assert_eq!(
+            database.canonical_domain("Unknown-Exchange.com."),
+            "unknown-exchange.com"
+        );
+        assert!(database.get("net").is_none());
+
+        let ads_txt = AdsTxt::parse(
+            "googlesyndication.com, pub-1, DIRECT\nadnxs.com, 2, RESELLER\nother.com, 3, DIRECT",
+        )
+        .unwrap();
+        assert_eq!(
+            database.canonicalize(&ads_txt),
+            AdsTxt::parse(
+                "google.com, pub-1, DIRECT\nappnexus.com, 2, RESELLER\nother.com, 3, DIRECT"
+            )
+            .unwrap()
+        );
+
+        let mut corpus = crate::corpus::AdsTxtCorpus::new();
+        let at = std::time::SystemTime::UNIX_EPOCH;
+        corpus.insert_content(
+            "a.com",
+            "google.com, pub-1, DIRECT\ndoubleclick.net, 1, RESELLER",
+            at,
+        );
+        corpus.insert_content("b.com", "googlesyndication.com, pub-2, DIRECT", at);
+        let table = corpus.exchange_table_with_aliases(&database);
+        let google = table.get("google.com").unwrap();
+        assert_eq!((google.records, google.publishers), (3, 2));
+        assert!(table.get("doubleclick.net").is_none());
+
+        // Updates replace ad systems and move aliases between them
+        let mut database = AliasDatabase::parse("a.com, A, a.net, shared.com\n\nb.com, B").unwrap();
+        let update = AliasDatabase::parse("# update\nb.com, B, shared.com\nc.com, C").unwrap();
+        database.update(update);
+        assert_eq!(database.canonical_domain("shared.com"), "b.com");
+        assert_eq!(database.canonical_domain("a.net"), "a.com");
+        assert_eq!(
+            database.to_text(),
+            "a.com, A, a.net\nb.com, B, shared.com\nc.com, C\n"
+        );
+        assert_eq!(AliasDatabase::parse(&database.to_text()).unwrap().len(), 3);
+
+        assert_eq!(
+            AliasDatabase::parse("a.com, A\nb.com")
+                .unwrap_err()
+                .to_string(),
+            "line 2: Invalid ad system: b.com"
+        );
+    }
+}
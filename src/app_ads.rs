@@ -0,0 +1,191 @@
+//! app-ads.txt support: finding the developer website of a mobile or CTV app
+//! from its store listing, whose domain publishes the app-ads.txt file
+
+use crate::fetch::Url;
+
+/// App details which stores expose in `appstore:` meta tags of listing pages,
+/// as recommended by the app-ads.txt spec
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StoreListing {
+    pub developer_url: Option<String>,
+    pub bundle_id: Option<String>,
+    pub store_id: Option<String>,
+    /// Store name, e.g. `google_play`
+    pub store: Option<String>,
+}
+
+impl StoreListing {
+    /// Reads the `appstore:` meta tags of a listing page, ignoring the rest
+    pub fn parse(html: &str) -> StoreListing {
+        let mut listing = StoreListing::default();
+        let lowercase = html.to_ascii_lowercase();
+
+        let mut start = 0;
+        while let Some(i) = lowercase[start..].find("<meta") {
+            let tag_start = start + i + "<meta".len();
+            let tag_end = match lowercase[tag_start..].find('>') {
+                Some(end) => tag_start + end,
+                None => break,
+            };
+            start = tag_end;
+
+            let attributes = attributes(&html[tag_start..tag_end]);
+            let attribute = |name: &str| {
+                attributes
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.clone())
+            };
+            let (name, content) = match (
+                attribute("name").or_else(|| attribute("property")),
+                attribute("content"),
+            ) {
+                (Some(name), Some(content)) => (name.to_ascii_lowercase(), content),
+                _ => continue,
+            };
+
+            let field = match name.as_str() {
+                "appstore:developer_url" => &mut listing.developer_url,
+                "appstore:bundle_id" => &mut listing.bundle_id,
+                "appstore:store_id" => &mut listing.store_id,
+                "appstore:store" => &mut listing.store,
+                _ => continue,
+            };
+            if field.is_none() && !content.trim().is_empty() {
+                *field = Some(content.trim().to_string());
+            }
+        }
+
+        listing
+    }
+
+    /// Domain whose app-ads.txt file applies to the app
+    pub fn developer_domain(&self) -> Option<String> {
+        self.developer_url.as_deref().and_then(developer_domain)
+    }
+}
+
+/// Attributes of an HTML tag, with entities in values decoded
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    let mut rest = tag.trim_end_matches('/');
+
+    loop {
+        rest = rest.trim_start();
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        if name_end == 0 {
+            break;
+        }
+        let name = rest[..name_end].to_string();
+        rest = rest[name_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (text, remaining) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => match value[1..].find(quote) {
+                        Some(end) => (&value[1..end + 1], &value[end + 2..]),
+                        None => (&value[1..], ""),
+                    },
+                    _ => {
+                        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = remaining;
+                decode_entities(text)
+            }
+            None => String::new(),
+        };
+        attributes.push((name, value));
+    }
+
+    attributes
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Finds the domain of a developer website, without the `www.` and `m.`
+/// prefixes which the spec ignores. A port other than the scheme's default is
+/// kept.
+pub fn developer_domain(developer_url: &str) -> Option<String> {
+    let developer_url = developer_url.trim();
+    let url = if developer_url.contains("://") {
+        Url::parse(developer_url).ok()?
+    } else {
+        Url::parse(&format!("http://{}", developer_url)).ok()?
+    };
+
+    let mut host = url.host.trim_end_matches('.');
+    for prefix in &["www.", "m."] {
+        if let Some(stripped) = host.strip_prefix(prefix) {
+            host = stripped;
+            break;
+        }
+    }
+
+    let default_port = if url.scheme == "https" { 443 } else { 80 };
+    if url.port == default_port {
+        Some(host.to_string())
+    } else {
+        Some(format!("{}:{}", host, url.port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_store_listings() {
+        let listing = StoreListing::parse(
+            r#"<html><head>
+            <meta charset="utf-8">
+            <META NAME="appstore:developer_url" CONTENT="https://www.example.com/apps?a=1&amp;b=2">
+            <meta name='appstore:bundle_id' content='com.example.game' />
+            <meta property="appstore:store_id" content=1234567>
+            <meta name="description" content="A game">
+            </head></html>"#,
+        );
+
+        assert_eq!(
+            listing,
+            StoreListing {
+                developer_url: Some("https://www.example.com/apps?a=1&b=2".to_string()),
+                bundle_id: Some("com.example.game".to_string()),
+                store_id: Some("1234567".to_string()),
+                store: None,
+            }
+        );
+        assert_eq!(listing.developer_domain(), Some("example.com".to_string()));
+        assert_eq!(
+            StoreListing::parse("<p>no tags</p>"),
+            StoreListing::default()
+        );
+    }
+
+    #[test]
+    fn finding_developer_domains() {
+        assert_eq!(
+            developer_domain("https://m.Example.com/"),
+            Some("example.com".to_string())
+        );
+        assert_eq!(
+            developer_domain("games.example.com"),
+            Some("games.example.com".to_string())
+        );
+        assert_eq!(
+            developer_domain("http://127.0.0.1:8080/dev"),
+            Some("127.0.0.1:8080".to_string())
+        );
+        assert_eq!(developer_domain("ftp://example.com"), None);
+    }
+}
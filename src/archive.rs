@@ -0,0 +1,673 @@
+//! Bulk reading of crawl snapshots stored as tar or zip archives of ads.txt
+//! files, parsing one entry at a time
+//!
+//! Entries may themselves be gzipped. Entries whose path maps to no publisher
+//! domain, such as directories or a README, are skipped.
+
+use crate::corpus::{content_hash, CorpusEntry, CorpusMetadata};
+use crate::graph::normalize_domain;
+use crate::gzip::{crc32, is_gzip, GzipDecoder};
+use crate::{ads_txt_error, is_valid_domain, AdsTxt, AdsTxtError, Result};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Largest entry read from an archive, guarding against unbounded buffering
+const MAX_ENTRY_SIZE: u64 = 64 << 20;
+
+/// How the paths of archive entries map to publisher domains
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum DomainMapping {
+    /// File name without its `.txt` suffix, e.g. `crawl/example.com.txt`,
+    /// `example.com.app-ads.txt` or `example.com.txt.gz`. Files without the
+    /// suffix are skipped.
+    #[default]
+    FileStem,
+    /// Directory holding the file, e.g. `crawl/example.com/ads.txt`
+    ParentDirectory,
+    /// Path component at the index, e.g. 1 for `2024-05/example.com/ads.txt`
+    Component(usize),
+}
+
+impl DomainMapping {
+    /// Publisher domain of an entry path, normalized as in a corpus
+    pub fn domain(&self, path: &str) -> Option<String> {
+        let components: Vec<&str> = path
+            .split(['/', '\\'])
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect();
+
+        let domain = match self {
+            DomainMapping::FileStem => {
+                let name = components.last()?;
+                let name = name.strip_suffix(".gz").unwrap_or(name);
+                let name = name.strip_suffix(".txt")?;
+                name.strip_suffix(".app-ads")
+                    .or_else(|| name.strip_suffix(".ads"))
+                    .unwrap_or(name)
+            }
+            DomainMapping::ParentDirectory => components.iter().rev().nth(1)?,
+            DomainMapping::Component(i) => components.get(*i)?,
+        };
+
+        let domain = normalize_domain(domain);
+        if is_valid_domain(&domain) {
+            Some(domain)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file read from an archive, parsed leniently
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub domain: String,
+    pub ads_txt: AdsTxt,
+    pub errors: Vec<AdsTxtError>,
+    /// `content_hash` of the decompressed file
+    pub hash: u64,
+}
+
+impl ArchiveEntry {
+    /// Parses the raw content of an entry, unless its path maps to no domain
+    fn parse(path: &str, content: Vec<u8>, mapping: DomainMapping) -> Option<Result<Self>> {
+        let domain = mapping.domain(path)?;
+
+        let content = if is_gzip(&content) {
+            let mut decompressed = vec![];
+            let mut decoder = GzipDecoder::new(&content[..]).take(MAX_ENTRY_SIZE + 1);
+            if let Err(e) = decoder.read_to_end(&mut decompressed) {
+                return Some(ads_txt_error(&format!(
+                    "Error decompressing {}: {}",
+                    path, e
+                )));
+            }
+            if decompressed.len() as u64 > MAX_ENTRY_SIZE {
+                return Some(ads_txt_error(&format!(
+                    "Error decompressing {}: entry is too large",
+                    path
+                )));
+            }
+            decompressed
+        } else {
+            content
+        };
+        let text = String::from_utf8_lossy(&content);
+        let (ads_txt, errors) = AdsTxt::parse_lenient(&text);
+
+        Some(Ok(ArchiveEntry {
+            path: path.to_string(),
+            domain,
+            ads_txt,
+            errors,
+            hash: content_hash(&text),
+        }))
+    }
+
+    pub fn into_corpus_entry(self, fetched_at: SystemTime) -> CorpusEntry {
+        CorpusEntry::new(
+            &self.domain,
+            self.ads_txt,
+            CorpusMetadata::new(fetched_at, self.hash),
+        )
+    }
+}
+
+fn archive_error<T>(e: io::Error) -> Result<T> {
+    Err(AdsTxtError::with_source(
+        &format!("Error reading archive: {}", e),
+        e,
+    ))
+}
+
+fn read_entry<R: Read>(reader: R, size: u64) -> io::Result<Vec<u8>> {
+    if size > MAX_ENTRY_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("entry of {} bytes is too large", size),
+        ));
+    }
+    let mut content = Vec::with_capacity(size as usize);
+    reader.take(size).read_to_end(&mut content)?;
+    if (content.len() as u64) < size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(content)
+}
+
+/// Iterator over the files of a tar archive read from `R`. A malformed
+/// archive yields one error and ends the iteration.
+pub struct TarReader<R> {
+    reader: R,
+    mapping: DomainMapping,
+    done: bool,
+}
+
+impl<R: Read> TarReader<R> {
+    pub fn new(reader: R) -> Self {
+        TarReader {
+            reader,
+            mapping: DomainMapping::default(),
+            done: false,
+        }
+    }
+
+    pub fn with_domain_mapping(mut self, mapping: DomainMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// Reads the next regular file whose path maps to a domain, with its path
+    fn next_file(&mut self) -> io::Result<Option<(String, Vec<u8>)>> {
+        // Path set by a GNU long name or pax header for the next entry
+        let mut long_path = None;
+
+        loop {
+            let mut header = [0; 512];
+            match self.reader.read_exact(&mut header) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                result => result?,
+            }
+            if header.iter().all(|&b| b == 0) {
+                return Ok(None);
+            }
+
+            let checksum: u64 = header[..148]
+                .iter()
+                .chain(&[b' '; 8])
+                .chain(&header[156..])
+                .map(|&b| b as u64)
+                .sum();
+            if octal(&header[148..156])? != checksum {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "tar header checksum mismatch",
+                ));
+            }
+
+            let size = octal(&header[124..136])?;
+            let padding = (512 - size % 512) % 512;
+            let mut file = None;
+            match header[156] {
+                b'0' | 0 => {
+                    let path = match long_path.take() {
+                        Some(path) => path,
+                        None if &header[257..262] == b"ustar" && header[345] != 0 => {
+                            format!("{}/{}", text(&header[345..500]), text(&header[..100]))
+                        }
+                        None => text(&header[..100]),
+                    };
+                    if self.mapping.domain(&path).is_some() {
+                        file = Some((path, read_entry(&mut self.reader, size)?));
+                    }
+                }
+                b'L' => long_path = Some(text(&read_entry(&mut self.reader, size)?)),
+                b'x' => {
+                    if let Some(path) = pax_path(&read_entry(&mut self.reader, size)?) {
+                        long_path = Some(path);
+                    }
+                }
+                // Directories, links and other special files
+                _ => long_path = None,
+            }
+
+            // Skips the content of ignored entries, then the padding
+            let skip = if file.is_some() || matches!(header[156], b'L' | b'x') {
+                padding
+            } else {
+                size + padding
+            };
+            io::copy(&mut (&mut self.reader).take(skip), &mut io::sink())?;
+
+            if file.is_some() {
+                return Ok(file);
+            }
+        }
+    }
+}
+
+/// Text of a zero-terminated header field
+fn text(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn octal(field: &[u8]) -> io::Result<u64> {
+    let digits = text(field);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid number in tar header"))
+}
+
+/// `path` record of a pax extended header, made of `<length> <key>=<value>\n`
+/// records
+fn pax_path(records: &[u8]) -> Option<String> {
+    let records = String::from_utf8_lossy(records);
+    records.lines().find_map(|record| {
+        let (_, field) = record.split_once(' ')?;
+        field.strip_prefix("path=").map(|path| path.to_string())
+    })
+}
+
+impl<R: Read> Iterator for TarReader<R> {
+    type Item = Result<ArchiveEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.next_file() {
+                Ok(Some((path, content))) => {
+                    if let Some(entry) = ArchiveEntry::parse(&path, content, self.mapping) {
+                        return Some(entry);
+                    }
+                }
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(archive_error(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ZipFile {
+    path: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u64,
+    size: u64,
+    offset: u64,
+}
+
+fn u16_at(bytes: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([bytes[i], bytes[i + 1]])
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
+
+fn invalid_zip<T>(message: &str) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+/// Iterator over the files of a zip archive, listed from its central
+/// directory. Stored and deflated files are supported, zip64 archives are not.
+pub struct ZipReader<R> {
+    reader: R,
+    mapping: DomainMapping,
+    files: std::vec::IntoIter<ZipFile>,
+}
+
+impl<R: Read + Seek> ZipReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        match Self::central_directory(&mut reader) {
+            Ok(files) => Ok(ZipReader {
+                reader,
+                mapping: DomainMapping::default(),
+                files: files.into_iter(),
+            }),
+            Err(e) => archive_error(e),
+        }
+    }
+
+    pub fn with_domain_mapping(mut self, mapping: DomainMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    fn central_directory(reader: &mut R) -> io::Result<Vec<ZipFile>> {
+        // The end of central directory record ends the archive, followed by
+        // a comment of up to 64KiB
+        let len = reader.seek(SeekFrom::End(0))?;
+        let tail_len = len.min(22 + 0xffff);
+        reader.seek(SeekFrom::Start(len - tail_len))?;
+        let mut tail = vec![0; tail_len as usize];
+        reader.read_exact(&mut tail)?;
+
+        let end = match (0..tail.len().saturating_sub(21))
+            .rev()
+            .find(|&i| u32_at(&tail, i) == 0x0605_4b50)
+        {
+            Some(i) => &tail[i..],
+            None => return invalid_zip("not a zip archive"),
+        };
+        let count = u16_at(end, 10) as usize;
+        let directory_size = u32_at(end, 12) as u64;
+        let directory_offset = u32_at(end, 16) as u64;
+        if directory_offset == 0xffff_ffff || directory_size > MAX_ENTRY_SIZE {
+            return invalid_zip("unsupported zip64 archive");
+        }
+
+        reader.seek(SeekFrom::Start(directory_offset))?;
+        let directory = read_entry(&mut *reader, directory_size)?;
+
+        let mut files = Vec::with_capacity(count);
+        let mut i = 0;
+        while files.len() < count {
+            if directory.len() < i + 46 || u32_at(&directory, i) != 0x0201_4b50 {
+                return invalid_zip("corrupt zip central directory");
+            }
+            let header = &directory[i..];
+            let name_end = 46 + u16_at(header, 28) as usize;
+            if header.len() < name_end {
+                return invalid_zip("corrupt zip central directory");
+            }
+
+            files.push(ZipFile {
+                path: String::from_utf8_lossy(&header[46..name_end]).into_owned(),
+                method: u16_at(header, 10),
+                crc: u32_at(header, 16),
+                compressed_size: u32_at(header, 20) as u64,
+                size: u32_at(header, 24) as u64,
+                offset: u32_at(header, 42) as u64,
+            });
+            i += name_end + u16_at(header, 30) as usize + u16_at(header, 32) as usize;
+        }
+
+        Ok(files)
+    }
+
+    fn read_file(&mut self, file: &ZipFile) -> io::Result<Vec<u8>> {
+        if file.size > MAX_ENTRY_SIZE {
+            return invalid_zip("zip entry is too large");
+        }
+
+        let mut header = [0; 30];
+        self.reader.seek(SeekFrom::Start(file.offset))?;
+        self.reader.read_exact(&mut header)?;
+        if u32_at(&header, 0) != 0x0403_4b50 {
+            return invalid_zip("corrupt zip local header");
+        }
+        let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+        self.reader.seek(SeekFrom::Current(skip))?;
+
+        let compressed = (&mut self.reader).take(file.compressed_size);
+        let mut content = Vec::with_capacity(file.size as usize);
+        match file.method {
+            0 => BufReader::new(compressed).read_to_end(&mut content)?,
+            8 => GzipDecoder::deflate(compressed)
+                .take(file.size + 1)
+                .read_to_end(&mut content)?,
+            _ => return invalid_zip("unsupported zip compression method"),
+        };
+
+        if content.len() as u64 > file.size {
+            return invalid_zip("zip entry is larger than declared");
+        } else if content.len() as u64 != file.size || crc32(0, &content) != file.crc {
+            return invalid_zip("zip entry checksum mismatch");
+        }
+        Ok(content)
+    }
+}
+
+impl<R: Read + Seek> Iterator for ZipReader<R> {
+    type Item = Result<ArchiveEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(file) = self.files.next() {
+            if file.path.ends_with('/') || self.mapping.domain(&file.path).is_none() {
+                continue;
+            }
+            let content = match self.read_file(&file) {
+                Ok(content) => content,
+                Err(e) => {
+                    return Some(ads_txt_error(&format!(
+                        "Error reading {} from archive: {}",
+                        file.path, e
+                    )))
+                }
+            };
+            if let Some(entry) = ArchiveEntry::parse(&file.path, content, self.mapping) {
+                return Some(entry);
+            }
+        }
+        None
+    }
+}
+
+/// Reads a tar, gzipped tar or zip archive, detected from its content
+pub fn read_archive<P: AsRef<Path>>(
+    path: P,
+    mapping: DomainMapping,
+) -> Result<Box<dyn Iterator<Item = Result<ArchiveEntry>>>> {
+    let mut file = match File::open(path.as_ref()) {
+        Ok(file) => file,
+        Err(e) => return archive_error(e),
+    };
+    let mut magic = [0; 4];
+    let magic = match file.read(&mut magic).and_then(|len| {
+        file.seek(SeekFrom::Start(0))?;
+        Ok(&magic[..len])
+    }) {
+        Ok(magic) => magic.to_vec(),
+        Err(e) => return archive_error(e),
+    };
+
+    if magic.starts_with(b"PK") {
+        Ok(Box::new(
+            ZipReader::new(BufReader::new(file))?.with_domain_mapping(mapping),
+        ))
+    } else if is_gzip(&magic) {
+        let reader = BufReader::new(GzipDecoder::new(BufReader::new(file)));
+        Ok(Box::new(
+            TarReader::new(reader).with_domain_mapping(mapping),
+        ))
+    } else {
+        Ok(Box::new(
+            TarReader::new(BufReader::new(file)).with_domain_mapping(mapping),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_header(path: &str, size: usize, kind: u8) -> Vec<u8> {
+        let mut header = vec![0; 512];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+        header
+    }
+
+    fn tar(files: &[(&str, u8, &[u8])]) -> Vec<u8> {
+        let mut tar = vec![];
+        for (path, kind, content) in files {
+            tar.extend(tar_header(path, content.len(), *kind));
+            tar.extend_from_slice(content);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        }
+        tar.resize(tar.len() + 1024, 0);
+        tar
+    }
+
+    /// Zip of stored files
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = vec![];
+        let mut directory = vec![];
+        for (path, content) in files {
+            let offset = zip.len() as u32;
+            let sizes = [
+                &crc32(0, content).to_le_bytes()[..],
+                &(content.len() as u32).to_le_bytes(),
+                &(content.len() as u32).to_le_bytes(),
+            ]
+            .concat();
+
+            zip.extend_from_slice(&[0x50, 0x4b, 3, 4, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            zip.extend_from_slice(&sizes);
+            zip.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            zip.extend_from_slice(&[0, 0]);
+            zip.extend_from_slice(path.as_bytes());
+            zip.extend_from_slice(content);
+
+            directory.extend_from_slice(&[0x50, 0x4b, 1, 2, 20, 0, 20, 0]);
+            directory.extend_from_slice(&[0; 8]);
+            directory.extend_from_slice(&sizes);
+            directory.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(path.as_bytes());
+        }
+
+        let directory_offset = zip.len() as u32;
+        zip.extend_from_slice(&directory);
+        zip.extend_from_slice(&[0x50, 0x4b, 5, 6, 0, 0, 0, 0]);
+        zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&directory_offset.to_le_bytes());
+        zip.extend_from_slice(&[0, 0]);
+        zip
+    }
+
+    #[test]
+    fn mapping_paths_to_domains() {
+        let stem = DomainMapping::FileStem;
+        assert_eq!(
+            stem.domain("crawl/www.Example.com.txt").unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            stem.domain("example.com.app-ads.txt").unwrap(),
+            "example.com"
+        );
+        assert_eq!(stem.domain("./example.com.txt.gz").unwrap(), "example.com");
+        assert_eq!(stem.domain("crawl/README.md"), None);
+        assert_eq!(stem.domain("crawl/ads.txt"), None);
+
+        let parent = DomainMapping::ParentDirectory;
+        assert_eq!(
+            parent.domain("crawl/example.com/ads.txt").unwrap(),
+            "example.com"
+        );
+        assert_eq!(parent.domain("ads.txt"), None);
+
+        let component = DomainMapping::Component(1);
+        assert_eq!(
+            component
+                .domain("2024-05/example.com/2024-05-01/ads.txt")
+                .unwrap(),
+            "example.com"
+        );
+    }
+
+    #[test]
+    fn reading_tar_archives() {
+        let long_name = format!("crawl/{}.com.txt", "a".repeat(100));
+        let archive = tar(&[
+            ("crawl/", b'5', b""),
+            (
+                "crawl/a.com.txt",
+                b'0',
+                b"exchange.com, 1, DIRECT\nbroken\n",
+            ),
+            ("crawl/README", b'0', b"Crawl of 2024-05"),
+            // GNU long name of the entry after it
+            ("././@LongLink", b'L', long_name.as_bytes()),
+            ("truncated-name", b'0', b"exchange.com, 2, RESELLER"),
+        ]);
+
+        let entries: Vec<ArchiveEntry> =
+            TarReader::new(&archive[..]).collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "crawl/a.com.txt");
+        assert_eq!(entries[0].domain, "a.com");
+        assert_eq!(entries[0].ads_txt.records.len(), 1);
+        assert_eq!(entries[0].errors.len(), 1);
+        assert_eq!(
+            entries[0].hash,
+            content_hash("exchange.com, 1, DIRECT\nbroken\n")
+        );
+        assert_eq!(entries[1].path, long_name);
+        assert_eq!(entries[1].domain, format!("{}.com", "a".repeat(100)));
+
+        let corpus: crate::corpus::AdsTxtCorpus = entries
+            .into_iter()
+            .map(|e| e.into_corpus_entry(SystemTime::UNIX_EPOCH))
+            .collect();
+        assert_eq!(corpus.publishers("exchange.com").len(), 2);
+
+        let path = std::env::temp_dir().join(format!("ads-txt-archive-{}.tar", std::process::id()));
+        std::fs::write(&path, &archive).unwrap();
+        let entries = read_archive(&path, DomainMapping::FileStem).unwrap();
+        assert_eq!(entries.count(), 2);
+        std::fs::remove_file(path).unwrap();
+
+        let mut corrupt = tar(&[("a.com.txt", b'0', b"exchange.com, 1, DIRECT")]);
+        corrupt[0] = b'b';
+        let results: Vec<_> = TarReader::new(&corrupt[..]).collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn reading_zip_archives() {
+        let archive = zip(&[
+            ("crawl/", b""),
+            ("crawl/a.com/ads.txt", b"exchange.com, 1, DIRECT"),
+            ("crawl/b.com/ads.txt", b"exchange.com, 2, RESELLER"),
+        ]);
+
+        let entries: Vec<ArchiveEntry> = ZipReader::new(io::Cursor::new(&archive))
+            .unwrap()
+            .with_domain_mapping(DomainMapping::ParentDirectory)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| e.domain.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a.com", "b.com"]
+        );
+        assert_eq!(entries[1].ads_txt.records[0].publisher_id, "2");
+
+        let mut corrupt = archive.clone();
+        let i = corrupt.windows(9).position(|w| w == b"exchange.").unwrap();
+        corrupt[i] = b'E';
+        let results: Vec<_> = ZipReader::new(io::Cursor::new(&corrupt))
+            .unwrap()
+            .with_domain_mapping(DomainMapping::ParentDirectory)
+            .collect();
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+
+        assert!(ZipReader::new(io::Cursor::new(b"not a zip")).is_err());
+
+        // Deflated entry, a single stored block, inflating past its declared size
+        let content = b"exchange.com, 1, DIRECT";
+        let mut deflated = vec![1];
+        deflated.extend_from_slice(&(content.len() as u16).to_le_bytes());
+        deflated.extend_from_slice(&(!(content.len() as u16)).to_le_bytes());
+        deflated.extend_from_slice(content);
+        let mut bomb = zip(&[("a.com.txt", &deflated)]);
+        let central = bomb
+            .windows(4)
+            .position(|w| w == [0x50, 0x4b, 1, 2])
+            .unwrap();
+        bomb[8] = 8;
+        bomb[central + 10] = 8;
+        bomb[central + 24..central + 28].copy_from_slice(&10u32.to_le_bytes());
+        let results: Vec<_> = ZipReader::new(io::Cursor::new(&bomb)).unwrap().collect();
+        assert!(results[0]
+            .as_ref()
+            .unwrap_err()
+            .message()
+            .contains("larger than declared"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Arena parsing mode: the strings of a parsed file are copied into one
+//! buffer owned by the caller and freed together, so parsing and discarding
+//! many files reuses the same memory instead of allocating every field
+
+use crate::{write_record, AccountRelation, AdsTxt, AdsTxtError, DataRecord, Variable};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Formatter;
+use core::ops::Range;
+
+/// Buffer holding the records and variables of the last parsed file
+#[derive(Debug, Clone, Default)]
+pub struct Arena {
+    strings: String,
+    records: Vec<RecordSpans>,
+    variables: Vec<(Range<usize>, Range<usize>)>,
+    invalid_lines: Vec<Range<usize>>,
+}
+
+#[derive(Debug, Clone)]
+struct RecordSpans {
+    domain: Range<usize>,
+    publisher_id: Range<usize>,
+    acc_relation: AccountRelation,
+    cert_authority: Option<Range<usize>>,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an arena with room for `bytes` bytes of strings
+    pub fn with_capacity(bytes: usize) -> Self {
+        Arena {
+            strings: String::with_capacity(bytes),
+            ..Self::default()
+        }
+    }
+
+    /// Bytes of strings held for the last parsed file
+    pub fn allocated(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Bytes of strings the arena holds without growing
+    pub fn capacity(&self) -> usize {
+        self.strings.capacity()
+    }
+
+    /// Frees the last parsed file, keeping the memory for the next one
+    pub fn reset(&mut self) {
+        self.strings.clear();
+        self.records.clear();
+        self.variables.clear();
+        self.invalid_lines.clear();
+    }
+
+    fn alloc(&mut self, text: &str) -> Range<usize> {
+        let start = self.strings.len();
+        self.strings.push_str(text);
+        start..self.strings.len()
+    }
+
+    /// Parses a file leniently like `AdsTxt::parse_lenient`, replacing the
+    /// previously parsed file
+    pub fn parse(&mut self, text: &str) -> ArenaAdsTxt<'_> {
+        self.reset();
+
+        for line in text.lines() {
+            let line = line.trim_start();
+
+            if line.is_empty() || AdsTxt::is_comment(line) {
+                continue;
+            }
+
+            if let Some((domain, publisher_id, acc_relation, cert_authority)) =
+                DataRecord::parsed_fields(line)
+            {
+                let record = RecordSpans {
+                    domain: self.alloc(domain),
+                    publisher_id: self.alloc(publisher_id),
+                    acc_relation,
+                    cert_authority: cert_authority.map(|c| self.alloc(c)),
+                };
+                self.records.push(record);
+            } else if let Some((name, value)) = Variable::parsed_fields(line) {
+                let variable = (self.alloc(name), self.alloc(value));
+                self.variables.push(variable);
+            } else {
+                let line = self.alloc(line);
+                self.invalid_lines.push(line);
+            }
+        }
+
+        ArenaAdsTxt { arena: self }
+    }
+}
+
+/// File parsed into an `Arena`, borrowing its strings
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaAdsTxt<'a> {
+    arena: &'a Arena,
+}
+
+impl<'a> ArenaAdsTxt<'a> {
+    fn str(&self, span: &Range<usize>) -> &'a str {
+        &self.arena.strings[span.clone()]
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = DataRecordRef<'a>> + 'a {
+        let document = *self;
+        self.arena.records.iter().map(move |r| DataRecordRef {
+            domain: document.str(&r.domain),
+            publisher_id: document.str(&r.publisher_id),
+            acc_relation: r.acc_relation.clone(),
+            cert_authority: r.cert_authority.as_ref().map(|c| document.str(c)),
+        })
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = VariableRef<'a>> + 'a {
+        let document = *self;
+        self.arena
+            .variables
+            .iter()
+            .map(move |(name, value)| VariableRef {
+                name: document.str(name),
+                value: document.str(value),
+            })
+    }
+
+    /// Lines which are neither records nor variables
+    pub fn invalid_lines(&self) -> impl Iterator<Item = &'a str> + 'a {
+        let document = *self;
+        self.arena
+            .invalid_lines
+            .iter()
+            .map(move |line| document.str(line))
+    }
+
+    /// Copies the file out of the arena, as `AdsTxt::parse_lenient` returns it
+    pub fn to_ads_txt(&self) -> (AdsTxt, Vec<AdsTxtError>) {
+        let ads_txt = AdsTxt {
+            records: self.records().map(|r| r.to_record()).collect(),
+            variables: self
+                .variables()
+                .map(|v| Variable::new(v.name, v.value))
+                .collect(),
+        };
+        let errors = self
+            .invalid_lines()
+            .map(|line| AdsTxtError::new(&format!("Invalid ads.txt line: {}", line)))
+            .collect();
+        (ads_txt, errors)
+    }
+}
+
+/// Record whose fields borrow from an `Arena`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DataRecordRef<'a> {
+    pub domain: &'a str,
+    pub publisher_id: &'a str,
+    pub acc_relation: AccountRelation,
+    pub cert_authority: Option<&'a str>,
+}
+
+impl DataRecordRef<'_> {
+    /// Writes the record as an ads.txt line like `DataRecord::write_into`
+    pub fn write_into<W: core::fmt::Write>(&self, out: &mut W) -> core::fmt::Result {
+        write_record(
+            out,
+            self.domain,
+            self.publisher_id,
+            &self.acc_relation,
+            self.cert_authority,
+        )
+    }
+
+    pub fn to_record(&self) -> DataRecord {
+        DataRecord::new(
+            self.domain,
+            self.publisher_id,
+            self.acc_relation.clone(),
+            self.cert_authority.map(|c| c.to_string()),
+        )
+    }
+}
+
+impl core::fmt::Display for DataRecordRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.write_into(f)
+    }
+}
+
+/// Variable whose name and value borrow from an `Arena`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct VariableRef<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_into_arena() {
+        let text = "# comment\ngreenadexchange.com, 12345, DIRECT, d75815a79\n\
+                    blueadexchange.com, XF436, reseller\ncontact=ads@example.com\nnonsense\n\
+                    a.com, 1, OWNER\na=b=c";
+
+        let mut arena = Arena::with_capacity(16);
+        let document = arena.parse(text);
+        let records: Vec<String> = document.records().map(|r| r.to_string()).collect();
+        assert_eq!(
+            records,
+            vec![
+                "greenadexchange.com, 12345, DIRECT, d75815a79",
+                "blueadexchange.com, XF436, RESELLER"
+            ]
+        );
+        assert_eq!(
+            document.variables().next(),
+            Some(VariableRef {
+                name: "contact",
+                value: "ads@example.com"
+            })
+        );
+        assert_eq!(
+            document.invalid_lines().collect::<Vec<_>>(),
+            vec!["nonsense", "a.com, 1, OWNER", "a=b=c"]
+        );
+        assert_eq!(document.to_ads_txt(), AdsTxt::parse_lenient(text));
+
+        // Parsing the next file frees the strings of the last one
+        let capacity = arena.capacity();
+        let document = arena.parse("a.com, 1, DIRECT");
+        assert_eq!(document.records().count(), 1);
+        assert_eq!(arena.allocated(), "a.com1".len());
+        assert_eq!(arena.capacity(), capacity);
+    }
+}
@@ -0,0 +1,126 @@
+//! Minimal command line argument parsing
+
+/// Positional arguments and `--name value`, `--name=value` or `--flag` options
+#[derive(Debug, Default)]
+pub struct Args {
+    pub positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// Parses arguments, accepting only the given options which take a value
+    /// and the given flags which do not
+    pub fn parse(args: &[String], options: &[&str], flags: &[&str]) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    parsed.positional.push(arg.clone());
+                    continue;
+                }
+            };
+
+            let (name, inline_value) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
+            };
+
+            if options.contains(&name) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => match args.next() {
+                        Some(value) => value.clone(),
+                        None => return Err(format!("Missing value for --{}", name)),
+                    },
+                };
+                parsed.options.push((name.to_string(), Some(value)));
+            } else if flags.contains(&name) && inline_value.is_none() {
+                parsed.options.push((name.to_string(), None));
+            } else {
+                return Err(format!("Unknown option: {}", arg));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Last value given for the option
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values(name).pop()
+    }
+
+    /// Every value given for the option, in order
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|(n, _)| n == name)
+            .filter_map(|(_, v)| v.as_deref())
+            .collect()
+    }
+
+    /// Value of the option parsed as `T`
+    pub fn parsed_value<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.value(name) {
+            Some(value) => match value.parse() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err(format!("Invalid value for --{}: {}", name, value)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, v)| n == name && v.is_none())
+    }
+
+    /// Adds a value for an option, or sets a flag if `None`, which values
+    /// given on the command line take precedence over
+    pub fn set_default(&mut self, name: &str, value: Option<String>) {
+        self.options.insert(0, (name.to_string(), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parsing_arguments() {
+        let parsed = Args::parse(
+            &args(&[
+                "a.txt",
+                "--timeout",
+                "5",
+                "--robots",
+                "b.txt",
+                "--timeout=7",
+            ]),
+            &["timeout"],
+            &["robots"],
+        )
+        .unwrap();
+
+        assert_eq!(parsed.positional, vec!["a.txt", "b.txt"]);
+        assert_eq!(parsed.values("timeout"), vec!["5", "7"]);
+        assert_eq!(parsed.parsed_value::<u64>("timeout"), Ok(Some(7)));
+        assert!(parsed.flag("robots"));
+        assert!(!parsed.flag("timeout"));
+
+        let mut parsed = parsed;
+        parsed.set_default("timeout", Some("3".to_string()));
+        parsed.set_default("user-agent", Some("bot".to_string()));
+        assert_eq!(parsed.value("timeout"), Some("7"));
+        assert_eq!(parsed.value("user-agent"), Some("bot"));
+
+        assert!(Args::parse(&args(&["--other"]), &[], &[]).is_err());
+        assert!(Args::parse(&args(&["--timeout"]), &["timeout"], &[]).is_err());
+        assert!(Args::parse(&args(&["--robots=1"]), &[], &["robots"]).is_err());
+    }
+}
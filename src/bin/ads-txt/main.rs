@@ -0,0 +1,1921 @@
+//! ads-txt command line tool
+
+mod args;
+mod serve;
+
+use args::Args;
+use rs_ads_txt::cross_validation::{cross_validate, RecordVerdict};
+use rs_ads_txt::diff::AdsTxtDiff;
+use rs_ads_txt::equality::{RecordEquality, RecordField};
+use rs_ads_txt::fetch::{
+    CrawlResult, CrawlSummary, Crawler, CrawlerConfig, FetchResult, FetchedAdsTxt, TcpHttpClient,
+    DEFAULT_USER_AGENT,
+};
+use rs_ads_txt::findings::Severity;
+use rs_ads_txt::format::format;
+use rs_ads_txt::json::JsonValue;
+use rs_ads_txt::merge::{merge, ConflictPolicy};
+use rs_ads_txt::publisher_id::{IdComparison, PublisherIdPolicy};
+use rs_ads_txt::sellers_json::SellersJson;
+use rs_ads_txt::serialize;
+use rs_ads_txt::stats::AdsTxtStats;
+use rs_ads_txt::table;
+use rs_ads_txt::toml::{Toml, TomlValue};
+use rs_ads_txt::validate::{Diagnostic, Validator};
+use rs_ads_txt::watch::Watcher;
+use rs_ads_txt::{AdsTxt, AdsTxtError, DataRecord, FileKind};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, process};
+
+const USAGE: &str = "Usage: ads-txt <command> [args]
+
+Commands:
+    parse FILE       Print the records and variables of an ads.txt file
+        --format table       Print them in aligned columns
+        --color              Color relations of tables
+    validate FILE    Print diagnostics for an ads.txt file
+        --app                Validate as app-ads.txt, which has no SUBDOMAIN
+        --format table       Print diagnostics in aligned columns
+        --color              Color severities of tables
+        --policy FILE        TOML policy file, as taken by lint --config
+    fetch DOMAIN     Fetch and print the ads.txt file of a domain
+        --timeout SECS       Request timeout, 10 by default
+        --user-agent AGENT   User agent to send
+        --robots             Honor robots.txt
+        --ca-cert FILE       PEM file of root certificates to trust besides
+                             the Mozilla roots
+        --http2              Prefer HTTP/2 with servers supporting it
+        --cache-dir DIR      Directory where fetched files are stored, which
+                             watch takes as baselines on startup
+        --app                Fetch app-ads.txt instead of ads.txt
+        --store-url URL      Fetch app-ads.txt from the developer website
+                             of the app store listing at URL, in place of
+                             DOMAIN, with fetch, check and crosscheck
+    diff OLD NEW     Print seats added, removed or changed between two files
+    diff --fetch DOMAIN --against FILE
+                     Compare a domain's live ads.txt file against FILE,
+                     taking the fetch options above
+        --ignore relation|cert-authority
+                             Field not making records of a seat differ,
+                             repeatable or comma-separated
+    fmt FILE         Rewrite a file in canonical form: sorted, deduplicated
+                     records followed by variables, keeping comments
+        --check              Only check, exiting with 1 if not formatted
+    stats FILE...    Summarize records, relations, ad systems and variables
+        --format table|json  Output format, table by default
+        --top N              Number of ad systems listed, 10 by default
+    crawl --input FILE
+                     Fetch the ads.txt files of the domains in the first
+                     column of FILE, writing one JSON result per line,
+                     exiting with 3 if any failed, taking the fetch options
+                     above
+        --out FILE           Output file instead of standard output
+        --concurrency N      Number of parallel fetches, 16 by default
+    check DOMAIN --system DOMAIN --seller-id ID
+                     Check whether a domain's ads.txt file authorizes a
+                     seller account, exiting with 2 if not, taking the fetch
+                     options above
+        --relation DIRECT|RESELLER
+                             Also require the given relation
+        --publisher-ids exact|case-insensitive
+                             Comparison of seller ids, exact by default
+    crosscheck DOMAIN
+                     Verify each record of a domain's ads.txt file against
+                     the sellers.json file of its ad system, exiting with 2
+                     if sellers are missing or of the wrong type, or with 1
+                     if a sellers.json file could not be fetched, taking
+                     the fetch options above
+        --concurrency N      Number of parallel fetches, 16 by default
+    lint FILE        Print diagnostics with source lines
+        --config FILE        TOML policy file with a [rules] table setting
+                             rules to error, warning, info or off, a
+                             [policy] table with cert-authority =
+                             \"required\"|\"optional\"|\"forbidden\",
+                             required-seats and forbidden-domains arrays,
+                             publisher-ids = \"exact\"|\"case-insensitive\",
+                             [policy.cert-authority] and
+                             [policy.publisher-ids] tables of these by ad
+                             system, and optionally deny-warnings = true
+        --deny-warnings      Exit with 2 on warnings too
+        --app                Lint as app-ads.txt
+    convert FILE --to txt|json|jsonl|csv
+                     Convert a file between ads.txt text, JSON, JSON lines
+                     and CSV, which only keeps records
+        --from txt|json|jsonl|csv
+                             Input format, guessed from the extension of FILE
+                             by default
+    merge FILE...    Merge files into one with deduplicated records and
+                     variables, reporting conflicting records of a seat
+        --policy keep-first|keep-last|keep-direct|keep-all
+                             Record kept on conflicts, keep-first by default
+        --out FILE           Output file instead of standard output
+    watch --domains FILE
+                     Keep fetching the ads.txt files of the domains in the
+                     first column of FILE, printing seat changes, taking the
+                     fetch options above
+        --interval N[s|m|h|d]
+                             Time between fetches, 6h by default
+        --webhook URL        Also POST every change as JSON to URL
+        --iterations N       Stop after N rounds of fetches
+        --concurrency N      Number of parallel fetches, 16 by default
+    serve            Serve a JSON API taking the fetch options above:
+                     GET /fetch?domain=D, POST /validate with the file as
+                     body, and GET /check?domain=D&system=S&seller_id=ID
+                     with an optional relation
+        --port N             Port to listen on, 8080 by default
+        --bind ADDRESS       Address to listen on, 127.0.0.1 by default.
+                             On other than loopback addresses, domains
+                             resolving to private addresses are refused
+        --workers N          Number of requests served at once, 16 by
+                             default
+        --publisher-ids exact|case-insensitive
+                             Comparison of seller ids by /check, exact by
+                             default
+    help             Print this message
+
+FILE may be - to read from standard input.
+
+Commands other than convert and serve take --format json to print JSON
+instead of text. The crawl command prints JSON lines by default and one
+JSON document with --format json. Errors are then printed as
+{\"error\": MESSAGE}.
+
+Exit codes:
+    0    Success
+    1    Warnings, differences found by diff, unformatted files with fmt
+         --check, or conflicts found by merge
+    2    Errors, unauthorized sellers with check, or invalid usage
+    3    Failure to fetch a file
+
+Defaults for the fetch options and --concurrency are read from the [fetch]
+table of ads-txt.toml in the working directory, or the file set with the
+ADS_TXT_CONFIG environment variable. Its [lint] table may set
+deny-warnings, and its [lint.rules] table rule severities as in lint
+configuration files, which take precedence. Its [check] table may set
+publisher-ids for check and serve, and its [check.publisher-ids] table
+the comparison of the seller ids of particular ad systems.";
+
+/// Exit code of a command, or the error which ended it
+type CliResult = Result<i32, Box<dyn std::error::Error>>;
+
+const EXIT_OK: i32 = 0;
+/// Warnings, or changes found by diff, unformatted files and merge conflicts
+const EXIT_WARNINGS: i32 = 1;
+/// Errors, or unauthorized sellers and invalid usage
+const EXIT_ERRORS: i32 = 2;
+const EXIT_FETCH_FAILED: i32 = 3;
+
+/// Error of a command which could not fetch a file
+#[derive(Debug)]
+struct FetchFailed(String);
+
+impl std::fmt::Display for FetchFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for FetchFailed {}
+
+/// Whether a command prints JSON rather than text, from `--format json|text`
+fn json_output(args: &Args) -> Result<bool, String> {
+    match args.value("format") {
+        None | Some("text") => Ok(false),
+        Some("json") => Ok(true),
+        Some(format) => Err(format!(
+            "Unknown format: {} (expected text or json)",
+            format
+        )),
+    }
+}
+
+/// Whether the command line asks for a table, which parse and validate print
+/// besides text and JSON
+fn table_output(args: &Args) -> bool {
+    args.value("format") == Some("table")
+}
+
+fn errors_json(errors: &[AdsTxtError]) -> JsonValue {
+    JsonValue::Array(
+        errors
+            .iter()
+            .map(|e| JsonValue::string(&e.to_string()))
+            .collect(),
+    )
+}
+
+/// Renders diagnostics as `{"file", "errors", "warnings", "diagnostics"}`
+fn diagnostics_json(path: &str, diagnostics: &[Diagnostic]) -> JsonValue {
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+
+    JsonValue::Object(vec![
+        ("file".to_string(), JsonValue::string(path)),
+        (
+            "errors".to_string(),
+            JsonValue::number(count(Severity::Error)),
+        ),
+        (
+            "warnings".to_string(),
+            JsonValue::number(count(Severity::Warning)),
+        ),
+        (
+            "diagnostics".to_string(),
+            JsonValue::Array(diagnostics.iter().map(|d| d.to_json()).collect()),
+        ),
+    ])
+}
+
+/// Exit code for diagnostics, with warnings counting as errors if denied
+fn diagnostics_exit_code(diagnostics: &[Diagnostic], deny_warnings: bool) -> i32 {
+    let worst = diagnostics.iter().map(|d| d.severity).max();
+    match worst {
+        Some(Severity::Error) => EXIT_ERRORS,
+        Some(Severity::Warning) if deny_warnings => EXIT_ERRORS,
+        Some(Severity::Warning) => EXIT_WARNINGS,
+        _ => EXIT_OK,
+    }
+}
+
+/// Renders a fetched file as `{"domain", "url", "ads_txt", "errors"}`
+fn fetched_json(fetched: &FetchedAdsTxt) -> JsonValue {
+    JsonValue::Object(vec![
+        ("domain".to_string(), JsonValue::string(&fetched.domain)),
+        ("url".to_string(), JsonValue::string(&fetched.url)),
+        (
+            "ads_txt".to_string(),
+            serialize::ads_txt_to_json(&fetched.ads_txt),
+        ),
+        ("errors".to_string(), errors_json(&fetched.errors)),
+    ])
+}
+
+/// Records of a seller account in a file, and whether one of them has the
+/// required relation if any
+fn seller_records<'a>(
+    ads_txt: &'a AdsTxt,
+    system: &str,
+    seller_id: &str,
+    relation: Option<&str>,
+    policy: &PublisherIdPolicy,
+) -> (Vec<&'a DataRecord>, bool) {
+    let records = ads_txt.seat_records_with_policy(system, seller_id, policy);
+    let authorized = records
+        .iter()
+        .any(|r| relation.is_none_or(|relation| r.acc_relation().as_str() == relation));
+    (records, authorized)
+}
+
+/// Renders an authorization check as `{"domain", "system", "seller_id",
+/// "relation", "authorized", "records"}`
+fn authorization_json(
+    domain: &str,
+    ads_txt: &AdsTxt,
+    system: &str,
+    seller_id: &str,
+    relation: Option<&str>,
+    policy: &PublisherIdPolicy,
+) -> JsonValue {
+    let (records, authorized) = seller_records(ads_txt, system, seller_id, relation, policy);
+
+    JsonValue::Object(vec![
+        ("domain".to_string(), JsonValue::string(domain)),
+        ("system".to_string(), JsonValue::string(system)),
+        ("seller_id".to_string(), JsonValue::string(seller_id)),
+        (
+            "relation".to_string(),
+            relation.map_or(JsonValue::Null, JsonValue::string),
+        ),
+        ("authorized".to_string(), JsonValue::Bool(authorized)),
+        (
+            "records".to_string(),
+            JsonValue::Array(records.into_iter().map(serialize::record_to_json).collect()),
+        ),
+    ])
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Error reading standard input: {}", e))?;
+        Ok(text)
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path, e))
+    }
+}
+
+fn single<'a>(args: &'a [String], name: &str) -> Result<&'a str, String> {
+    match args {
+        [arg] => Ok(arg),
+        _ => Err(format!("Expected a single {} argument\n\n{}", name, USAGE)),
+    }
+}
+
+fn parse(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = Args::parse(args, &["format"], &["color"])?;
+    let table = table_output(&args);
+    let json = !table && json_output(&args)?;
+    let path = single(&args.positional, "FILE")?;
+    let (ads_txt, errors) = AdsTxt::parse_lenient(&read_file(path)?);
+
+    if table {
+        write!(
+            out,
+            "{}",
+            table::render_ads_txt(&ads_txt, args.flag("color"))
+        )?;
+        for e in &errors {
+            eprintln!("{}: {}", path, e);
+        }
+    } else if json {
+        let report = JsonValue::Object(vec![
+            ("file".to_string(), JsonValue::string(path)),
+            ("ads_txt".to_string(), serialize::ads_txt_to_json(&ads_txt)),
+            ("errors".to_string(), errors_json(&errors)),
+        ]);
+        writeln!(out, "{}", report)?;
+    } else {
+        write!(out, "{}", serialize::ads_txt_to_text(&ads_txt))?;
+        for e in &errors {
+            eprintln!("{}: {}", path, e);
+        }
+    }
+
+    Ok(if errors.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_WARNINGS
+    })
+}
+
+fn validate_file(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = Args::parse(args, &["format", "policy"], &["app", "color"])?;
+    let table = table_output(&args);
+    let json = !table && json_output(&args)?;
+    let path = single(&args.positional, "FILE")?;
+    let mut validator = Validator::new().with_file_kind(file_kind(&args));
+    if let Some(policy) = args.value("policy") {
+        let config = Toml::parse(&read_file(policy)?).map_err(|e| format!("{}: {}", policy, e))?;
+        validator = validator
+            .with_policy(&config)
+            .map_err(|e| format!("{}: {}", policy, e))?;
+    }
+    let diagnostics = validator.validate(&read_file(path)?);
+
+    if json {
+        writeln!(out, "{}", diagnostics_json(path, &diagnostics))?;
+        return Ok(diagnostics_exit_code(&diagnostics, false));
+    }
+
+    if table {
+        let table = table::diagnostics_table(&diagnostics).with_color(args.flag("color"));
+        write!(out, "{}", table)?;
+    } else {
+        for d in &diagnostics {
+            writeln!(
+                out,
+                "{}:{}: {}: {} [{}]",
+                path,
+                d.line,
+                d.severity.as_str().to_lowercase(),
+                d.message,
+                d.rule.as_str()
+            )?;
+        }
+    }
+
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+    writeln!(
+        out,
+        "{} errors, {} warnings",
+        count(Severity::Error),
+        count(Severity::Warning)
+    )?;
+
+    Ok(diagnostics_exit_code(&diagnostics, false))
+}
+
+/// Options of commands which fetch files
+const FETCH_OPTIONS: &[&str] = &["timeout", "user-agent", "cache-dir", "ca-cert"];
+const FETCH_FLAGS: &[&str] = &["robots", "app", "http2"];
+
+/// Settings of the `[fetch]` table of the configuration file, besides the
+/// fetch options and flags
+const FETCH_SETTINGS: &[&str] = &["concurrency"];
+
+/// Configuration file read from the working directory
+const CONFIG_FILE: &str = "ads-txt.toml";
+
+/// Reads the configuration file, `ads-txt.toml` in the working directory
+/// unless another path is set with the `ADS_TXT_CONFIG` environment variable
+fn load_config() -> Result<Toml, String> {
+    let path = match std::env::var("ADS_TXT_CONFIG") {
+        Ok(path) => path,
+        Err(_) if Path::new(CONFIG_FILE).exists() => CONFIG_FILE.to_string(),
+        Err(_) => return Ok(Toml::default()),
+    };
+    Toml::parse(&read_file(&path)?).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Takes defaults for the options and flags of a command from the `[fetch]`
+/// table of the configuration
+fn apply_fetch_config(
+    args: &mut Args,
+    config: &Toml,
+    options: &[&str],
+    flags: &[&str],
+) -> Result<(), String> {
+    for (name, value) in config.table("fetch") {
+        let known = FETCH_OPTIONS.contains(&name)
+            || FETCH_FLAGS.contains(&name)
+            || FETCH_SETTINGS.contains(&name);
+        if !known {
+            return Err(format!(
+                "Unknown setting in {}: fetch.{}",
+                CONFIG_FILE, name
+            ));
+        }
+
+        let value = match value {
+            TomlValue::String(s) => s.clone(),
+            TomlValue::Integer(i) => i.to_string(),
+            TomlValue::Boolean(b) => b.to_string(),
+            _ => {
+                return Err(format!(
+                    "Invalid setting in {}: fetch.{}",
+                    CONFIG_FILE, name
+                ))
+            }
+        };
+
+        if options.contains(&name) {
+            args.set_default(name, Some(value));
+        } else if flags.contains(&name) && value == "true" {
+            args.set_default(name, None);
+        }
+    }
+    Ok(())
+}
+
+/// Parses the arguments of a command taking the fetch options besides the
+/// given ones, with defaults from the configuration
+fn parse_fetch_args(args: &[String], options: &[&str]) -> Result<Args, String> {
+    let mut options = options.to_vec();
+    options.extend(FETCH_OPTIONS);
+
+    let mut parsed = Args::parse(args, &options, FETCH_FLAGS)?;
+    apply_fetch_config(&mut parsed, &load_config()?, &options, FETCH_FLAGS)?;
+    Ok(parsed)
+}
+
+/// Comparison of publisher ids by check and serve: `publisher-ids` of the
+/// `[check]` table of the configuration, overridden by `--publisher-ids`,
+/// and the comparisons of ad systems of its `[check.publisher-ids]` table
+fn publisher_id_policy(args: &Args) -> Result<PublisherIdPolicy, String> {
+    let config = load_config()?;
+    let comparison = |value: Option<&str>, setting: &str| {
+        value
+            .and_then(IdComparison::from_name)
+            .ok_or_else(|| format!("Invalid {}: expected exact or case-insensitive", setting))
+    };
+
+    let mut policy = PublisherIdPolicy::default();
+    for (name, value) in config.table("check") {
+        if name != "publisher-ids" {
+            return Err(format!(
+                "Unknown setting in {}: check.{}",
+                CONFIG_FILE, name
+            ));
+        }
+        let setting = format!("setting in {}: check.{}", CONFIG_FILE, name);
+        policy = policy.with_default(comparison(value.as_str(), &setting)?);
+    }
+    for (ad_system, value) in config.table("check.publisher-ids") {
+        let setting = format!(
+            "setting in {}: check.publisher-ids.{}",
+            CONFIG_FILE, ad_system
+        );
+        policy = policy.with_override(ad_system, comparison(value.as_str(), &setting)?);
+    }
+    if let Some(value) = args.value("publisher-ids") {
+        policy = policy.with_default(comparison(Some(value), "--publisher-ids")?);
+    }
+    Ok(policy)
+}
+
+/// Kind of file fetched and validated, app-ads.txt with `--app`
+fn file_kind(args: &Args) -> FileKind {
+    if args.flag("app") || args.value("store-url").is_some() {
+        FileKind::AppAdsTxt
+    } else {
+        FileKind::AdsTxt
+    }
+}
+
+/// Path of the cached copy of a domain's ads.txt or app-ads.txt file
+fn cache_path(dir: &str, domain: &str, file: FileKind) -> PathBuf {
+    let name: String = domain
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+    match file {
+        FileKind::AdsTxt => Path::new(dir).join(format!("{}.txt", name)),
+        other => Path::new(dir).join(format!("{}.{}", name, other.as_str())),
+    }
+}
+
+/// Stores a fetched file in the cache directory, if one is set
+fn cache_store(args: &Args, fetched: &FetchedAdsTxt) {
+    if let Some(dir) = args.value("cache-dir") {
+        let path = cache_path(dir, &fetched.domain, file_kind(args));
+        let stored = fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&path, serialize::ads_txt_to_text(&fetched.ads_txt)));
+        if let Err(e) = stored {
+            eprintln!("Error caching {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Cached copy of a domain's ads.txt file, if any
+fn cache_load(args: &Args, domain: &str) -> Option<AdsTxt> {
+    let path = cache_path(args.value("cache-dir")?, domain, file_kind(args));
+    let text = fs::read_to_string(path).ok()?;
+    Some(AdsTxt::parse_lenient(&text).0)
+}
+
+fn http_client(args: &Args) -> Result<TcpHttpClient, String> {
+    let timeout = args.parsed_value("timeout")?.unwrap_or(10);
+    let client = TcpHttpClient::new(Duration::from_secs(timeout)).with_http2(args.flag("http2"));
+    match args.value("ca-cert") {
+        Some(path) => {
+            let pem = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            client
+                .with_root_certificates_pem(&pem)
+                .map_err(|e| format!("{}: {}", path, e))
+        }
+        None => Ok(client),
+    }
+}
+
+fn crawler(args: &Args) -> Result<Crawler<TcpHttpClient>, String> {
+    Ok(Crawler::new(http_client(args)?, crawler_config(args)))
+}
+
+fn crawler_config(args: &Args) -> CrawlerConfig {
+    let mut config = CrawlerConfig {
+        respect_robots_txt: args.flag("robots"),
+        www_fallback: true,
+        file: file_kind(args),
+        ..CrawlerConfig::default()
+    };
+    if let Some(user_agent) = args.value("user-agent") {
+        config.user_agent = user_agent.to_string();
+    }
+    config
+}
+
+fn fetch_ads_txt(
+    args: &Args,
+    crawler: &Crawler<TcpHttpClient>,
+    domain: &str,
+) -> Result<FetchedAdsTxt, FetchFailed> {
+    match crawler.fetch(domain) {
+        CrawlResult::Fetched(fetched) => {
+            eprintln!("Fetched {}", fetched.url);
+            for e in &fetched.errors {
+                eprintln!("{}: {}", fetched.url, e);
+            }
+            cache_store(args, &fetched);
+            Ok(fetched)
+        }
+        CrawlResult::SkippedByRobots => Err(FetchFailed(format!(
+            "{}: fetching {} is disallowed by robots.txt",
+            domain,
+            crawler.config().file.as_str()
+        ))),
+        CrawlResult::Failed(e) => Err(FetchFailed(format!("{}: {} ({})", domain, e, e.class()))),
+    }
+}
+
+/// Domain whose file a command fetches: the DOMAIN argument, or with
+/// `--store-url` the developer domain found in an app's store listing
+fn target_domain(
+    args: &Args,
+    crawler: &Crawler<TcpHttpClient>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match args.value("store-url") {
+        Some(url) if args.positional.is_empty() => {
+            let domain = crawler
+                .resolve_developer_domain(url)
+                .map_err(|e| FetchFailed(format!("{}: {} ({})", url, e, e.class())))?;
+            eprintln!("Resolved developer domain {} from {}", domain, url);
+            Ok(domain)
+        }
+        Some(_) => Err("Expected either DOMAIN or --store-url URL".into()),
+        None => Ok(single(&args.positional, "DOMAIN")?.to_string()),
+    }
+}
+
+fn fetch(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = parse_fetch_args(args, &["store-url", "format"])?;
+    let json = json_output(&args)?;
+
+    let crawler = crawler(&args)?;
+    let domain = target_domain(&args, &crawler)?;
+    let fetched = fetch_ads_txt(&args, &crawler, &domain)?;
+    if json {
+        writeln!(out, "{}", fetched_json(&fetched))?;
+    } else {
+        write!(out, "{}", serialize::ads_txt_to_text(&fetched.ads_txt))?;
+    }
+
+    Ok(if fetched.errors.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_WARNINGS
+    })
+}
+
+fn diff(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = parse_fetch_args(args, &["fetch", "against", "format", "ignore"])?;
+    let json = json_output(&args)?;
+    let equality = args
+        .values("ignore")
+        .iter()
+        .flat_map(|fields| fields.split(','))
+        .try_fold(RecordEquality::new(), |equality, name| {
+            RecordField::from_name(name)
+                .map(|field| equality.ignoring(field))
+                .ok_or_else(|| format!("Unknown record field: {}", name))
+        })?;
+
+    let (old, new) = match (args.value("fetch"), args.value("against")) {
+        (Some(domain), Some(baseline)) if args.positional.is_empty() => {
+            let fetched = fetch_ads_txt(&args, &crawler(&args)?, domain)?;
+            let (baseline, _) = AdsTxt::parse_lenient(&read_file(baseline)?);
+            (baseline, fetched.ads_txt)
+        }
+        (None, None) => match args.positional.as_slice() {
+            [old, new] => (
+                AdsTxt::parse_lenient(&read_file(old)?).0,
+                AdsTxt::parse_lenient(&read_file(new)?).0,
+            ),
+            _ => return Err(format!("Expected OLD and NEW file arguments\n\n{}", USAGE).into()),
+        },
+        _ => return Err(format!("Expected --fetch DOMAIN --against FILE\n\n{}", USAGE).into()),
+    };
+
+    let diff = AdsTxtDiff::between_with_equality(&old, &new, &equality);
+    if json {
+        writeln!(out, "{}", diff.to_json())?;
+    } else {
+        write!(out, "{}", diff)?;
+    }
+    Ok(if diff.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_WARNINGS
+    })
+}
+
+fn fmt(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = Args::parse(args, &["format"], &["check"])?;
+    let json = json_output(&args)?;
+    let path = single(&args.positional, "FILE")?;
+
+    let text = read_file(path)?;
+    let formatted = format(&text);
+    let is_formatted = formatted == text;
+
+    if json {
+        let mut report = vec![
+            ("file".to_string(), JsonValue::string(path)),
+            ("formatted".to_string(), JsonValue::Bool(is_formatted)),
+        ];
+        if path == "-" && !args.flag("check") {
+            report.push(("output".to_string(), JsonValue::string(&formatted)));
+        }
+        writeln!(out, "{}", JsonValue::Object(report))?;
+    }
+
+    if args.flag("check") {
+        if !is_formatted && !json {
+            writeln!(out, "{} is not formatted", path)?;
+        }
+        return Ok(if is_formatted { EXIT_OK } else { EXIT_WARNINGS });
+    }
+
+    if path == "-" {
+        if !json {
+            write!(out, "{}", formatted)?;
+        }
+    } else if !is_formatted {
+        fs::write(path, formatted).map_err(|e| format!("Error writing {}: {}", path, e))?;
+    }
+    Ok(EXIT_OK)
+}
+
+fn stats(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = Args::parse(args, &["format", "top"], &[])?;
+    if args.positional.is_empty() {
+        return Err(format!("Expected at least one FILE argument\n\n{}", USAGE).into());
+    }
+    let top = args.parsed_value("top")?.unwrap_or(10);
+
+    let mut stats = AdsTxtStats::new();
+    for path in &args.positional {
+        stats.add(&AdsTxt::parse_lenient(&read_file(path)?).0);
+    }
+
+    match args.value("format").unwrap_or("table") {
+        "json" => writeln!(out, "{}", stats.to_json(top))?,
+        "table" | "text" => {
+            let share = |count: usize| match stats.records {
+                0 => 0.0,
+                records => 100.0 * count as f64 / records as f64,
+            };
+            writeln!(out, "{:<30} {:>8}", "files", stats.files)?;
+            writeln!(out, "{:<30} {:>8}", "records", stats.records)?;
+            writeln!(
+                out,
+                "{:<30} {:>8} {:>5.1}%",
+                "DIRECT",
+                stats.direct,
+                share(stats.direct)
+            )?;
+            writeln!(
+                out,
+                "{:<30} {:>8} {:>5.1}%",
+                "RESELLER",
+                stats.reseller,
+                share(stats.reseller)
+            )?;
+
+            writeln!(out, "\n{:<30} {:>8}", "ad system", "records")?;
+            for (ad_system, count) in stats.top_ad_systems(top) {
+                writeln!(out, "{:<30} {:>8}", ad_system, count)?;
+            }
+
+            writeln!(out, "\n{:<30} {:>8}", "variable", "uses")?;
+            for (name, count) in stats.top_variables() {
+                writeln!(out, "{:<30} {:>8}", name, count)?;
+            }
+        }
+        format => return Err(format!("Unknown format: {}", format).into()),
+    }
+    Ok(0)
+}
+
+/// Reads domains from the first column of a CSV file or a plain list,
+/// skipping blank lines, comments and a header row
+fn read_domains(path: &str) -> Result<Vec<String>, String> {
+    let domains = read_file(path)?
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split(',').next())
+        .map(|domain| domain.trim().trim_matches('"').to_string())
+        .filter(|domain| domain.contains('.'))
+        .collect();
+    Ok(domains)
+}
+
+fn crawl(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = parse_fetch_args(args, &["input", "out", "concurrency", "format"])?;
+
+    let input = match args.value("input") {
+        Some(input) => input,
+        None => return Err(format!("Expected --input FILE\n\n{}", USAGE).into()),
+    };
+    let json = match args.value("format") {
+        None | Some("jsonl") => false,
+        Some("json") => true,
+        Some(format) => {
+            return Err(format!("Unknown format: {} (expected jsonl or json)", format).into())
+        }
+    };
+    let domains = read_domains(input)?;
+    let concurrency = args.parsed_value("concurrency")?.unwrap_or(16);
+
+    let mut file;
+    let writer: &mut dyn Write = match args.value("out") {
+        Some(path) => {
+            let created =
+                fs::File::create(path).map_err(|e| format!("Error creating {}: {}", path, e))?;
+            file = io::BufWriter::new(created);
+            &mut file
+        }
+        None => out,
+    };
+
+    let mut summary = CrawlSummary::default();
+    let mut results = vec![];
+    let stream = Arc::new(crawler(&args)?).crawl_stream(domains, concurrency, concurrency);
+    for (domain, result) in stream {
+        if let CrawlResult::Fetched(f) = &result {
+            cache_store(&args, f);
+        }
+        summary.add(&result);
+
+        if json {
+            results.push(result.to_json(&domain));
+        } else {
+            writeln!(writer, "{}", result.to_json(&domain))?;
+        }
+    }
+
+    if json {
+        let counts = JsonValue::Object(vec![
+            ("domains".to_string(), JsonValue::number(summary.total)),
+            ("fetched".to_string(), JsonValue::number(summary.fetched)),
+            ("failed".to_string(), JsonValue::number(summary.failed)),
+            (
+                "skipped".to_string(),
+                JsonValue::number(summary.skipped_by_robots),
+            ),
+        ]);
+        let report = JsonValue::Object(vec![
+            ("summary".to_string(), counts),
+            ("results".to_string(), JsonValue::Array(results)),
+        ]);
+        writeln!(writer, "{}", report)?;
+    }
+    writer.flush()?;
+
+    eprintln!("Crawled {}", summary);
+    Ok(if summary.failed == 0 {
+        EXIT_OK
+    } else {
+        EXIT_FETCH_FAILED
+    })
+}
+
+fn check(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = parse_fetch_args(
+        args,
+        &[
+            "system",
+            "seller-id",
+            "relation",
+            "publisher-ids",
+            "store-url",
+            "format",
+        ],
+    )?;
+    let json = json_output(&args)?;
+    let policy = publisher_id_policy(&args)?;
+
+    let (system, seller_id) = match (args.value("system"), args.value("seller-id")) {
+        (Some(system), Some(seller_id)) => (system, seller_id),
+        _ => return Err(format!("Expected --system DOMAIN --seller-id ID\n\n{}", USAGE).into()),
+    };
+    let relation = match args.value("relation").map(str::to_uppercase) {
+        Some(relation) if relation == "DIRECT" || relation == "RESELLER" => Some(relation),
+        Some(relation) => return Err(format!("Invalid relation: {}", relation).into()),
+        None => None,
+    };
+
+    let crawler = crawler(&args)?;
+    let domain = &target_domain(&args, &crawler)?;
+    let fetched = fetch_ads_txt(&args, &crawler, domain)?;
+    let relation = relation.as_deref();
+    let authorized = if json {
+        let ads_txt = &fetched.ads_txt;
+        let report = authorization_json(domain, ads_txt, system, seller_id, relation, &policy);
+        writeln!(out, "{}", report)?;
+        seller_records(ads_txt, system, seller_id, relation, &policy).1
+    } else {
+        let ads_txt = &fetched.ads_txt;
+        report_authorization(out, domain, ads_txt, system, seller_id, relation, &policy)?
+    };
+    Ok(if authorized { EXIT_OK } else { EXIT_ERRORS })
+}
+
+/// Prints whether the file authorizes the seller account, with details
+fn report_authorization(
+    out: &mut dyn Write,
+    domain: &str,
+    ads_txt: &AdsTxt,
+    system: &str,
+    seller_id: &str,
+    relation: Option<&str>,
+    policy: &PublisherIdPolicy,
+) -> io::Result<bool> {
+    let (records, authorized) = seller_records(ads_txt, system, seller_id, relation, policy);
+    let matching = records
+        .iter()
+        .find(|r| relation.is_none_or(|relation| r.acc_relation().as_str() == relation));
+
+    if let (true, Some(record)) = (authorized, matching) {
+        writeln!(out, "AUTHORIZED: {} lists {}", domain, record)?;
+        return Ok(true);
+    }
+
+    write!(out, "NOT AUTHORIZED: {} ", domain)?;
+    if let Some(record) = records.first() {
+        writeln!(
+            out,
+            "lists {} as {}, not {}",
+            seller_id,
+            record.acc_relation().as_str(),
+            relation.unwrap_or_default()
+        )?;
+    } else {
+        writeln!(out, "does not list seller {} on {}", seller_id, system)?;
+        let others: Vec<&str> = ads_txt
+            .records
+            .iter()
+            .filter(|r| r.domain().eq_ignore_ascii_case(system.trim()))
+            .map(|r| r.publisher_id())
+            .collect();
+        if !others.is_empty() {
+            writeln!(out, "Sellers listed on {}: {}", system, others.join(", "))?;
+        }
+    }
+    Ok(false)
+}
+
+/// Fetches the sellers.json files of the ad systems listed in the file, in
+/// parallel, returning them keyed by lowercased domain along with the ad
+/// systems whose file could not be fetched
+fn fetch_sellers_json(
+    crawler: &Crawler<TcpHttpClient>,
+    ads_txt: &AdsTxt,
+    concurrency: usize,
+) -> (HashMap<String, SellersJson>, Vec<(String, String)>) {
+    let mut systems: Vec<String> = vec![];
+    for record in &ads_txt.records {
+        let system = record.domain().to_lowercase();
+        if !systems.contains(&system) {
+            systems.push(system);
+        }
+    }
+
+    let mut batches: Vec<Vec<String>> = vec![vec![]; concurrency.clamp(1, systems.len().max(1))];
+    let batch_count = batches.len();
+    for (i, system) in systems.iter().enumerate() {
+        batches[i % batch_count].push(system.clone());
+    }
+
+    let results: Vec<(String, FetchResult<SellersJson>)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = batches
+            .into_iter()
+            .map(|batch| {
+                scope.spawn(move || {
+                    batch
+                        .into_iter()
+                        .map(|system| {
+                            let result = crawler.fetch_sellers_json(&system);
+                            (system, result)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("sellers.json fetcher panicked"))
+            .collect()
+    });
+
+    let mut sellers = HashMap::new();
+    let mut failures = vec![];
+    for (system, result) in results {
+        match result {
+            Ok(sellers_json) => {
+                sellers.insert(system, sellers_json);
+            }
+            Err(e) => failures.push((system, format!("{} ({})", e, e.class()))),
+        }
+    }
+    failures.sort_by_key(|(system, _)| systems.iter().position(|s| s == system));
+    (sellers, failures)
+}
+
+fn crosscheck(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = parse_fetch_args(args, &["concurrency", "store-url", "format"])?;
+    let json = json_output(&args)?;
+    let concurrency = args.parsed_value("concurrency")?.unwrap_or(16);
+
+    let crawler = crawler(&args)?;
+    let domain = target_domain(&args, &crawler)?;
+    let fetched = fetch_ads_txt(&args, &crawler, &domain)?;
+    let (sellers, failures) = fetch_sellers_json(&crawler, &fetched.ads_txt, concurrency);
+    for (system, e) in &failures {
+        eprintln!("{}: sellers.json: {}", system, e);
+    }
+
+    let checks = cross_validate(&fetched.ads_txt, &sellers);
+    let count =
+        |matches: fn(&RecordVerdict) -> bool| checks.iter().filter(|c| matches(&c.verdict)).count();
+    let verified = count(|v| *v == RecordVerdict::Verified);
+    let missing = count(|v| *v == RecordVerdict::SellersJsonMissing);
+    let not_found = count(|v| *v == RecordVerdict::SellerNotFound);
+    let mismatches = count(|v| matches!(v, RecordVerdict::SellerTypeMismatch(_)));
+
+    if json {
+        let records = checks.iter().map(|check| {
+            let mut fields = vec![
+                (
+                    "record".to_string(),
+                    serialize::record_to_json(&check.record),
+                ),
+                (
+                    "status".to_string(),
+                    JsonValue::string(check.verdict.as_str()),
+                ),
+            ];
+            if let Some(seller) = &check.seller {
+                fields.push((
+                    "seller_type".to_string(),
+                    JsonValue::string(seller.seller_type.as_str()),
+                ));
+            }
+            JsonValue::Object(fields)
+        });
+        let failures = failures.iter().map(|(system, e)| {
+            JsonValue::Object(vec![
+                ("ad_system".to_string(), JsonValue::string(system)),
+                ("error".to_string(), JsonValue::string(e)),
+            ])
+        });
+        let summary = JsonValue::Object(vec![
+            ("records".to_string(), JsonValue::number(checks.len())),
+            ("verified".to_string(), JsonValue::number(verified)),
+            ("seller_not_found".to_string(), JsonValue::number(not_found)),
+            (
+                "seller_type_mismatch".to_string(),
+                JsonValue::number(mismatches),
+            ),
+            (
+                "sellers_json_missing".to_string(),
+                JsonValue::number(missing),
+            ),
+        ]);
+        let report = JsonValue::Object(vec![
+            ("domain".to_string(), JsonValue::string(&domain)),
+            ("url".to_string(), JsonValue::string(&fetched.url)),
+            ("summary".to_string(), summary),
+            ("records".to_string(), JsonValue::Array(records.collect())),
+            (
+                "sellers_json_errors".to_string(),
+                JsonValue::Array(failures.collect()),
+            ),
+        ]);
+        writeln!(out, "{}", report)?;
+    } else {
+        for check in &checks {
+            match &check.verdict {
+                RecordVerdict::SellerTypeMismatch(seller_type) => writeln!(
+                    out,
+                    "{:<20} {} (listed as {})",
+                    check.verdict.as_str(),
+                    check.record,
+                    seller_type.as_str()
+                )?,
+                verdict => writeln!(out, "{:<20} {}", verdict.as_str(), check.record)?,
+            }
+        }
+        writeln!(
+            out,
+            "{} records: {} verified, {} not found, {} type mismatches, {} without sellers.json",
+            checks.len(),
+            verified,
+            not_found,
+            mismatches,
+            missing
+        )?;
+    }
+
+    Ok(if not_found + mismatches > 0 {
+        EXIT_ERRORS
+    } else if missing > 0 {
+        EXIT_WARNINGS
+    } else {
+        EXIT_OK
+    })
+}
+
+/// Parses an interval such as `90`, `30s`, `15m`, `6h` or `1d`, in seconds
+/// without a unit
+fn parse_interval(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => text.split_at(i),
+        None => (text, "s"),
+    };
+
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => 0,
+    };
+    match number.parse::<u64>() {
+        Ok(n) if n > 0 && seconds > 0 => Ok(Duration::from_secs(n * seconds)),
+        _ => Err(format!("Invalid interval: {}", text)),
+    }
+}
+
+/// Webhook payload announcing a change of a watched file
+fn watch_payload(domain: &str, diff: &AdsTxtDiff) -> JsonValue {
+    let detected_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    JsonValue::Object(vec![
+        ("domain".to_string(), JsonValue::string(domain)),
+        ("detected_at".to_string(), JsonValue::number(detected_at)),
+        ("diff".to_string(), diff.to_json()),
+    ])
+}
+
+fn watch(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = parse_fetch_args(
+        args,
+        &[
+            "domains",
+            "interval",
+            "webhook",
+            "iterations",
+            "concurrency",
+            "format",
+        ],
+    )?;
+    let json = json_output(&args)?;
+
+    let domains = match args.value("domains") {
+        Some(path) => read_domains(path)?,
+        None => return Err(format!("Expected --domains FILE\n\n{}", USAGE).into()),
+    };
+    let interval = parse_interval(args.value("interval").unwrap_or("6h"))?;
+    let iterations: Option<usize> = args.parsed_value("iterations")?;
+    let concurrency = args.parsed_value("concurrency")?.unwrap_or(16);
+    let crawler = Arc::new(crawler(&args)?);
+    let webhook_client = http_client(&args)?;
+
+    let mut watcher = Watcher::new();
+    eprintln!("Watching {} domains every {:?}", domains.len(), interval);
+
+    for round in 0.. {
+        if iterations.is_some_and(|n| round >= n) {
+            break;
+        }
+        if round > 0 {
+            std::thread::sleep(interval);
+        }
+
+        let stream = Arc::clone(&crawler).crawl_stream(domains.clone(), concurrency, concurrency);
+        for (domain, result) in stream {
+            let fetched = match result {
+                CrawlResult::Fetched(fetched) => fetched,
+                CrawlResult::SkippedByRobots => {
+                    eprintln!("{}: fetching ads.txt is disallowed by robots.txt", domain);
+                    continue;
+                }
+                CrawlResult::Failed(e) => {
+                    eprintln!("{}: {} ({})", domain, e, e.class());
+                    continue;
+                }
+            };
+
+            // The first successful fetch of a domain is its baseline, unless
+            // a copy was cached by an earlier run
+            if watcher.file(&domain).is_none() {
+                if let Some(cached) = cache_load(&args, &domain) {
+                    watcher.update(&domain, cached);
+                }
+            }
+            cache_store(&args, &fetched);
+            let diff = watcher.update(&domain, fetched.ads_txt);
+            if diff.is_empty() {
+                continue;
+            }
+
+            let payload = watch_payload(&domain, &diff);
+            if json {
+                writeln!(out, "{}", payload)?;
+            } else {
+                write!(out, "{} changed:\n{}", domain, diff)?;
+            }
+            out.flush()?;
+
+            if let Some(url) = args.value("webhook") {
+                let body = payload.to_string();
+                match webhook_client.post(
+                    url,
+                    "application/json",
+                    body.as_bytes(),
+                    DEFAULT_USER_AGENT,
+                ) {
+                    Ok(response) if (200..300).contains(&response.status) => {}
+                    Ok(response) => eprintln!("{}: webhook returned {}", url, response.status),
+                    Err(e) => eprintln!("{}: {}", url, e),
+                }
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+fn serve_api(args: &[String]) -> CliResult {
+    let args = parse_fetch_args(args, &["port", "bind", "workers", "publisher-ids"])?;
+
+    let port: u16 = args.parsed_value("port")?.unwrap_or(8080);
+    let address = format!("{}:{}", args.value("bind").unwrap_or("127.0.0.1"), port);
+    let listener =
+        TcpListener::bind(&address).map_err(|e| format!("Error binding {}: {}", address, e))?;
+
+    // Clients of a server reachable from other hosts must not use it to
+    // reach the private network of the server
+    let public_only = !listener.local_addr()?.ip().is_loopback();
+    let config = serve::ServeConfig {
+        workers: args.parsed_value("workers")?.unwrap_or(16),
+        public_domains_only: public_only,
+        publisher_ids: publisher_id_policy(&args)?,
+    };
+    let client = http_client(&args)?.with_public_addresses_only(public_only);
+
+    eprintln!("Listening on http://{}", address);
+    serve::serve(
+        listener,
+        Arc::new(Crawler::new(client, crawler_config(&args))),
+        config,
+    );
+    Ok(0)
+}
+
+fn merge_files(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = Args::parse(args, &["policy", "out", "format"], &[])?;
+    let json = json_output(&args)?;
+    if args.positional.is_empty() {
+        return Err("Missing FILE".into());
+    }
+
+    let policy = match args.value("policy") {
+        Some(name) => ConflictPolicy::from_name(name).ok_or_else(|| {
+            format!(
+                "Unknown policy: {} (expected keep-first, keep-last, keep-direct or keep-all)",
+                name
+            )
+        })?,
+        None => ConflictPolicy::KeepFirst,
+    };
+
+    let mut files = vec![];
+    for path in &args.positional {
+        let (ads_txt, errors) = AdsTxt::parse_lenient(&read_file(path)?);
+        for e in &errors {
+            eprintln!("{}: {}", path, e);
+        }
+        files.push(ads_txt);
+    }
+
+    let (merged, conflicts) = merge(&files, policy);
+    if !json {
+        for conflict in &conflicts {
+            for dropped in &conflict.dropped {
+                eprintln!("conflict: kept {}, dropped {}", conflict.kept, dropped);
+            }
+        }
+    }
+
+    let text = serialize::ads_txt_to_text(&merged);
+    match args.value("out") {
+        Some(path) => fs::write(path, text).map_err(|e| format!("{}: {}", path, e))?,
+        None if !json => write!(out, "{}", text)?,
+        None => {}
+    }
+
+    if json {
+        let conflicts = conflicts
+            .iter()
+            .map(|c| {
+                JsonValue::Object(vec![
+                    ("kept".to_string(), serialize::record_to_json(&c.kept)),
+                    (
+                        "dropped".to_string(),
+                        JsonValue::Array(c.dropped.iter().map(serialize::record_to_json).collect()),
+                    ),
+                ])
+            })
+            .collect();
+        let report = JsonValue::Object(vec![
+            ("ads_txt".to_string(), serialize::ads_txt_to_json(&merged)),
+            ("conflicts".to_string(), JsonValue::Array(conflicts)),
+        ]);
+        writeln!(out, "{}", report)?;
+    }
+
+    Ok(if conflicts.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_WARNINGS
+    })
+}
+
+const CONVERT_FORMATS: &[&str] = &["txt", "json", "jsonl", "csv"];
+
+/// Format of a file given to `convert`, guessed from its extension if not
+/// given and defaulting to ads.txt text
+fn convert_format(format: Option<&str>, path: &str) -> Result<&'static str, String> {
+    let find = |format: &str| {
+        CONVERT_FORMATS
+            .iter()
+            .copied()
+            .find(|f| f.eq_ignore_ascii_case(format))
+    };
+
+    match format {
+        Some(format) => find(format).ok_or_else(|| {
+            format!(
+                "Unknown format: {} (expected txt, json, jsonl or csv)",
+                format
+            )
+        }),
+        None => Ok(path
+            .rsplit_once('.')
+            .and_then(|(_, extension)| find(extension))
+            .unwrap_or("txt")),
+    }
+}
+
+fn convert(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = Args::parse(args, &["from", "to"], &[])?;
+    let path = single(&args.positional, "FILE")?;
+    let from = convert_format(args.value("from"), path)?;
+    let to = match args.value("to") {
+        Some(to) => convert_format(Some(to), path)?,
+        None => return Err("Missing --to txt|json|jsonl|csv".into()),
+    };
+
+    let text = read_file(path)?;
+    let ads_txt = match from {
+        "json" => JsonValue::parse(&text).and_then(|json| serialize::ads_txt_from_json(&json)),
+        "jsonl" => serialize::ads_txt_from_jsonl(&text),
+        "csv" => serialize::ads_txt_from_csv(&text),
+        _ => {
+            let (ads_txt, errors) = AdsTxt::parse_lenient(&text);
+            for e in &errors {
+                eprintln!("{}: {}", path, e);
+            }
+            Ok(ads_txt)
+        }
+    }
+    .map_err(|e| format!("{}: {}", path, e))?;
+
+    match to {
+        "json" => writeln!(out, "{}", serialize::ads_txt_to_json(&ads_txt))?,
+        "jsonl" => write!(out, "{}", serialize::ads_txt_to_jsonl(&ads_txt))?,
+        "csv" => {
+            if !ads_txt.variables.is_empty() {
+                eprintln!("{}: variables are not converted to CSV", path);
+            }
+            write!(out, "{}", serialize::ads_txt_to_csv(&ads_txt))?
+        }
+        _ => write!(out, "{}", serialize::ads_txt_to_text(&ads_txt))?,
+    }
+
+    Ok(0)
+}
+
+fn lint(args: &[String], out: &mut dyn Write) -> CliResult {
+    let args = Args::parse(args, &["config", "format"], &["deny-warnings", "app"])?;
+    let json = json_output(&args)?;
+    let path = single(&args.positional, "FILE")?;
+
+    // Settings of the lint configuration override those of ads-txt.toml
+    let shared = load_config()?;
+    let config = match args.value("config") {
+        Some(path) => Toml::parse(&read_file(path)?)?,
+        None => Toml::default(),
+    };
+    let validator = Validator::new().with_file_kind(file_kind(&args));
+    let validator = validator
+        .with_rules(&shared, "lint.rules")?
+        .with_policy(&config)?;
+    let deny_warnings = args.flag("deny-warnings")
+        || config
+            .get("", "deny-warnings")
+            .or_else(|| shared.get("lint", "deny-warnings"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+    let text = read_file(path)?;
+    let lines: Vec<&str> = text.lines().collect();
+    let diagnostics = validator.validate(&text);
+
+    if json {
+        writeln!(out, "{}", diagnostics_json(path, &diagnostics))?;
+        return Ok(diagnostics_exit_code(&diagnostics, deny_warnings));
+    }
+
+    for d in &diagnostics {
+        let number = d.line.to_string();
+        let gutter = " ".repeat(number.len());
+        writeln!(
+            out,
+            "{}[{}]: {}",
+            d.severity.as_str().to_lowercase(),
+            d.rule.as_str(),
+            d.message
+        )?;
+        if d.line == 0 {
+            // Not about a line, e.g. a missing seat
+            writeln!(out, "{}--> {}\n", gutter, path)?;
+            continue;
+        }
+        writeln!(out, "{}--> {}:{}", gutter, path, d.line)?;
+        writeln!(out, "{} |", gutter)?;
+        writeln!(out, "{} | {}", number, lines[d.line - 1].trim_end())?;
+        writeln!(out, "{} |\n", gutter)?;
+    }
+
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+    writeln!(
+        out,
+        "{} errors, {} warnings",
+        count(Severity::Error),
+        count(Severity::Warning)
+    )?;
+
+    Ok(diagnostics_exit_code(&diagnostics, deny_warnings))
+}
+
+fn run(args: &[String], out: &mut dyn Write) -> CliResult {
+    match args.first().map(|a| a.as_str()) {
+        Some("parse") => parse(&args[1..], out),
+        Some("validate") => validate_file(&args[1..], out),
+        Some("fetch") => fetch(&args[1..], out),
+        Some("diff") => diff(&args[1..], out),
+        Some("fmt") => fmt(&args[1..], out),
+        Some("stats") => stats(&args[1..], out),
+        Some("crawl") => crawl(&args[1..], out),
+        Some("check") => check(&args[1..], out),
+        Some("crosscheck") => crosscheck(&args[1..], out),
+        Some("lint") => lint(&args[1..], out),
+        Some("convert") => convert(&args[1..], out),
+        Some("merge") => merge_files(&args[1..], out),
+        Some("watch") => watch(&args[1..], out),
+        Some("serve") => serve_api(&args[1..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            writeln!(out, "{}", USAGE)?;
+            Ok(0)
+        }
+        Some(command) => Err(format!("Unknown command: {}\n\n{}", command, USAGE).into()),
+        None => Err(USAGE.into()),
+    }
+}
+
+/// Whether the command line asks for JSON output, for reporting errors
+fn wants_json(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--format=json")
+        || args
+            .windows(2)
+            .any(|w| w[0] == "--format" && w[1] == "json")
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let stdout = io::stdout();
+
+    let code = match run(&args, &mut stdout.lock()) {
+        Ok(code) => code,
+        Err(e) => {
+            if wants_json(&args) {
+                let error = JsonValue::Object(vec![(
+                    "error".to_string(),
+                    JsonValue::string(&e.to_string()),
+                )]);
+                println!("{}", error);
+            } else {
+                eprintln!("ads-txt: {}", e);
+            }
+
+            if e.is::<FetchFailed>() {
+                EXIT_FETCH_FAILED
+            } else {
+                EXIT_ERRORS
+            }
+        }
+    };
+    process::exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with_file(command: &str, text: &str) -> (i32, String) {
+        run_with_file_args(command, &[], text)
+    }
+
+    /// Runs a command on a temporary file holding `text`, followed by the
+    /// given arguments, with the file's path replaced by FILE in the output
+    fn run_with_file_args(command: &str, extra: &[&str], text: &str) -> (i32, String) {
+        let path = std::env::temp_dir().join(format!(
+            "ads-txt-cli-{}-{}-{}.txt",
+            command,
+            extra.len(),
+            std::process::id()
+        ));
+        fs::write(&path, text).unwrap();
+
+        let mut out = vec![];
+        let mut args = vec![command.to_string(), path.to_string_lossy().into_owned()];
+        args.extend(extra.iter().map(|a| a.to_string()));
+        let code = run(&args, &mut out).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        (code, out.replace(&*path.to_string_lossy(), "FILE"))
+    }
+
+    #[test]
+    fn parsing_files() {
+        let (code, out) = run_with_file(
+            "parse",
+            "# comment\ngreenadexchange.com,12345,direct,d75815a79\ncontact=adops@example.com",
+        );
+        assert_eq!(code, 0);
+        assert_eq!(
+            out,
+            "greenadexchange.com, 12345, DIRECT, d75815a79\ncontact=adops@example.com\n"
+        );
+    }
+
+    #[test]
+    fn validating_files() {
+        let (code, out) = run_with_file("validate", "a.com, 1, DIRECT\na.com, 1, DIRECT\nbad");
+        assert_eq!(code, EXIT_ERRORS);
+        assert_eq!(
+            out,
+            "FILE:2: warning: Duplicate record: a.com, 1, DIRECT [duplicate-record]\n\
+             FILE:3: error: Invalid ads.txt line: bad [invalid-line]\n\
+             1 errors, 1 warnings\n"
+        );
+
+        let (code, _) = run_with_file("validate", "a.com, 1, DIRECT");
+        assert_eq!(code, EXIT_OK);
+
+        let (code, out) = run_with_file_args("validate", &["--format", "table"], "a.com, 1, OTHER");
+        assert_eq!(code, EXIT_ERRORS);
+        assert_eq!(
+            out,
+            "LINE  SEVERITY  RULE          MESSAGE\n\
+             1     error     invalid-line  Invalid account relation:  OTHER\n\
+             1 errors, 0 warnings\n"
+        );
+
+        let (code, out) = run_with_file_args("validate", &["--format", "json"], "contact=x\nc=1");
+        assert_eq!(code, EXIT_WARNINGS);
+        assert_eq!(
+            out,
+            "{\"file\":\"FILE\",\"errors\":0,\"warnings\":1,\"diagnostics\":[{\"line\":2,\"rule\":\"unknown-variable\",\"severity\":\"WARNING\",\"message\":\"Unknown variable: c\"}]}\n"
+        );
+
+        let subdomain = "subdomain=games.example.com";
+        assert_eq!(run_with_file("validate", subdomain).0, EXIT_OK);
+        assert_eq!(
+            run_with_file_args("validate", &["--app"], subdomain).0,
+            EXIT_WARNINGS
+        );
+    }
+
+    #[test]
+    fn diffing_files() {
+        let dir = std::env::temp_dir();
+        let old = dir.join(format!("ads-txt-cli-old-{}.txt", std::process::id()));
+        let new = dir.join(format!("ads-txt-cli-new-{}.txt", std::process::id()));
+        fs::write(&old, "a.com, 1, DIRECT\nb.com, 2, DIRECT").unwrap();
+        fs::write(&new, "a.com, 1, RESELLER\nc.com, 3, DIRECT").unwrap();
+
+        let mut out = vec![];
+        let mut args = vec![
+            "diff".to_string(),
+            old.to_string_lossy().into_owned(),
+            new.to_string_lossy().into_owned(),
+        ];
+        let code = run(&args, &mut out).unwrap();
+        assert_eq!(code, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "~ a.com, 1, DIRECT -> a.com, 1, RESELLER\n- b.com, 2, DIRECT\n+ c.com, 3, DIRECT\n"
+        );
+
+        let mut out = vec![];
+        args.extend([
+            "--ignore".to_string(),
+            "cert-authority,relation".to_string(),
+        ]);
+        run(&args, &mut out).unwrap();
+        args.push("--ignore=owner".to_string());
+        assert!(run(&args, &mut vec![]).is_err());
+        fs::remove_file(&old).unwrap();
+        fs::remove_file(&new).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "- b.com, 2, DIRECT\n+ c.com, 3, DIRECT\n"
+        );
+    }
+
+    #[test]
+    fn formatting_files() {
+        let path = std::env::temp_dir().join(format!("ads-txt-cli-fmt-{}.txt", std::process::id()));
+        fs::write(&path, "b.com, 1, direct\na.com, 2, DIRECT\n").unwrap();
+        let path_arg = path.to_string_lossy().into_owned();
+
+        let mut out = vec![];
+        let check = vec!["fmt".to_string(), path_arg.clone(), "--check".to_string()];
+        assert_eq!(run(&check, &mut out).unwrap(), 1);
+
+        assert_eq!(run(&["fmt".to_string(), path_arg], &mut out).unwrap(), 0);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "a.com, 2, DIRECT\nb.com, 1, DIRECT\n"
+        );
+        assert_eq!(run(&check, &mut out).unwrap(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn summarizing_files() {
+        let (code, out) = run_with_file(
+            "stats",
+            "a.com, 1, DIRECT\nb.com, 2, RESELLER\nA.com, 3, DIRECT",
+        );
+        assert_eq!(code, 0);
+        assert!(out.starts_with("files                                 1\n"));
+        assert!(out.contains("DIRECT                                2  66.7%\n"));
+        assert!(out.contains("a.com                                 2\n"));
+    }
+
+    #[test]
+    fn reading_domain_lists() {
+        let path =
+            std::env::temp_dir().join(format!("ads-txt-cli-domains-{}.csv", std::process::id()));
+        fs::write(
+            &path,
+            "domain,rank\nexample.com,1\n\"other.com\",2\n# skipped.com\n\n",
+        )
+        .unwrap();
+
+        let domains = read_domains(&path.to_string_lossy()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(domains, vec!["example.com", "other.com"]);
+    }
+
+    #[test]
+    fn reporting_authorization() {
+        let ads_txt = AdsTxt::parse("ssp.com, 1, DIRECT\nssp.com, 2, RESELLER").unwrap();
+        let report = |seller_id, relation| {
+            let mut out = vec![];
+            let authorized = report_authorization(
+                &mut out,
+                "a.com",
+                &ads_txt,
+                "SSP.com",
+                seller_id,
+                relation,
+                &PublisherIdPolicy::default(),
+            )
+            .unwrap();
+            (authorized, String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(
+            report("1", None),
+            (
+                true,
+                "AUTHORIZED: a.com lists ssp.com, 1, DIRECT\n".to_string()
+            )
+        );
+        assert_eq!(
+            report("1", Some("RESELLER")),
+            (
+                false,
+                "NOT AUTHORIZED: a.com lists 1 as DIRECT, not RESELLER\n".to_string()
+            )
+        );
+        assert_eq!(
+            report("3", None),
+            (
+                false,
+                "NOT AUTHORIZED: a.com does not list seller 3 on SSP.com\nSellers listed on SSP.com: 1, 2\n"
+                    .to_string()
+            )
+        );
+
+        let ads_txt = AdsTxt::parse("ssp.com, AB, DIRECT").unwrap();
+        let policy = PublisherIdPolicy::new(IdComparison::CaseInsensitive);
+        assert!(seller_records(&ads_txt, "ssp.com", "ab", None, &policy).1);
+        assert!(
+            !seller_records(
+                &ads_txt,
+                "ssp.com",
+                "ab",
+                None,
+                &PublisherIdPolicy::default()
+            )
+            .1
+        );
+    }
+
+    #[test]
+    fn linting_files() {
+        let (code, out) = run_with_file("lint", "a.com, 1, DIRECT\ncolour=blue");
+        assert_eq!(code, EXIT_WARNINGS);
+        assert_eq!(
+            out,
+            "warning[unknown-variable]: Unknown variable: colour\n \
+             --> FILE:2\n  |\n2 | colour=blue\n  |\n\n0 errors, 1 warnings\n"
+        );
+    }
+
+    #[test]
+    fn converting_files() {
+        assert_eq!(convert_format(None, "ads.txt"), Ok("txt"));
+        assert_eq!(convert_format(None, "seats.CSV"), Ok("csv"));
+        assert_eq!(convert_format(None, "-"), Ok("txt"));
+        assert_eq!(convert_format(Some("JSONL"), "ads.txt"), Ok("jsonl"));
+        assert!(convert_format(Some("xml"), "ads.txt").is_err());
+
+        let path =
+            std::env::temp_dir().join(format!("ads-txt-cli-convert-{}.csv", std::process::id()));
+        fs::write(
+            &path,
+            "domain,publisher_id,relation,cert_authority\na.com,1,direct,\n",
+        )
+        .unwrap();
+
+        let mut out = vec![];
+        let args: Vec<String> = vec![
+            "convert".into(),
+            path.to_string_lossy().into_owned(),
+            "--to".into(),
+            "json".into(),
+        ];
+        assert_eq!(run(&args, &mut out).unwrap(), 0);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"records\":[{\"domain\":\"a.com\",\"publisher_id\":\"1\",\"relation\":\"DIRECT\",\"cert_authority\":null}],\"variables\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn merging_files() {
+        let dir = std::env::temp_dir();
+        let a = dir.join(format!("ads-txt-cli-merge-a-{}.txt", std::process::id()));
+        let b = dir.join(format!("ads-txt-cli-merge-b-{}.txt", std::process::id()));
+        fs::write(&a, "a.com, 1, RESELLER\ncontact=x").unwrap();
+        fs::write(&b, "a.com, 1, DIRECT\nb.com, 2, DIRECT\ncontact=x").unwrap();
+
+        let mut out = vec![];
+        let args = vec![
+            "merge".to_string(),
+            a.to_string_lossy().into_owned(),
+            b.to_string_lossy().into_owned(),
+            "--policy=keep-direct".to_string(),
+        ];
+        let code = run(&args, &mut out).unwrap();
+        fs::remove_file(&a).unwrap();
+        fs::remove_file(&b).unwrap();
+
+        assert_eq!(code, EXIT_WARNINGS);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "a.com, 1, DIRECT\nb.com, 2, DIRECT\ncontact=x\n"
+        );
+
+        let args: Vec<String> = vec!["merge".into(), "-".into(), "--policy".into(), "x".into()];
+        assert!(run(&args, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn parsing_intervals() {
+        assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_interval("6h"), Ok(Duration::from_secs(21600)));
+        assert_eq!(parse_interval("1d"), Ok(Duration::from_secs(86400)));
+        assert!(parse_interval("0h").is_err());
+        assert!(parse_interval("6w").is_err());
+        assert!(parse_interval("h").is_err());
+
+        let diff = AdsTxtDiff::between(
+            &AdsTxt::parse("a.com, 1, DIRECT").unwrap(),
+            &AdsTxt::empty(),
+        );
+        let payload = watch_payload("example.com", &diff);
+        assert_eq!(
+            payload.get("domain").and_then(|d| d.as_str()),
+            Some("example.com")
+        );
+        assert_eq!(payload.get("diff"), Some(&diff.to_json()));
+    }
+
+    #[test]
+    fn applying_configuration() {
+        let config = Toml::parse(
+            "[fetch]\nuser-agent = \"bot\"\ntimeout = 30\nrobots = true\nconcurrency = 4",
+        )
+        .unwrap();
+        let options = ["timeout", "user-agent"];
+
+        let mut args = Args::parse(&["--timeout=5".to_string()], &options, FETCH_FLAGS).unwrap();
+        apply_fetch_config(&mut args, &config, &options, FETCH_FLAGS).unwrap();
+        assert_eq!(args.value("timeout"), Some("5"));
+        assert_eq!(args.value("user-agent"), Some("bot"));
+        assert_eq!(args.value("concurrency"), None);
+        assert!(args.flag("robots"));
+
+        for text in &["[fetch]\nother = 1", "[fetch]\ntimeout = [1]"] {
+            let config = Toml::parse(text).unwrap();
+            assert!(apply_fetch_config(&mut Args::default(), &config, &options, &[]).is_err());
+        }
+
+        assert_eq!(
+            cache_path("cache", "Example.com:8080/x", FileKind::AdsTxt),
+            Path::new("cache").join("example.com_8080_x.txt")
+        );
+        assert_eq!(
+            cache_path("cache", "example.com", FileKind::AppAdsTxt),
+            Path::new("cache").join("example.com.app-ads.txt")
+        );
+    }
+
+    #[test]
+    fn printing_json() {
+        let (code, out) = run_with_file_args("parse", &["--format=json"], "a.com, 1, DIRECT\nbad");
+        assert_eq!(code, EXIT_WARNINGS);
+        assert_eq!(
+            out,
+            "{\"file\":\"FILE\",\"ads_txt\":{\"records\":[{\"domain\":\"a.com\",\"publisher_id\":\"1\",\"relation\":\"DIRECT\",\"cert_authority\":null}],\"variables\":[]},\"errors\":[\"Invalid ads.txt line: bad\"]}\n"
+        );
+
+        let (code, out) = run_with_file_args(
+            "fmt",
+            &["--check", "--format=json"],
+            "b.com, 1, DIRECT\na.com, 1, DIRECT\n",
+        );
+        assert_eq!(code, EXIT_WARNINGS);
+        assert_eq!(out, "{\"file\":\"FILE\",\"formatted\":false}\n");
+
+        let ads_txt = AdsTxt::parse("ssp.com, 1, DIRECT").unwrap();
+        assert_eq!(
+            authorization_json(
+                "a.com",
+                &ads_txt,
+                "ssp.com",
+                "1",
+                Some("RESELLER"),
+                &PublisherIdPolicy::default()
+            )
+            .to_string(),
+            r#"{"domain":"a.com","system":"ssp.com","seller_id":"1","relation":"RESELLER","authorized":false,"records":[{"domain":"ssp.com","publisher_id":"1","relation":"DIRECT","cert_authority":null}]}"#
+        );
+
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert!(wants_json(&args(&["fetch", "a.com", "--format", "json"])));
+        assert!(wants_json(&args(&["fetch", "--format=json", "a.com"])));
+        assert!(!wants_json(&args(&["fetch", "a.com"])));
+        assert!(run(&args(&["parse", "-", "--format", "xml"]), &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn rejecting_bad_usage() {
+        let mut out = vec![];
+        assert!(run(&["frobnicate".to_string()], &mut out).is_err());
+        assert!(run(&["parse".to_string()], &mut out).is_err());
+        let args: Vec<String> = vec!["diff".into(), "--fetch".into(), "a.com".into()];
+        assert!(run(&args, &mut out).is_err());
+        assert!(run(&["crosscheck".to_string()], &mut out).is_err());
+        assert_eq!(run(&["help".to_string()], &mut out).unwrap(), 0);
+    }
+}
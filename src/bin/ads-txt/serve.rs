@@ -0,0 +1,509 @@
+//! HTTP API exposing fetching, validation and authorization checks as JSON
+//!
+//! - `GET /fetch?domain=D` fetches and parses the ads.txt file of a domain
+//! - `POST /validate` validates the ads.txt file sent as the request body, as
+//!   app-ads.txt if the server fetches app-ads.txt files
+//! - `GET /check?domain=D&system=S&seller_id=ID[&relation=R]` checks whether
+//!   a domain's ads.txt file authorizes a seller account
+//!
+//! `/fetch` and `/check` make the server request any domain on behalf of its
+//! clients, so `domain` must be a bare host name. When listening on a
+//! non-loopback address, `ServeConfig::public_domains_only` refuses domains
+//! which do not resolve or resolve to non-public addresses, and the crawler's
+//! `TcpHttpClient::with_public_addresses_only` refuses to connect to any such
+//! address, be it of a redirect, the www fallback, robots.txt or a domain
+//! resolving differently by the time it is fetched.
+
+use crate::{authorization_json, fetched_json};
+use rs_ads_txt::fetch::{is_public_address, CrawlResult, Crawler, FetchedAdsTxt, HttpClient};
+use rs_ads_txt::findings::Severity;
+use rs_ads_txt::json::JsonValue;
+use rs_ads_txt::publisher_id::PublisherIdPolicy;
+use rs_ads_txt::validate::Validator;
+use rs_ads_txt::{is_valid_domain, FileKind};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Largest request body accepted, the size of a large ads.txt file
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+/// Settings of the server
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ServeConfig {
+    /// Number of connections handled at once, further ones waiting to be
+    /// accepted
+    pub workers: usize,
+    /// Whether to refuse fetching domains which do not resolve, or resolve
+    /// to loopback, private or link-local addresses
+    pub public_domains_only: bool,
+    /// Comparison of seller ids by `/check`
+    pub publisher_ids: PublisherIdPolicy,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            workers: 16,
+            public_domains_only: false,
+            publisher_ids: PublisherIdPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    fn param(&self, name: &str) -> Result<&str, (u16, JsonValue)> {
+        match self.query.iter().rev().find(|(k, _)| k == name) {
+            Some((_, value)) if !value.trim().is_empty() => Ok(value.trim()),
+            _ => Err(error(400, &format!("Missing query parameter: {}", name))),
+        }
+    }
+}
+
+/// Decodes `%XX` escapes and `+` as a space
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit() =>
+            {
+                let hex = |b: u8| (b as char).to_digit(16).unwrap_or(0) as u8;
+                decoded.push(hex(bytes[i + 1]) * 16 + hex(bytes[i + 2]));
+                i += 2;
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, String> {
+    let mut line = String::new();
+    reader
+        .take(8 * 1024)
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    if !line.ends_with('\n') {
+        return Err("Truncated request".to_string());
+    }
+    Ok(line.trim_end().to_string())
+}
+
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, String> {
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target),
+        _ => return Err(format!("Invalid request line: {}", request_line)),
+    };
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (target, ""),
+    };
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((k, v)) => (percent_decode(k), percent_decode(v)),
+            None => (percent_decode(pair), String::new()),
+        })
+        .collect();
+
+    let mut content_length = 0;
+    loop {
+        let header = read_line(reader)?;
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid content length: {}", value.trim()))?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_SIZE {
+        return Err("Request body too large".to_string());
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        body,
+    })
+}
+
+fn error(status: u16, message: &str) -> (u16, JsonValue) {
+    (
+        status,
+        JsonValue::Object(vec![("error".to_string(), JsonValue::string(message))]),
+    )
+}
+
+/// Whether a domain resolves, and only to public addresses. Domains which
+/// do not resolve are refused rather than left to fail the fetch.
+fn resolves_publicly(domain: &str) -> bool {
+    match (domain.trim_end_matches('.'), 80).to_socket_addrs() {
+        Ok(addresses) => {
+            let addresses: Vec<_> = addresses.collect();
+            !addresses.is_empty() && addresses.iter().all(|a| is_public_address(a.ip()))
+        }
+        Err(_) => false,
+    }
+}
+
+fn fetch<C: HttpClient>(
+    crawler: &Crawler<C>,
+    config: &ServeConfig,
+    domain: &str,
+) -> Result<FetchedAdsTxt, (u16, JsonValue)> {
+    if !is_valid_domain(domain) {
+        return Err(error(400, &format!("Invalid domain: {}", domain)));
+    }
+    if config.public_domains_only && !resolves_publicly(domain) {
+        return Err(error(403, "Fetching non-public domains is disabled"));
+    }
+
+    match crawler.fetch(domain) {
+        CrawlResult::Fetched(fetched) => Ok(fetched),
+        CrawlResult::SkippedByRobots => {
+            Err(error(403, "Fetching ads.txt is disallowed by robots.txt"))
+        }
+        CrawlResult::Failed(e) => Err((
+            502,
+            JsonValue::Object(vec![
+                ("error".to_string(), JsonValue::string(&e.to_string())),
+                ("class".to_string(), JsonValue::string(e.class())),
+            ]),
+        )),
+    }
+}
+
+fn fetch_endpoint<C: HttpClient>(
+    crawler: &Crawler<C>,
+    config: &ServeConfig,
+    request: &Request,
+) -> Result<JsonValue, (u16, JsonValue)> {
+    let fetched = fetch(crawler, config, request.param("domain")?)?;
+    Ok(fetched_json(&fetched))
+}
+
+fn validate_endpoint(file: FileKind, request: &Request) -> Result<JsonValue, (u16, JsonValue)> {
+    let text = std::str::from_utf8(&request.body)
+        .map_err(|_| error(400, "Request body is not valid UTF-8"))?;
+    let diagnostics = Validator::new().with_file_kind(file).validate(text);
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+
+    Ok(JsonValue::Object(vec![
+        (
+            "errors".to_string(),
+            JsonValue::number(count(Severity::Error)),
+        ),
+        (
+            "warnings".to_string(),
+            JsonValue::number(count(Severity::Warning)),
+        ),
+        (
+            "diagnostics".to_string(),
+            JsonValue::Array(diagnostics.iter().map(|d| d.to_json()).collect()),
+        ),
+    ]))
+}
+
+fn check_endpoint<C: HttpClient>(
+    crawler: &Crawler<C>,
+    config: &ServeConfig,
+    request: &Request,
+) -> Result<JsonValue, (u16, JsonValue)> {
+    let domain = request.param("domain")?;
+    let system = request.param("system")?;
+    let seller_id = request.param("seller_id")?;
+    let relation = match request.param("relation").ok().map(str::to_uppercase) {
+        Some(relation) if relation == "DIRECT" || relation == "RESELLER" => Some(relation),
+        Some(relation) => return Err(error(400, &format!("Invalid relation: {}", relation))),
+        None => None,
+    };
+
+    let fetched = fetch(crawler, config, domain)?;
+    Ok(authorization_json(
+        domain,
+        &fetched.ads_txt,
+        system,
+        seller_id,
+        relation.as_deref(),
+        &config.publisher_ids,
+    ))
+}
+
+/// Handles a request, returning the response status and JSON body
+pub fn handle<C: HttpClient>(
+    crawler: &Crawler<C>,
+    config: &ServeConfig,
+    request: &Request,
+) -> (u16, JsonValue) {
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/fetch") => fetch_endpoint(crawler, config, request),
+        ("POST", "/validate") => validate_endpoint(crawler.config().file, request),
+        ("GET", "/check") => check_endpoint(crawler, config, request),
+        (_, "/fetch") | (_, "/validate") | (_, "/check") => Err(error(405, "Method not allowed")),
+        _ => Err(error(404, "Not found")),
+    };
+
+    match result {
+        Ok(json) => (200, json),
+        Err(response) => response,
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Bad Gateway",
+    }
+}
+
+fn respond<C: HttpClient>(
+    crawler: &Crawler<C>,
+    config: &ServeConfig,
+    stream: &TcpStream,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+
+    let (status, json) = match read_request(&mut BufReader::new(stream)) {
+        Ok(request) => handle(crawler, config, &request),
+        Err(e) => error(400, &e),
+    };
+
+    let body = json.to_string();
+    let mut writer = stream;
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    )
+}
+
+/// Serves requests on `config.workers` threads. Connections beyond those
+/// being handled wait in the listener's backlog, and failures to accept one
+/// are logged without stopping the server.
+pub fn serve<C>(listener: TcpListener, crawler: Arc<Crawler<C>>, config: ServeConfig)
+where
+    C: HttpClient + Send + Sync + 'static,
+{
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(0);
+    let receiver = Arc::new(Mutex::new(receiver));
+    let config = Arc::new(config);
+
+    for _ in 0..config.workers.max(1) {
+        let receiver = Arc::clone(&receiver);
+        let crawler = Arc::clone(&crawler);
+        let config = Arc::clone(&config);
+        thread::spawn(move || loop {
+            let stream = match receiver.lock().map(|r| r.recv()) {
+                Ok(Ok(stream)) => stream,
+                _ => return,
+            };
+            if let Err(e) = respond(&crawler, &config, &stream) {
+                eprintln!("Error responding to request: {}", e);
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if sender.send(stream).is_err() {
+                    return;
+                }
+            }
+            Err(e) => eprintln!("Error accepting connection: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rs_ads_txt::fetch::{CrawlerConfig, FetchError, FetchResult, HttpResponse};
+
+    struct FixedClient;
+
+    impl HttpClient for FixedClient {
+        fn get(&self, url: &str, _user_agent: &str) -> FetchResult<HttpResponse> {
+            match url {
+                "http://example.com/ads.txt" => Ok(HttpResponse::new(
+                    200,
+                    &[("Content-Type", "text/plain")],
+                    b"ssp.com, 1, DIRECT\nbroken",
+                )),
+                _ => Err(FetchError::NotFound),
+            }
+        }
+    }
+
+    fn request_with(config: &ServeConfig, text: &str) -> (u16, String) {
+        let crawler = Crawler::new(FixedClient, CrawlerConfig::default());
+        let request = read_request(&mut text.as_bytes()).unwrap();
+        let (status, json) = handle(&crawler, config, &request);
+        (status, json.to_string())
+    }
+
+    fn request(text: &str) -> (u16, String) {
+        request_with(&ServeConfig::default(), text)
+    }
+
+    #[test]
+    fn reading_requests() {
+        let request = read_request(
+            &mut &b"POST /validate?a=x%2Cy+z&b HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcdef"[..],
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/validate");
+        assert_eq!(
+            request.query,
+            vec![
+                ("a".to_string(), "x,y z".to_string()),
+                ("b".to_string(), String::new())
+            ]
+        );
+        assert_eq!(request.body, b"abc");
+
+        assert!(read_request(&mut &b"GET\r\n\r\n"[..]).is_err());
+        assert!(read_request(&mut &b"GET / HTTP/1.1\r\nContent-Length: 9"[..]).is_err());
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%e2%82%ac%+1"), "\u{20ac}% 1");
+    }
+
+    #[test]
+    fn handling_requests() {
+        assert_eq!(
+            request("GET /fetch?domain=example.com HTTP/1.1\r\n\r\n"),
+            (
+                200,
+                r#"{"domain":"example.com","url":"http://example.com/ads.txt","ads_txt":{"records":[{"domain":"ssp.com","publisher_id":"1","relation":"DIRECT","cert_authority":null}],"variables":[]},"errors":["Invalid ads.txt line: broken"]}"#
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            request("GET /fetch?domain=missing.com HTTP/1.1\r\n\r\n"),
+            (
+                502,
+                r#"{"error":"Not found","class":"not_found"}"#.to_string()
+            )
+        );
+        assert_eq!(
+            request("GET /fetch HTTP/1.1\r\n\r\n"),
+            (
+                400,
+                r#"{"error":"Missing query parameter: domain"}"#.to_string()
+            )
+        );
+
+        let (status, json) = request("POST /validate HTTP/1.1\r\nContent-Length: 3\r\n\r\nbad");
+        assert_eq!(status, 200);
+        assert!(json.starts_with(r#"{"errors":1,"warnings":0,"diagnostics":[{"line":1"#));
+
+        let check = "GET /check?domain=example.com&system=SSP.com&seller_id=1";
+        let (status, json) = request(&format!("{} HTTP/1.1\r\n\r\n", check));
+        assert_eq!(status, 200);
+        assert!(json.contains(r#""authorized":true"#));
+        let (_, json) = request(&format!("{}&relation=reseller HTTP/1.1\r\n\r\n", check));
+        assert!(json.contains(r#""authorized":false"#));
+
+        assert_eq!(request("GET /validate HTTP/1.1\r\n\r\n").0, 405);
+        assert_eq!(request("GET / HTTP/1.1\r\n\r\n").0, 404);
+
+        let public = ServeConfig {
+            public_domains_only: true,
+            ..ServeConfig::default()
+        };
+        for domain in &[
+            "127.0.0.1",
+            "10.1.2.3",
+            "169.254.169.254",
+            "does-not-resolve.invalid",
+        ] {
+            let text = format!("GET /fetch?domain={} HTTP/1.1\r\n\r\n", domain);
+            assert_eq!(request_with(&public, &text).0, 403);
+        }
+        for domain in &[
+            "localhost",
+            "[::1]",
+            "example.com:8080",
+            "example.com%2Fpath",
+            "user%40example.com",
+        ] {
+            let text = format!("GET /fetch?domain={} HTTP/1.1\r\n\r\n", domain);
+            assert_eq!(request_with(&public, &text).0, 400);
+        }
+    }
+
+    #[test]
+    fn serving_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let crawler = Arc::new(Crawler::new(FixedClient, CrawlerConfig::default()));
+        let config = ServeConfig {
+            workers: 2,
+            ..ServeConfig::default()
+        };
+        thread::spawn(move || serve(listener, crawler, config));
+
+        // More connections than workers are all served
+        let clients: Vec<_> = (0..5)
+            .map(|_| {
+                thread::spawn(move || {
+                    let mut stream = TcpStream::connect(address).unwrap();
+                    stream
+                        .write_all(b"GET /fetch?domain=example.com HTTP/1.1\r\n\r\n")
+                        .unwrap();
+                    let mut response = String::new();
+                    stream.read_to_string(&mut response).unwrap();
+                    response
+                })
+            })
+            .collect();
+        for client in clients {
+            assert!(client.join().unwrap().starts_with("HTTP/1.1 200 OK\r\n"));
+        }
+    }
+}
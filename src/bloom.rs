@@ -0,0 +1,112 @@
+//! Probabilistic pre-filter over the seats listed in a corpus
+//!
+//! A miss is definite, so "is this seat authorized anywhere?" checks can skip
+//! index lookups for unknown seats. A hit may be a false positive and must be
+//! confirmed against the corpus.
+
+use crate::graph::normalize_domain;
+use crate::AdsTxt;
+
+fn fnv1a(basis: u64, parts: &[&str]) -> u64 {
+    let mut hash = basis;
+    for part in parts {
+        for b in part.bytes().chain(std::iter::once(0)) {
+            hash = (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SeatFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl SeatFilter {
+    /// Sizes the filter for the expected number of seats and false positive
+    /// rate, e.g. 0.01 for 1%
+    pub fn with_capacity(expected: usize, false_positive_rate: f64) -> Self {
+        let expected = expected.max(1) as f64;
+        let rate = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+
+        let num_bits = (-expected * rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / expected) * ln2).round().max(1.0) as u32;
+
+        SeatFilter {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    /// Builds a filter over every seat listed in (publisher domain, ads.txt) pairs
+    pub fn build<'a, I>(files: I, false_positive_rate: f64) -> SeatFilter
+    where
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+        I::IntoIter: Clone,
+    {
+        let files = files.into_iter();
+        let expected = files.clone().map(|(_, a)| a.records.len()).sum();
+
+        let mut filter = SeatFilter::with_capacity(expected, false_positive_rate);
+        for (_, ads_txt) in files {
+            for record in &ads_txt.records {
+                filter.insert(&record.domain, &record.publisher_id);
+            }
+        }
+        filter
+    }
+
+    /// Bit positions of a seat, by double hashing
+    fn positions(&self, ad_system: &str, seat: &str) -> impl Iterator<Item = u64> {
+        let ad_system = normalize_domain(ad_system);
+        let parts = [ad_system.as_str(), seat.trim()];
+        let h1 = fnv1a(0xcbf2_9ce4_8422_2325, &parts);
+        let h2 = fnv1a(0x8422_2325_cbf2_9ce4, &parts) | 1;
+        let num_bits = self.num_bits;
+
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    pub fn insert(&mut self, ad_system: &str, seat: &str) {
+        for bit in self.positions(ad_system, seat) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns false only if the seat is definitely not listed
+    pub fn might_contain(&self, ad_system: &str, seat: &str) -> bool {
+        self.positions(ad_system, seat)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filtering_seats() {
+        let a = AdsTxt::parse("exchange.com, 1, DIRECT\nother.com, 2, RESELLER").unwrap();
+        let b = AdsTxt::parse("www.Exchange.com, 3, DIRECT").unwrap();
+        let filter = SeatFilter::build(vec![("a.com", &a), ("b.com", &b)], 0.01);
+
+        assert!(filter.might_contain("exchange.com", "1"));
+        assert!(filter.might_contain("other.com", " 2 "));
+        assert!(filter.might_contain("EXCHANGE.COM", "3"));
+
+        let mut filter = SeatFilter::with_capacity(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert("exchange.com", &i.to_string());
+        }
+        assert!((0..1000).all(|i| filter.might_contain("exchange.com", &i.to_string())));
+
+        let false_positives = (1000..11000)
+            .filter(|i| filter.might_contain("exchange.com", &i.to_string()))
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+}
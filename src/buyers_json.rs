@@ -0,0 +1,168 @@
+//! IAB buyers.json data model and parser
+//!
+//! Spec - <https://iabtechlab.com/buyers-json-demand-chain/>
+
+use crate::json::JsonValue;
+use crate::sellers_json::{flag_field, string_field, Identifier};
+use crate::{ads_txt_error, AdsTxtError, Result};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Buyer {
+    /// Id of the buyer on the demand platform, as used in DemandChain objects
+    pub buyer_id: String,
+    /// Business name, absent for confidential buyers
+    pub name: Option<String>,
+    /// Business domain, absent for confidential buyers
+    pub domain: Option<String>,
+    pub is_confidential: bool,
+    pub comment: Option<String>,
+}
+
+impl Buyer {
+    pub fn new(buyer_id: &str) -> Self {
+        Buyer {
+            buyer_id: buyer_id.trim().to_string(),
+            name: None,
+            domain: None,
+            is_confidential: false,
+            comment: None,
+        }
+    }
+
+    pub fn from_json(value: &JsonValue) -> Result<Buyer> {
+        let buyer_id = match string_field(value, "buyer_id") {
+            Some(buyer_id) if !buyer_id.is_empty() => buyer_id,
+            _ => return ads_txt_error(&format!("Invalid buyer without buyer_id: {}", value)),
+        };
+
+        Ok(Buyer {
+            buyer_id,
+            name: string_field(value, "name"),
+            domain: string_field(value, "domain"),
+            is_confidential: flag_field(value, "is_confidential"),
+            comment: string_field(value, "comment"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BuyersJson {
+    pub contact_email: Option<String>,
+    pub contact_address: Option<String>,
+    pub version: String,
+    pub identifiers: Vec<Identifier>,
+    pub buyers: Vec<Buyer>,
+}
+
+impl BuyersJson {
+    pub fn new(buyers: &[Buyer]) -> Self {
+        BuyersJson {
+            contact_email: None,
+            contact_address: None,
+            version: "1.0".to_string(),
+            identifiers: vec![],
+            buyers: buyers.to_vec(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<BuyersJson> {
+        let (buyers_json, errors) = Self::parse_lenient(text)?;
+
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(buyers_json),
+        }
+    }
+
+    /// Parses buyers.json leniently, skipping invalid buyers and identifiers.
+    /// Fails only if the document is not a JSON object with a buyers array.
+    pub fn parse_lenient(text: &str) -> Result<(BuyersJson, Vec<AdsTxtError>)> {
+        let json = JsonValue::parse(text)?;
+        let mut errors = vec![];
+
+        let buyers = match json.get("buyers").and_then(|b| b.as_array()) {
+            Some(buyers) => buyers,
+            None => return ads_txt_error("Invalid buyers.json without buyers array"),
+        };
+
+        let mut identifiers = vec![];
+        if let Some(values) = json.get("identifiers").and_then(|i| i.as_array()) {
+            for value in values {
+                match Identifier::from_json(value) {
+                    Ok(identifier) => identifiers.push(identifier),
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+
+        let mut parsed = Vec::with_capacity(buyers.len());
+        for value in buyers {
+            match Buyer::from_json(value) {
+                Ok(buyer) => parsed.push(buyer),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let buyers_json = BuyersJson {
+            contact_email: string_field(&json, "contact_email"),
+            contact_address: string_field(&json, "contact_address"),
+            version: string_field(&json, "version").unwrap_or_else(|| "1.0".to_string()),
+            identifiers,
+            buyers: parsed,
+        };
+
+        Ok((buyers_json, errors))
+    }
+
+    /// Finds the buyer with the given id
+    pub fn buyer(&self, buyer_id: &str) -> Option<&Buyer> {
+        let buyer_id = buyer_id.trim();
+        self.buyers.iter().find(|b| b.buyer_id == buyer_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_buyers_json() {
+        let text = r#"{
+            "contact_email": "demand@dsp.com",
+            "version": "1.0",
+            "buyers": [
+                {"buyer_id": "a1b2c3", "name": "Agency One", "domain": "agencyone.com"},
+                {"buyer_id": 42, "is_confidential": 1},
+                {"name": "No id"}
+            ]
+        }"#;
+
+        assert_eq!(
+            BuyersJson::parse(text),
+            ads_txt_error(r#"Invalid buyer without buyer_id: {"name":"No id"}"#)
+        );
+
+        let (buyers_json, errors) = BuyersJson::parse_lenient(text).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            buyers_json.contact_email,
+            Some("demand@dsp.com".to_string())
+        );
+        assert_eq!(
+            buyers_json.buyer("a1b2c3"),
+            Some(&Buyer {
+                name: Some("Agency One".to_string()),
+                domain: Some("agencyone.com".to_string()),
+                ..Buyer::new("a1b2c3")
+            })
+        );
+        assert_eq!(
+            buyers_json.buyer("42"),
+            Some(&Buyer {
+                is_confidential: true,
+                ..Buyer::new("42")
+            })
+        );
+        assert!(BuyersJson::parse(r#"{"sellers": []}"#).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Decoding of fetched bodies by the charset of their Content-Type header,
+//! or by their byte order mark, which takes precedence as in browsers
+
+/// Character encoding of a fetched body
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[non_exhaustive]
+pub enum Charset {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Also used for `iso-8859-1` and `us-ascii`, as browsers do
+    Windows1252,
+}
+
+/// Characters of windows-1252 bytes 0x80 to 0x9F, which differ from latin1
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl Charset {
+    pub const ALL: &'static [Charset] = &[
+        Charset::Utf8,
+        Charset::Utf16Le,
+        Charset::Utf16Be,
+        Charset::Windows1252,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Utf16Le => "utf-16le",
+            Charset::Utf16Be => "utf-16be",
+            Charset::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// Finds a charset by a label of the Content-Type charset parameter,
+    /// case-insensitively
+    pub fn from_label(label: &str) -> Option<Charset> {
+        match label.trim().trim_matches('"').to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => Some(Charset::Utf8),
+            // Without a byte order mark UTF-16 is big-endian, RFC 2781
+            "utf-16" | "utf-16be" => Some(Charset::Utf16Be),
+            "utf-16le" => Some(Charset::Utf16Le),
+            "windows-1252" | "cp1252" | "iso-8859-1" | "iso8859-1" | "latin1" | "l1"
+            | "us-ascii" | "ascii" => Some(Charset::Windows1252),
+            _ => None,
+        }
+    }
+
+    /// Charset of the `charset` parameter of a Content-Type header value
+    pub fn from_content_type(content_type: &str) -> Option<Charset> {
+        content_type
+            .split(';')
+            .skip(1)
+            .find_map(|parameter| match parameter.split_once('=') {
+                Some((name, value)) if name.trim().eq_ignore_ascii_case("charset") => {
+                    Charset::from_label(value)
+                }
+                _ => None,
+            })
+    }
+
+    /// Charset of a byte order mark at the start of the body, and its length
+    fn sniff_bom(body: &[u8]) -> Option<(Charset, usize)> {
+        match body {
+            [0xEF, 0xBB, 0xBF, ..] => Some((Charset::Utf8, 3)),
+            [0xFF, 0xFE, ..] => Some((Charset::Utf16Le, 2)),
+            [0xFE, 0xFF, ..] => Some((Charset::Utf16Be, 2)),
+            _ => None,
+        }
+    }
+
+    /// Decodes bytes of the charset, replacing invalid sequences
+    pub fn decode_bytes(&self, bytes: &[u8]) -> String {
+        match self {
+            Charset::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Charset::Utf16Le | Charset::Utf16Be => {
+                let units = bytes.chunks(2).map(|pair| match (pair, self) {
+                    ([low, high], Charset::Utf16Le) => u16::from_le_bytes([*low, *high]),
+                    ([high, low], _) => u16::from_be_bytes([*high, *low]),
+                    // An odd trailing byte
+                    _ => 0xFFFD,
+                });
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect()
+            }
+            Charset::Windows1252 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Charset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Decodes a fetched body by its byte order mark, else by the charset of its
+/// Content-Type header, else as UTF-8. Returns the text without the byte
+/// order mark and the charset it was decoded from.
+pub fn decode_body(body: &[u8], content_type: Option<&str>) -> (String, Charset) {
+    if let Some((charset, bom_len)) = Charset::sniff_bom(body) {
+        return (charset.decode_bytes(&body[bom_len..]), charset);
+    }
+
+    let charset = content_type
+        .and_then(Charset::from_content_type)
+        .unwrap_or_default();
+    (charset.decode_bytes(body), charset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoding_bodies() {
+        let text = "exchange.com, 1, DIRECT\n";
+        assert_eq!(
+            decode_body(text.as_bytes(), None),
+            (text.to_string(), Charset::Utf8)
+        );
+
+        let mut utf8_bom = vec![0xEF, 0xBB, 0xBF];
+        utf8_bom.extend_from_slice(text.as_bytes());
+        assert_eq!(
+            decode_body(&utf8_bom, Some("text/plain; charset=iso-8859-1")),
+            (text.to_string(), Charset::Utf8)
+        );
+
+        let mut utf16le = vec![0xFF, 0xFE];
+        utf16le.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(
+            decode_body(&utf16le, Some("text/plain")),
+            (text.to_string(), Charset::Utf16Le)
+        );
+
+        let utf16be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(
+            decode_body(&utf16be, Some("text/plain; Charset=\"UTF-16\"")),
+            (text.to_string(), Charset::Utf16Be)
+        );
+
+        assert_eq!(
+            decode_body(
+                b"# Caf\xe9 \x80\ncontact=x",
+                Some("text/plain;charset=ISO-8859-1")
+            ),
+            (
+                "# Caf\u{e9} \u{20ac}\ncontact=x".to_string(),
+                Charset::Windows1252
+            )
+        );
+        assert_eq!(
+            decode_body(b"a\xe9", Some("text/plain; charset=klingon")),
+            ("a\u{FFFD}".to_string(), Charset::Utf8)
+        );
+        assert_eq!(Charset::from_label("CP1252"), Some(Charset::Windows1252));
+    }
+}
@@ -0,0 +1,198 @@
+//! Persisted progress of bulk crawls, so they can be resumed after a crash
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Progress of a crawl through a list of domains, which are crawled in
+/// order, so that saving it takes the same time however long the list is
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct CrawlCheckpoint {
+    /// Number of domains at the start of the list which have been crawled
+    pub completed: usize,
+    /// Number of domains in the list
+    pub total: usize,
+    /// Hash of the list, telling apart checkpoints of other lists
+    pub fingerprint: u64,
+}
+
+impl CrawlCheckpoint {
+    /// Checkpoint of a crawl of the domains which has not started
+    pub fn new<D: AsRef<str>>(domains: &[D]) -> Self {
+        CrawlCheckpoint {
+            completed: 0,
+            total: domains.len(),
+            fingerprint: fingerprint(domains),
+        }
+    }
+
+    /// Whether the checkpoint is of a crawl of the domains
+    pub fn matches<D: AsRef<str>>(&self, domains: &[D]) -> bool {
+        self.total == domains.len() && self.fingerprint == fingerprint(domains)
+    }
+}
+
+/// 64-bit FNV-1a hash of the domains, each followed by a line break
+fn fingerprint<D: AsRef<str>>(domains: &[D]) -> u64 {
+    domains
+        .iter()
+        .flat_map(|d| d.as_ref().bytes().chain(Some(b'\n')))
+        .fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Storage for crawl checkpoints
+pub trait CheckpointStore {
+    /// Loads the last saved checkpoint, if any
+    fn load(&mut self) -> io::Result<Option<CrawlCheckpoint>>;
+
+    fn save(&mut self, checkpoint: &CrawlCheckpoint) -> io::Result<()>;
+
+    /// Removes the saved checkpoint once a crawl has finished
+    fn clear(&mut self) -> io::Result<()>;
+}
+
+impl<S: CheckpointStore + ?Sized> CheckpointStore for &mut S {
+    fn load(&mut self) -> io::Result<Option<CrawlCheckpoint>> {
+        (**self).load()
+    }
+
+    fn save(&mut self, checkpoint: &CrawlCheckpoint) -> io::Result<()> {
+        (**self).save(checkpoint)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        (**self).clear()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCheckpointStore {
+    checkpoint: Option<CrawlCheckpoint>,
+}
+
+impl MemoryCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn checkpoint(&self) -> Option<&CrawlCheckpoint> {
+        self.checkpoint.as_ref()
+    }
+}
+
+impl CheckpointStore for MemoryCheckpointStore {
+    fn load(&mut self) -> io::Result<Option<CrawlCheckpoint>> {
+        Ok(self.checkpoint)
+    }
+
+    fn save(&mut self, checkpoint: &CrawlCheckpoint) -> io::Result<()> {
+        self.checkpoint = Some(*checkpoint);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.checkpoint = None;
+        Ok(())
+    }
+}
+
+/// Stores checkpoints in a text file with `completed <count>`,
+/// `total <count>` and `fingerprint <hex>` lines
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        FileCheckpointStore {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&mut self) -> io::Result<Option<CrawlCheckpoint>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut checkpoint = CrawlCheckpoint::default();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid checkpoint line: {}", line),
+                )
+            };
+            let (key, value) = line.split_once(' ').ok_or_else(invalid)?;
+            match key {
+                "completed" => checkpoint.completed = value.parse().map_err(|_| invalid())?,
+                "total" => checkpoint.total = value.parse().map_err(|_| invalid())?,
+                "fingerprint" => {
+                    checkpoint.fingerprint =
+                        u64::from_str_radix(value, 16).map_err(|_| invalid())?
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Some(checkpoint))
+    }
+
+    fn save(&mut self, checkpoint: &CrawlCheckpoint) -> io::Result<()> {
+        let text = format!(
+            "completed {}\ntotal {}\nfingerprint {:016x}\n",
+            checkpoint.completed, checkpoint.total, checkpoint.fingerprint
+        );
+
+        // Write to a temporary file first so a crash never leaves a torn checkpoint
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storing_checkpoints_in_files() {
+        let path = std::env::temp_dir().join(format!("ads-txt-checkpoint-{}", std::process::id()));
+        let mut store = FileCheckpointStore::new(&path);
+
+        assert_eq!(store.load().unwrap(), None);
+
+        let domains = ["a.com", "b.com", "c.com"];
+        let checkpoint = CrawlCheckpoint {
+            completed: 1,
+            ..CrawlCheckpoint::new(&domains)
+        };
+        store.save(&checkpoint).unwrap();
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded, checkpoint);
+        assert!(loaded.matches(&domains));
+        assert!(!loaded.matches(&["a.com", "b.com", "d.com"]));
+        assert!(!loaded.matches(&["a.com", "b.comc.com"]));
+
+        std::fs::write(&path, "completed a.com\n").unwrap();
+        assert_eq!(store.load().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        store.clear().unwrap();
+        assert_eq!(store.load().unwrap(), None);
+        store.clear().unwrap();
+    }
+}
@@ -0,0 +1,727 @@
+//! In-memory store of many publishers' ads.txt files with per-domain metadata
+
+#[cfg(feature = "aliases")]
+use crate::aliases::AliasDatabase;
+use crate::bloom::SeatFilter;
+use crate::graph::normalize_domain;
+use crate::reverse_index::{Listing, ReverseIndex};
+use crate::stats::{
+    duplicate_publisher_ids, CertAuthorityReport, DuplicatePublisherId, ExchangeTable,
+};
+use crate::{AdsTxt, AdsTxtError};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// 64-bit FNV-1a hash of a file's content, used to detect changes
+pub fn content_hash(content: &str) -> u64 {
+    content_hash_bytes(content.as_bytes())
+}
+
+/// `content_hash` of undecoded bytes, e.g. a response body before its
+/// charset is decoded
+pub fn content_hash_bytes(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// `content_hash` of a parsed file in a canonical form, which ignores
+/// comments, whitespace, letter case of domains, relations and cert authority
+/// ids, and the order and repetition of lines. Files with the same normalized
+/// hash authorize the same sellers.
+pub fn normalized_hash(ads_txt: &AdsTxt) -> u64 {
+    let mut lines: Vec<String> = ads_txt
+        .records
+        .iter()
+        .map(|r| {
+            format!(
+                "{},{},{},{}",
+                r.domain.to_lowercase(),
+                r.publisher_id,
+                r.acc_relation.as_str(),
+                r.cert_authority.as_deref().unwrap_or("").to_lowercase()
+            )
+        })
+        .chain(
+            ads_txt
+                .variables
+                .iter()
+                .map(|v| format!("{}={}", v.name.to_lowercase(), v.value)),
+        )
+        .collect();
+    lines.sort_unstable();
+    lines.dedup();
+    content_hash(&lines.join("\n"))
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CorpusMetadata {
+    /// Time of the last successful fetch of the file
+    pub fetched_at: SystemTime,
+    /// `content_hash` of the raw file
+    pub hash: u64,
+    /// Time of the last failed refresh since the successful fetch, if any
+    pub failed_at: Option<SystemTime>,
+}
+
+impl CorpusMetadata {
+    pub fn new(fetched_at: SystemTime, hash: u64) -> Self {
+        CorpusMetadata {
+            fetched_at,
+            hash,
+            failed_at: None,
+        }
+    }
+
+    pub fn for_content(content: &str, fetched_at: SystemTime) -> Self {
+        CorpusMetadata::new(fetched_at, content_hash(content))
+    }
+
+    /// Time since the last successful fetch, zero for fetches after `now`
+    pub fn staleness(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.fetched_at).unwrap_or_default()
+    }
+
+    /// Whether the file was last fetched successfully more than `max_age`
+    /// before `now`
+    pub fn is_stale(&self, now: SystemTime, max_age: Duration) -> bool {
+        self.staleness(now) > max_age
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CorpusEntry {
+    publisher: String,
+    pub ads_txt: AdsTxt,
+    pub metadata: CorpusMetadata,
+}
+
+impl CorpusEntry {
+    pub fn new(publisher: &str, ads_txt: AdsTxt, metadata: CorpusMetadata) -> Self {
+        CorpusEntry {
+            publisher: normalize_domain(publisher),
+            ads_txt,
+            metadata,
+        }
+    }
+
+    /// Domain of the publisher, normalized
+    pub fn publisher(&self) -> &str {
+        &self.publisher
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AdsTxtCorpus {
+    entries: BTreeMap<String, CorpusEntry>,
+    index: ReverseIndex,
+}
+
+impl AdsTxtCorpus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry, returning the one it replaces for the same publisher
+    pub fn insert(&mut self, entry: CorpusEntry) -> Option<CorpusEntry> {
+        self.index.add_ads_txt(&entry.publisher, &entry.ads_txt);
+        self.entries.insert(entry.publisher.clone(), entry)
+    }
+
+    /// Parses and adds a publisher's raw ads.txt content leniently, returning
+    /// the parse errors
+    pub fn insert_content(
+        &mut self,
+        publisher: &str,
+        content: &str,
+        fetched_at: SystemTime,
+    ) -> Vec<AdsTxtError> {
+        let (ads_txt, errors) = AdsTxt::parse_lenient(content);
+        let metadata = CorpusMetadata::for_content(content, fetched_at);
+        self.insert(CorpusEntry::new(publisher, ads_txt, metadata));
+        errors
+    }
+
+    pub fn remove(&mut self, publisher: &str) -> Option<CorpusEntry> {
+        let publisher = normalize_domain(publisher);
+        self.index.remove(&publisher);
+        self.entries.remove(&publisher)
+    }
+
+    /// Records a failed refresh of the publisher's file, keeping the file of
+    /// the last successful fetch. Returns whether the publisher is known.
+    pub fn record_failure(&mut self, publisher: &str, failed_at: SystemTime) -> bool {
+        match self.entries.get_mut(&normalize_domain(publisher)) {
+            Some(entry) => {
+                entry.metadata.failed_at = Some(failed_at);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Entries not fetched successfully within `max_age` before `now`,
+    /// stalest first, i.e. in the order to refresh them
+    pub fn stale(&self, now: SystemTime, max_age: Duration) -> Vec<&CorpusEntry> {
+        let mut stale: Vec<&CorpusEntry> = self
+            .entries
+            .values()
+            .filter(|e| e.metadata.is_stale(now, max_age))
+            .collect();
+        stale.sort_by(|a, b| {
+            a.metadata
+                .fetched_at
+                .cmp(&b.metadata.fetched_at)
+                .then_with(|| a.publisher.cmp(&b.publisher))
+        });
+        stale
+    }
+
+    pub fn get(&self, publisher: &str) -> Option<&CorpusEntry> {
+        self.entries.get(&normalize_domain(publisher))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries sorted by publisher
+    pub fn iter(&self) -> impl Iterator<Item = &CorpusEntry> {
+        self.entries.values()
+    }
+
+    /// (publisher, ads.txt) pairs, as taken by reports such as `CoverageReport`
+    pub fn files(&self) -> impl Iterator<Item = (&str, &AdsTxt)> + Clone {
+        self.entries
+            .values()
+            .map(|e| (e.publisher.as_str(), &e.ads_txt))
+    }
+
+    /// Builds a pre-filter over every seat listed in the corpus
+    pub fn seat_filter(&self, false_positive_rate: f64) -> SeatFilter {
+        SeatFilter::build(self.files(), false_positive_rate)
+    }
+
+    /// Writes the corpus to an on-disk index, see `DiskIndex::build`
+    #[cfg(feature = "disk-index")]
+    pub fn write_disk_index<P: AsRef<std::path::Path>>(
+        &self,
+        dir: P,
+    ) -> std::io::Result<crate::disk_index::DiskIndex> {
+        crate::disk_index::DiskIndex::build(dir, self.files())
+    }
+
+    /// Per-exchange record, relation and publisher counts
+    pub fn exchange_table(&self) -> ExchangeTable {
+        ExchangeTable::build(self.files())
+    }
+
+    /// Record counts per ad system like `exchange_table`, counting the
+    /// aliases of an ad system as the ad system
+    #[cfg(feature = "aliases")]
+    pub fn exchange_table_with_aliases(&self, aliases: &AliasDatabase) -> ExchangeTable {
+        let files: Vec<(&str, AdsTxt)> = self
+            .files()
+            .map(|(publisher, ads_txt)| (publisher, aliases.canonicalize(ads_txt)))
+            .collect();
+        ExchangeTable::build(
+            files
+                .iter()
+                .map(|(publisher, ads_txt)| (*publisher, ads_txt)),
+        )
+    }
+
+    /// Cert authority id usage per ad system
+    pub fn cert_authority_report(&self) -> CertAuthorityReport {
+        CertAuthorityReport::build(self.files())
+    }
+
+    /// Publisher ids listed under many ad systems, see
+    /// `stats::duplicate_publisher_ids`
+    pub fn duplicate_publisher_ids(
+        &self,
+        min_ad_systems: usize,
+        min_publishers: usize,
+    ) -> Vec<DuplicatePublisherId> {
+        duplicate_publisher_ids(self.files(), min_ad_systems, min_publishers)
+    }
+
+    pub fn index(&self) -> &ReverseIndex {
+        &self.index
+    }
+
+    /// Publishers listing the ad system, sorted
+    pub fn publishers(&self, ad_system: &str) -> Vec<&str> {
+        self.index.publishers(ad_system)
+    }
+
+    /// Every record listing the seat on the ad system, sorted by publisher
+    pub fn seat_listings(&self, ad_system: &str, seat: &str) -> Vec<&Listing> {
+        self.index.seat_listings(ad_system, seat)
+    }
+}
+
+impl Extend<CorpusEntry> for AdsTxtCorpus {
+    fn extend<I: IntoIterator<Item = CorpusEntry>>(&mut self, entries: I) {
+        for entry in entries {
+            self.insert(entry);
+        }
+    }
+}
+
+impl std::iter::FromIterator<CorpusEntry> for AdsTxtCorpus {
+    fn from_iter<I: IntoIterator<Item = CorpusEntry>>(entries: I) -> Self {
+        let mut corpus = AdsTxtCorpus::new();
+        corpus.extend(entries);
+        corpus
+    }
+}
+
+/// Corpus partitioned into shards by publisher domain hash, so that shards
+/// can be built and queried on separate threads
+#[derive(Debug, Clone)]
+pub struct ShardedCorpus {
+    shards: Vec<AdsTxtCorpus>,
+}
+
+impl ShardedCorpus {
+    pub fn new(num_shards: usize) -> Self {
+        ShardedCorpus {
+            shards: vec![AdsTxtCorpus::new(); num_shards.max(1)],
+        }
+    }
+
+    /// Partitions the entries and builds each shard on its own thread
+    pub fn build<I: IntoIterator<Item = CorpusEntry>>(num_shards: usize, entries: I) -> Self {
+        let mut partitions: Vec<Vec<CorpusEntry>> = vec![vec![]; num_shards.max(1)];
+        for entry in entries {
+            let shard = shard_of(&entry.publisher, partitions.len());
+            partitions[shard].push(entry);
+        }
+
+        let shards = std::thread::scope(|scope| {
+            let handles: Vec<_> = partitions
+                .into_iter()
+                .map(|entries| scope.spawn(move || entries.into_iter().collect::<AdsTxtCorpus>()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("corpus shard builder panicked"))
+                .collect()
+        });
+
+        ShardedCorpus { shards }
+    }
+
+    pub fn shards(&self) -> &[AdsTxtCorpus] {
+        &self.shards
+    }
+
+    /// Shard holding the publisher's file
+    pub fn shard(&self, publisher: &str) -> &AdsTxtCorpus {
+        &self.shards[shard_of(&normalize_domain(publisher), self.shards.len())]
+    }
+
+    pub fn insert(&mut self, entry: CorpusEntry) -> Option<CorpusEntry> {
+        let shard = shard_of(&entry.publisher, self.shards.len());
+        self.shards[shard].insert(entry)
+    }
+
+    pub fn remove(&mut self, publisher: &str) -> Option<CorpusEntry> {
+        let shard = shard_of(&normalize_domain(publisher), self.shards.len());
+        self.shards[shard].remove(publisher)
+    }
+
+    pub fn get(&self, publisher: &str) -> Option<&CorpusEntry> {
+        self.shard(publisher).get(publisher)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.is_empty())
+    }
+
+    /// Runs the query on every shard in parallel, returning the results in
+    /// shard order
+    pub fn query<T, F>(&self, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&AdsTxtCorpus) -> T + Sync,
+    {
+        let f = &f;
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .shards
+                .iter()
+                .map(|shard| scope.spawn(move || f(shard)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("corpus shard query panicked"))
+                .collect()
+        })
+    }
+
+    /// Publishers listing the ad system, sorted
+    pub fn publishers(&self, ad_system: &str) -> Vec<String> {
+        let mut publishers: Vec<String> = self
+            .query(|shard| {
+                shard
+                    .publishers(ad_system)
+                    .into_iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .into_iter()
+            .flatten()
+            .collect();
+        publishers.sort_unstable();
+        publishers
+    }
+
+    pub fn record_failure(&mut self, publisher: &str, failed_at: SystemTime) -> bool {
+        let shard = shard_of(&normalize_domain(publisher), self.shards.len());
+        self.shards[shard].record_failure(publisher, failed_at)
+    }
+
+    /// Publishers not fetched successfully within `max_age` before `now`,
+    /// stalest first
+    pub fn stale(&self, now: SystemTime, max_age: Duration) -> Vec<(String, SystemTime)> {
+        let mut stale: Vec<(String, SystemTime)> = self
+            .query(|shard| {
+                shard
+                    .stale(now, max_age)
+                    .into_iter()
+                    .map(|e| (e.publisher.clone(), e.metadata.fetched_at))
+                    .collect::<Vec<_>>()
+            })
+            .into_iter()
+            .flatten()
+            .collect();
+        stale.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        stale
+    }
+
+    /// Every record listing the seat on the ad system, sorted by publisher
+    pub fn seat_listings(&self, ad_system: &str, seat: &str) -> Vec<Listing> {
+        let mut listings: Vec<Listing> = self
+            .query(|shard| {
+                shard
+                    .seat_listings(ad_system, seat)
+                    .into_iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .into_iter()
+            .flatten()
+            .collect();
+        listings.sort_by(|a, b| a.publisher.cmp(&b.publisher));
+        listings
+    }
+}
+
+/// A file parsed by `parse_corpus_parallel`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedFile {
+    pub domain: String,
+    pub ads_txt: AdsTxt,
+    pub errors: Vec<AdsTxtError>,
+    /// `content_hash` of the raw file
+    pub hash: u64,
+    pub bytes: usize,
+    pub parse_time: Duration,
+}
+
+/// Files parsed by `parse_corpus_parallel` in input order, with timings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorpusParse {
+    pub files: Vec<ParsedFile>,
+    /// Wall clock time of the whole parse
+    pub elapsed: Duration,
+    /// Time spent parsing summed over all threads
+    pub parse_time: Duration,
+    pub threads: usize,
+}
+
+impl CorpusParse {
+    pub fn bytes(&self) -> usize {
+        self.files.iter().map(|f| f.bytes).sum()
+    }
+
+    pub fn records(&self) -> usize {
+        self.files.iter().map(|f| f.ads_txt.records.len()).sum()
+    }
+
+    pub fn errors(&self) -> usize {
+        self.files.iter().map(|f| f.errors.len()).sum()
+    }
+
+    /// Files parsed per second of wall clock time
+    pub fn files_per_second(&self) -> f64 {
+        self.files.len() as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    /// Builds a corpus of the parsed files, all fetched at the given time
+    pub fn into_corpus(self, fetched_at: SystemTime) -> AdsTxtCorpus {
+        self.files
+            .into_iter()
+            .map(|f| {
+                CorpusEntry::new(
+                    &f.domain,
+                    f.ads_txt,
+                    CorpusMetadata::new(fetched_at, f.hash),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Parses many (domain, content) pairs leniently on a pool of threads. Each
+/// thread takes the next unparsed file, so a few large files don't hold up
+/// the rest.
+pub fn parse_corpus_parallel<D, C>(items: &[(D, C)], threads: usize) -> CorpusParse
+where
+    D: AsRef<str> + Sync,
+    C: AsRef<str> + Sync,
+{
+    let started = Instant::now();
+    let threads = threads.clamp(1, items.len().max(1));
+    let next = AtomicUsize::new(0);
+    let parsed = Mutex::new(Vec::with_capacity(items.len()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                let mut files = vec![];
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let (domain, content) = match items.get(i) {
+                        Some((domain, content)) => (domain.as_ref(), content.as_ref()),
+                        None => break,
+                    };
+
+                    let parse_started = Instant::now();
+                    let (ads_txt, errors) = AdsTxt::parse_lenient(content);
+                    let parse_time = parse_started.elapsed();
+                    files.push((
+                        i,
+                        ParsedFile {
+                            domain: domain.to_string(),
+                            ads_txt,
+                            errors,
+                            hash: content_hash(content),
+                            bytes: content.len(),
+                            parse_time,
+                        },
+                    ));
+                }
+                parsed
+                    .lock()
+                    .expect("corpus parse results poisoned")
+                    .extend(files);
+            });
+        }
+    });
+
+    let mut parsed = parsed.into_inner().expect("corpus parse results poisoned");
+    parsed.sort_unstable_by_key(|(i, _)| *i);
+    let files: Vec<ParsedFile> = parsed.into_iter().map(|(_, f)| f).collect();
+
+    CorpusParse {
+        parse_time: files.iter().map(|f| f.parse_time).sum(),
+        files,
+        elapsed: started.elapsed(),
+        threads,
+    }
+}
+
+fn shard_of(publisher: &str, num_shards: usize) -> usize {
+    (content_hash(publisher) % num_shards as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::CoverageReport;
+
+    #[test]
+    fn storing_files() {
+        let fetched_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let mut corpus: AdsTxtCorpus = vec![
+            CorpusEntry::new(
+                "b.com",
+                AdsTxt::parse("exchange.com, 1, DIRECT").unwrap(),
+                CorpusMetadata::new(fetched_at, 1),
+            ),
+            CorpusEntry::new(
+                "www.a.com",
+                AdsTxt::parse("exchange.com, 2, RESELLER").unwrap(),
+                CorpusMetadata::new(fetched_at, 2),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(corpus.len(), 2);
+        assert_eq!(corpus.get("A.com").map(|e| e.metadata.hash), Some(2));
+        assert_eq!(corpus.publishers("exchange.com"), vec!["a.com", "b.com"]);
+
+        let content = "other.com, 3, DIRECT\nbroken";
+        let errors = corpus.insert_content("a.com", content, fetched_at);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            corpus.get("a.com").unwrap().metadata,
+            CorpusMetadata::new(fetched_at, content_hash(content))
+        );
+        assert_eq!(corpus.publishers("exchange.com"), vec!["b.com"]);
+        assert_eq!(corpus.seat_listings("other.com", "3").len(), 1);
+
+        let report = CoverageReport::build("exchange.com", &[], corpus.files());
+        assert_eq!(report.unauthorized, vec!["a.com"]);
+
+        let filter = corpus.seat_filter(0.01);
+        assert!(filter.might_contain("other.com", "3"));
+
+        assert!(corpus.remove("b.com").is_some());
+        assert!(corpus.publishers("exchange.com").is_empty());
+        assert_eq!(
+            corpus
+                .iter()
+                .map(|e| e.publisher.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a.com"]
+        );
+    }
+
+    #[test]
+    fn sharding_corpus() {
+        let entries = (0..100).map(|i| {
+            CorpusEntry::new(
+                &format!("pub{}.com", i),
+                AdsTxt::parse(&format!("exchange.com, {}, DIRECT", i % 2)).unwrap(),
+                CorpusMetadata::new(SystemTime::UNIX_EPOCH, i),
+            )
+        });
+        let mut corpus = ShardedCorpus::build(4, entries);
+
+        assert_eq!(corpus.len(), 100);
+        assert_eq!(corpus.shards().len(), 4);
+        assert!(corpus.shards().iter().all(|s| !s.is_empty()));
+        assert_eq!(corpus.get("PUB42.com").map(|e| e.metadata.hash), Some(42));
+        assert_eq!(corpus.publishers("exchange.com").len(), 100);
+
+        let listings = corpus.seat_listings("exchange.com", "1");
+        assert_eq!(listings.len(), 50);
+        assert_eq!(listings[0].publisher, "pub1.com");
+
+        let counts = corpus.query(|shard| shard.len());
+        assert_eq!(counts.iter().sum::<usize>(), 100);
+
+        assert!(corpus.remove("pub1.com").is_some());
+        assert_eq!(corpus.seat_listings("exchange.com", "1").len(), 49);
+    }
+
+    #[test]
+    fn finding_stale_files() {
+        let day = |n: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(n * 86_400);
+        let entries = (0..10).map(|i| {
+            CorpusEntry::new(
+                &format!("pub{}.com", i),
+                AdsTxt::empty(),
+                CorpusMetadata::new(day(10 + i), i),
+            )
+        });
+        let mut corpus: AdsTxtCorpus = entries.clone().collect();
+        let sharded = ShardedCorpus::build(3, entries);
+
+        let now = day(20);
+        assert_eq!(
+            corpus.get("pub3.com").unwrap().metadata.staleness(now),
+            Duration::from_secs(7 * 86_400)
+        );
+        assert_eq!(
+            corpus.get("pub3.com").unwrap().metadata.staleness(day(0)),
+            Duration::ZERO
+        );
+
+        let stale: Vec<&str> = corpus
+            .stale(now, Duration::from_secs(7 * 86_400))
+            .iter()
+            .map(|e| e.publisher.as_str())
+            .collect();
+        assert_eq!(stale, vec!["pub0.com", "pub1.com", "pub2.com"]);
+        assert_eq!(
+            sharded.stale(now, Duration::from_secs(7 * 86_400)),
+            vec![
+                ("pub0.com".to_string(), day(10)),
+                ("pub1.com".to_string(), day(11)),
+                ("pub2.com".to_string(), day(12)),
+            ]
+        );
+
+        assert!(corpus.record_failure("www.pub1.com", day(19)));
+        assert!(!corpus.record_failure("other.com", day(19)));
+        let entry = corpus.get("pub1.com").unwrap();
+        assert_eq!(entry.metadata.failed_at, Some(day(19)));
+        assert!(entry.metadata.is_stale(now, Duration::from_secs(86_400)));
+
+        corpus.insert_content("pub1.com", "", now);
+        assert_eq!(corpus.get("pub1.com").unwrap().metadata.failed_at, None);
+        assert_eq!(corpus.stale(now, Duration::from_secs(7 * 86_400)).len(), 2);
+    }
+
+    #[test]
+    fn parsing_corpus_in_parallel() {
+        let items: Vec<(String, String)> = (0..50)
+            .map(|i| {
+                let content = format!("exchange.com, {}, DIRECT\nbroken line {}", i, i);
+                (format!("pub{}.com", i), content)
+            })
+            .collect();
+
+        let parse = parse_corpus_parallel(&items, 4);
+        assert_eq!(parse.threads, 4);
+        assert_eq!(parse.files.len(), 50);
+        assert_eq!(parse.files[7].domain, "pub7.com");
+        assert_eq!(parse.files[7].ads_txt.records[0].publisher_id, "7");
+        assert_eq!(parse.files[7].hash, content_hash(&items[7].1));
+        assert_eq!(parse.records(), 50);
+        assert_eq!(parse.errors(), 50);
+        assert_eq!(
+            parse.bytes(),
+            items.iter().map(|(_, c)| c.len()).sum::<usize>()
+        );
+
+        let corpus = parse.into_corpus(SystemTime::UNIX_EPOCH);
+        assert_eq!(corpus.publishers("exchange.com").len(), 50);
+
+        let empty: &[(&str, &str)] = &[];
+        assert_eq!(parse_corpus_parallel(empty, 8).threads, 1);
+    }
+
+    #[test]
+    fn hashing_content() {
+        assert_eq!(content_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(content_hash("ab"), content_hash("ba"));
+
+        let ads_txt = AdsTxt::parse("a.com, 1, DIRECT\nb.com, 2, RESELLER, abc").unwrap();
+        let cosmetic = AdsTxt::parse(
+            "# Sellers\n  B.com,2,reseller,ABC\n\nA.COM, 1, DIRECT\na.com, 1, DIRECT",
+        )
+        .unwrap();
+        let changed = AdsTxt::parse("a.com, 1, RESELLER\nb.com, 2, RESELLER, abc").unwrap();
+        assert_eq!(normalized_hash(&ads_txt), normalized_hash(&cosmetic));
+        assert_ne!(normalized_hash(&ads_txt), normalized_hash(&changed));
+        assert_ne!(normalized_hash(&ads_txt), normalized_hash(&AdsTxt::empty()));
+    }
+}
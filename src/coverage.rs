@@ -0,0 +1,666 @@
+//! Authorization coverage of an exchange across a corpus of publisher files
+
+use crate::publisher_id::PublisherIdPolicy;
+use crate::serialize::csv_fields;
+use crate::{ads_txt_error, AccountRelation, AdsTxt, Result};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Authorization {
+    /// Our seat, i.e. the publisher id in the ads.txt record
+    pub seat: String,
+    pub relation: AccountRelation,
+    pub cert_authority: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PublisherCoverage {
+    pub publisher: String,
+    /// Every record authorizing one of our seats, in file order
+    pub authorizations: Vec<Authorization>,
+}
+
+impl PublisherCoverage {
+    pub fn has_direct(&self) -> bool {
+        self.authorizations
+            .iter()
+            .any(|a| a.relation == AccountRelation::Direct)
+    }
+
+    pub fn has_reseller(&self) -> bool {
+        self.authorizations
+            .iter()
+            .any(|a| a.relation == AccountRelation::Reseller)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CoverageReport {
+    pub exchange: String,
+    /// Publishers authorizing at least one of our seats
+    pub authorized: Vec<PublisherCoverage>,
+    /// Publishers not authorizing any of our seats
+    pub unauthorized: Vec<String>,
+}
+
+impl CoverageReport {
+    /// Builds the report for the given exchange domain and seats from
+    /// (publisher domain, ads.txt) pairs. An empty seat list matches any seat
+    /// on the exchange.
+    pub fn build<'a, I>(exchange: &str, seats: &[&str], files: I) -> CoverageReport
+    where
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+    {
+        Self::build_with_policy(exchange, seats, files, &PublisherIdPolicy::default())
+    }
+
+    /// Like `build`, comparing seats by the policy
+    pub fn build_with_policy<'a, I>(
+        exchange: &str,
+        seats: &[&str],
+        files: I,
+        policy: &PublisherIdPolicy,
+    ) -> CoverageReport
+    where
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+    {
+        let exchange = exchange.trim().to_lowercase();
+        let mut authorized = vec![];
+        let mut unauthorized = vec![];
+
+        for (publisher, ads_txt) in files {
+            let authorizations: Vec<Authorization> = ads_txt
+                .records
+                .iter()
+                .filter(|r| r.domain.eq_ignore_ascii_case(&exchange))
+                .filter(|r| {
+                    seats.is_empty()
+                        || seats
+                            .iter()
+                            .any(|s| policy.same_id(&exchange, s, &r.publisher_id))
+                })
+                .map(|r| Authorization {
+                    seat: r.publisher_id.to_string(),
+                    relation: r.acc_relation.clone(),
+                    cert_authority: r.cert_authority.as_deref().map(str::to_string),
+                })
+                .collect();
+
+            if authorizations.is_empty() {
+                unauthorized.push(publisher.to_string());
+            } else {
+                authorized.push(PublisherCoverage {
+                    publisher: publisher.to_string(),
+                    authorizations,
+                });
+            }
+        }
+
+        CoverageReport {
+            exchange,
+            authorized,
+            unauthorized,
+        }
+    }
+
+    pub fn total_publishers(&self) -> usize {
+        self.authorized.len() + self.unauthorized.len()
+    }
+
+    /// Share of publishers authorizing us, between 0 and 1
+    pub fn coverage(&self) -> f64 {
+        match self.total_publishers() {
+            0 => 0.0,
+            total => self.authorized.len() as f64 / total as f64,
+        }
+    }
+
+    /// Number of publishers authorizing us with at least one DIRECT record
+    pub fn direct_publishers(&self) -> usize {
+        self.authorized.iter().filter(|p| p.has_direct()).count()
+    }
+
+    /// Number of publishers authorizing us with at least one RESELLER record
+    pub fn reseller_publishers(&self) -> usize {
+        self.authorized.iter().filter(|p| p.has_reseller()).count()
+    }
+
+    /// Renders one CSV row per authorization:
+    /// `publisher,seat,relation,cert_authority`
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("publisher,seat,relation,cert_authority\n");
+
+        for publisher in &self.authorized {
+            for a in &publisher.authorizations {
+                csv.push_str(&format!(
+                    "{},{},{},{}\n",
+                    publisher.publisher,
+                    a.seat,
+                    a.relation.as_str(),
+                    a.cert_authority.as_deref().unwrap_or("")
+                ));
+            }
+        }
+
+        csv
+    }
+}
+
+/// Seat publishers are required to list, e.g. for a supply path to be
+/// compliant
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RequiredSeat {
+    pub ad_system: String,
+    pub seat_id: String,
+    pub relation: AccountRelation,
+    /// Required cert authority id, if any
+    pub cert_authority: Option<String>,
+}
+
+impl RequiredSeat {
+    pub fn new(ad_system: &str, seat_id: &str, relation: AccountRelation) -> Self {
+        RequiredSeat {
+            ad_system: ad_system.trim().to_lowercase(),
+            seat_id: seat_id.trim().to_string(),
+            relation,
+            cert_authority: None,
+        }
+    }
+
+    pub fn with_cert_authority(mut self, cert_authority: &str) -> Self {
+        let cert_authority = cert_authority.trim();
+        self.cert_authority = Some(cert_authority.to_string()).filter(|c| !c.is_empty());
+        self
+    }
+
+    /// How the file lists the seat. A record with the required relation and
+    /// cert authority takes precedence over any other record of the seat.
+    pub fn status_in(&self, ads_txt: &AdsTxt) -> SeatStatus {
+        self.status_in_with_policy(ads_txt, &PublisherIdPolicy::default())
+    }
+
+    /// Like `status_in`, comparing publisher ids by the policy
+    pub fn status_in_with_policy(
+        &self,
+        ads_txt: &AdsTxt,
+        policy: &PublisherIdPolicy,
+    ) -> SeatStatus {
+        let records = ads_txt.seat_records_with_policy(&self.ad_system, &self.seat_id, policy);
+        let with_relation: Vec<_> = records
+            .iter()
+            .filter(|r| r.acc_relation == self.relation)
+            .collect();
+
+        let cert_authority_matches = |cert_authority: Option<&str>| match &self.cert_authority {
+            Some(required) => cert_authority.is_some_and(|c| c.eq_ignore_ascii_case(required)),
+            None => true,
+        };
+
+        if with_relation
+            .iter()
+            .any(|r| cert_authority_matches(r.cert_authority.as_deref()))
+        {
+            SeatStatus::Listed
+        } else if let Some(record) = with_relation.first() {
+            SeatStatus::WrongCertAuthority(record.cert_authority.as_deref().map(str::to_string))
+        } else if let Some(record) = records.first() {
+            SeatStatus::WrongRelation(record.acc_relation.clone())
+        } else {
+            SeatStatus::Missing
+        }
+    }
+
+    /// Whether the file lists the seat with the required relation and cert
+    /// authority
+    pub fn is_listed_by(&self, ads_txt: &AdsTxt) -> bool {
+        self.status_in(ads_txt) == SeatStatus::Listed
+    }
+}
+
+/// Column names of required seat CSV files, as exported by ad servers
+const AD_SYSTEM_COLUMNS: &[&str] = &["domain", "ad system", "ad_system", "advertising system"];
+const SEAT_ID_COLUMNS: &[&str] = &[
+    "publisher id",
+    "publisher_id",
+    "seat id",
+    "seat_id",
+    "seller id",
+    "seller_id",
+    "account id",
+    "account_id",
+];
+const RELATION_COLUMNS: &[&str] = &[
+    "relation",
+    "relationship",
+    "account type",
+    "account_type",
+    "type",
+];
+const CERT_AUTHORITY_COLUMNS: &[&str] = &[
+    "cert_authority",
+    "cert authority",
+    "certification authority id",
+    "certification_authority_id",
+    "tag id",
+    "tag_id",
+];
+
+/// Reads required seats from CSV. A header row naming the columns, e.g.
+/// `Domain,Publisher ID,Relationship,Certification Authority ID` as exported
+/// by ad servers, may list them in any order and with other columns. Without
+/// a header the columns are ad system, seat id, relation and an optional
+/// cert authority id.
+pub fn required_seats_from_csv(text: &str) -> Result<Vec<RequiredSeat>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let first = match lines.next() {
+        Some(first) => first,
+        None => return Ok(vec![]),
+    };
+    let header: Vec<String> = csv_fields(first.1)
+        .iter()
+        .map(|f| f.trim().trim_start_matches('\u{feff}').to_lowercase())
+        .collect();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+
+    let (columns, rows): (_, Box<dyn Iterator<Item = (usize, &str)>>) = match (
+        column(AD_SYSTEM_COLUMNS),
+        column(SEAT_ID_COLUMNS),
+        column(RELATION_COLUMNS),
+    ) {
+        (Some(ad_system), Some(seat_id), Some(relation)) => (
+            (ad_system, seat_id, relation, column(CERT_AUTHORITY_COLUMNS)),
+            Box::new(lines),
+        ),
+        _ => (
+            (0, 1, 2, Some(3)),
+            Box::new(std::iter::once(first).chain(lines)),
+        ),
+    };
+
+    let mut seats = vec![];
+    for (i, line) in rows {
+        let fields = csv_fields(line);
+        let field = |column: usize| fields.get(column).map(|f| f.trim()).unwrap_or("");
+        let (ad_system, seat_id) = (field(columns.0), field(columns.1));
+        if ad_system.is_empty() || seat_id.is_empty() {
+            return ads_txt_error(&format!("line {}: Invalid required seat: {}", i + 1, line));
+        }
+        let relation = match AccountRelation::parse(field(columns.2)) {
+            Ok(relation) => relation,
+            Err(e) => return ads_txt_error(&format!("line {}: {}", i + 1, e)),
+        };
+
+        let mut seat = RequiredSeat::new(ad_system, seat_id, relation);
+        if let Some(cert_authority) = columns.3 {
+            seat = seat.with_cert_authority(field(cert_authority));
+        }
+        seats.push(seat);
+    }
+
+    Ok(seats)
+}
+
+/// How a file lists a required seat
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SeatStatus {
+    /// Listed with the required relation and cert authority
+    Listed,
+    /// Listed only with the other relation
+    WrongRelation(AccountRelation),
+    /// Listed with the required relation, but not the required cert
+    /// authority
+    WrongCertAuthority(Option<String>),
+    Missing,
+}
+
+impl SeatStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeatStatus::Listed => "listed",
+            SeatStatus::WrongRelation(_) => "wrong_relation",
+            SeatStatus::WrongCertAuthority(_) => "wrong_cert_authority",
+            SeatStatus::Missing => "missing",
+        }
+    }
+}
+
+/// Status of each required seat in one file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RequiredSeatCheck {
+    pub seats: Vec<(RequiredSeat, SeatStatus)>,
+}
+
+impl RequiredSeatCheck {
+    pub fn check(required: &[RequiredSeat], ads_txt: &AdsTxt) -> RequiredSeatCheck {
+        Self::check_with_policy(required, ads_txt, &PublisherIdPolicy::default())
+    }
+
+    /// Like `check`, comparing publisher ids by the policy
+    pub fn check_with_policy(
+        required: &[RequiredSeat],
+        ads_txt: &AdsTxt,
+        policy: &PublisherIdPolicy,
+    ) -> RequiredSeatCheck {
+        RequiredSeatCheck {
+            seats: required
+                .iter()
+                .map(|seat| (seat.clone(), seat.status_in_with_policy(ads_txt, policy)))
+                .collect(),
+        }
+    }
+
+    /// Whether every required seat is listed exactly
+    pub fn is_conformant(&self) -> bool {
+        self.seats.iter().all(|(_, s)| *s == SeatStatus::Listed)
+    }
+
+    /// Required seats which are not listed exactly
+    pub fn failures(&self) -> impl Iterator<Item = &(RequiredSeat, SeatStatus)> {
+        self.seats.iter().filter(|(_, s)| *s != SeatStatus::Listed)
+    }
+
+    /// Renders one CSV row per required seat:
+    /// `ad_system,seat_id,relation,cert_authority,status,found`, where
+    /// `found` is the relation or cert authority listed instead
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("ad_system,seat_id,relation,cert_authority,status,found\n");
+
+        for (seat, status) in &self.seats {
+            let found = match status {
+                SeatStatus::WrongRelation(relation) => relation.as_str(),
+                SeatStatus::WrongCertAuthority(cert_authority) => {
+                    cert_authority.as_deref().unwrap_or("")
+                }
+                _ => "",
+            };
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                seat.ad_system,
+                seat.seat_id,
+                seat.relation.as_str(),
+                seat.cert_authority.as_deref().unwrap_or(""),
+                status.as_str(),
+                found
+            ));
+        }
+
+        csv
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NonCompliantPublisher {
+    pub publisher: String,
+    /// Required seats the publisher does not list
+    pub missing: Vec<RequiredSeat>,
+}
+
+/// Coverage of a set of required seats across a corpus
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SeatCoverageReport {
+    /// Required seats, each with the number of publishers listing it
+    pub seats: Vec<(RequiredSeat, usize)>,
+    pub total_publishers: usize,
+    /// Publishers missing at least one required seat
+    pub non_compliant: Vec<NonCompliantPublisher>,
+}
+
+impl SeatCoverageReport {
+    /// Builds the report from (publisher domain, ads.txt) pairs
+    pub fn build<'a, I>(required: &[RequiredSeat], files: I) -> SeatCoverageReport
+    where
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+    {
+        Self::build_with_policy(required, files, &PublisherIdPolicy::default())
+    }
+
+    /// Like `build`, comparing publisher ids by the policy
+    pub fn build_with_policy<'a, I>(
+        required: &[RequiredSeat],
+        files: I,
+        policy: &PublisherIdPolicy,
+    ) -> SeatCoverageReport
+    where
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+    {
+        let mut seats: Vec<(RequiredSeat, usize)> =
+            required.iter().map(|s| (s.clone(), 0)).collect();
+        let mut total_publishers = 0;
+        let mut non_compliant = vec![];
+
+        for (publisher, ads_txt) in files {
+            total_publishers += 1;
+            let mut missing = vec![];
+            for (seat, listed) in &mut seats {
+                if seat.status_in_with_policy(ads_txt, policy) == SeatStatus::Listed {
+                    *listed += 1;
+                } else {
+                    missing.push(seat.clone());
+                }
+            }
+
+            if !missing.is_empty() {
+                non_compliant.push(NonCompliantPublisher {
+                    publisher: publisher.to_string(),
+                    missing,
+                });
+            }
+        }
+
+        SeatCoverageReport {
+            seats,
+            total_publishers,
+            non_compliant,
+        }
+    }
+
+    /// Share of publishers listing each required seat, between 0 and 1
+    pub fn seat_coverage(&self) -> Vec<(&RequiredSeat, f64)> {
+        self.seats
+            .iter()
+            .map(|(seat, listed)| (seat, self.share(*listed)))
+            .collect()
+    }
+
+    /// Number of publishers listing every required seat
+    pub fn compliant_publishers(&self) -> usize {
+        self.total_publishers - self.non_compliant.len()
+    }
+
+    /// Share of publishers listing every required seat, between 0 and 1
+    pub fn compliance(&self) -> f64 {
+        self.share(self.compliant_publishers())
+    }
+
+    fn share(&self, publishers: usize) -> f64 {
+        match self.total_publishers {
+            0 => 0.0,
+            total => publishers as f64 / total as f64,
+        }
+    }
+
+    /// Renders one CSV row per required seat:
+    /// `ad_system,seat_id,relation,publishers,coverage`, with the coverage as
+    /// a percentage
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("ad_system,seat_id,relation,publishers,coverage\n");
+
+        for (seat, listed) in &self.seats {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.1}\n",
+                seat.ad_system,
+                seat.seat_id,
+                seat.relation.as_str(),
+                listed,
+                self.share(*listed) * 100.0
+            ));
+        }
+
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publisher_id::IdComparison;
+
+    #[test]
+    fn building_coverage_reports() {
+        let a = AdsTxt::parse(
+            r"
+            ourexchange.com, 100, DIRECT, abc123
+            ourexchange.com, 200, RESELLER
+            other.com, 100, DIRECT
+            ",
+        )
+        .unwrap();
+        let b = AdsTxt::parse("OurExchange.com, 300, RESELLER").unwrap();
+        let c = AdsTxt::parse("other.com, 100, DIRECT").unwrap();
+
+        let files = vec![("a.com", &a), ("b.com", &b), ("c.com", &c)];
+        let report = CoverageReport::build("ourexchange.com", &["100", "200"], files.clone());
+
+        assert_eq!(
+            report.authorized,
+            vec![PublisherCoverage {
+                publisher: "a.com".to_string(),
+                authorizations: vec![
+                    Authorization {
+                        seat: "100".to_string(),
+                        relation: AccountRelation::Direct,
+                        cert_authority: Some("abc123".to_string()),
+                    },
+                    Authorization {
+                        seat: "200".to_string(),
+                        relation: AccountRelation::Reseller,
+                        cert_authority: None,
+                    },
+                ],
+            }]
+        );
+        assert_eq!(report.unauthorized, vec!["b.com", "c.com"]);
+        assert_eq!(
+            report.to_csv(),
+            "publisher,seat,relation,cert_authority\na.com,100,DIRECT,abc123\na.com,200,RESELLER,\n"
+        );
+
+        // Seats differing in case are the same on case-insensitive exchanges
+        let c = AdsTxt::parse("ourexchange.com, AB1, DIRECT").unwrap();
+        let policy = PublisherIdPolicy::default()
+            .with_override("ourexchange.com", IdComparison::CaseInsensitive);
+        let report = CoverageReport::build("ourexchange.com", &["ab1"], vec![("c.com", &c)]);
+        assert!(report.authorized.is_empty());
+        let report = CoverageReport::build_with_policy(
+            "ourexchange.com",
+            &["ab1"],
+            vec![("c.com", &c)],
+            &policy,
+        );
+        assert_eq!(report.authorized[0].authorizations[0].seat, "AB1");
+
+        // Any seat on the exchange counts without a seat list
+        let report = CoverageReport::build("ourexchange.com", &[], files);
+        assert_eq!(report.authorized.len(), 2);
+        assert_eq!(report.direct_publishers(), 1);
+        assert_eq!(report.reseller_publishers(), 2);
+        assert!((report.coverage() - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn computing_seat_coverage() {
+        let a = AdsTxt::parse("ourexchange.com, 100, DIRECT\nssp.com, 7, RESELLER").unwrap();
+        let b = AdsTxt::parse("OurExchange.com, 100, DIRECT\nssp.com, 7, DIRECT").unwrap();
+        let c = AdsTxt::parse("other.com, 100, DIRECT").unwrap();
+
+        let required = vec![
+            RequiredSeat::new("OurExchange.com", "100", AccountRelation::Direct),
+            RequiredSeat::new("ssp.com", "7", AccountRelation::Reseller),
+        ];
+        let report =
+            SeatCoverageReport::build(&required, vec![("a.com", &a), ("b.com", &b), ("c.com", &c)]);
+
+        assert_eq!(report.total_publishers, 3);
+        assert_eq!(
+            report.seat_coverage(),
+            vec![(&required[0], 2.0 / 3.0), (&required[1], 1.0 / 3.0)]
+        );
+        assert_eq!(report.compliant_publishers(), 1);
+        assert!((report.compliance() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            report.non_compliant,
+            vec![
+                NonCompliantPublisher {
+                    publisher: "b.com".to_string(),
+                    missing: vec![required[1].clone()],
+                },
+                NonCompliantPublisher {
+                    publisher: "c.com".to_string(),
+                    missing: required.clone(),
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_csv(),
+            "ad_system,seat_id,relation,publishers,coverage\n\
+             ourexchange.com,100,DIRECT,2,66.7\n\
+             ssp.com,7,RESELLER,1,33.3\n"
+        );
+
+        let empty = SeatCoverageReport::build(&required, vec![]);
+        assert_eq!(empty.compliance(), 0.0);
+    }
+
+    #[test]
+    fn checking_required_seats() {
+        let csv = "\u{feff}Account ID,Domain,Relationship,Certification Authority ID,Notes\n\
+                   100,ourexchange.com,DIRECT,abc123,main seat\n\
+                   200,ourexchange.com,RESELLER,,\n\
+                   7,ssp.com,DIRECT,f00,\n\
+                   8,ssp.com,DIRECT,,\n\
+                   9,ssp.com,RESELLER,,\n";
+        let required = required_seats_from_csv(csv).unwrap();
+        assert_eq!(required.len(), 5);
+        assert_eq!(
+            required[0],
+            RequiredSeat::new("ourexchange.com", "100", AccountRelation::Direct)
+                .with_cert_authority("abc123")
+        );
+        assert_eq!(required[1].cert_authority, None);
+
+        let headerless = required_seats_from_csv("OurExchange.com, 100, direct, abc123\n").unwrap();
+        assert_eq!(headerless, vec![required[0].clone()]);
+        assert!(required_seats_from_csv("Domain,Seller ID,Type\na.com,1,OWNER").is_err());
+        assert!(required_seats_from_csv("a.com,,DIRECT").is_err());
+
+        let ads_txt = AdsTxt::parse(
+            "ourexchange.com, 100, DIRECT, ABC123\nourexchange.com, 200, DIRECT\n\
+             ssp.com, 7, DIRECT, bad\nssp.com, 8, DIRECT",
+        )
+        .unwrap();
+        let check = RequiredSeatCheck::check(&required, &ads_txt);
+        assert!(!check.is_conformant());
+        assert_eq!(check.failures().count(), 3);
+        assert_eq!(
+            check.to_csv(),
+            "ad_system,seat_id,relation,cert_authority,status,found\n\
+             ourexchange.com,100,DIRECT,abc123,listed,\n\
+             ourexchange.com,200,RESELLER,,wrong_relation,DIRECT\n\
+             ssp.com,7,DIRECT,f00,wrong_cert_authority,bad\n\
+             ssp.com,8,DIRECT,,listed,\n\
+             ssp.com,9,RESELLER,,missing,\n"
+        );
+
+        let ads_txt = AdsTxt::parse("ssp.com, AB, DIRECT").unwrap();
+        let required = [RequiredSeat::new("ssp.com", "ab", AccountRelation::Direct)];
+        let policy = PublisherIdPolicy::new(IdComparison::CaseInsensitive);
+        assert!(!RequiredSeatCheck::check(&required, &ads_txt).is_conformant());
+        assert!(RequiredSeatCheck::check_with_policy(&required, &ads_txt, &policy).is_conformant());
+        let report =
+            SeatCoverageReport::build_with_policy(&required, vec![("a.com", &ads_txt)], &policy);
+        assert_eq!(report.compliant_publishers(), 1);
+    }
+}
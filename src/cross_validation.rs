@@ -0,0 +1,401 @@
+//! Cross-validation of ads.txt records against the sellers.json files of the
+//! referenced ad systems
+
+use crate::sellers_json::{Seller, SellerType, SellersJson, TAG_ID};
+use crate::{AccountRelation, AdsTxt, DataRecord};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RecordVerdict {
+    /// The publisher id is listed as a seller with a consistent seller type
+    Verified,
+    /// No sellers.json was provided for the record's ad system
+    SellersJsonMissing,
+    /// The publisher id is not listed in the ad system's sellers.json
+    SellerNotFound,
+    /// The seller type contradicts the account relation, e.g. a DIRECT record
+    /// for a seller listed as INTERMEDIARY
+    SellerTypeMismatch(SellerType),
+}
+
+impl RecordVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordVerdict::Verified => "VERIFIED",
+            RecordVerdict::SellersJsonMissing => "SELLERS_JSON_MISSING",
+            RecordVerdict::SellerNotFound => "SELLER_NOT_FOUND",
+            RecordVerdict::SellerTypeMismatch(_) => "SELLER_TYPE_MISMATCH",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RecordCheck {
+    pub record: DataRecord,
+    /// Matching sellers.json entry, if any
+    pub seller: Option<Seller>,
+    pub verdict: RecordVerdict,
+}
+
+impl RecordCheck {
+    pub fn is_verified(&self) -> bool {
+        self.verdict == RecordVerdict::Verified
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CertAuthorityCheck {
+    /// The record's cert authority is a TAG id declared by the ad system
+    Matches,
+    /// The ad system declares TAG ids, but not the one in the record
+    Mismatch { declared: Vec<String> },
+    /// The record has a cert authority but the ad system declares no TAG id
+    NotDeclared,
+    /// The record has no cert authority field
+    Missing,
+}
+
+/// Matches the cert authority field of a record against the TAG ids declared
+/// in the `identifiers` of the ad system's sellers.json
+pub fn check_cert_authority(record: &DataRecord, sellers_json: &SellersJson) -> CertAuthorityCheck {
+    let cert_authority = match &record.cert_authority {
+        Some(cert_authority) => cert_authority,
+        None => return CertAuthorityCheck::Missing,
+    };
+
+    let declared = sellers_json.identifier_values(TAG_ID);
+    if declared.is_empty() {
+        CertAuthorityCheck::NotDeclared
+    } else if sellers_json.declares_tag_id(cert_authority) {
+        CertAuthorityCheck::Matches
+    } else {
+        CertAuthorityCheck::Mismatch {
+            declared: declared.into_iter().map(|d| d.to_string()).collect(),
+        }
+    }
+}
+
+/// Checks whether a seller type is consistent with an ads.txt account relation
+pub fn is_consistent(relation: &AccountRelation, seller_type: SellerType) -> bool {
+    match relation {
+        AccountRelation::Direct => seller_type != SellerType::Intermediary,
+        AccountRelation::Reseller => seller_type != SellerType::Publisher,
+    }
+}
+
+/// Verifies a single record against the sellers.json of its ad system
+pub fn check_record(record: &DataRecord, sellers_json: Option<&SellersJson>) -> RecordCheck {
+    let sellers_json = match sellers_json {
+        Some(sellers_json) => sellers_json,
+        None => {
+            return RecordCheck {
+                record: record.clone(),
+                seller: None,
+                verdict: RecordVerdict::SellersJsonMissing,
+            }
+        }
+    };
+
+    let seller = sellers_json.seller(&record.publisher_id);
+    let verdict = match seller {
+        None => RecordVerdict::SellerNotFound,
+        Some(seller) if !is_consistent(&record.acc_relation, seller.seller_type) => {
+            RecordVerdict::SellerTypeMismatch(seller.seller_type)
+        }
+        Some(_) => RecordVerdict::Verified,
+    };
+
+    RecordCheck {
+        record: record.clone(),
+        seller: seller.cloned(),
+        verdict,
+    }
+}
+
+/// Verifies every record of an ads.txt file, given the sellers.json files
+/// keyed by ad system domain
+pub fn cross_validate(
+    ads_txt: &AdsTxt,
+    sellers: &HashMap<String, SellersJson>,
+) -> Vec<RecordCheck> {
+    ads_txt
+        .records
+        .iter()
+        .map(|record| check_record(record, sellers.get(&record.domain.to_lowercase())))
+        .collect()
+}
+
+/// A chain of sellers through which a publisher's inventory is resold, e.g.
+/// publisher → intermediary → exchange
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResellerChain {
+    /// Domain of the publisher owning the inventory
+    pub publisher: String,
+    /// Seller accounts in order, starting with the one closest to the publisher
+    pub links: Vec<ChainLink>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChainLink {
+    /// Ad system on which the seller account exists
+    pub ad_system: String,
+    pub seller_id: String,
+}
+
+impl ChainLink {
+    pub fn new(ad_system: &str, seller_id: &str) -> Self {
+        ChainLink {
+            ad_system: ad_system.trim().to_lowercase(),
+            seller_id: seller_id.trim().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum LinkFailure {
+    /// No ads.txt was provided for the publisher
+    AdsTxtMissing,
+    /// The publisher's ads.txt does not authorize the seller account
+    NotInAdsTxt,
+    /// The ads.txt record fails the sellers.json check
+    Unverified(RecordVerdict),
+    /// The seller's sellers.json domain is not the preceding business in the
+    /// chain, e.g. an intermediary account which belongs to someone else
+    SellerDomainMismatch { expected: String, found: String },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnauthorizedLink {
+    /// Position of the link in the chain
+    pub index: usize,
+    pub link: ChainLink,
+    pub failure: LinkFailure,
+}
+
+fn same_business_domain(a: &str, b: &str) -> bool {
+    let strip = |d: &str| d.trim().trim_start_matches("www.").to_lowercase();
+    strip(a) == strip(b)
+}
+
+/// Verifies that every link of a reseller chain is authorized by the
+/// publisher's ads.txt and listed with a consistent seller type and business
+/// domain in the ad system's sellers.json, returning the first unauthorized link
+pub fn verify_reseller_chain(
+    chain: &ResellerChain,
+    ads_txts: &HashMap<String, AdsTxt>,
+    sellers: &HashMap<String, SellersJson>,
+) -> Result<(), UnauthorizedLink> {
+    let ads_txt = ads_txts.get(&chain.publisher.to_lowercase());
+
+    // The business expected to own the next seller account
+    let mut expected_owner = chain.publisher.clone();
+
+    for (index, link) in chain.links.iter().enumerate() {
+        let fail = |failure: LinkFailure| {
+            Err(UnauthorizedLink {
+                index,
+                link: link.clone(),
+                failure,
+            })
+        };
+
+        let ads_txt = match ads_txt {
+            Some(ads_txt) => ads_txt,
+            None => return fail(LinkFailure::AdsTxtMissing),
+        };
+
+        let record = ads_txt.records.iter().find(|r| {
+            r.domain.eq_ignore_ascii_case(&link.ad_system) && r.publisher_id == link.seller_id
+        });
+        let record = match record {
+            Some(record) => record,
+            None => return fail(LinkFailure::NotInAdsTxt),
+        };
+
+        let check = check_record(record, sellers.get(&link.ad_system));
+        if !check.is_verified() {
+            return fail(LinkFailure::Unverified(check.verdict));
+        }
+
+        if let Some(domain) = check.seller.as_ref().and_then(|s| s.domain.as_ref()) {
+            if !same_business_domain(domain, &expected_owner) {
+                return fail(LinkFailure::SellerDomainMismatch {
+                    expected: expected_owner,
+                    found: domain.to_string(),
+                });
+            }
+        }
+
+        expected_owner = link.ad_system.clone();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sellers_json::Identifier;
+
+    #[test]
+    fn cross_validating_records() {
+        let ads_txt = AdsTxt::parse(
+            r"
+            greenadexchange.com, 12345, DIRECT
+            greenadexchange.com, 67890, DIRECT
+            GreenAdExchange.com, 555, RESELLER
+            greenadexchange.com, 999, RESELLER
+            blueadexchange.com, XF436, DIRECT
+            ",
+        )
+        .unwrap();
+
+        let mut sellers = HashMap::new();
+        sellers.insert(
+            "greenadexchange.com".to_string(),
+            SellersJson::new(&[
+                Seller::new("12345", SellerType::Publisher),
+                Seller::new("67890", SellerType::Intermediary),
+                Seller::new("555", SellerType::Both),
+            ]),
+        );
+
+        let verdicts: Vec<RecordVerdict> = cross_validate(&ads_txt, &sellers)
+            .into_iter()
+            .map(|check| check.verdict)
+            .collect();
+
+        assert_eq!(
+            verdicts,
+            vec![
+                RecordVerdict::Verified,
+                RecordVerdict::SellerTypeMismatch(SellerType::Intermediary),
+                RecordVerdict::Verified,
+                RecordVerdict::SellerNotFound,
+                RecordVerdict::SellersJsonMissing,
+            ]
+        );
+        assert_eq!(verdicts[3].as_str(), "SELLER_NOT_FOUND");
+    }
+
+    #[test]
+    fn checking_cert_authorities() {
+        let mut sellers_json = SellersJson::new(&[]);
+        let record = |cert_authority: Option<&str>| {
+            DataRecord::new(
+                "greenadexchange.com",
+                "12345",
+                AccountRelation::Direct,
+                cert_authority.map(|c| c.to_string()),
+            )
+        };
+
+        assert_eq!(
+            check_cert_authority(&record(Some("d75815a79")), &sellers_json),
+            CertAuthorityCheck::NotDeclared
+        );
+
+        sellers_json
+            .identifiers
+            .push(Identifier::new("TAG-ID", "d75815a79"));
+        assert_eq!(
+            check_cert_authority(&record(Some("D75815A79")), &sellers_json),
+            CertAuthorityCheck::Matches
+        );
+        assert_eq!(
+            check_cert_authority(&record(Some("f08c47fec")), &sellers_json),
+            CertAuthorityCheck::Mismatch {
+                declared: vec!["d75815a79".to_string()]
+            }
+        );
+        assert_eq!(
+            check_cert_authority(&record(None), &sellers_json),
+            CertAuthorityCheck::Missing
+        );
+    }
+
+    #[test]
+    fn verifying_reseller_chains() {
+        let mut ads_txts = HashMap::new();
+        ads_txts.insert(
+            "publisher.com".to_string(),
+            AdsTxt::parse(
+                r"
+                intermediary.com, 100, DIRECT
+                exchange.com, 200, RESELLER
+                exchange.com, 300, RESELLER
+                ",
+            )
+            .unwrap(),
+        );
+
+        let seller = |id: &str, seller_type, domain: &str| Seller {
+            domain: Some(domain.to_string()),
+            ..Seller::new(id, seller_type)
+        };
+
+        let mut sellers = HashMap::new();
+        sellers.insert(
+            "intermediary.com".to_string(),
+            SellersJson::new(&[seller("100", SellerType::Publisher, "www.publisher.com")]),
+        );
+        sellers.insert(
+            "exchange.com".to_string(),
+            SellersJson::new(&[
+                seller("200", SellerType::Intermediary, "intermediary.com"),
+                seller("300", SellerType::Intermediary, "someoneelse.com"),
+            ]),
+        );
+
+        let chain = |links: &[ChainLink]| ResellerChain {
+            publisher: "publisher.com".to_string(),
+            links: links.to_vec(),
+        };
+
+        assert_eq!(
+            verify_reseller_chain(
+                &chain(&[
+                    ChainLink::new("intermediary.com", "100"),
+                    ChainLink::new("exchange.com", "200"),
+                ]),
+                &ads_txts,
+                &sellers
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify_reseller_chain(
+                &chain(&[
+                    ChainLink::new("intermediary.com", "100"),
+                    ChainLink::new("exchange.com", "300"),
+                ]),
+                &ads_txts,
+                &sellers
+            ),
+            Err(UnauthorizedLink {
+                index: 1,
+                link: ChainLink::new("exchange.com", "300"),
+                failure: LinkFailure::SellerDomainMismatch {
+                    expected: "intermediary.com".to_string(),
+                    found: "someoneelse.com".to_string(),
+                },
+            })
+        );
+
+        let result = verify_reseller_chain(
+            &chain(&[ChainLink::new("exchange.com", "400")]),
+            &ads_txts,
+            &sellers,
+        );
+        assert_eq!(result.unwrap_err().failure, LinkFailure::NotInAdsTxt);
+
+        let result = verify_reseller_chain(
+            &chain(&[ChainLink::new("intermediary.com", "100")]),
+            &HashMap::new(),
+            &sellers,
+        );
+        assert_eq!(result.unwrap_err().failure, LinkFailure::AdsTxtMissing);
+    }
+}
@@ -0,0 +1,130 @@
+//! Time budgets for parsing and validation, so that a pathological input
+//! cannot stall a latency-sensitive thread
+//!
+//! A `Deadline` expires at an instant, when its `CancellationToken` is
+//! cancelled from another thread, or both. `AdsTxt::parse_lenient_until` and
+//! `Validator::validate_until` check it as they go and give up with
+//! `TimedOut` once it expires.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Lines processed between checks of the clock
+const CHECK_INTERVAL: usize = 16;
+
+/// Flag cancelling the work of every deadline holding a clone of it
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Deadline {
+    at: Option<Instant>,
+    token: Option<CancellationToken>,
+}
+
+impl Deadline {
+    /// Deadline which only expires when cancelled, if given a token
+    pub fn never() -> Self {
+        Self::default()
+    }
+
+    pub fn at(at: Instant) -> Self {
+        Deadline {
+            at: Some(at),
+            token: None,
+        }
+    }
+
+    pub fn after(timeout: Duration) -> Self {
+        Self::at(Instant::now() + timeout)
+    }
+
+    /// Also expires when the token is cancelled
+    pub fn with_token(mut self, token: CancellationToken) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.token.as_ref().is_some_and(|t| t.is_cancelled())
+            || self.at.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// Fails once the deadline expired, checking every few lines to keep
+    /// the clock off the per-line cost
+    pub(crate) fn check(&self, lines: usize) -> Result<(), TimedOut> {
+        if lines.is_multiple_of(CHECK_INTERVAL) && self.is_expired() {
+            Err(TimedOut { lines })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The deadline expired before the input was processed
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TimedOut {
+    /// Number of lines processed until then
+    pub lines: usize,
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Timed out after {} lines", self.lines)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::Validator;
+    use crate::AdsTxt;
+
+    #[test]
+    fn stopping_at_deadlines() {
+        let text = "a.com, 1, DIRECT\nbad\n".repeat(100);
+
+        let (ads_txt, errors) = AdsTxt::parse_lenient_until(&text, &Deadline::never()).unwrap();
+        assert_eq!((ads_txt.records.len(), errors.len()), (100, 100));
+        let far = Deadline::after(Duration::from_secs(3600));
+        assert_eq!(
+            Validator::new().validate_until(&text, &far).unwrap().len(),
+            Validator::new().validate(&text).len()
+        );
+
+        let past = Deadline::at(Instant::now());
+        assert_eq!(
+            AdsTxt::parse_lenient_until(&text, &past),
+            Err(TimedOut { lines: 0 })
+        );
+
+        let token = CancellationToken::new();
+        let cancellable = Deadline::never().with_token(token.clone());
+        assert!(!cancellable.is_expired());
+        token.cancel();
+        assert_eq!(
+            Validator::new()
+                .validate_until(&text, &cancellable)
+                .unwrap_err()
+                .to_string(),
+            "Timed out after 0 lines"
+        );
+    }
+}
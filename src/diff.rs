@@ -0,0 +1,503 @@
+//! Seat-level differences between two versions of an ads.txt file
+
+use crate::equality::RecordEquality;
+use crate::json::JsonValue;
+use crate::normalize::Normalizer;
+use crate::publisher_id::PublisherIdPolicy;
+use crate::serialize::{record_to_json, variable_to_json};
+use crate::{AdsTxt, DataRecord, Variable};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SeatChange {
+    Added(DataRecord),
+    Removed(DataRecord),
+    /// The seat is listed in both versions with a different relation or
+    /// cert authority
+    Changed {
+        old: DataRecord,
+        new: DataRecord,
+    },
+}
+
+impl std::fmt::Display for SeatChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeatChange::Added(record) => write!(f, "+ {}", record),
+            SeatChange::Removed(record) => write!(f, "- {}", record),
+            SeatChange::Changed { old, new } => write!(f, "~ {} -> {}", old, new),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct AdsTxtDiff {
+    /// Seat changes sorted by ad system and seller id
+    pub seats: Vec<SeatChange>,
+    pub added_variables: Vec<Variable>,
+    pub removed_variables: Vec<Variable>,
+}
+
+/// Seats are identified by ad system and seller id, ignoring case of the
+/// ad system and comparing seller ids by the policy
+fn seat_key(record: &DataRecord, policy: &PublisherIdPolicy) -> (String, String) {
+    (
+        record.domain.to_lowercase(),
+        policy
+            .key(&record.domain, &record.publisher_id)
+            .into_owned(),
+    )
+}
+
+/// Records of a seat in the old and new version
+type SeatVersions<'a> = (Vec<&'a DataRecord>, Vec<&'a DataRecord>);
+
+fn same_record(
+    a: &DataRecord,
+    b: &DataRecord,
+    policy: &PublisherIdPolicy,
+    equality: &RecordEquality,
+) -> bool {
+    seat_key(a, policy) == seat_key(b, policy) && equality.same_fields(a, b)
+}
+
+fn same_variable(a: &Variable, b: &Variable) -> bool {
+    a.name.eq_ignore_ascii_case(&b.name) && a.value == b.value
+}
+
+impl AdsTxtDiff {
+    pub fn between(old: &AdsTxt, new: &AdsTxt) -> AdsTxtDiff {
+        Self::between_with_policy(old, new, &PublisherIdPolicy::default())
+    }
+
+    /// Differences like `between` between the files normalized by the
+    /// normalizer, e.g. with the same rules as the index they are checked
+    /// against
+    pub fn between_normalized<N: Normalizer>(
+        old: &AdsTxt,
+        new: &AdsTxt,
+        normalizer: &N,
+    ) -> AdsTxtDiff {
+        Self::between(&normalizer.ads_txt(old), &normalizer.ads_txt(new))
+    }
+
+    /// Differences like `between`, identifying seats by comparing publisher
+    /// ids by the policy
+    pub fn between_with_policy(
+        old: &AdsTxt,
+        new: &AdsTxt,
+        policy: &PublisherIdPolicy,
+    ) -> AdsTxtDiff {
+        Self::between_with(old, new, policy, &RecordEquality::new())
+    }
+
+    /// Differences like `between`, comparing the records of a seat by the
+    /// equality, e.g. ignoring cert authority ids
+    pub fn between_with_equality(
+        old: &AdsTxt,
+        new: &AdsTxt,
+        equality: &RecordEquality,
+    ) -> AdsTxtDiff {
+        Self::between_with(old, new, &PublisherIdPolicy::default(), equality)
+    }
+
+    fn between_with(
+        old: &AdsTxt,
+        new: &AdsTxt,
+        policy: &PublisherIdPolicy,
+        equality: &RecordEquality,
+    ) -> AdsTxtDiff {
+        let mut seats: BTreeMap<(String, String), SeatVersions> = BTreeMap::new();
+        for record in &old.records {
+            seats
+                .entry(seat_key(record, policy))
+                .or_default()
+                .0
+                .push(record);
+        }
+        for record in &new.records {
+            seats
+                .entry(seat_key(record, policy))
+                .or_default()
+                .1
+                .push(record);
+        }
+
+        let mut changes = vec![];
+        for (old, new) in seats.values() {
+            let removed: Vec<&DataRecord> = old
+                .iter()
+                .filter(|o| !new.iter().any(|n| same_record(o, n, policy, equality)))
+                .copied()
+                .collect();
+            let added: Vec<&DataRecord> = new
+                .iter()
+                .filter(|n| !old.iter().any(|o| same_record(o, n, policy, equality)))
+                .copied()
+                .collect();
+
+            match (removed.as_slice(), added.as_slice()) {
+                ([old], [new]) => changes.push(SeatChange::Changed {
+                    old: (*old).clone(),
+                    new: (*new).clone(),
+                }),
+                _ => {
+                    changes.extend(removed.into_iter().cloned().map(SeatChange::Removed));
+                    changes.extend(added.into_iter().cloned().map(SeatChange::Added));
+                }
+            }
+        }
+
+        AdsTxtDiff {
+            seats: changes,
+            added_variables: new
+                .variables
+                .iter()
+                .filter(|n| !old.variables.iter().any(|o| same_variable(o, n)))
+                .cloned()
+                .collect(),
+            removed_variables: old
+                .variables
+                .iter()
+                .filter(|o| !new.variables.iter().any(|n| same_variable(o, n)))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seats.is_empty()
+            && self.added_variables.is_empty()
+            && self.removed_variables.is_empty()
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let variables = |variables: &[Variable]| {
+            JsonValue::Array(variables.iter().map(variable_to_json).collect())
+        };
+
+        JsonValue::Object(vec![
+            (
+                "seats".to_string(),
+                JsonValue::Array(self.seats.iter().map(|c| c.to_json()).collect()),
+            ),
+            (
+                "added_variables".to_string(),
+                variables(&self.added_variables),
+            ),
+            (
+                "removed_variables".to_string(),
+                variables(&self.removed_variables),
+            ),
+        ])
+    }
+}
+
+impl SeatChange {
+    /// Renders the change as `{"change": "added", "record": {...}}`, with
+    /// `old` and `new` records for changed seats
+    pub fn to_json(&self) -> JsonValue {
+        let (change, records) = match self {
+            SeatChange::Added(record) => ("added", vec![("record", record)]),
+            SeatChange::Removed(record) => ("removed", vec![("record", record)]),
+            SeatChange::Changed { old, new } => ("changed", vec![("old", old), ("new", new)]),
+        };
+
+        let mut json = vec![("change".to_string(), JsonValue::string(change))];
+        json.extend(
+            records
+                .into_iter()
+                .map(|(key, record)| (key.to_string(), record_to_json(record))),
+        );
+        JsonValue::Object(json)
+    }
+}
+
+impl std::fmt::Display for AdsTxtDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.seats {
+            writeln!(f, "{}", change)?;
+        }
+        for variable in &self.removed_variables {
+            writeln!(f, "- {}", variable)?;
+        }
+        for variable in &self.added_variables {
+            writeln!(f, "+ {}", variable)?;
+        }
+        Ok(())
+    }
+}
+
+/// Values of a variable in the expected and the live file, which differ
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VariableMismatch {
+    /// Lowercased variable name
+    pub name: String,
+    pub expected: Vec<String>,
+    pub live: Vec<String>,
+}
+
+/// Comparison of a live file against the file it is expected to match, e.g.
+/// before a publisher goes live with an exchange
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ConformanceReport {
+    /// Expected records the live file does not list
+    pub missing: Vec<DataRecord>,
+    /// Live records of seats the expected file does not list
+    pub unexpected: Vec<DataRecord>,
+    /// Seats listed by both files with a different relation or cert
+    /// authority, as (expected, live) records
+    pub mismatched: Vec<(DataRecord, DataRecord)>,
+    pub variables: Vec<VariableMismatch>,
+}
+
+impl ConformanceReport {
+    pub fn check(expected: &AdsTxt, live: &AdsTxt) -> ConformanceReport {
+        let mut report = ConformanceReport::default();
+        for change in AdsTxtDiff::between(expected, live).seats {
+            match change {
+                SeatChange::Removed(record) => report.missing.push(record),
+                SeatChange::Added(record) => report.unexpected.push(record),
+                SeatChange::Changed { old, new } => report.mismatched.push((old, new)),
+            }
+        }
+
+        let mut values: BTreeMap<String, (Vec<String>, Vec<String>)> = BTreeMap::new();
+        for variable in &expected.variables {
+            let entry = values.entry(variable.name.to_lowercase()).or_default();
+            entry.0.push(variable.value.to_string());
+        }
+        for variable in &live.variables {
+            let entry = values.entry(variable.name.to_lowercase()).or_default();
+            entry.1.push(variable.value.to_string());
+        }
+        for (name, (mut expected, mut live)) in values {
+            expected.sort_unstable();
+            live.sort_unstable();
+            if expected != live {
+                report.variables.push(VariableMismatch {
+                    name,
+                    expected,
+                    live,
+                });
+            }
+        }
+
+        report
+    }
+
+    /// Whether the live file lists every expected record and variable value.
+    /// Additional records are allowed.
+    pub fn is_conformant(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty() && self.variables.is_empty()
+    }
+
+    /// Whether the live file lists exactly the expected records and variables
+    pub fn is_exact(&self) -> bool {
+        self.is_conformant() && self.unexpected.is_empty()
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let records =
+            |records: &[DataRecord]| JsonValue::Array(records.iter().map(record_to_json).collect());
+        let values = |values: &[String]| {
+            JsonValue::Array(values.iter().map(|v| JsonValue::string(v)).collect())
+        };
+
+        JsonValue::Object(vec![
+            (
+                "conformant".to_string(),
+                JsonValue::Bool(self.is_conformant()),
+            ),
+            ("missing".to_string(), records(&self.missing)),
+            ("unexpected".to_string(), records(&self.unexpected)),
+            (
+                "mismatched".to_string(),
+                JsonValue::Array(
+                    self.mismatched
+                        .iter()
+                        .map(|(expected, live)| {
+                            JsonValue::Object(vec![
+                                ("expected".to_string(), record_to_json(expected)),
+                                ("live".to_string(), record_to_json(live)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "variables".to_string(),
+                JsonValue::Array(
+                    self.variables
+                        .iter()
+                        .map(|v| {
+                            JsonValue::Object(vec![
+                                ("name".to_string(), JsonValue::string(&v.name)),
+                                ("expected".to_string(), values(&v.expected)),
+                                ("live".to_string(), values(&v.live)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
+
+impl std::fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for record in &self.missing {
+            writeln!(f, "missing: {}", record)?;
+        }
+        for (expected, live) in &self.mismatched {
+            writeln!(f, "mismatched: {} (live: {})", expected, live)?;
+        }
+        for v in &self.variables {
+            writeln!(
+                f,
+                "variable {}: expected [{}], live [{}]",
+                v.name,
+                v.expected.join(", "),
+                v.live.join(", ")
+            )?;
+        }
+        for record in &self.unexpected {
+            writeln!(f, "unexpected: {}", record)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equality::RecordField;
+    use crate::publisher_id::IdComparison;
+
+    #[test]
+    fn diffing_files() {
+        let old = AdsTxt::parse(
+            r"
+            exchange.com, 1, DIRECT, abc
+            exchange.com, 2, DIRECT
+            other.com, 3, RESELLER
+            other.com, 4, DIRECT
+            contact=old@example.com
+            ",
+        )
+        .unwrap();
+        let new = AdsTxt::parse(
+            r"
+            EXCHANGE.com, 1, DIRECT, ABC
+            exchange.com, 2, RESELLER
+            other.com, 4, DIRECT
+            new.com, 5, DIRECT
+            CONTACT=old@example.com
+            subdomain=sub.example.com
+            ",
+        )
+        .unwrap();
+
+        let diff = AdsTxtDiff::between(&old, &new);
+        assert_eq!(
+            diff.to_string(),
+            "~ exchange.com, 2, DIRECT -> exchange.com, 2, RESELLER\n\
+             + new.com, 5, DIRECT\n\
+             - other.com, 3, RESELLER\n\
+             + subdomain=sub.example.com\n"
+        );
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.seats[1].to_json().to_string(),
+            r#"{"change":"added","record":{"domain":"new.com","publisher_id":"5","relation":"DIRECT","cert_authority":null}}"#
+        );
+        let json = diff.to_json();
+        assert_eq!(
+            json.get("seats")
+                .and_then(|s| s.as_array())
+                .map(|s| s.len()),
+            Some(3)
+        );
+        assert_eq!(
+            json.get("added_variables").map(|v| v.to_string()),
+            Some(r#"[{"name":"subdomain","value":"sub.example.com"}]"#.to_string())
+        );
+        assert!(AdsTxtDiff::between(&new, &new).is_empty());
+
+        // Seller ids differing in case are one seat where ids are
+        // case-insensitive
+        let old = AdsTxt::parse(
+            "exchange.com, ab1, DIRECT
+other.com, cd2, DIRECT",
+        )
+        .unwrap();
+        let new = AdsTxt::parse(
+            "exchange.com, AB1, RESELLER
+other.com, CD2, DIRECT",
+        )
+        .unwrap();
+        let policy = PublisherIdPolicy::new(IdComparison::CaseInsensitive)
+            .with_override("other.com", IdComparison::Exact);
+        assert_eq!(
+            AdsTxtDiff::between_with_policy(&old, &new, &policy).to_string(),
+            "~ exchange.com, ab1, DIRECT -> exchange.com, AB1, RESELLER\n\
+             + other.com, CD2, DIRECT\n\
+             - other.com, cd2, DIRECT\n"
+        );
+        assert_eq!(AdsTxtDiff::between(&old, &new).seats.len(), 4);
+
+        let old = AdsTxt::parse("a.com, 1, DIRECT, abc\nb.com, 2, DIRECT").unwrap();
+        let new = AdsTxt::parse("a.com, 1, DIRECT\nb.com, 2, RESELLER").unwrap();
+        assert_eq!(AdsTxtDiff::between(&old, &new).seats.len(), 2);
+        let diff = AdsTxtDiff::between_with_equality(
+            &old,
+            &new,
+            &RecordEquality::new().ignoring(RecordField::CertAuthority),
+        );
+        assert_eq!(
+            diff.to_string(),
+            "~ b.com, 2, DIRECT -> b.com, 2, RESELLER\n"
+        );
+        assert!(
+            AdsTxtDiff::between_with_equality(&old, &new, &RecordEquality::identity()).is_empty()
+        );
+    }
+
+    #[test]
+    fn checking_conformance() {
+        let expected = AdsTxt::parse(
+            "exchange.com, 1, DIRECT, abc\nexchange.com, 2, DIRECT\nother.com, 3, RESELLER\n\
+             contact=ads@example.com\nownerdomain=example.com",
+        )
+        .unwrap();
+        let live = AdsTxt::parse(
+            "Exchange.com, 1, DIRECT, ABC\nexchange.com, 2, RESELLER\nnew.com, 4, DIRECT\n\
+             CONTACT=ads@example.com\nownerdomain=example.org",
+        )
+        .unwrap();
+
+        let report = ConformanceReport::check(&expected, &live);
+        assert!(!report.is_conformant());
+        assert_eq!(
+            report.to_string(),
+            "missing: other.com, 3, RESELLER\n\
+             mismatched: exchange.com, 2, DIRECT (live: exchange.com, 2, RESELLER)\n\
+             variable ownerdomain: expected [example.com], live [example.org]\n\
+             unexpected: new.com, 4, DIRECT\n"
+        );
+        assert_eq!(
+            report.to_json().get("variables").map(|v| v.to_string()),
+            Some(
+                r#"[{"name":"ownerdomain","expected":["example.com"],"live":["example.org"]}]"#
+                    .to_string()
+            )
+        );
+
+        let text = crate::serialize::ads_txt_to_text(&expected) + "new.com, 4, DIRECT";
+        let extra = AdsTxt::parse(&text).unwrap();
+        let report = ConformanceReport::check(&expected, &extra);
+        assert!(report.is_conformant());
+        assert!(!report.is_exact());
+        assert!(ConformanceReport::check(&expected, &expected).is_exact());
+    }
+}
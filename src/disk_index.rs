@@ -0,0 +1,265 @@
+//! On-disk reverse index over a corpus, queried without loading it into memory
+//!
+//! The index is a directory holding `records.tsv`, one sorted line per record
+//! keyed by ad system and seller id, and `records.idx`, a sparse index with
+//! the byte offset of every `BLOCK_SIZE`th line. Only the sparse index is kept
+//! in memory; queries seek to the right block and scan from there.
+
+use crate::graph::normalize_domain;
+use crate::reverse_index::Listing;
+use crate::{AccountRelation, AdsTxt};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const RECORDS_FILE: &str = "records.tsv";
+const INDEX_FILE: &str = "records.idx";
+
+/// Number of records per block of the sparse index
+const BLOCK_SIZE: usize = 128;
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+}
+
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+fn invalid_data<T>(message: &str) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[derive(Debug)]
+pub struct DiskIndex {
+    records: PathBuf,
+    /// First line and byte offset of every block, in key order
+    blocks: Vec<(String, u64)>,
+}
+
+impl DiskIndex {
+    /// Writes the index of (publisher domain, ads.txt) pairs to a directory,
+    /// replacing any index already there. Records are sorted in memory, so
+    /// building needs room for every record but not for the parsed files.
+    pub fn build<'a, P, I>(dir: P, files: I) -> io::Result<DiskIndex>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+    {
+        let mut lines = vec![];
+        for (publisher, ads_txt) in files {
+            let publisher = escape(&normalize_domain(publisher));
+            for record in &ads_txt.records {
+                let mut line = format!(
+                    "{}\t{}\t{}\t{}",
+                    escape(&normalize_domain(&record.domain)),
+                    escape(&record.publisher_id),
+                    publisher,
+                    record.acc_relation.as_str()
+                );
+                if let Some(cert_authority) = &record.cert_authority {
+                    line.push('\t');
+                    line.push_str(&escape(cert_authority));
+                }
+                lines.push(line);
+            }
+        }
+        lines.sort_unstable();
+        lines.dedup();
+
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut records = BufWriter::new(File::create(dir.join(RECORDS_FILE))?);
+        let mut index = BufWriter::new(File::create(dir.join(INDEX_FILE))?);
+        let mut offset = 0;
+        for (i, line) in lines.iter().enumerate() {
+            if i % BLOCK_SIZE == 0 {
+                writeln!(index, "{}\t{}", offset, line)?;
+            }
+            writeln!(records, "{}", line)?;
+            offset += line.len() as u64 + 1;
+        }
+        records.flush()?;
+        index.flush()?;
+
+        Self::open(dir)
+    }
+
+    /// Opens an index written by `build`, reading only its sparse index
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<DiskIndex> {
+        let dir = dir.as_ref();
+        let mut blocks = vec![];
+
+        for line in BufReader::new(File::open(dir.join(INDEX_FILE))?).lines() {
+            let line = line?;
+            match line.split_once('\t') {
+                Some((offset, first)) => match offset.parse() {
+                    Ok(offset) => blocks.push((first.to_string(), offset)),
+                    Err(_) => return invalid_data("Invalid offset in ads.txt index"),
+                },
+                None => return invalid_data("Invalid line in ads.txt index"),
+            }
+        }
+
+        Ok(DiskIndex {
+            records: dir.join(RECORDS_FILE),
+            blocks,
+        })
+    }
+
+    /// Calls `f` with every record line starting with the given key prefix
+    fn scan<F: FnMut(&str) -> io::Result<()>>(&self, prefix: &str, mut f: F) -> io::Result<()> {
+        // The matching lines start in the last block whose first line sorts
+        // before the prefix
+        let block = self
+            .blocks
+            .partition_point(|(first, _)| first.as_str() < prefix);
+        let offset = match block {
+            0 => 0,
+            block => self.blocks[block - 1].1,
+        };
+
+        let mut reader = BufReader::new(File::open(&self.records)?);
+        reader.seek(SeekFrom::Start(offset))?;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim_end_matches('\n');
+
+            if line.starts_with(prefix) {
+                f(line)?;
+            } else if line > prefix {
+                return Ok(());
+            }
+        }
+    }
+
+    fn parse_listing(line: &str) -> io::Result<Listing> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 4 && fields.len() != 5 {
+            return invalid_data("Invalid record in ads.txt index");
+        }
+
+        let relation = match fields[3] {
+            "DIRECT" => AccountRelation::Direct,
+            "RESELLER" => AccountRelation::Reseller,
+            _ => return invalid_data("Invalid relation in ads.txt index"),
+        };
+
+        Ok(Listing {
+            publisher: unescape(fields[2]),
+            seller_id: unescape(fields[1]),
+            relation,
+            cert_authority: fields.get(4).map(|c| unescape(c)),
+        })
+    }
+
+    /// Every record listing the ad system, sorted by seller id and publisher
+    pub fn listings(&self, ad_system: &str) -> io::Result<Vec<Listing>> {
+        let prefix = format!("{}\t", escape(&normalize_domain(ad_system)));
+        let mut listings = vec![];
+        self.scan(&prefix, |line| {
+            listings.push(Self::parse_listing(line)?);
+            Ok(())
+        })?;
+        Ok(listings)
+    }
+
+    /// Every record listing the seat on the ad system, sorted by publisher
+    pub fn seat_listings(&self, ad_system: &str, seat: &str) -> io::Result<Vec<Listing>> {
+        let prefix = format!(
+            "{}\t{}\t",
+            escape(&normalize_domain(ad_system)),
+            escape(seat.trim())
+        );
+        let mut listings = vec![];
+        self.scan(&prefix, |line| {
+            listings.push(Self::parse_listing(line)?);
+            Ok(())
+        })?;
+        Ok(listings)
+    }
+
+    /// Publishers listing the ad system, sorted
+    pub fn publishers(&self, ad_system: &str) -> io::Result<Vec<String>> {
+        let mut publishers: Vec<String> = self
+            .listings(ad_system)?
+            .into_iter()
+            .map(|l| l.publisher)
+            .collect();
+        publishers.sort_unstable();
+        publishers.dedup();
+        Ok(publishers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn querying_disk_index() {
+        let dir = std::env::temp_dir().join(format!("ads-txt-index-{}", std::process::id()));
+
+        // Enough publishers to span several blocks
+        let files: Vec<(String, AdsTxt)> = (0..300)
+            .map(|i| {
+                let text = format!(
+                    "exchange.com, {}, DIRECT\nother.com, x\\y, RESELLER, abc",
+                    i % 7
+                );
+                (format!("pub{:03}.com", i), AdsTxt::parse(&text).unwrap())
+            })
+            .collect();
+        let index = DiskIndex::build(&dir, files.iter().map(|(p, a)| (p.as_str(), a))).unwrap();
+
+        let listings = index.seat_listings("Exchange.com", "3").unwrap();
+        assert_eq!(listings.len(), 43);
+        assert!(listings.iter().all(|l| l.seller_id == "3"));
+        assert_eq!(listings[0].publisher, "pub003.com");
+
+        assert_eq!(index.listings("exchange.com").unwrap().len(), 300);
+        assert_eq!(
+            index.seat_listings("other.com", "x\\y").unwrap()[0],
+            Listing {
+                publisher: "pub000.com".to_string(),
+                seller_id: "x\\y".to_string(),
+                relation: AccountRelation::Reseller,
+                cert_authority: Some("abc".to_string()),
+            }
+        );
+        assert_eq!(index.publishers("other.com").unwrap().len(), 300);
+        assert!(index.listings("exchange.co").unwrap().is_empty());
+        assert!(index.listings("unknown.com").unwrap().is_empty());
+
+        let reopened = DiskIndex::open(&dir).unwrap();
+        assert_eq!(
+            reopened.seat_listings("exchange.com", "6").unwrap().len(),
+            42
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+//! Equality of records ignoring selected fields. Many workflows identify a
+//! record by its ad system and publisher id alone, treating the relation and
+//! cert authority as metadata which should not make two records distinct.
+
+use crate::publisher_id::PublisherIdPolicy;
+use crate::{AdsTxt, DataRecord};
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+
+/// Field of a record which a `RecordEquality` may ignore. The ad system and
+/// publisher id identify a record, so they are always compared.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum RecordField {
+    Relation,
+    CertAuthority,
+}
+
+impl RecordField {
+    pub const ALL: &'static [RecordField] = &[RecordField::Relation, RecordField::CertAuthority];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordField::Relation => "relation",
+            RecordField::CertAuthority => "cert-authority",
+        }
+    }
+
+    /// Finds a field by its `as_str` name
+    pub fn from_name(name: &str) -> Option<RecordField> {
+        RecordField::ALL
+            .iter()
+            .copied()
+            .find(|f| f.as_str() == name.trim())
+    }
+}
+
+/// Comparison of records: ad systems ignoring case, publisher ids exactly or
+/// by a `PublisherIdPolicy`, and the fields which are not ignored, cert
+/// authority ids ignoring case
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct RecordEquality {
+    ignore_relation: bool,
+    ignore_cert_authority: bool,
+}
+
+/// Form of a record which is equal for all records which are the same
+pub type RecordKey = (String, String, Option<&'static str>, Option<Option<String>>);
+
+impl RecordEquality {
+    /// Equality comparing all fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Equality comparing only the ad system and publisher id
+    pub fn identity() -> Self {
+        RecordField::ALL
+            .iter()
+            .fold(Self::new(), |equality, field| equality.ignoring(*field))
+    }
+
+    pub fn ignoring(mut self, field: RecordField) -> Self {
+        match field {
+            RecordField::Relation => self.ignore_relation = true,
+            RecordField::CertAuthority => self.ignore_cert_authority = true,
+        }
+        self
+    }
+
+    pub fn ignores(&self, field: RecordField) -> bool {
+        match field {
+            RecordField::Relation => self.ignore_relation,
+            RecordField::CertAuthority => self.ignore_cert_authority,
+        }
+    }
+
+    pub fn same(&self, a: &DataRecord, b: &DataRecord) -> bool {
+        self.same_with_policy(a, b, &PublisherIdPolicy::default())
+    }
+
+    /// Like `same`, comparing publisher ids by the policy
+    pub fn same_with_policy(
+        &self,
+        a: &DataRecord,
+        b: &DataRecord,
+        policy: &PublisherIdPolicy,
+    ) -> bool {
+        a.domain.eq_ignore_ascii_case(&b.domain)
+            && policy.same_id(&a.domain, &a.publisher_id, &b.publisher_id)
+            && self.same_fields(a, b)
+    }
+
+    /// Whether the relation and cert authority are the same where compared
+    pub(crate) fn same_fields(&self, a: &DataRecord, b: &DataRecord) -> bool {
+        (self.ignore_relation || a.acc_relation == b.acc_relation)
+            && (self.ignore_cert_authority
+                || a.cert_authority.as_deref().map(str::to_lowercase)
+                    == b.cert_authority.as_deref().map(str::to_lowercase))
+    }
+
+    /// Key of the record, for keying maps and sets by record
+    pub fn key(&self, record: &DataRecord) -> RecordKey {
+        self.key_with_policy(record, &PublisherIdPolicy::default())
+    }
+
+    /// Like `key`, keying publisher ids by the policy
+    pub fn key_with_policy(&self, record: &DataRecord, policy: &PublisherIdPolicy) -> RecordKey {
+        (
+            record.domain.to_lowercase(),
+            policy
+                .key(&record.domain, &record.publisher_id)
+                .into_owned(),
+            if self.ignore_relation {
+                None
+            } else {
+                Some(record.acc_relation.as_str())
+            },
+            if self.ignore_cert_authority {
+                None
+            } else {
+                Some(record.cert_authority.as_deref().map(str::to_lowercase))
+            },
+        )
+    }
+
+    /// Removes the records which are the same as an earlier one, keeping
+    /// the variables
+    pub fn dedup(&self, ads_txt: &mut AdsTxt) {
+        let mut seen = BTreeSet::new();
+        ads_txt
+            .records
+            .retain(|record| seen.insert(self.key(record)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comparing_masked_records() {
+        let a = DataRecord::parse("a.com, 1, DIRECT, abc").unwrap();
+        let b = DataRecord::parse("A.com, 1, RESELLER").unwrap();
+        assert!(!RecordEquality::new().same(&a, &b));
+        assert!(!RecordEquality::new()
+            .ignoring(RecordField::Relation)
+            .same(&a, &b));
+        assert!(RecordEquality::identity().same(&a, &b));
+        assert_eq!(
+            RecordEquality::identity().key(&a),
+            RecordEquality::identity().key(&b)
+        );
+        let c = DataRecord::parse("a.com, AB, DIRECT, abc").unwrap();
+        let d = DataRecord::parse("a.com, ab, DIRECT, abc").unwrap();
+        let policy = PublisherIdPolicy::new(crate::publisher_id::IdComparison::CaseInsensitive);
+        assert!(!RecordEquality::new().same(&c, &d));
+        assert!(RecordEquality::new().same_with_policy(&c, &d, &policy));
+        assert_eq!(
+            RecordEquality::new().key_with_policy(&c, &policy),
+            RecordEquality::new().key_with_policy(&d, &policy)
+        );
+        assert_eq!(
+            RecordField::from_name("cert-authority"),
+            Some(RecordField::CertAuthority)
+        );
+
+        let mut ads_txt = AdsTxt::parse(
+            "a.com, 1, DIRECT, abc\na.com, 1, DIRECT, ABC\na.com, 1, DIRECT\nb.com, 1, RESELLER",
+        )
+        .unwrap();
+        RecordEquality::new().dedup(&mut ads_txt);
+        assert_eq!(ads_txt.records.len(), 3);
+        RecordEquality::new()
+            .ignoring(RecordField::CertAuthority)
+            .dedup(&mut ads_txt);
+        assert_eq!(
+            ads_txt,
+            AdsTxt::parse("a.com, 1, DIRECT, abc\nb.com, 1, RESELLER").unwrap()
+        );
+    }
+}
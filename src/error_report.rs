@@ -0,0 +1,311 @@
+//! Aggregation of the errors of many lenient parses into a report grouped by
+//! error code, for corpus-wide data quality summaries
+//!
+//! Errors of lines which are not valid ads.txt lines are grouped by the
+//! `RecordIssue` of the line, e.g. `field-count`; other errors under
+//! `other`.
+
+use crate::fetch::FetchedAdsTxt;
+use crate::json::JsonValue;
+use crate::{AdsTxt, AdsTxtError, DataRecord};
+use std::collections::{BTreeMap, HashMap};
+
+const INVALID_LINE_PREFIX: &str = "Invalid ads.txt line: ";
+
+/// Code of errors which are not of an invalid line
+pub const OTHER_CODE: &str = "other";
+
+/// Code of the group of an error and the line it is about, if any
+fn classify(error: &AdsTxtError) -> (&'static str, Option<&str>) {
+    match error.message().strip_prefix(INVALID_LINE_PREFIX) {
+        Some(line) => match DataRecord::validate_str(line) {
+            Err(issue) => (issue.as_str(), Some(line)),
+            Ok(()) => ("invalid-line", Some(line)),
+        },
+        None => (OTHER_CODE, None),
+    }
+}
+
+/// Error found in a file, kept as an example of its group
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ErrorExample {
+    pub domain: String,
+    /// Offending line, or the error message when it is not about a line
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ErrorGroup {
+    count: usize,
+    by_domain: HashMap<String, usize>,
+    examples: Vec<ErrorExample>,
+}
+
+/// Collects the errors of lenient parses of many files
+#[derive(Debug, Clone)]
+pub struct ErrorAggregator {
+    groups: BTreeMap<&'static str, ErrorGroup>,
+    files: usize,
+    files_with_errors: usize,
+    max_examples: usize,
+}
+
+impl Default for ErrorAggregator {
+    fn default() -> Self {
+        ErrorAggregator {
+            groups: BTreeMap::new(),
+            files: 0,
+            files_with_errors: 0,
+            max_examples: 3,
+        }
+    }
+}
+
+impl ErrorAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps up to the given number of example lines per group, 3 by default
+    pub fn with_max_examples(mut self, max_examples: usize) -> Self {
+        self.max_examples = max_examples;
+        self
+    }
+
+    /// Adds the errors of parsing the file of a domain, which may be none
+    pub fn add(&mut self, domain: &str, errors: &[AdsTxtError]) {
+        self.files += 1;
+        if !errors.is_empty() {
+            self.files_with_errors += 1;
+        }
+
+        for error in errors {
+            let (code, line) = classify(error);
+            let group = self.groups.entry(code).or_default();
+            group.count += 1;
+            *group.by_domain.entry(domain.to_string()).or_default() += 1;
+            if group.examples.len() < self.max_examples {
+                group.examples.push(ErrorExample {
+                    domain: domain.to_string(),
+                    text: line.map_or_else(|| error.to_string(), str::to_string),
+                });
+            }
+        }
+    }
+
+    /// Parses the file of a domain leniently, adding its errors
+    pub fn add_text(&mut self, domain: &str, text: &str) -> AdsTxt {
+        let (ads_txt, errors) = AdsTxt::parse_lenient(text);
+        self.add(domain, &errors);
+        ads_txt
+    }
+
+    pub fn add_fetched(&mut self, fetched: &FetchedAdsTxt) {
+        self.add(&fetched.domain, &fetched.errors);
+    }
+
+    /// Adds the errors collected by another aggregator, e.g. of another
+    /// thread
+    pub fn merge(&mut self, other: ErrorAggregator) {
+        self.files += other.files;
+        self.files_with_errors += other.files_with_errors;
+        for (code, other) in other.groups {
+            let group = self.groups.entry(code).or_default();
+            group.count += other.count;
+            for (domain, count) in other.by_domain {
+                *group.by_domain.entry(domain).or_default() += count;
+            }
+            let room = self.max_examples.saturating_sub(group.examples.len());
+            group.examples.extend(other.examples.into_iter().take(room));
+        }
+    }
+
+    /// Report of the errors by group, most frequent first, with the given
+    /// number of domains with the most errors of each
+    pub fn report(&self, top_domains: usize) -> ErrorReport {
+        let mut groups: Vec<GroupReport> = self
+            .groups
+            .iter()
+            .map(|(code, group)| {
+                let mut domains: Vec<(String, usize)> = group
+                    .by_domain
+                    .iter()
+                    .map(|(domain, count)| (domain.clone(), *count))
+                    .collect();
+                domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                domains.truncate(top_domains);
+
+                GroupReport {
+                    code,
+                    count: group.count,
+                    domains: group.by_domain.len(),
+                    top_domains: domains,
+                    examples: group.examples.clone(),
+                }
+            })
+            .collect();
+        groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.code.cmp(b.code)));
+
+        ErrorReport {
+            files: self.files,
+            files_with_errors: self.files_with_errors,
+            errors: groups.iter().map(|g| g.count).sum(),
+            groups,
+        }
+    }
+}
+
+/// Errors of one code
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GroupReport {
+    pub code: &'static str,
+    pub count: usize,
+    /// Number of domains with errors of the code
+    pub domains: usize,
+    /// Domains with the most errors of the code and their counts
+    pub top_domains: Vec<(String, usize)>,
+    pub examples: Vec<ErrorExample>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ErrorReport {
+    pub files: usize,
+    pub files_with_errors: usize,
+    pub errors: usize,
+    /// Groups by error code, most frequent first
+    pub groups: Vec<GroupReport>,
+}
+
+impl ErrorReport {
+    pub fn to_json(&self) -> JsonValue {
+        let groups = self
+            .groups
+            .iter()
+            .map(|g| {
+                JsonValue::Object(vec![
+                    ("code".to_string(), JsonValue::string(g.code)),
+                    ("count".to_string(), JsonValue::number(g.count)),
+                    ("domains".to_string(), JsonValue::number(g.domains)),
+                    (
+                        "top_domains".to_string(),
+                        JsonValue::Array(
+                            g.top_domains
+                                .iter()
+                                .map(|(domain, count)| {
+                                    JsonValue::Object(vec![
+                                        ("domain".to_string(), JsonValue::string(domain)),
+                                        ("count".to_string(), JsonValue::number(*count)),
+                                    ])
+                                })
+                                .collect(),
+                        ),
+                    ),
+                    (
+                        "examples".to_string(),
+                        JsonValue::Array(
+                            g.examples
+                                .iter()
+                                .map(|e| {
+                                    JsonValue::Object(vec![
+                                        ("domain".to_string(), JsonValue::string(&e.domain)),
+                                        ("text".to_string(), JsonValue::string(&e.text)),
+                                    ])
+                                })
+                                .collect(),
+                        ),
+                    ),
+                ])
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            ("files".to_string(), JsonValue::number(self.files)),
+            (
+                "files_with_errors".to_string(),
+                JsonValue::number(self.files_with_errors),
+            ),
+            ("errors".to_string(), JsonValue::number(self.errors)),
+            ("groups".to_string(), JsonValue::Array(groups)),
+        ])
+    }
+}
+
+impl std::fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} errors in {} of {} files",
+            self.errors, self.files_with_errors, self.files
+        )?;
+        for group in &self.groups {
+            writeln!(
+                f,
+                "{}: {} in {} domains",
+                group.code, group.count, group.domains
+            )?;
+            for (domain, count) in &group.top_domains {
+                writeln!(f, "    {} {}", domain, count)?;
+            }
+            for example in &group.examples {
+                writeln!(f, "    e.g. {}: {}", example.domain, example.text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregating_errors() {
+        let mut aggregator = ErrorAggregator::new().with_max_examples(1);
+        aggregator.add_text("a.com", "x.com, 1, DIRECT\nx.com, 1, OTHER\nx.com 2 DIRECT");
+        aggregator.add_text("b.com", "x.com, 1, DIRECT");
+
+        let mut other = ErrorAggregator::new();
+        other.add_text("c.com", "y.com, 1\ny.com; 2; DIRECT\ny.com, 3, RESALE");
+        other.add("d.com", &[AdsTxtError::new("Error reading ads.txt: reset")]);
+        aggregator.merge(other);
+
+        let report = aggregator.report(1);
+        assert_eq!(
+            (report.files, report.files_with_errors, report.errors),
+            (4, 3, 6)
+        );
+        let groups: Vec<(&str, usize, usize)> = report
+            .groups
+            .iter()
+            .map(|g| (g.code, g.count, g.domains))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("field-count", 3, 2),
+                ("invalid-relation", 2, 2),
+                ("other", 1, 1)
+            ]
+        );
+        assert_eq!(report.groups[0].top_domains, vec![("c.com".to_string(), 2)]);
+        assert_eq!(
+            report.groups[0].examples,
+            vec![ErrorExample {
+                domain: "a.com".to_string(),
+                text: "x.com 2 DIRECT".to_string(),
+            }]
+        );
+
+        assert_eq!(
+            report.to_string(),
+            "6 errors in 3 of 4 files\n\
+             field-count: 3 in 2 domains\n    c.com 2\n    e.g. a.com: x.com 2 DIRECT\n\
+             invalid-relation: 2 in 2 domains\n    a.com 1\n    e.g. a.com: x.com, 1, OTHER\n\
+             other: 1 in 1 domains\n    d.com 1\n    e.g. d.com: Error reading ads.txt: reset\n"
+        );
+        assert_eq!(
+            report.to_json().get("groups").unwrap().as_array().unwrap()[2].to_string(),
+            r#"{"code":"other","count":1,"domains":1,"top_domains":[{"domain":"d.com","count":1}],"examples":[{"domain":"d.com","text":"Error reading ads.txt: reset"}]}"#
+        );
+    }
+}
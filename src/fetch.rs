@@ -0,0 +1,2901 @@
+//! Fetching ads.txt files over HTTP
+
+use crate::app_ads::StoreListing;
+use crate::charset::{decode_body, Charset};
+use crate::checkpoint::{CheckpointStore, CrawlCheckpoint};
+use crate::corpus::{
+    content_hash_bytes, normalized_hash, AdsTxtCorpus, CorpusEntry, CorpusMetadata,
+};
+use crate::diff::ConformanceReport;
+use crate::json::JsonValue;
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsRecorder;
+use crate::robots::RobotsTxt;
+use crate::sellers_json::SellersJson;
+use crate::serialize::ads_txt_to_json;
+use crate::{AdsTxt, AdsTxtError, FileKind};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Formatter;
+use std::io;
+#[cfg(feature = "fetch")]
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+#[cfg(feature = "fetch")]
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+#[cfg(feature = "futures")]
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "futures")]
+use std::sync::mpsc::TryRecvError;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+#[cfg(feature = "futures")]
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+#[cfg(feature = "fetch")]
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_USER_AGENT: &str = concat!("rs-ads-txt/", env!("CARGO_PKG_VERSION"));
+
+pub type FetchResult<T> = ::std::result::Result<T, FetchError>;
+
+/// Classification of crawl failures
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FetchError {
+    /// The URL could not be parsed
+    InvalidUrl(String),
+    /// The host name could not be resolved
+    Dns(String),
+    /// Connecting to the host timed out
+    ConnectTimeout(String),
+    /// Any other connection or IO failure
+    Connection(String),
+    /// TLS handshake failed or HTTPS is not supported by the client
+    Tls(String),
+    /// The server responded with something other than HTTP
+    InvalidResponse(String),
+    /// The server responded with 404
+    NotFound,
+    /// The server responded with a 5xx status
+    ServerError(u16),
+    /// The server responded with any other unexpected status
+    HttpStatus(u16),
+    /// A redirect left the scope of the root domain more than once or
+    /// redirected too often
+    RedirectPolicy(String),
+    /// The file was not served as `text/plain`
+    WrongContentType(String),
+    /// The response body exceeded the client's size limit
+    OversizeBody,
+    /// The body contained no valid ads.txt content, e.g. an HTML error page
+    Parse(AdsTxtError),
+}
+
+impl FetchError {
+    /// Short stable label of the failure class, for bucketing in crawl analytics
+    pub fn class(&self) -> &'static str {
+        match self {
+            FetchError::InvalidUrl(_) => "invalid_url",
+            FetchError::Dns(_) => "dns",
+            FetchError::ConnectTimeout(_) => "connect_timeout",
+            FetchError::Connection(_) => "connection",
+            FetchError::Tls(_) => "tls",
+            FetchError::InvalidResponse(_) => "invalid_response",
+            FetchError::NotFound => "not_found",
+            FetchError::ServerError(_) => "server_error",
+            FetchError::HttpStatus(_) => "http_status",
+            FetchError::RedirectPolicy(_) => "redirect_policy",
+            FetchError::WrongContentType(_) => "content_type",
+            FetchError::OversizeBody => "oversize_body",
+            FetchError::Parse(_) => "parse",
+        }
+    }
+
+    fn from_status(status: u16) -> FetchError {
+        match status {
+            404 => FetchError::NotFound,
+            500..=599 => FetchError::ServerError(status),
+            _ => FetchError::HttpStatus(status),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<AdsTxtError> for FetchError {
+    fn from(e: AdsTxtError) -> Self {
+        FetchError::Parse(e)
+    }
+}
+
+/// IO failures once connected, e.g. reading a response
+impl From<io::Error> for FetchError {
+    fn from(e: io::Error) -> Self {
+        FetchError::Connection(e.to_string())
+    }
+}
+
+/// Wraps a failed fetch, e.g. where fetching and parsing share a `Result`
+impl From<FetchError> for AdsTxtError {
+    fn from(e: FetchError) -> Self {
+        AdsTxtError::with_source(&e.to_string(), e)
+    }
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::InvalidUrl(url) => write!(f, "Invalid URL: {}", url),
+            FetchError::Dns(message) => write!(f, "DNS failure: {}", message),
+            FetchError::ConnectTimeout(host) => write!(f, "Connection timed out: {}", host),
+            FetchError::Connection(message) => write!(f, "Connection error: {}", message),
+            FetchError::Tls(message) => write!(f, "TLS error: {}", message),
+            FetchError::InvalidResponse(message) => write!(f, "Invalid response: {}", message),
+            FetchError::NotFound => write!(f, "Not found"),
+            FetchError::ServerError(status) => write!(f, "Server error: HTTP {}", status),
+            FetchError::HttpStatus(status) => write!(f, "Unexpected status: HTTP {}", status),
+            FetchError::RedirectPolicy(message) => {
+                write!(f, "Redirect policy violation: {}", message)
+            }
+            FetchError::WrongContentType(content_type) => {
+                write!(f, "Wrong content type: {}", content_type)
+            }
+            FetchError::OversizeBody => write!(f, "Response body too large"),
+            FetchError::Parse(e) => write!(f, "Parse failure: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, headers: &[(&str, &str)], body: &[u8]) -> Self {
+        HttpResponse {
+            status,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body: body.to_vec(),
+        }
+    }
+
+    /// Returns the value of the first header with the given name, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// HTTP transport used by the crawler, so that callers can plug in their own
+/// client (e.g. one with HTTPS support)
+pub trait HttpClient {
+    /// Performs a GET request without following redirects
+    fn get(&self, url: &str, user_agent: &str) -> FetchResult<HttpResponse>;
+}
+
+impl<C: HttpClient + ?Sized> HttpClient for &C {
+    fn get(&self, url: &str, user_agent: &str) -> FetchResult<HttpResponse> {
+        (**self).get(url, user_agent)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Url {
+    pub scheme: String,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> FetchResult<Url> {
+        let invalid = || FetchError::InvalidUrl(url.to_string());
+
+        let (scheme, rest) = match url.find("://") {
+            Some(i) => (url[..i].to_lowercase(), &url[i + 3..]),
+            None => return Err(invalid()),
+        };
+
+        let default_port = match scheme.as_str() {
+            "http" => 80,
+            "https" => 443,
+            _ => return Err(invalid()),
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+
+        let (host, port) = match authority.rfind(':') {
+            Some(i) => match authority[i + 1..].parse::<u16>() {
+                Ok(port) => (&authority[..i], port),
+                Err(_) => return Err(invalid()),
+            },
+            None => (authority, default_port),
+        };
+
+        if host.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Url {
+            scheme,
+            host: host.to_lowercase(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Resolves a `Location` header value against this URL
+    pub fn join(&self, location: &str) -> FetchResult<Url> {
+        let location = location.trim();
+
+        if location.contains("://") {
+            Url::parse(location)
+        } else if let Some(rest) = location.strip_prefix("//") {
+            Url::parse(&format!("{}://{}", self.scheme, rest))
+        } else if location.starts_with('/') {
+            Ok(Url {
+                path: location.to_string(),
+                ..self.clone()
+            })
+        } else {
+            let dir = match self.path.rfind('/') {
+                Some(i) => &self.path[..=i],
+                None => "/",
+            };
+            Ok(Url {
+                path: format!("{}{}", dir, location),
+                ..self.clone()
+            })
+        }
+    }
+}
+
+impl std::fmt::Display for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let default_port = if self.scheme == "https" { 443 } else { 80 };
+        if self.port == default_port {
+            write!(f, "{}://{}{}", self.scheme, self.host, self.path)
+        } else {
+            write!(
+                f,
+                "{}://{}:{}{}",
+                self.scheme, self.host, self.port, self.path
+            )
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+type PoolKey = (String, u16);
+#[cfg(feature = "fetch")]
+type Pool = HashMap<PoolKey, Vec<(Connection, Protocol, Instant)>>;
+
+/// HTTP version spoken on a connection
+#[cfg(feature = "fetch")]
+#[derive(Debug)]
+enum Protocol {
+    Http1,
+    /// Selected by the server through ALPN
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    Http2(Box<crate::http2::Session>),
+}
+
+/// Connection of `TcpHttpClient`, over TLS for https URLs
+#[cfg(feature = "fetch")]
+#[derive(Debug)]
+enum Connection {
+    Plain(TcpStream),
+    #[cfg(feature = "rustls")]
+    Rustls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+    #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+    NativeTls(native_tls::TlsStream<TcpStream>),
+}
+
+#[cfg(feature = "fetch")]
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "rustls")]
+            Connection::Rustls(stream) => stream.read(buf),
+            #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+            Connection::NativeTls(stream) => stream.read(buf),
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "rustls")]
+            Connection::Rustls(stream) => stream.write(buf),
+            #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+            Connection::NativeTls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.flush(),
+            #[cfg(feature = "rustls")]
+            Connection::Rustls(stream) => stream.flush(),
+            #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+            Connection::NativeTls(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+impl Connection {
+    /// Whether the server selected HTTP/2 during the TLS handshake
+    fn negotiated_http2(&self) -> bool {
+        match self {
+            Connection::Plain(_) => false,
+            #[cfg(feature = "rustls")]
+            Connection::Rustls(stream) => stream.conn.alpn_protocol() == Some(b"h2"),
+            #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+            Connection::NativeTls(stream) => {
+                matches!(stream.negotiated_alpn(), Ok(Some(protocol)) if protocol == b"h2")
+            }
+        }
+    }
+}
+
+/// PEM blocks of the certificates of a bundle
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+fn pem_certificates(pem: &[u8]) -> FetchResult<Vec<String>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let text = std::str::from_utf8(pem)
+        .map_err(|_| FetchError::Tls("root certificates are not PEM".to_string()))?;
+    let mut blocks = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find(BEGIN) {
+        let end = rest[start..]
+            .find(END)
+            .ok_or_else(|| FetchError::Tls("unterminated PEM certificate".to_string()))?;
+        blocks.push(rest[start..start + end + END.len()].to_string());
+        rest = &rest[start + end + END.len()..];
+    }
+
+    if blocks.is_empty() {
+        return Err(FetchError::Tls("no PEM certificate found".to_string()));
+    }
+    Ok(blocks)
+}
+
+/// TLS client configuration of the backend selected by features, rustls if
+/// both `rustls` and `native-tls` are enabled
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+#[derive(Debug, Clone)]
+struct TlsConnector {
+    #[cfg(feature = "rustls")]
+    config: Arc<rustls::ClientConfig>,
+    #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+    connector: native_tls::TlsConnector,
+    /// Connector offering `h2` through ALPN
+    #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+    http2_connector: native_tls::TlsConnector,
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+impl TlsConnector {
+    /// Connector trusting the backend's default roots, the Mozilla roots of
+    /// `webpki-roots` with rustls and the system's with native-tls, and the
+    /// given PEM certificates
+    #[cfg(feature = "rustls")]
+    fn new(root_certificates: &[String]) -> FetchResult<Self> {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::CertificateDer;
+
+        let tls = |e: &dyn std::fmt::Display| FetchError::Tls(e.to_string());
+        let mut roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        for pem in root_certificates {
+            let der = CertificateDer::from_pem_slice(pem.as_bytes()).map_err(|e| tls(&e))?;
+            roots.add(der).map_err(|e| tls(&e))?;
+        }
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| tls(&e))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(TlsConnector {
+            config: Arc::new(config),
+        })
+    }
+
+    #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+    fn new(root_certificates: &[String]) -> FetchResult<Self> {
+        let tls = |e: native_tls::Error| FetchError::Tls(e.to_string());
+        let mut builder = native_tls::TlsConnector::builder();
+        for pem in root_certificates {
+            builder.add_root_certificate(
+                native_tls::Certificate::from_pem(pem.as_bytes()).map_err(tls)?,
+            );
+        }
+        let connector = builder.build().map_err(tls)?;
+        builder.request_alpns(&["h2", "http/1.1"]);
+        Ok(TlsConnector {
+            connector,
+            http2_connector: builder.build().map_err(tls)?,
+        })
+    }
+
+    /// Connects over TLS, offering `h2` through ALPN if `http2`
+    #[cfg(feature = "rustls")]
+    fn connect(&self, host: &str, mut stream: TcpStream, http2: bool) -> FetchResult<Connection> {
+        use std::convert::TryFrom;
+
+        let tls = |e: &dyn std::fmt::Display| FetchError::Tls(format!("{}: {}", host, e));
+        let name =
+            rustls::pki_types::ServerName::try_from(host.to_string()).map_err(|e| tls(&e))?;
+        let protocols = if http2 {
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        } else {
+            vec![]
+        };
+        let mut connection =
+            rustls::ClientConnection::new_with_alpn(Arc::clone(&self.config), name, protocols)
+                .map_err(|e| tls(&e))?;
+        // Handshake up front, so that its failures are told apart from
+        // those of the request
+        while connection.is_handshaking() {
+            connection.complete_io(&mut stream).map_err(|e| tls(&e))?;
+        }
+        Ok(Connection::Rustls(Box::new(rustls::StreamOwned::new(
+            connection, stream,
+        ))))
+    }
+
+    #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+    fn connect(&self, host: &str, stream: TcpStream, http2: bool) -> FetchResult<Connection> {
+        let connector = if http2 {
+            &self.http2_connector
+        } else {
+            &self.connector
+        };
+        connector
+            .connect(host, stream)
+            .map(Connection::NativeTls)
+            .map_err(|e| FetchError::Tls(format!("{}: {}", host, e)))
+    }
+}
+
+/// Whether an address is reachable from the public internet, i.e. not
+/// loopback, private, link-local, carrier-grade NAT or otherwise reserved
+#[cfg(feature = "fetch")]
+pub fn is_public_address(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                // Shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_address(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Blocking HTTP/1.1 client built on `std::net`. HTTPS needs the `rustls` or
+/// `native-tls` feature, with which HTTP/2 may be preferred by `with_http2`.
+/// Connections are kept alive and reused per host; clones share the pool.
+#[cfg(feature = "fetch")]
+#[derive(Debug, Clone)]
+pub struct TcpHttpClient {
+    timeout: Duration,
+    max_body_size: usize,
+    max_idle_per_host: usize,
+    pool_idle_timeout: Duration,
+    pool: Arc<Mutex<Pool>>,
+    /// `None` if the TLS backend failed to initialize
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    tls: Option<TlsConnector>,
+    http2: bool,
+    public_addresses_only: bool,
+}
+
+#[cfg(feature = "fetch")]
+impl Default for TcpHttpClient {
+    fn default() -> Self {
+        TcpHttpClient::new(Duration::from_secs(10))
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl TcpHttpClient {
+    pub fn new(timeout: Duration) -> Self {
+        TcpHttpClient {
+            timeout,
+            max_body_size: 1024 * 1024,
+            max_idle_per_host: 4,
+            pool_idle_timeout: Duration::from_secs(90),
+            pool: Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            tls: TlsConnector::new(&[]).ok(),
+            http2: false,
+            public_addresses_only: false,
+        }
+    }
+
+    /// Trusts the certificates of a PEM bundle, e.g. a corporate CA, besides
+    /// the default roots of the TLS backend
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn with_root_certificates_pem(mut self, pem: &[u8]) -> FetchResult<Self> {
+        self.tls = Some(TlsConnector::new(&pem_certificates(pem)?)?);
+        Ok(self)
+    }
+
+    /// Prefers HTTP/2 for HTTPS, offering `h2` through ALPN and speaking
+    /// HTTP/1.1 with servers which do not select it. HTTP/2 connections are
+    /// pooled like HTTP/1.1 ones, carrying one request at a time.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn with_http2(mut self, http2: bool) -> Self {
+        self.http2 = http2;
+        self
+    }
+
+    /// Refuses to connect to addresses which are not `is_public_address`.
+    /// The addresses a host resolves to are checked before connecting, so
+    /// redirects, fallbacks and hosts re-resolving to another address are
+    /// all covered.
+    pub fn with_public_addresses_only(mut self, public_addresses_only: bool) -> Self {
+        self.public_addresses_only = public_addresses_only;
+        self
+    }
+
+    /// Sets the maximum accepted response body size in bytes
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Sets how many idle connections are kept per host, zero disables keep-alive
+    pub fn with_max_idle_per_host(mut self, max_idle_per_host: usize) -> Self {
+        self.max_idle_per_host = max_idle_per_host;
+        self
+    }
+
+    /// Sets how long an idle connection is kept before it is discarded
+    pub fn with_pool_idle_timeout(mut self, pool_idle_timeout: Duration) -> Self {
+        self.pool_idle_timeout = pool_idle_timeout;
+        self
+    }
+
+    /// Number of idle connections currently pooled for the given host
+    pub fn idle_connections(&self, host: &str, port: u16) -> usize {
+        match self.pool.lock() {
+            Ok(pool) => pool
+                .get(&(host.to_lowercase(), port))
+                .map_or(0, |idle| idle.len()),
+            Err(_) => 0,
+        }
+    }
+
+    fn take_idle(&self, key: &PoolKey) -> Option<(Connection, Protocol)> {
+        let mut pool = self.pool.lock().ok()?;
+        let idle = pool.get_mut(key)?;
+
+        while let Some((stream, protocol, since)) = idle.pop() {
+            if since.elapsed() < self.pool_idle_timeout {
+                return Some((stream, protocol));
+            }
+        }
+        None
+    }
+
+    fn put_idle(&self, key: PoolKey, stream: Connection, protocol: Protocol) {
+        if let Ok(mut pool) = self.pool.lock() {
+            let idle = pool.entry(key).or_default();
+            idle.retain(|(_, _, since)| since.elapsed() < self.pool_idle_timeout);
+            if idle.len() < self.max_idle_per_host {
+                idle.push((stream, protocol, Instant::now()));
+            }
+        }
+    }
+
+    fn connect(&self, url: &Url) -> FetchResult<TcpStream> {
+        let addrs = (url.host.as_str(), url.port)
+            .to_socket_addrs()
+            .map_err(|e| FetchError::Dns(format!("{}: {}", url.host, e)))?;
+
+        let mut last_error = None;
+        let mut refused = None;
+        for addr in addrs {
+            if self.public_addresses_only && !is_public_address(addr.ip()) {
+                refused = Some(addr.ip());
+                continue;
+            }
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => {
+                    stream
+                        .set_read_timeout(Some(self.timeout))
+                        .and_then(|_| stream.set_write_timeout(Some(self.timeout)))
+                        .map_err(|e| FetchError::Connection(e.to_string()))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match last_error {
+            Some(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                Err(FetchError::ConnectTimeout(url.host.clone()))
+            }
+            Some(e) => Err(FetchError::Connection(format!("{}: {}", url.host, e))),
+            None => match refused {
+                Some(ip) => Err(FetchError::Connection(format!(
+                    "{}: {} is not a public address",
+                    url.host, ip
+                ))),
+                None => Err(FetchError::Dns(format!("{}: no addresses", url.host))),
+            },
+        }
+    }
+
+    /// Connects to the host of the URL, over TLS for https, starting an
+    /// HTTP/2 session if `http2` and the server selects it
+    #[cfg_attr(
+        not(any(feature = "rustls", feature = "native-tls")),
+        allow(unused_variables)
+    )]
+    fn open(&self, url: &Url, http2: bool) -> FetchResult<(Connection, Protocol)> {
+        if url.scheme != "https" {
+            return Ok((Connection::Plain(self.connect(url)?), Protocol::Http1));
+        }
+
+        #[cfg(any(feature = "rustls", feature = "native-tls"))]
+        match &self.tls {
+            Some(tls) => {
+                let mut stream = tls.connect(&url.host, self.connect(url)?, http2)?;
+                if !stream.negotiated_http2() {
+                    return Ok((stream, Protocol::Http1));
+                }
+                let session = crate::http2::Session::start(&mut stream)?;
+                Ok((stream, Protocol::Http2(Box::new(session))))
+            }
+            None => Err(FetchError::Tls(format!(
+                "the TLS backend failed to initialize: {}",
+                url
+            ))),
+        }
+        #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+        Err(FetchError::Tls(format!(
+            "HTTPS is not supported by the built-in client without the rustls or native-tls feature: {}",
+            url
+        )))
+    }
+
+    /// Sends a request on the given connection, returning the response and
+    /// whether the connection may be reused, or `None` if the connection was
+    /// closed before any of the response arrived
+    fn request(
+        &self,
+        stream: &mut Connection,
+        protocol: &mut Protocol,
+        url: &Url,
+        user_agent: &str,
+    ) -> FetchResult<Option<(HttpResponse, bool)>> {
+        let keep_alive = self.max_idle_per_host > 0;
+        match protocol {
+            Protocol::Http1 => {}
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            Protocol::Http2(session) => {
+                let response = session.get(stream, url, user_agent, self.max_body_size)?;
+                return Ok(response.map(|response| (response, keep_alive && session.is_reusable())));
+            }
+        }
+
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: text/plain, */*\r\nConnection: {}\r\n\r\n",
+            url.path,
+            url.host,
+            user_agent,
+            if keep_alive { "keep-alive" } else { "close" }
+        );
+        match stream.write_all(request.as_bytes()) {
+            Ok(()) => {}
+            Err(e) if closed_before_response(&e) => return Ok(None),
+            Err(e) => return Err(FetchError::Connection(e.to_string())),
+        }
+
+        let mut reader = BufReader::new(stream);
+        match reader.fill_buf() {
+            Ok([]) => return Ok(None),
+            Ok(_) => {}
+            Err(e) if closed_before_response(&e) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let (response, reusable) = read_response(&mut reader, self.max_body_size)?;
+        Ok(Some((response, keep_alive && reusable)))
+    }
+
+    /// Sends a POST request on a new connection, e.g. to deliver a webhook
+    pub fn post(
+        &self,
+        url: &str,
+        content_type: &str,
+        body: &[u8],
+        user_agent: &str,
+    ) -> FetchResult<HttpResponse> {
+        let url = Url::parse(url)?;
+        let (mut stream, _) = self.open(&url, false)?;
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            url.path,
+            url.host,
+            user_agent,
+            content_type,
+            body.len()
+        );
+        stream
+            .write_all(head.as_bytes())
+            .and_then(|_| stream.write_all(body))?;
+
+        let (response, _) = read_response(&mut BufReader::new(&mut stream), self.max_body_size)?;
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl HttpClient for TcpHttpClient {
+    fn get(&self, url: &str, user_agent: &str) -> FetchResult<HttpResponse> {
+        let url = Url::parse(url)?;
+        let key = (url.host.clone(), url.port);
+
+        // A pooled connection may have been closed by the server in the
+        // meantime, in which case the request is retried on a new one. Any
+        // failure once the response has started is returned as is.
+        if let Some((mut stream, mut protocol)) = self.take_idle(&key) {
+            if let Some((response, reusable)) =
+                self.request(&mut stream, &mut protocol, &url, user_agent)?
+            {
+                if reusable {
+                    self.put_idle(key, stream, protocol);
+                }
+                return Ok(response);
+            }
+        }
+
+        let (mut stream, mut protocol) = self.open(&url, self.http2)?;
+        match self.request(&mut stream, &mut protocol, &url, user_agent)? {
+            Some((response, reusable)) => {
+                if reusable {
+                    self.put_idle(key, stream, protocol);
+                }
+                Ok(response)
+            }
+            None => Err(FetchError::Connection(format!(
+                "{}: connection closed without a response",
+                url.host
+            ))),
+        }
+    }
+}
+
+/// Whether an IO error means the peer closed the connection, as servers do
+/// with idle keep-alive connections
+#[cfg(feature = "fetch")]
+pub(crate) fn closed_before_response(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::UnexpectedEof
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+    )
+}
+
+#[cfg(feature = "fetch")]
+fn read_line<R: BufRead>(reader: &mut R) -> FetchResult<String> {
+    let mut line = vec![];
+    reader
+        .by_ref()
+        .take(8 * 1024)
+        .read_until(b'\n', &mut line)?;
+
+    if !line.ends_with(b"\n") {
+        return Err(FetchError::InvalidResponse(
+            "truncated response".to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+/// Reads an HTTP/1.1 response, returning it along with whether the body was
+/// delimited so the connection can be reused for another request
+#[cfg(feature = "fetch")]
+pub(crate) fn read_response<R: BufRead>(
+    reader: &mut R,
+    max_body_size: usize,
+) -> FetchResult<(HttpResponse, bool)> {
+    let status_line = read_line(reader)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok());
+    let status = match status {
+        Some(status) => status,
+        None => {
+            return Err(FetchError::InvalidResponse(
+                "malformed status line".to_string(),
+            ))
+        }
+    };
+
+    let mut headers = vec![];
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(i) = line.find(':') {
+            headers.push((
+                line[..i].trim().to_string(),
+                line[i + 1..].trim().to_string(),
+            ));
+        }
+        if headers.len() > 256 {
+            return Err(FetchError::InvalidResponse("too many headers".to_string()));
+        }
+    }
+
+    let mut response = HttpResponse {
+        status,
+        headers,
+        body: vec![],
+    };
+
+    let chunked = response
+        .header("transfer-encoding")
+        .is_some_and(|te| te.to_lowercase().contains("chunked"));
+    let content_length = response
+        .header("content-length")
+        .and_then(|l| l.parse::<usize>().ok());
+    let close = response
+        .header("connection")
+        .is_some_and(|c| c.eq_ignore_ascii_case("close"));
+
+    let delimited = if chunked {
+        response.body = read_chunked(reader, max_body_size)?;
+        true
+    } else if let Some(length) = content_length {
+        if length > max_body_size {
+            return Err(FetchError::OversizeBody);
+        }
+        response.body = vec![0; length];
+        reader.read_exact(&mut response.body)?;
+        true
+    } else {
+        reader
+            .take(max_body_size as u64 + 1)
+            .read_to_end(&mut response.body)?;
+        if response.body.len() > max_body_size {
+            return Err(FetchError::OversizeBody);
+        }
+        false
+    };
+
+    Ok((response, delimited && !close))
+}
+
+#[cfg(feature = "fetch")]
+fn read_chunked<R: BufRead>(reader: &mut R, max_body_size: usize) -> FetchResult<Vec<u8>> {
+    let mut body = vec![];
+
+    loop {
+        let size_line = read_line(reader)?;
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = match usize::from_str_radix(size_hex, 16) {
+            Ok(size) => size,
+            Err(_) => {
+                return Err(FetchError::InvalidResponse(
+                    "malformed chunked encoding".to_string(),
+                ))
+            }
+        };
+
+        if size == 0 {
+            // Skip any trailers up to the terminating empty line
+            while !read_line(reader)?.is_empty() {}
+            return Ok(body);
+        }
+        if body.len() + size > max_body_size {
+            return Err(FetchError::OversizeBody);
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        read_line(reader)?;
+    }
+}
+
+/// Checks whether a host is the given root domain or one of its subdomains
+fn within_domain(host: &str, domain: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    let domain = domain.trim_end_matches('.');
+
+    let host = host.to_ascii_lowercase();
+    let domain = domain.to_ascii_lowercase();
+    host == domain
+        || host
+            .strip_suffix(&domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CrawlerConfig {
+    /// User agent sent with requests and matched against robots.txt
+    pub user_agent: String,
+    /// Maximum number of redirects followed per request
+    pub max_redirects: usize,
+    /// Whether robots.txt is fetched and honored before requesting ads.txt
+    pub respect_robots_txt: bool,
+    /// Whether ads.txt is requested from `www.` + domain when the domain
+    /// itself does not serve it or cannot be reached
+    pub www_fallback: bool,
+    /// File fetched from each domain, ads.txt by default
+    pub file: FileKind,
+    /// Maximum number of `subdomain=` referrals between the root domain and
+    /// a host followed from it
+    pub max_referral_depth: usize,
+    /// Maximum number of referred hosts followed per root domain
+    pub max_referred_hosts: usize,
+}
+
+impl Default for CrawlerConfig {
+    fn default() -> Self {
+        CrawlerConfig {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            max_redirects: 5,
+            respect_robots_txt: false,
+            www_fallback: false,
+            file: FileKind::AdsTxt,
+            max_referral_depth: 2,
+            max_referred_hosts: 50,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct FetchedAdsTxt {
+    /// Domain for which the file was requested
+    pub domain: String,
+    /// Final URL the file was fetched from after redirects
+    pub url: String,
+    /// Leniently parsed file contents
+    pub ads_txt: AdsTxt,
+    /// Lines which could not be parsed
+    pub errors: Vec<AdsTxtError>,
+    /// 1-based line number of each record in the body
+    pub record_lines: Vec<usize>,
+    /// `content_hash_bytes` of the response body as served, before decoding,
+    /// which changes with any edit
+    pub raw_hash: u64,
+    /// `normalized_hash` of the parsed file, which only changes when the
+    /// authorized sellers or variables do
+    pub normalized_hash: u64,
+    /// Encoding the body was decoded from
+    pub charset: Charset,
+}
+
+impl FetchedAdsTxt {
+    /// Whether the file was edited since it had the given raw hash
+    pub fn changed_since(&self, raw_hash: u64) -> bool {
+        self.raw_hash != raw_hash
+    }
+
+    /// Whether the authorizations of the file changed since it had the
+    /// given normalized hash, rather than only comments or formatting
+    pub fn authorizations_changed_since(&self, normalized_hash: u64) -> bool {
+        self.normalized_hash != normalized_hash
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum CrawlResult {
+    Fetched(FetchedAdsTxt),
+    /// The fetch was not attempted since robots.txt disallows it
+    SkippedByRobots,
+    Failed(FetchError),
+}
+
+impl CrawlResult {
+    /// Renders the result of crawling the domain as one JSON object, e.g. a
+    /// line of JSONL crawl output
+    pub fn to_json(&self, domain: &str) -> JsonValue {
+        let mut members = vec![("domain".to_string(), JsonValue::string(domain))];
+
+        match self {
+            CrawlResult::Fetched(fetched) => {
+                members.push(("status".to_string(), JsonValue::string("fetched")));
+                members.push(("url".to_string(), JsonValue::string(&fetched.url)));
+                if let JsonValue::Object(file) = ads_txt_to_json(&fetched.ads_txt) {
+                    members.extend(file);
+                }
+                members.push((
+                    "errors".to_string(),
+                    JsonValue::Array(
+                        fetched
+                            .errors
+                            .iter()
+                            .map(|e| JsonValue::string(&e.to_string()))
+                            .collect(),
+                    ),
+                ));
+            }
+            CrawlResult::SkippedByRobots => {
+                members.push(("status".to_string(), JsonValue::string("skipped_by_robots")));
+            }
+            CrawlResult::Failed(e) => {
+                members.push(("status".to_string(), JsonValue::string("failed")));
+                members.push((
+                    "error".to_string(),
+                    JsonValue::Object(vec![
+                        ("class".to_string(), JsonValue::string(e.class())),
+                        ("message".to_string(), JsonValue::string(&e.to_string())),
+                    ]),
+                ));
+            }
+        }
+
+        JsonValue::Object(members)
+    }
+}
+
+/// Failure to crawl one domain of a bulk crawl
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DomainFailure {
+    pub domain: String,
+    pub error: FetchError,
+}
+
+/// Counts of the outcomes of a bulk crawl
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CrawlSummary {
+    pub total: usize,
+    pub fetched: usize,
+    pub skipped_by_robots: usize,
+    pub failed: usize,
+    /// Failures by `FetchError::class`
+    pub failures_by_class: BTreeMap<&'static str, usize>,
+}
+
+impl CrawlSummary {
+    /// Counts the result of crawling one more domain
+    pub fn add(&mut self, result: &CrawlResult) {
+        self.total += 1;
+        match result {
+            CrawlResult::Fetched(_) => self.fetched += 1,
+            CrawlResult::SkippedByRobots => self.skipped_by_robots += 1,
+            CrawlResult::Failed(e) => {
+                self.failed += 1;
+                *self.failures_by_class.entry(e.class()).or_insert(0) += 1;
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CrawlSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} domains: {} fetched, {} skipped by robots.txt, {} failed",
+            self.total, self.fetched, self.skipped_by_robots, self.failed
+        )?;
+        if !self.failures_by_class.is_empty() {
+            let classes: Vec<String> = self
+                .failures_by_class
+                .iter()
+                .map(|(class, count)| format!("{}: {}", class, count))
+                .collect();
+            write!(f, " ({})", classes.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Results of a bulk crawl, holding the files fetched alongside the domains
+/// which failed, so one failing domain never fails the batch
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct BulkCrawl {
+    pub fetched: Vec<FetchedAdsTxt>,
+    pub skipped_by_robots: Vec<String>,
+    pub failures: Vec<DomainFailure>,
+}
+
+impl BulkCrawl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the result of crawling a domain
+    pub fn push(&mut self, domain: &str, result: CrawlResult) {
+        match result {
+            CrawlResult::Fetched(fetched) => self.fetched.push(fetched),
+            CrawlResult::SkippedByRobots => self.skipped_by_robots.push(domain.to_string()),
+            CrawlResult::Failed(error) => self.failures.push(DomainFailure {
+                domain: domain.to_string(),
+                error,
+            }),
+        }
+    }
+
+    /// Whether every domain was fetched
+    pub fn is_complete(&self) -> bool {
+        self.skipped_by_robots.is_empty() && self.failures.is_empty()
+    }
+
+    pub fn summary(&self) -> CrawlSummary {
+        let mut failures_by_class = BTreeMap::new();
+        for failure in &self.failures {
+            *failures_by_class.entry(failure.error.class()).or_insert(0) += 1;
+        }
+
+        CrawlSummary {
+            total: self.fetched.len() + self.skipped_by_robots.len() + self.failures.len(),
+            fetched: self.fetched.len(),
+            skipped_by_robots: self.skipped_by_robots.len(),
+            failed: self.failures.len(),
+            failures_by_class,
+        }
+    }
+
+    /// Adds the fetched files to the corpus and records a failed refresh for
+    /// the domains which failed, keeping their previously fetched files.
+    /// Returns the number of files added.
+    pub fn update_corpus(&self, corpus: &mut AdsTxtCorpus, at: SystemTime) -> usize {
+        for fetched in &self.fetched {
+            let metadata = CorpusMetadata::new(at, fetched.raw_hash);
+            corpus.insert(CorpusEntry::new(
+                &fetched.domain,
+                fetched.ads_txt.clone(),
+                metadata,
+            ));
+        }
+        for failure in &self.failures {
+            corpus.record_failure(&failure.domain, at);
+        }
+
+        self.fetched.len()
+    }
+}
+
+impl Extend<(String, CrawlResult)> for BulkCrawl {
+    fn extend<I: IntoIterator<Item = (String, CrawlResult)>>(&mut self, results: I) {
+        for (domain, result) in results {
+            self.push(&domain, result);
+        }
+    }
+}
+
+impl std::iter::FromIterator<(String, CrawlResult)> for BulkCrawl {
+    fn from_iter<I: IntoIterator<Item = (String, CrawlResult)>>(results: I) -> Self {
+        let mut crawl = BulkCrawl::new();
+        crawl.extend(results);
+        crawl
+    }
+}
+
+/// Why a `subdomain=` referral was not followed
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The referred host leads back to the referring one. Holds the chain of
+    /// hosts from the referred host to the referring one.
+    Cycle(Vec<String>),
+    /// The referred host was already crawled through another referral
+    AlreadyVisited,
+    /// The referred host is not within the root domain
+    OutOfScope,
+    /// The referred host is further from the root domain than
+    /// `CrawlerConfig::max_referral_depth`
+    TooDeep,
+    /// `CrawlerConfig::max_referred_hosts` hosts were already followed
+    TooManyHosts,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SkippedReferral {
+    pub from: String,
+    pub to: String,
+    pub reason: SkipReason,
+}
+
+/// Files of a root domain and the subdomains it refers to
+#[derive(Debug, Eq, PartialEq)]
+pub struct SubdomainCrawl {
+    /// Result of each crawled host, the root domain first, each host once
+    pub results: Vec<(String, CrawlResult)>,
+    pub skipped: Vec<SkippedReferral>,
+}
+
+impl SubdomainCrawl {
+    /// Referrals skipped because they form a cycle
+    pub fn cycles(&self) -> impl Iterator<Item = &SkippedReferral> {
+        self.skipped
+            .iter()
+            .filter(|s| matches!(s.reason, SkipReason::Cycle(_)))
+    }
+
+    /// Files fetched successfully, by host
+    pub fn fetched(&self) -> impl Iterator<Item = (&str, &FetchedAdsTxt)> {
+        self.results
+            .iter()
+            .filter_map(|(host, result)| match result {
+                CrawlResult::Fetched(fetched) => Some((host.as_str(), fetched)),
+                _ => None,
+            })
+    }
+}
+
+pub struct Crawler<C: HttpClient> {
+    client: C,
+    config: CrawlerConfig,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<dyn MetricsRecorder>>,
+}
+
+#[cfg(feature = "fetch")]
+impl Crawler<TcpHttpClient> {
+    /// Creates a crawler using the built-in plain HTTP client
+    pub fn with_default_client(config: CrawlerConfig) -> Self {
+        Crawler::new(TcpHttpClient::default(), config)
+    }
+}
+
+impl<C: HttpClient> Crawler<C> {
+    pub fn new(client: C, config: CrawlerConfig) -> Self {
+        Crawler {
+            client,
+            config,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Reports fetch outcomes and parse statistics to the recorder
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn config(&self) -> &CrawlerConfig {
+        &self.config
+    }
+
+    /// Fetches and parses the ads.txt file of the given domain
+    pub fn fetch(&self, domain: &str) -> CrawlResult {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+
+        let result = match self.check_robots_txt(&domain) {
+            Ok(true) => self.fetch_with_fallback(&domain),
+            Ok(false) => {
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &self.metrics {
+                    metrics.fetch_outcome("skipped_by_robots");
+                }
+                return CrawlResult::SkippedByRobots;
+            }
+            Err(e) => Err(e),
+        };
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            match &result {
+                Ok(_) => metrics.fetch_outcome("fetched"),
+                Err(e) => metrics.fetch_outcome(e.class()),
+            }
+        }
+
+        match result {
+            Ok(fetched) => CrawlResult::Fetched(fetched),
+            Err(e) => CrawlResult::Failed(e),
+        }
+    }
+
+    /// Fetches the file of the normalized domain, from the www host if
+    /// configured and the domain fails
+    fn fetch_with_fallback(&self, domain: &str) -> FetchResult<FetchedAdsTxt> {
+        match self.fetch_ads_txt(domain, domain) {
+            Err(e) if self.config.www_fallback && Self::can_fall_back(domain, &e) => {
+                self.fetch_ads_txt(&format!("www.{}", domain), domain)
+            }
+            result => result,
+        }
+    }
+
+    fn can_fall_back(domain: &str, e: &FetchError) -> bool {
+        let missing = matches!(
+            e,
+            FetchError::NotFound
+                | FetchError::Dns(_)
+                | FetchError::ConnectTimeout(_)
+                | FetchError::Connection(_)
+        );
+        // IP addresses have no www host to fall back to
+        let host = domain.split(':').next().unwrap_or(domain);
+        missing && !domain.starts_with("www.") && host.parse::<std::net::IpAddr>().is_err()
+    }
+
+    /// Fetches ads.txt, or app-ads.txt if configured, from the host,
+    /// following redirects within the domain and one out of it
+    fn fetch_ads_txt(&self, host: &str, domain: &str) -> FetchResult<FetchedAdsTxt> {
+        let url = format!("http://{}{}", host, self.config.file.path());
+        let (response, url) = self.get(&url, domain)?;
+
+        if response.status != 200 {
+            return Err(FetchError::from_status(response.status));
+        }
+
+        if let Some(content_type) = response.header("content-type") {
+            let media_type = content_type.split(';').next().unwrap_or("").trim();
+            if !media_type.eq_ignore_ascii_case("text/plain") {
+                return Err(FetchError::WrongContentType(content_type.to_string()));
+            }
+        }
+
+        let (text, charset) = decode_body(&response.body, response.header("content-type"));
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let (ads_txt, record_lines, errors) = AdsTxt::parse_lenient_with_lines(&text);
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.file_parsed(started.elapsed(), ads_txt.records.len());
+        }
+
+        // Nothing but invalid lines is most likely not an ads.txt file at all
+        if ads_txt.records.is_empty() && ads_txt.variables.is_empty() && !errors.is_empty() {
+            return Err(FetchError::Parse(errors[0].clone()));
+        }
+
+        Ok(FetchedAdsTxt {
+            domain: domain.to_string(),
+            url,
+            raw_hash: content_hash_bytes(&response.body),
+            normalized_hash: normalized_hash(&ads_txt),
+            charset,
+            ads_txt,
+            errors,
+            record_lines,
+        })
+    }
+
+    /// Fetches the domain's live file and compares it against the expected
+    /// one, the check before a publisher goes live. The check is a single
+    /// fetch on behalf of the publisher, so robots.txt is not consulted.
+    pub fn check_conformance(
+        &self,
+        domain: &str,
+        expected: &AdsTxt,
+    ) -> FetchResult<ConformanceReport> {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+        let fetched = self.fetch_with_fallback(&domain)?;
+        Ok(ConformanceReport::check(expected, &fetched.ads_txt))
+    }
+
+    /// Fetches and leniently parses the sellers.json file of an ad system,
+    /// following redirects within its domain. Invalid sellers are skipped.
+    pub fn fetch_sellers_json(&self, domain: &str) -> FetchResult<SellersJson> {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+
+        match self.get_sellers_json(&domain, &domain) {
+            Err(e) if self.config.www_fallback && Self::can_fall_back(&domain, &e) => {
+                self.get_sellers_json(&format!("www.{}", domain), &domain)
+            }
+            result => result,
+        }
+    }
+
+    /// Fetches sellers.json from the host, following redirects within the domain
+    fn get_sellers_json(&self, host: &str, domain: &str) -> FetchResult<SellersJson> {
+        let (response, _) = self.get(&format!("http://{}/sellers.json", host), domain)?;
+
+        if response.status != 200 {
+            return Err(FetchError::from_status(response.status));
+        }
+
+        let (text, _) = decode_body(&response.body, response.header("content-type"));
+        match SellersJson::parse_lenient(&text) {
+            Ok((sellers_json, _)) => Ok(sellers_json),
+            Err(e) => Err(FetchError::Parse(e)),
+        }
+    }
+
+    /// Fetches the file of the root domain and of the subdomains it refers
+    /// to with `subdomain=` variables, following referrals in the
+    /// subdomains' files too. Every host is fetched once. Referrals leading
+    /// back to a host on their own chain, to an already crawled host, out
+    /// of the root domain or beyond the referral limits of the config are
+    /// skipped and reported.
+    pub fn fetch_with_subdomains(&self, domain: &str) -> SubdomainCrawl {
+        let root = domain.trim().trim_end_matches('.').to_lowercase();
+        let mut crawl = SubdomainCrawl {
+            results: vec![],
+            skipped: vec![],
+        };
+        // Referring host of every crawled host, none for the root
+        let mut referrers: HashMap<String, Option<String>> = HashMap::new();
+        referrers.insert(root.clone(), None);
+        let mut queue = VecDeque::from(vec![(root.clone(), 0)]);
+
+        while let Some((host, depth)) = queue.pop_front() {
+            let result = self.fetch(&host);
+            if let CrawlResult::Fetched(fetched) = &result {
+                for sub_domain in fetched.ads_txt.iter_sub_domains() {
+                    let to = sub_domain.trim().trim_end_matches('.').to_lowercase();
+                    let reason = if !within_domain(&to, &root) {
+                        SkipReason::OutOfScope
+                    } else if referrers.contains_key(&to) {
+                        // Walk back the referral chain of the referring host
+                        let mut chain = vec![host.clone()];
+                        let mut current = &host;
+                        while *current != to {
+                            match referrers.get(current) {
+                                Some(Some(referrer)) => {
+                                    chain.push(referrer.clone());
+                                    current = referrer;
+                                }
+                                _ => break,
+                            }
+                        }
+                        if *current == to {
+                            chain.reverse();
+                            SkipReason::Cycle(chain)
+                        } else {
+                            SkipReason::AlreadyVisited
+                        }
+                    } else if depth >= self.config.max_referral_depth {
+                        SkipReason::TooDeep
+                    } else if referrers.len() > self.config.max_referred_hosts {
+                        SkipReason::TooManyHosts
+                    } else {
+                        referrers.insert(to.clone(), Some(host.clone()));
+                        queue.push_back((to, depth + 1));
+                        continue;
+                    };
+
+                    crawl.skipped.push(SkippedReferral {
+                        from: host.clone(),
+                        to,
+                        reason,
+                    });
+                }
+            }
+            crawl.results.push((host, result));
+        }
+
+        crawl
+    }
+
+    /// Fetches the store listing of an app and finds the domain of its
+    /// developer website, from which app-ads.txt is fetched
+    pub fn resolve_developer_domain(&self, listing_url: &str) -> FetchResult<String> {
+        let host = Url::parse(listing_url)?.host;
+        let (response, url) = self.get(listing_url, &host)?;
+
+        if response.status != 200 {
+            return Err(FetchError::from_status(response.status));
+        }
+
+        StoreListing::parse(&String::from_utf8_lossy(&response.body))
+            .developer_domain()
+            .ok_or_else(|| {
+                FetchError::InvalidResponse(format!("no developer URL in store listing {}", url))
+            })
+    }
+
+    /// Crawls each of the given domains in order, passing every result to `on_result`
+    pub fn crawl_all<D, F>(&self, domains: &[D], mut on_result: F)
+    where
+        D: AsRef<str>,
+        F: FnMut(&str, CrawlResult),
+    {
+        for domain in domains {
+            let domain = domain.as_ref();
+            on_result(domain, self.fetch(domain));
+        }
+    }
+
+    /// Crawls each of the given domains in order, collecting the results.
+    /// A `CrawlStream` can be collected into a `BulkCrawl` likewise.
+    pub fn crawl_batch<D: AsRef<str>>(&self, domains: &[D]) -> BulkCrawl {
+        let mut crawl = BulkCrawl::new();
+        self.crawl_all(domains, |domain, result| crawl.push(domain, result));
+        crawl
+    }
+
+    /// Crawls the given domains like `crawl_all`, saving progress to `store`
+    /// after every `checkpoint_interval` domains. If the store holds a
+    /// checkpoint from an interrupted crawl of the same domains, the crawl
+    /// resumes after the domains it completed; a checkpoint of other domains
+    /// fails with `InvalidInput` and is left in the store. The checkpoint is
+    /// cleared once the crawl completes.
+    pub fn crawl_all_resumable<D, S, F>(
+        &self,
+        domains: &[D],
+        mut store: S,
+        checkpoint_interval: usize,
+        mut on_result: F,
+    ) -> io::Result<()>
+    where
+        D: AsRef<str>,
+        S: CheckpointStore,
+        F: FnMut(&str, CrawlResult),
+    {
+        let mut checkpoint = CrawlCheckpoint::new(domains);
+        match store.load()? {
+            Some(saved) if saved.matches(domains) => checkpoint.completed = saved.completed,
+            Some(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the checkpoint is of a crawl of other domains",
+                ))
+            }
+            None => {}
+        }
+
+        let start = checkpoint.completed;
+        let interval = checkpoint_interval.max(1);
+        for (i, domain) in domains.iter().enumerate().skip(start) {
+            let domain = domain.as_ref();
+            on_result(domain, self.fetch(domain));
+            checkpoint.completed = i + 1;
+
+            if (i + 1 - start).is_multiple_of(interval) && i + 1 < domains.len() {
+                store.save(&checkpoint)?;
+            }
+        }
+
+        store.clear()
+    }
+
+    /// Whether robots.txt of the domain allows fetching its file, always if
+    /// robots.txt is not respected
+    fn check_robots_txt(&self, domain: &str) -> FetchResult<bool> {
+        if !self.config.respect_robots_txt {
+            return Ok(true);
+        }
+        Ok(self
+            .robots_txt(domain)?
+            .is_allowed(&self.config.user_agent, &self.config.file.path()))
+    }
+
+    /// Fetches robots.txt of the given domain, treating a missing file as
+    /// allowing everything. Failing to reach it, or any other status, fails
+    /// the fetch with the classified error rather than skipping the domain,
+    /// so that only explicit rules skip it.
+    fn robots_txt(&self, domain: &str) -> FetchResult<RobotsTxt> {
+        let url = format!("http://{}/robots.txt", domain);
+        let (response, _) = self.get(&url, domain)?;
+
+        match response.status {
+            200..=299 => Ok(RobotsTxt::parse(&String::from_utf8_lossy(&response.body))),
+            400..=499 => Ok(RobotsTxt::allow_all()),
+            status => Err(FetchError::from_status(status)),
+        }
+    }
+
+    /// Performs a GET request following redirects within the root domain and
+    /// a single one out of it. As of the IAB ads.txt 1.0.2 specification,
+    /// that one hop delegates the file to a third party server, which must
+    /// then serve it without redirecting further.
+    fn get(&self, url: &str, domain: &str) -> FetchResult<(HttpResponse, String)> {
+        let mut url = Url::parse(url)?;
+        let mut redirects = 0;
+        let mut out_of_scope = false;
+
+        loop {
+            let url_text = url.to_string();
+            let response = self.client.get(&url_text, &self.config.user_agent)?;
+
+            if ![301, 302, 303, 307, 308].contains(&response.status) {
+                return Ok((response, url_text));
+            }
+
+            let location = match response.header("location") {
+                Some(location) => location,
+                None => {
+                    return Err(FetchError::InvalidResponse(format!(
+                        "redirect without location from {}",
+                        url_text
+                    )))
+                }
+            };
+
+            if out_of_scope {
+                return Err(FetchError::RedirectPolicy(format!(
+                    "redirect from {} out of {} scope",
+                    url_text, domain
+                )));
+            }
+
+            redirects += 1;
+            if redirects > self.config.max_redirects {
+                return Err(FetchError::RedirectPolicy(format!(
+                    "too many redirects from {}",
+                    url_text
+                )));
+            }
+
+            let next = url.join(location)?;
+            out_of_scope = !within_domain(&next.host, domain);
+            url = next;
+        }
+    }
+}
+
+impl<C: HttpClient + Send + Sync + 'static> Crawler<C> {
+    /// Crawls the given domains on `concurrency` worker threads, yielding
+    /// results as they complete. At most `buffer` results are held before
+    /// workers block, so a slow consumer applies backpressure to the crawl.
+    /// Dropping the stream cancels the crawl without waiting for it: workers
+    /// take no further domain and exit in the background once their
+    /// in-flight fetch completes.
+    ///
+    /// The stream is an `Iterator` blocking on the next result and, with the
+    /// `futures` feature, a `futures_core::Stream` which does not block.
+    pub fn crawl_stream<I>(
+        self: Arc<Self>,
+        domains: I,
+        concurrency: usize,
+        buffer: usize,
+    ) -> CrawlStream
+    where
+        I: IntoIterator<Item = String>,
+        I::IntoIter: Send + 'static,
+    {
+        let domains = Arc::new(Mutex::new(domains.into_iter()));
+        let (sender, receiver) = sync_channel(buffer);
+        let waker: Arc<Mutex<Option<Waker>>> = Arc::default();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        for _ in 0..concurrency.max(1) {
+            let crawler = Arc::clone(&self);
+            let domains = Arc::clone(&domains);
+            let sender = sender.clone();
+            let waker = Arc::clone(&waker);
+            let cancelled = Arc::clone(&cancelled);
+
+            std::thread::spawn(move || {
+                loop {
+                    if cancelled.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let domain = match domains.lock() {
+                        Ok(mut domains) => domains.next(),
+                        Err(_) => None,
+                    };
+                    let domain = match domain {
+                        Some(domain) => domain,
+                        None => break,
+                    };
+
+                    let result = crawler.fetch(&domain);
+                    if sender.send((domain, result)).is_err() {
+                        // The stream has been dropped
+                        return;
+                    }
+                    wake(&waker);
+                }
+
+                // Wake a polling task after disconnecting, so that it
+                // sees the end of the stream once every worker is done
+                drop(sender);
+                wake(&waker);
+            });
+        }
+
+        CrawlStream {
+            receiver: Some(receiver),
+            cancelled,
+            waker,
+        }
+    }
+}
+
+/// Results of a bulk crawl as they complete, see `Crawler::crawl_stream`
+pub struct CrawlStream {
+    receiver: Option<Receiver<(String, CrawlResult)>>,
+    /// Set on drop, stopping the detached workers between domains
+    cancelled: Arc<AtomicBool>,
+    /// Task to wake when a result arrives, set while it is pending
+    #[cfg_attr(not(feature = "futures"), allow(dead_code))]
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+fn wake(waker: &Mutex<Option<Waker>>) {
+    if let Some(waker) = waker.lock().ok().and_then(|mut w| w.take()) {
+        waker.wake();
+    }
+}
+
+impl Iterator for CrawlStream {
+    type Item = (String, CrawlResult);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.as_ref().and_then(|r| r.recv().ok())
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures_core::Stream for CrawlStream {
+    type Item = (String, CrawlResult);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let receiver = match &this.receiver {
+            Some(receiver) => receiver,
+            None => return Poll::Ready(None),
+        };
+
+        // Registered before receiving, so that a result sent in between
+        // still wakes the task
+        if let Ok(mut waker) = this.waker.lock() {
+            *waker = Some(cx.waker().clone());
+        }
+        match receiver.try_recv() {
+            Ok(item) => Poll::Ready(Some(item)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for CrawlStream {
+    fn drop(&mut self) {
+        // Dropping the receiver also wakes workers blocked on a full buffer
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::MemoryCheckpointStore;
+    use crate::corpus::content_hash;
+    use crate::{AccountRelation, DataRecord};
+    use std::collections::HashMap;
+
+    struct MockClient {
+        responses: HashMap<String, HttpResponse>,
+    }
+
+    impl MockClient {
+        fn new(responses: &[(&str, HttpResponse)]) -> Self {
+            MockClient {
+                responses: responses
+                    .iter()
+                    .map(|(url, r)| (url.to_string(), r.clone()))
+                    .collect(),
+            }
+        }
+    }
+
+    impl HttpClient for MockClient {
+        fn get(&self, url: &str, _user_agent: &str) -> FetchResult<HttpResponse> {
+            match self.responses.get(url) {
+                Some(response) => Ok(response.clone()),
+                None => Err(FetchError::Connection(format!("refused: {}", url))),
+            }
+        }
+    }
+
+    const ADS_TXT: &str = "greenadexchange.com, 12345, DIRECT, d75815a79\n";
+
+    #[test]
+    fn parsing_urls() {
+        let url = Url::parse("http://Example.com:8080/ads.txt").unwrap();
+        assert_eq!(url.host, "example.com");
+        assert_eq!(url.port, 8080);
+        assert_eq!(url.to_string(), "http://example.com:8080/ads.txt");
+
+        assert_eq!(
+            url.join("/other.txt").unwrap().to_string(),
+            "http://example.com:8080/other.txt"
+        );
+        assert_eq!(
+            url.join("https://www.example.com/ads.txt")
+                .unwrap()
+                .to_string(),
+            "https://www.example.com/ads.txt"
+        );
+        assert!(Url::parse("example.com/ads.txt").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fetch")]
+    fn reading_http_responses() {
+        let mut raw: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let (response, reusable) = read_response(&mut raw, 1024).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-type"), Some("text/plain"));
+        assert_eq!(response.body, b"hello world");
+        assert!(reusable);
+
+        let mut raw: &[u8] = b"HTTP/1.0 404 Not Found\r\nContent-Length: 3\r\n\r\nabcdef";
+        let (response, reusable) = read_response(&mut raw, 1024).unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body, b"abc");
+        assert!(reusable);
+
+        let mut raw: &[u8] = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nabcdef";
+        let (response, reusable) = read_response(&mut raw, 1024).unwrap();
+        assert_eq!(response.body, b"abcdef");
+        assert!(!reusable);
+
+        let mut raw: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2048\r\n\r\n";
+        assert_eq!(read_response(&mut raw, 1024), Err(FetchError::OversizeBody));
+    }
+
+    #[test]
+    #[cfg(feature = "fetch")]
+    fn reusing_pooled_connections() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Serves two requests on a single connection, then stops accepting
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            for _ in 0..2 {
+                while read_line(&mut reader).unwrap() != "" {}
+                let mut writer = &stream;
+                writer
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .unwrap();
+            }
+        });
+
+        let client = TcpHttpClient::new(Duration::from_secs(5));
+        let url = format!("http://127.0.0.1:{}/ads.txt", port);
+        for _ in 0..2 {
+            let response = client.get(&url, DEFAULT_USER_AGENT).unwrap();
+            assert_eq!(response.body, b"ok");
+            assert_eq!(client.idle_connections("127.0.0.1", port), 1);
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "fetch")]
+    fn retrying_closed_connections() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let respond = |stream: &TcpStream, response: &[u8]| {
+            let mut reader = BufReader::new(stream);
+            while read_line(&mut reader).unwrap() != "" {}
+            let mut writer = stream;
+            writer.write_all(response).unwrap();
+        };
+
+        // Closes the first connection once idle, then answers the second
+        // request of the next one with a body over the limit
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            respond(&stream, b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+            drop(stream);
+            let (stream, _) = listener.accept().unwrap();
+            respond(&stream, b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+            respond(&stream, b"HTTP/1.1 200 OK\r\nContent-Length: 2048\r\n\r\n");
+            listener
+        });
+
+        let client = TcpHttpClient::new(Duration::from_secs(5)).with_max_body_size(1024);
+        let url = format!("http://127.0.0.1:{}/ads.txt", port);
+        assert_eq!(client.get(&url, DEFAULT_USER_AGENT).unwrap().body, b"ok");
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(client.get(&url, DEFAULT_USER_AGENT).unwrap().body, b"ok");
+        assert_eq!(
+            client.get(&url, DEFAULT_USER_AGENT),
+            Err(FetchError::OversizeBody)
+        );
+
+        // The oversize response is not requested again
+        let listener = server.join().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        assert!(listener.accept().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fetch")]
+    fn refusing_non_public_addresses() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = TcpHttpClient::new(Duration::from_secs(5)).with_public_addresses_only(true);
+        for host in &["127.0.0.1", "localhost"] {
+            let url = format!("http://{}:{}/ads.txt", host, port);
+            match client.get(&url, DEFAULT_USER_AGENT) {
+                Err(FetchError::Connection(message)) => {
+                    assert!(message.ends_with("is not a public address"))
+                }
+                other => panic!("connected to {}: {:?}", host, other),
+            }
+        }
+        listener.set_nonblocking(true).unwrap();
+        assert!(listener.accept().is_err());
+
+        assert!(is_public_address("93.184.216.34".parse().unwrap()));
+        assert!(!is_public_address("100.64.0.1".parse().unwrap()));
+        assert!(!is_public_address("::ffff:192.168.0.1".parse().unwrap()));
+        assert!(!is_public_address("fd00::1".parse().unwrap()));
+        assert!(!is_public_address("fe80::1".parse().unwrap()));
+    }
+
+    #[test]
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    fn connecting_over_tls() {
+        use std::net::TcpListener;
+
+        // A server which does not speak TLS fails the handshake
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = &stream;
+            writer
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        });
+
+        let client = TcpHttpClient::new(Duration::from_secs(5));
+        let url = format!("https://127.0.0.1:{}/ads.txt", port);
+        assert!(matches!(
+            client.get(&url, DEFAULT_USER_AGENT),
+            Err(FetchError::Tls(_))
+        ));
+        server.join().unwrap();
+
+        assert!(matches!(
+            client
+                .clone()
+                .with_root_certificates_pem(b"not a certificate"),
+            Err(FetchError::Tls(_))
+        ));
+        let truncated = b"-----BEGIN CERTIFICATE-----\nMIIB\n";
+        assert!(client.with_root_certificates_pem(truncated).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fetch")]
+    fn posting_requests() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut head = vec![];
+            loop {
+                let line = read_line(&mut reader).unwrap();
+                if line.is_empty() {
+                    break;
+                }
+                head.push(line);
+            }
+            let mut body = [0; 7];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut writer = &stream;
+            writer
+                .write_all(b"HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            (head, body)
+        });
+
+        let client = TcpHttpClient::new(Duration::from_secs(5));
+        let url = format!("http://127.0.0.1:{}/hook", port);
+        let response = client
+            .post(&url, "application/json", b"{\"a\":1}", DEFAULT_USER_AGENT)
+            .unwrap();
+        assert_eq!(response.status, 202);
+
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "POST /hook HTTP/1.1");
+        assert!(head.contains(&"Content-Type: application/json".to_string()));
+        assert!(head.contains(&"Content-Length: 7".to_string()));
+        assert_eq!(&body, b"{\"a\":1}");
+
+        assert!(client
+            .post(
+                "https://example.com/",
+                "text/plain",
+                b"",
+                DEFAULT_USER_AGENT
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn checking_redirect_scope() {
+        assert!(within_domain("example.com", "example.com"));
+        assert!(within_domain("www.example.com", "example.com"));
+        assert!(!within_domain("badexample.com", "example.com"));
+        assert!(!within_domain("example.org", "example.com"));
+        assert!(!within_domain("x\u{e9}.com", "b.com"));
+        assert!(!within_domain("b.com", "x\u{e9}.com"));
+        assert!(within_domain("ads.x\u{e9}.com", "X\u{e9}.com"));
+
+        let client = MockClient::new(&[(
+            "http://b.com/ads.txt",
+            HttpResponse::new(301, &[("Location", "http://x\u{e9}.com/ads.txt")], b""),
+        )]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+        assert!(matches!(crawler.fetch("b.com"), CrawlResult::Failed(_)));
+    }
+
+    #[test]
+    fn fetching_ads_txt() {
+        let client = MockClient::new(&[
+            (
+                "http://example.com/ads.txt",
+                HttpResponse::new(301, &[("Location", "http://www.example.com/ads.txt")], b""),
+            ),
+            (
+                "http://www.example.com/ads.txt",
+                HttpResponse::new(200, &[], ADS_TXT.as_bytes()),
+            ),
+            (
+                "http://delegated.com/ads.txt",
+                HttpResponse::new(302, &[("Location", "http://host.net/ads.txt")], b""),
+            ),
+            (
+                "http://host.net/ads.txt",
+                HttpResponse::new(200, &[], ADS_TXT.as_bytes()),
+            ),
+            (
+                "http://evil.com/ads.txt",
+                HttpResponse::new(302, &[("Location", "http://other.com/ads.txt")], b""),
+            ),
+            (
+                "http://other.com/ads.txt",
+                HttpResponse::new(302, &[("Location", "http://third.com/ads.txt")], b""),
+            ),
+        ]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+
+        let ads_txt = AdsTxt::new(
+            &[DataRecord::new(
+                "greenadexchange.com",
+                "12345",
+                AccountRelation::Direct,
+                Some("d75815a79".to_string()),
+            )],
+            &[],
+        );
+        assert_eq!(
+            crawler.fetch("example.com"),
+            CrawlResult::Fetched(FetchedAdsTxt {
+                domain: "example.com".to_string(),
+                url: "http://www.example.com/ads.txt".to_string(),
+                raw_hash: content_hash(ADS_TXT),
+                normalized_hash: normalized_hash(&ads_txt),
+                charset: Charset::Utf8,
+                record_lines: vec![1],
+                ads_txt,
+                errors: vec![],
+            })
+        );
+        if let CrawlResult::Fetched(fetched) = crawler.fetch("example.com") {
+            let cosmetic = "# Sellers\nGreenAdExchange.com,12345,direct,d75815a79\n";
+            let cosmetic_hash = normalized_hash(&AdsTxt::parse(cosmetic).unwrap());
+            assert!(fetched.changed_since(content_hash(cosmetic)));
+            assert!(!fetched.authorizations_changed_since(cosmetic_hash));
+            assert!(fetched.authorizations_changed_since(normalized_hash(&AdsTxt::empty())));
+        }
+
+        // One hop out of the root domain is followed, but no further one
+        match crawler.fetch("delegated.com") {
+            CrawlResult::Fetched(fetched) => {
+                assert_eq!(fetched.domain, "delegated.com");
+                assert_eq!(fetched.url, "http://host.net/ads.txt");
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(
+            crawler.fetch("evil.com"),
+            CrawlResult::Failed(FetchError::RedirectPolicy(
+                "redirect from http://other.com/ads.txt out of evil.com scope".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn hashing_raw_bodies() {
+        let mut with_bom = vec![0xEF, 0xBB, 0xBF];
+        with_bom.extend_from_slice(ADS_TXT.as_bytes());
+        let client = MockClient::new(&[
+            (
+                "http://plain.com/ads.txt",
+                HttpResponse::new(200, &[], ADS_TXT.as_bytes()),
+            ),
+            (
+                "http://bom.com/ads.txt",
+                HttpResponse::new(200, &[], &with_bom),
+            ),
+        ]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+
+        match (crawler.fetch("plain.com"), crawler.fetch("bom.com")) {
+            (CrawlResult::Fetched(plain), CrawlResult::Fetched(bom)) => {
+                // The bodies decode to the same file but are not the same
+                assert_eq!(plain.ads_txt, bom.ads_txt);
+                assert_eq!(plain.normalized_hash, bom.normalized_hash);
+                assert_eq!(bom.raw_hash, content_hash_bytes(&with_bom));
+                assert_ne!(plain.raw_hash, bom.raw_hash);
+            }
+            results => panic!("unexpected {:?}", results),
+        }
+    }
+
+    #[test]
+    fn falling_back_to_www() {
+        let client = MockClient::new(&[
+            (
+                "http://example.com/ads.txt",
+                HttpResponse::new(404, &[], b""),
+            ),
+            (
+                "http://www.example.com/ads.txt",
+                HttpResponse::new(200, &[], ADS_TXT.as_bytes()),
+            ),
+            (
+                "http://broken.com/ads.txt",
+                HttpResponse::new(500, &[], b""),
+            ),
+            (
+                "http://www.broken.com/ads.txt",
+                HttpResponse::new(200, &[], ADS_TXT.as_bytes()),
+            ),
+        ]);
+
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+        assert_eq!(
+            crawler.fetch("example.com"),
+            CrawlResult::Failed(FetchError::NotFound)
+        );
+
+        let config = CrawlerConfig {
+            www_fallback: true,
+            ..CrawlerConfig::default()
+        };
+        let crawler = Crawler::new(&client, config);
+        match crawler.fetch("example.com") {
+            CrawlResult::Fetched(fetched) => {
+                assert_eq!(fetched.domain, "example.com");
+                assert_eq!(fetched.url, "http://www.example.com/ads.txt");
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        // Only missing files fall back, not server errors
+        assert_eq!(
+            crawler.fetch("broken.com"),
+            CrawlResult::Failed(FetchError::ServerError(500))
+        );
+        assert_eq!(
+            crawler.fetch("www.example.org"),
+            CrawlResult::Failed(FetchError::Connection(
+                "refused: http://www.example.org/ads.txt".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn checking_live_files() {
+        let client = MockClient::new(&[(
+            "http://example.com/ads.txt",
+            HttpResponse::new(200, &[], ADS_TXT.as_bytes()),
+        )]);
+        let config = CrawlerConfig {
+            respect_robots_txt: true,
+            ..CrawlerConfig::default()
+        };
+        let crawler = Crawler::new(&client, config);
+
+        let expected = AdsTxt::parse(ADS_TXT).unwrap();
+        assert!(crawler
+            .check_conformance("Example.com", &expected)
+            .unwrap()
+            .is_exact());
+
+        let expected = AdsTxt::parse("greenadexchange.com, 67890, DIRECT").unwrap();
+        let report = crawler.check_conformance("example.com", &expected).unwrap();
+        assert_eq!(report.missing, expected.records);
+        assert_eq!(report.unexpected.len(), 1);
+        assert_eq!(
+            crawler.check_conformance("missing.com", &expected),
+            Err(FetchError::Connection(
+                "refused: http://missing.com/ads.txt".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn following_subdomains() {
+        let file = |text: &str| HttpResponse::new(200, &[], text.as_bytes());
+        let client = MockClient::new(&[
+            (
+                "http://example.com/ads.txt",
+                file("a.com, 1, DIRECT\nsubdomain=one.example.com\nsubdomain=two.example.com\nsubdomain=evil.com"),
+            ),
+            (
+                "http://one.example.com/ads.txt",
+                file("b.com, 2, DIRECT\nsubdomain=deep.one.example.com\nsubdomain=two.example.com"),
+            ),
+            (
+                "http://two.example.com/ads.txt",
+                file("c.com, 3, DIRECT\nsubdomain=Two.Example.com."),
+            ),
+            (
+                "http://deep.one.example.com/ads.txt",
+                file("d.com, 4, DIRECT\nsubdomain=one.example.com\nsubdomain=example.com"),
+            ),
+        ]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+
+        let crawl = crawler.fetch_with_subdomains("Example.com");
+        assert_eq!(
+            crawl
+                .results
+                .iter()
+                .map(|(h, _)| h.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "example.com",
+                "one.example.com",
+                "two.example.com",
+                "deep.one.example.com"
+            ]
+        );
+        assert_eq!(crawl.fetched().count(), 4);
+
+        let provenanced = crate::provenance::ProvenancedAdsTxt::from_subdomain_crawl(
+            &crawl,
+            SystemTime::UNIX_EPOCH,
+        );
+        let sources: Vec<(&str, Option<usize>, bool)> = provenanced
+            .iter()
+            .map(|(_, p)| (p.source_domain.as_str(), p.line, p.via_referral))
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                ("example.com", Some(1), false),
+                ("one.example.com", Some(1), true),
+                ("two.example.com", Some(1), true),
+                ("deep.one.example.com", Some(1), true),
+            ]
+        );
+
+        let skipped: Vec<(&str, &str, &SkipReason)> = crawl
+            .skipped
+            .iter()
+            .map(|s| (s.from.as_str(), s.to.as_str(), &s.reason))
+            .collect();
+        let chain =
+            |hosts: &[&str]| SkipReason::Cycle(hosts.iter().map(|h| h.to_string()).collect());
+        assert_eq!(
+            skipped,
+            vec![
+                ("example.com", "evil.com", &SkipReason::OutOfScope),
+                (
+                    "one.example.com",
+                    "two.example.com",
+                    &SkipReason::AlreadyVisited
+                ),
+                (
+                    "two.example.com",
+                    "two.example.com",
+                    &chain(&["two.example.com"])
+                ),
+                (
+                    "deep.one.example.com",
+                    "one.example.com",
+                    &chain(&["one.example.com", "deep.one.example.com"])
+                ),
+                (
+                    "deep.one.example.com",
+                    "example.com",
+                    &chain(&["example.com", "one.example.com", "deep.one.example.com"])
+                ),
+            ]
+        );
+        assert_eq!(crawl.cycles().count(), 3);
+    }
+
+    #[test]
+    fn limiting_referrals() {
+        let file = |text: &str| HttpResponse::new(200, &[], text.as_bytes());
+        let client = MockClient::new(&[
+            (
+                "http://example.com/ads.txt",
+                file("subdomain=a.example.com\nsubdomain=b.example.com\nsubdomain=c.example.com"),
+            ),
+            (
+                "http://a.example.com/ads.txt",
+                file("subdomain=x.a.example.com"),
+            ),
+            ("http://b.example.com/ads.txt", file("b.com, 2, DIRECT")),
+        ]);
+        let config = CrawlerConfig {
+            max_referral_depth: 1,
+            max_referred_hosts: 2,
+            ..CrawlerConfig::default()
+        };
+        let crawler = Crawler::new(&client, config);
+
+        let crawl = crawler.fetch_with_subdomains("example.com");
+        assert_eq!(crawl.results.len(), 3);
+        assert_eq!(
+            crawl.skipped,
+            vec![
+                SkippedReferral {
+                    from: "example.com".to_string(),
+                    to: "c.example.com".to_string(),
+                    reason: SkipReason::TooManyHosts,
+                },
+                SkippedReferral {
+                    from: "a.example.com".to_string(),
+                    to: "x.a.example.com".to_string(),
+                    reason: SkipReason::TooDeep,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fetching_sellers_json() {
+        let sellers = r#"{"sellers": [
+            {"seller_id": "1", "seller_type": "PUBLISHER"},
+            {"seller_id": "2", "seller_type": "RESELLER"}
+        ]}"#;
+        let client = MockClient::new(&[
+            (
+                "http://ssp.com/sellers.json",
+                HttpResponse::new(301, &[("Location", "/sellers/sellers.json")], b""),
+            ),
+            (
+                "http://ssp.com/sellers/sellers.json",
+                HttpResponse::new(200, &[], sellers.as_bytes()),
+            ),
+            (
+                "http://broken.com/sellers.json",
+                HttpResponse::new(200, &[], b"<html></html>"),
+            ),
+        ]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+
+        let sellers_json = crawler.fetch_sellers_json("SSP.com").unwrap();
+        assert_eq!(sellers_json.sellers.len(), 1);
+        assert!(sellers_json.seller("1").is_some());
+
+        assert!(matches!(
+            crawler.fetch_sellers_json("broken.com"),
+            Err(FetchError::Parse(_))
+        ));
+        assert!(crawler.fetch_sellers_json("missing.com").is_err());
+    }
+
+    #[test]
+    fn resolving_developer_domains() {
+        let listing = br#"<meta name="appstore:developer_url" content="https://www.games.com">"#;
+        let client = MockClient::new(&[
+            (
+                "http://store.com/app/1",
+                HttpResponse::new(200, &[], listing),
+            ),
+            (
+                "http://store.com/app/2",
+                HttpResponse::new(200, &[], b"<html></html>"),
+            ),
+            (
+                "http://games.com/app-ads.txt",
+                HttpResponse::new(200, &[], ADS_TXT.as_bytes()),
+            ),
+        ]);
+        let config = CrawlerConfig {
+            file: FileKind::AppAdsTxt,
+            ..CrawlerConfig::default()
+        };
+        let crawler = Crawler::new(&client, config);
+
+        let domain = crawler
+            .resolve_developer_domain("http://store.com/app/1")
+            .unwrap();
+        assert_eq!(domain, "games.com");
+        match crawler.fetch(&domain) {
+            CrawlResult::Fetched(fetched) => {
+                assert_eq!(fetched.url, "http://games.com/app-ads.txt")
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(
+            crawler.resolve_developer_domain("http://store.com/app/2"),
+            Err(FetchError::InvalidResponse(
+                "no developer URL in store listing http://store.com/app/2".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn rendering_crawl_results() {
+        let (ads_txt, errors) = AdsTxt::parse_lenient("a.com, 1, DIRECT\nbad");
+        let fetched = CrawlResult::Fetched(FetchedAdsTxt {
+            domain: "example.com".to_string(),
+            url: "http://example.com/ads.txt".to_string(),
+            raw_hash: 0,
+            normalized_hash: 0,
+            charset: Charset::Utf8,
+            record_lines: vec![1],
+            ads_txt,
+            errors,
+        });
+
+        assert_eq!(
+            fetched.to_json("example.com").to_string(),
+            r#"{"domain":"example.com","status":"fetched","url":"http://example.com/ads.txt","records":[{"domain":"a.com","publisher_id":"1","relation":"DIRECT","cert_authority":null}],"variables":[],"errors":["Invalid ads.txt line: bad"]}"#
+        );
+        assert_eq!(
+            CrawlResult::Failed(FetchError::NotFound)
+                .to_json("example.com")
+                .to_string(),
+            r#"{"domain":"example.com","status":"failed","error":{"class":"not_found","message":"Not found"}}"#
+        );
+    }
+
+    #[test]
+    fn honoring_robots_txt() {
+        let ads_txt = HttpResponse::new(200, &[], ADS_TXT.as_bytes());
+        let client = MockClient::new(&[
+            (
+                "http://blocked.com/robots.txt",
+                HttpResponse::new(200, &[], b"User-agent: *\nDisallow: /ads.txt\n"),
+            ),
+            ("http://blocked.com/ads.txt", ads_txt.clone()),
+            (
+                "http://norobots.com/robots.txt",
+                HttpResponse::new(404, &[], b""),
+            ),
+            ("http://norobots.com/ads.txt", ads_txt.clone()),
+            ("http://unreachable.com/ads.txt", ads_txt.clone()),
+            (
+                "http://broken.com/robots.txt",
+                HttpResponse::new(503, &[], b""),
+            ),
+            ("http://broken.com/ads.txt", ads_txt),
+        ]);
+
+        let config = CrawlerConfig {
+            respect_robots_txt: true,
+            ..CrawlerConfig::default()
+        };
+        let crawler = Crawler::new(&client, config);
+
+        assert_eq!(crawler.fetch("blocked.com"), CrawlResult::SkippedByRobots);
+        assert!(matches!(
+            crawler.fetch("norobots.com"),
+            CrawlResult::Fetched(_)
+        ));
+        assert_eq!(
+            crawler.fetch("unreachable.com"),
+            CrawlResult::Failed(FetchError::Connection(
+                "refused: http://unreachable.com/robots.txt".to_string()
+            ))
+        );
+        assert_eq!(
+            crawler.fetch("broken.com"),
+            CrawlResult::Failed(FetchError::ServerError(503))
+        );
+
+        // robots.txt is ignored unless explicitly enabled
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+        assert!(matches!(
+            crawler.fetch("blocked.com"),
+            CrawlResult::Fetched(_)
+        ));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn recording_crawl_metrics() {
+        use crate::metrics::CrawlerMetrics;
+
+        let client = MockClient::new(&[
+            (
+                "http://example.com/ads.txt",
+                HttpResponse::new(200, &[], ADS_TXT.as_bytes()),
+            ),
+            (
+                "http://missing.com/ads.txt",
+                HttpResponse::new(404, &[], b""),
+            ),
+        ]);
+        let metrics = Arc::new(CrawlerMetrics::new());
+        let crawler = Crawler::new(&client, CrawlerConfig::default()).with_metrics(metrics.clone());
+
+        crawler.crawl_all(&["example.com", "missing.com", "example.com"], |_, _| {});
+        assert_eq!(metrics.fetches("fetched"), 2);
+        assert_eq!(metrics.fetches("not_found"), 1);
+        assert_eq!(metrics.files_parsed(), 2);
+        assert!(metrics
+            .render()
+            .contains("ads_txt_records_bucket{le=\"10\"} 2\n"));
+    }
+
+    #[test]
+    fn classifying_fetch_failures() {
+        let client = MockClient::new(&[
+            (
+                "http://missing.com/ads.txt",
+                HttpResponse::new(404, &[], b""),
+            ),
+            (
+                "http://broken.com/ads.txt",
+                HttpResponse::new(503, &[], b""),
+            ),
+            (
+                "http://forbidden.com/ads.txt",
+                HttpResponse::new(403, &[], b""),
+            ),
+            (
+                "http://html.com/ads.txt",
+                HttpResponse::new(200, &[("Content-Type", "text/html")], b"<html></html>"),
+            ),
+            (
+                "http://soft404.com/ads.txt",
+                HttpResponse::new(
+                    200,
+                    &[("Content-Type", "text/plain; charset=utf-8")],
+                    b"Not found",
+                ),
+            ),
+            (
+                "http://secure.com/ads.txt",
+                HttpResponse::new(301, &[("Location", "https://secure.com/ads.txt")], b""),
+            ),
+        ]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+
+        let failure = |domain: &str| match crawler.fetch(domain) {
+            CrawlResult::Failed(e) => e,
+            result => panic!("Unexpected result {:?}", result),
+        };
+
+        assert_eq!(failure("missing.com"), FetchError::NotFound);
+        assert_eq!(failure("broken.com"), FetchError::ServerError(503));
+        assert_eq!(failure("forbidden.com"), FetchError::HttpStatus(403));
+        assert_eq!(
+            failure("html.com"),
+            FetchError::WrongContentType("text/html".to_string())
+        );
+        assert_eq!(failure("soft404.com").class(), "parse");
+        assert_eq!(failure("secure.com").class(), "connection");
+        assert_eq!(failure("unknown.com").class(), "connection");
+
+        #[cfg(all(
+            feature = "fetch",
+            not(any(feature = "rustls", feature = "native-tls"))
+        ))]
+        assert!(matches!(
+            TcpHttpClient::default().get("https://example.com/ads.txt", DEFAULT_USER_AGENT),
+            Err(FetchError::Tls(_))
+        ));
+    }
+
+    #[test]
+    fn chaining_error_sources() {
+        use std::error::Error;
+
+        fn read(text: &str) -> crate::Result<AdsTxt> {
+            if text.is_empty() {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "empty file"))?;
+            }
+            AdsTxt::parse(text)
+        }
+        let e = read("").unwrap_err();
+        assert_eq!(e.to_string(), "IO error: empty file");
+        assert_eq!(e.source().unwrap().to_string(), "empty file");
+        assert!(read("a.com, 1, PARTNER").unwrap_err().source().is_none());
+
+        fn fetch(client: &MockClient, domain: &str) -> FetchResult<AdsTxt> {
+            let url = format!("http://{}/ads.txt", domain);
+            let response = client.get(&url, DEFAULT_USER_AGENT)?;
+            Ok(AdsTxt::parse(&String::from_utf8_lossy(&response.body))?)
+        }
+        let client = MockClient::new(&[(
+            "http://example.com/ads.txt",
+            HttpResponse::new(200, &[], b"<html>"),
+        )]);
+        let e = fetch(&client, "example.com").unwrap_err();
+        assert_eq!(e.class(), "parse");
+        assert_eq!(
+            e.to_string(),
+            format!("Parse failure: {}", e.source().unwrap())
+        );
+        let e = FetchError::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset"));
+        assert_eq!(e, FetchError::Connection("reset".to_string()));
+
+        // Fetch failures chain through the parse error type
+        let e = AdsTxtError::from(FetchError::NotFound);
+        assert_eq!(e.to_string(), "Not found");
+        assert_eq!(e.source().unwrap().to_string(), "Not found");
+        assert_eq!(e, AdsTxtError::new("Not found"));
+    }
+
+    #[test]
+    fn decoding_fetched_files() {
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(ADS_TXT.encode_utf16().flat_map(u16::to_le_bytes));
+        let client = MockClient::new(&[
+            ("http://a.com/ads.txt", HttpResponse::new(200, &[], &utf16)),
+            (
+                "http://b.com/ads.txt",
+                HttpResponse::new(
+                    200,
+                    &[("Content-Type", "text/plain; charset=windows-1252")],
+                    b"# \x93Sellers\x94\ngreenadexchange.com, 12345, DIRECT, d75815a79\n",
+                ),
+            ),
+        ]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+
+        for (domain, charset) in &[("a.com", Charset::Utf16Le), ("b.com", Charset::Windows1252)] {
+            match crawler.fetch(domain) {
+                CrawlResult::Fetched(fetched) => {
+                    assert_eq!(fetched.charset, *charset);
+                    assert_eq!(fetched.ads_txt, AdsTxt::parse(ADS_TXT).unwrap());
+                    assert!(fetched.errors.is_empty());
+                }
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn collecting_bulk_crawls() {
+        let ads_txt = HttpResponse::new(200, &[], ADS_TXT.as_bytes());
+        let client = MockClient::new(&[
+            ("http://a.com/ads.txt", ads_txt.clone()),
+            ("http://b.com/ads.txt", HttpResponse::new(404, &[], b"")),
+            ("http://d.com/ads.txt", ads_txt),
+        ]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+
+        let crawl = crawler.crawl_batch(&["a.com", "b.com", "c.com", "d.com"]);
+        assert!(!crawl.is_complete());
+        assert_eq!(
+            crawl
+                .fetched
+                .iter()
+                .map(|f| f.domain.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a.com", "d.com"]
+        );
+        assert_eq!(
+            crawl.failures[0],
+            DomainFailure {
+                domain: "b.com".to_string(),
+                error: FetchError::NotFound,
+            }
+        );
+        assert_eq!(crawl.failures[1].domain, "c.com");
+
+        let summary = crawl.summary();
+        assert_eq!((summary.total, summary.fetched, summary.failed), (4, 2, 2));
+        assert_eq!(
+            summary.to_string(),
+            "4 domains: 2 fetched, 0 skipped by robots.txt, 2 failed (connection: 1, not_found: 1)"
+        );
+
+        let t1 = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1000);
+        let t2 = t1 + std::time::Duration::from_secs(1000);
+        let mut corpus = AdsTxtCorpus::new();
+        assert_eq!(crawl.update_corpus(&mut corpus, t1), 2);
+
+        // A refresh where a.com fails keeps its file and marks it failed
+        let refresh: BulkCrawl = vec![
+            (
+                "a.com".to_string(),
+                CrawlResult::Failed(FetchError::ServerError(503)),
+            ),
+            ("b.com".to_string(), CrawlResult::SkippedByRobots),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(refresh.update_corpus(&mut corpus, t2), 0);
+        assert_eq!(corpus.len(), 2);
+        let a = corpus.get("a.com").unwrap();
+        assert_eq!(a.metadata.fetched_at, t1);
+        assert_eq!(a.metadata.failed_at, Some(t2));
+        assert_eq!(refresh.summary().skipped_by_robots, 1);
+    }
+
+    #[test]
+    fn resuming_bulk_crawls() {
+        let ads_txt = HttpResponse::new(200, &[], ADS_TXT.as_bytes());
+        let client = MockClient::new(&[
+            ("http://a.com/ads.txt", ads_txt.clone()),
+            ("http://b.com/ads.txt", ads_txt.clone()),
+            ("http://c.com/ads.txt", ads_txt),
+        ]);
+        let crawler = Crawler::new(&client, CrawlerConfig::default());
+        let domains = ["a.com", "b.com", "c.com", "d.com"];
+
+        let mut crawled = vec![];
+        crawler.crawl_all(&domains, |domain, _| crawled.push(domain.to_string()));
+        assert_eq!(crawled, domains);
+
+        // Simulate a crash after a.com and b.com were crawled
+        let mut store = MemoryCheckpointStore::new();
+        let checkpoint = CrawlCheckpoint {
+            completed: 2,
+            ..CrawlCheckpoint::new(&domains)
+        };
+        store.save(&checkpoint).unwrap();
+
+        // A checkpoint is not resumed by a crawl of other domains
+        let error = crawler
+            .crawl_all_resumable(&["a.com", "b.com", "e.com"], &mut store, 1, |_, _| {
+                panic!("crawled with another list's checkpoint")
+            })
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(store.checkpoint(), Some(&checkpoint));
+
+        let mut results = vec![];
+        crawler
+            .crawl_all_resumable(&domains, &mut store, 1, |domain, result| {
+                results.push((domain.to_string(), result))
+            })
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "c.com");
+        assert!(matches!(results[0].1, CrawlResult::Fetched(_)));
+        assert_eq!(results[1].0, "d.com");
+        assert!(matches!(results[1].1, CrawlResult::Failed(_)));
+        assert_eq!(store.checkpoint(), None);
+    }
+
+    #[test]
+    fn streaming_crawl_results() {
+        let ads_txt = HttpResponse::new(200, &[], ADS_TXT.as_bytes());
+        let client = MockClient::new(&[
+            ("http://a.com/ads.txt", ads_txt.clone()),
+            ("http://b.com/ads.txt", ads_txt.clone()),
+            ("http://c.com/ads.txt", ads_txt),
+        ]);
+        let crawler = Arc::new(Crawler::new(client, CrawlerConfig::default()));
+        let domains: Vec<String> = ["a.com", "b.com", "c.com", "d.com"]
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+
+        let mut results: Vec<(String, bool)> = Arc::clone(&crawler)
+            .crawl_stream(domains.clone(), 3, 1)
+            .map(|(domain, result)| (domain, matches!(result, CrawlResult::Fetched(_))))
+            .collect();
+        results.sort();
+
+        assert_eq!(
+            results,
+            vec![
+                ("a.com".to_string(), true),
+                ("b.com".to_string(), true),
+                ("c.com".to_string(), true),
+                ("d.com".to_string(), false),
+            ]
+        );
+
+        // Dropping the stream early must not hang on blocked workers
+        let mut stream = Arc::clone(&crawler).crawl_stream(domains.clone(), 2, 0);
+        assert!(stream.next().is_some());
+        drop(stream);
+
+        // Nor wait for in-flight fetches, which are the last ones
+        struct SlowClient(Arc<std::sync::atomic::AtomicUsize>);
+
+        impl HttpClient for SlowClient {
+            fn get(&self, _url: &str, _user_agent: &str) -> FetchResult<HttpResponse> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                Ok(HttpResponse::new(200, &[], ADS_TXT.as_bytes()))
+            }
+        }
+
+        let requests = Arc::default();
+        let slow = Arc::new(Crawler::new(
+            SlowClient(Arc::clone(&requests)),
+            CrawlerConfig::default(),
+        ));
+        let stream = slow.crawl_stream(domains.clone(), 2, 4);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let started = std::time::Instant::now();
+        drop(stream);
+        assert!(started.elapsed() < std::time::Duration::from_millis(100));
+        std::thread::sleep(std::time::Duration::from_millis(400));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        #[cfg(feature = "futures")]
+        {
+            use futures_core::Stream;
+            use std::task::Wake;
+
+            /// Unparks the polling thread, a minimal executor
+            struct Unpark(std::thread::Thread);
+
+            impl Wake for Unpark {
+                fn wake(self: Arc<Self>) {
+                    self.0.unpark();
+                }
+            }
+
+            let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+            let mut cx = Context::from_waker(&waker);
+            let mut stream = crawler.crawl_stream(domains, 2, 1);
+            let mut domains = vec![];
+            loop {
+                match Pin::new(&mut stream).poll_next(&mut cx) {
+                    Poll::Ready(Some((domain, _))) => domains.push(domain),
+                    Poll::Ready(None) => break,
+                    Poll::Pending => std::thread::park(),
+                }
+            }
+            domains.sort();
+            assert_eq!(domains, vec!["a.com", "b.com", "c.com", "d.com"]);
+        }
+    }
+}
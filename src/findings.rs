@@ -0,0 +1,340 @@
+//! Aggregated crawl and cross-validation findings, exportable as JSON for
+//! exchange with partners' IAB Tech Lab style tooling
+
+use crate::cross_validation::{RecordCheck, RecordVerdict};
+use crate::fetch::{CrawlResult, FetchError};
+use crate::json::JsonValue;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Error => "ERROR",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum FindingCode {
+    FetchFailed,
+    SkippedByRobots,
+    InvalidLine,
+    SellersJsonNotAvailable,
+    SellerIdNotFound,
+    SellerTypeMismatch,
+}
+
+impl FindingCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FindingCode::FetchFailed => "FETCH_FAILED",
+            FindingCode::SkippedByRobots => "SKIPPED_BY_ROBOTS",
+            FindingCode::InvalidLine => "INVALID_LINE",
+            FindingCode::SellersJsonNotAvailable => "SELLERS_JSON_NOT_AVAILABLE",
+            FindingCode::SellerIdNotFound => "SELLER_ID_NOT_FOUND",
+            FindingCode::SellerTypeMismatch => "SELLER_TYPE_MISMATCH",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Finding {
+    pub code: FindingCode,
+    pub severity: Severity,
+    pub message: String,
+    /// Ad system domain of the record concerned, if any
+    pub ad_system: Option<String>,
+    /// Seller id of the record concerned, if any
+    pub seller_id: Option<String>,
+}
+
+impl Finding {
+    pub fn new(code: FindingCode, severity: Severity, message: &str) -> Self {
+        Finding {
+            code,
+            severity,
+            message: message.to_string(),
+            ad_system: None,
+            seller_id: None,
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let optional = |v: &Option<String>| match v {
+            Some(v) => JsonValue::string(v),
+            None => JsonValue::Null,
+        };
+
+        JsonValue::Object(vec![
+            ("type".to_string(), JsonValue::string(self.code.as_str())),
+            (
+                "severity".to_string(),
+                JsonValue::string(self.severity.as_str()),
+            ),
+            ("message".to_string(), JsonValue::string(&self.message)),
+            ("ad_system".to_string(), optional(&self.ad_system)),
+            ("seller_id".to_string(), optional(&self.seller_id)),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DomainFindings {
+    pub domain: String,
+    pub fetch_error: Option<FetchError>,
+    pub records: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl DomainFindings {
+    pub fn new(domain: &str) -> Self {
+        DomainFindings {
+            domain: domain.to_string(),
+            fetch_error: None,
+            records: 0,
+            findings: vec![],
+        }
+    }
+
+    /// Collects the findings of a crawl: fetch failures and unparsable lines
+    pub fn from_crawl(domain: &str, result: &CrawlResult) -> Self {
+        let mut findings = DomainFindings::new(domain);
+
+        match result {
+            CrawlResult::Fetched(fetched) => {
+                findings.records = fetched.ads_txt.records.len();
+                for e in &fetched.errors {
+                    findings.findings.push(Finding::new(
+                        FindingCode::InvalidLine,
+                        Severity::Warning,
+                        &e.to_string(),
+                    ));
+                }
+            }
+            CrawlResult::SkippedByRobots => findings.findings.push(Finding::new(
+                FindingCode::SkippedByRobots,
+                Severity::Info,
+                "Fetching ads.txt is disallowed by robots.txt",
+            )),
+            CrawlResult::Failed(e) => {
+                findings.fetch_error = Some(e.clone());
+                findings.findings.push(Finding::new(
+                    FindingCode::FetchFailed,
+                    Severity::Error,
+                    &e.to_string(),
+                ));
+            }
+        }
+
+        findings
+    }
+
+    /// Adds a finding for every record which failed cross-validation
+    pub fn add_record_checks(&mut self, checks: &[RecordCheck]) {
+        for check in checks {
+            let (code, severity, message) = match &check.verdict {
+                RecordVerdict::Verified => continue,
+                RecordVerdict::SellersJsonMissing => (
+                    FindingCode::SellersJsonNotAvailable,
+                    Severity::Warning,
+                    "No sellers.json available for the ad system".to_string(),
+                ),
+                RecordVerdict::SellerNotFound => (
+                    FindingCode::SellerIdNotFound,
+                    Severity::Error,
+                    "Seller id not found in the ad system's sellers.json".to_string(),
+                ),
+                RecordVerdict::SellerTypeMismatch(seller_type) => (
+                    FindingCode::SellerTypeMismatch,
+                    Severity::Error,
+                    format!(
+                        "{} record for a seller of type {}",
+                        check.record.acc_relation.as_str(),
+                        seller_type.as_str()
+                    ),
+                ),
+            };
+
+            self.findings.push(Finding {
+                ad_system: Some(check.record.domain.to_string()),
+                seller_id: Some(check.record.publisher_id.to_string()),
+                ..Finding::new(code, severity, &message)
+            });
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let fetch_error = match &self.fetch_error {
+            Some(e) => JsonValue::Object(vec![
+                ("class".to_string(), JsonValue::string(e.class())),
+                ("message".to_string(), JsonValue::string(&e.to_string())),
+            ]),
+            None => JsonValue::Null,
+        };
+
+        JsonValue::Object(vec![
+            ("domain".to_string(), JsonValue::string(&self.domain)),
+            (
+                "status".to_string(),
+                JsonValue::string(if self.fetch_error.is_some() {
+                    "ERROR"
+                } else {
+                    "OK"
+                }),
+            ),
+            ("fetch_error".to_string(), fetch_error),
+            ("records".to_string(), JsonValue::number(self.records)),
+            (
+                "findings".to_string(),
+                JsonValue::Array(self.findings.iter().map(|f| f.to_json()).collect()),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct FindingsExport {
+    pub domains: Vec<DomainFindings>,
+}
+
+impl FindingsExport {
+    pub fn new(domains: Vec<DomainFindings>) -> Self {
+        FindingsExport { domains }
+    }
+
+    /// Number of findings per finding type, in first-seen order
+    pub fn counts(&self) -> Vec<(FindingCode, usize)> {
+        let mut counts: Vec<(FindingCode, usize)> = vec![];
+
+        for finding in self.domains.iter().flat_map(|d| &d.findings) {
+            match counts.iter_mut().find(|(code, _)| *code == finding.code) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((finding.code, 1)),
+            }
+        }
+
+        counts
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let fetch_errors = self
+            .domains
+            .iter()
+            .filter(|d| d.fetch_error.is_some())
+            .count();
+        let findings: usize = self.domains.iter().map(|d| d.findings.len()).sum();
+        let records: usize = self.domains.iter().map(|d| d.records).sum();
+
+        let by_type = self
+            .counts()
+            .into_iter()
+            .map(|(code, count)| (code.as_str().to_string(), JsonValue::number(count)))
+            .collect();
+
+        JsonValue::Object(vec![
+            ("version".to_string(), JsonValue::string("1.0")),
+            (
+                "generator".to_string(),
+                JsonValue::string(crate::fetch::DEFAULT_USER_AGENT),
+            ),
+            (
+                "summary".to_string(),
+                JsonValue::Object(vec![
+                    ("domains".to_string(), JsonValue::number(self.domains.len())),
+                    ("fetch_errors".to_string(), JsonValue::number(fetch_errors)),
+                    ("records".to_string(), JsonValue::number(records)),
+                    ("findings".to_string(), JsonValue::number(findings)),
+                    ("findings_by_type".to_string(), JsonValue::Object(by_type)),
+                ]),
+            ),
+            (
+                "domains".to_string(),
+                JsonValue::Array(self.domains.iter().map(|d| d.to_json()).collect()),
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::charset::Charset;
+    use crate::cross_validation::cross_validate;
+    use crate::fetch::FetchedAdsTxt;
+    use crate::sellers_json::{Seller, SellerType, SellersJson};
+    use crate::AdsTxt;
+    use std::collections::HashMap;
+
+    #[test]
+    fn exporting_findings() {
+        let (ads_txt, errors) = AdsTxt::parse_lenient(
+            r"
+            greenadexchange.com, 12345, DIRECT
+            greenadexchange.com, 999, DIRECT
+            broken line
+            ",
+        );
+
+        let mut sellers = HashMap::new();
+        sellers.insert(
+            "greenadexchange.com".to_string(),
+            SellersJson::new(&[Seller::new("12345", SellerType::Publisher)]),
+        );
+        let checks = cross_validate(&ads_txt, &sellers);
+
+        let mut example = DomainFindings::from_crawl(
+            "example.com",
+            &CrawlResult::Fetched(FetchedAdsTxt {
+                domain: "example.com".to_string(),
+                url: "http://example.com/ads.txt".to_string(),
+                raw_hash: 0,
+                normalized_hash: 0,
+                charset: Charset::Utf8,
+                record_lines: vec![2, 3],
+                ads_txt,
+                errors,
+            }),
+        );
+        example.add_record_checks(&checks);
+
+        let missing =
+            DomainFindings::from_crawl("missing.com", &CrawlResult::Failed(FetchError::NotFound));
+
+        let export = FindingsExport::new(vec![example, missing]);
+        assert_eq!(
+            export.counts(),
+            vec![
+                (FindingCode::InvalidLine, 1),
+                (FindingCode::SellerIdNotFound, 1),
+                (FindingCode::FetchFailed, 1),
+            ]
+        );
+
+        let json = export.to_json();
+        let summary = json.get("summary").unwrap();
+        assert_eq!(summary.get("domains").and_then(|d| d.as_i64()), Some(2));
+        assert_eq!(
+            summary.get("fetch_errors").and_then(|d| d.as_i64()),
+            Some(1)
+        );
+        assert_eq!(summary.get("records").and_then(|d| d.as_i64()), Some(2));
+
+        let domains = json.get("domains").and_then(|d| d.as_array()).unwrap();
+        assert_eq!(
+            domains[1].to_string(),
+            r#"{"domain":"missing.com","status":"ERROR","fetch_error":{"class":"not_found","message":"Not found"},"records":0,"findings":[{"type":"FETCH_FAILED","severity":"ERROR","message":"Not found","ad_system":null,"seller_id":null}]}"#
+        );
+
+        // The export must be valid JSON
+        assert_eq!(JsonValue::parse(&json.to_string()), Ok(json));
+    }
+}
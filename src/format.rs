@@ -0,0 +1,175 @@
+//! Canonical formatting of ads.txt files
+//!
+//! Records are normalized (lowercase ad system, uppercase relation), sorted
+//! and deduplicated, followed by variables and then any unparsable lines.
+//! Comments at the top of the file stay there; other comments move with the
+//! line that follows them.
+
+use crate::{DataRecord, Variable};
+use std::collections::HashMap;
+
+struct Entry<'a> {
+    comments: Vec<&'a str>,
+    line: String,
+}
+
+fn push_entries(out: &mut String, entries: &[Entry]) {
+    if entries.is_empty() {
+        return;
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    for entry in entries {
+        for comment in &entry.comments {
+            out.push_str(comment);
+            out.push('\n');
+        }
+        out.push_str(&entry.line);
+        out.push('\n');
+    }
+}
+
+/// Drops entries with the same line as an earlier one, keeping their comments
+fn dedup(entries: Vec<Entry>) -> Vec<Entry> {
+    let mut deduped: Vec<Entry> = vec![];
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for entry in entries {
+        match positions.get(&entry.line) {
+            Some(&i) => deduped[i].comments.extend(entry.comments),
+            None => {
+                positions.insert(entry.line.clone(), deduped.len());
+                deduped.push(entry);
+            }
+        }
+    }
+
+    deduped
+}
+
+/// Rewrites an ads.txt file in canonical form
+pub fn format(text: &str) -> String {
+    let mut header = vec![];
+    let mut comments = vec![];
+    let mut records: Vec<(DataRecord, Entry)> = vec![];
+    let mut variables = vec![];
+    let mut invalid = vec![];
+    let mut in_header = true;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            in_header = false;
+            continue;
+        }
+        if line.starts_with('#') {
+            if in_header {
+                header.push(line);
+            } else {
+                comments.push(line);
+            }
+            continue;
+        }
+        in_header = false;
+
+        let entry_comments = std::mem::take(&mut comments);
+        if let Ok(mut record) = DataRecord::parse(line) {
+            record.domain = record.domain.to_lowercase().as_str().into();
+            let line = record.to_string();
+            records.push((
+                record,
+                Entry {
+                    comments: entry_comments,
+                    line,
+                },
+            ));
+        } else if let Ok(mut variable) = Variable::parse(line) {
+            variable.name = variable.name.to_uppercase();
+            variables.push(Entry {
+                comments: entry_comments,
+                line: variable.to_string(),
+            });
+        } else {
+            invalid.push(Entry {
+                comments: entry_comments,
+                line: line.to_string(),
+            });
+        }
+    }
+
+    records.sort_by(|(a, _), (b, _)| {
+        (&a.domain, &a.publisher_id, a.acc_relation.as_str())
+            .cmp(&(&b.domain, &b.publisher_id, b.acc_relation.as_str()))
+            .then_with(|| a.cert_authority.cmp(&b.cert_authority))
+    });
+
+    let mut out = String::new();
+    for comment in &header {
+        out.push_str(comment);
+        out.push('\n');
+    }
+    push_entries(
+        &mut out,
+        &dedup(records.into_iter().map(|(_, e)| e).collect()),
+    );
+    push_entries(&mut out, &dedup(variables));
+    push_entries(&mut out, &invalid);
+
+    if !comments.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        for comment in comments {
+            out.push_str(comment);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_ads_txt() {
+        let text = r"# ads.txt file for example.com
+
+# Our main exchange
+Exchange.com,  2, direct
+blueadexchange.com, XF436, RESELLER, f08c47fec0942fa0
+subdomain=divisionone.example.com
+
+exchange.com, 1 , DIRECT
+# duplicate
+exchange.com, 2, DIRECT
+not a record
+contact=adops@example.com
+# trailing comment
+";
+
+        let formatted = format(text);
+        assert_eq!(
+            formatted,
+            r"# ads.txt file for example.com
+
+blueadexchange.com, XF436, RESELLER, f08c47fec0942fa0
+exchange.com, 1, DIRECT
+# Our main exchange
+# duplicate
+exchange.com, 2, DIRECT
+
+SUBDOMAIN=divisionone.example.com
+CONTACT=adops@example.com
+
+not a record
+
+# trailing comment
+"
+        );
+        assert_eq!(format(&formatted), formatted);
+        assert_eq!(format(""), "");
+    }
+}
@@ -0,0 +1,98 @@
+//! `Arbitrary` implementations for fuzzing, with the `arbitrary` feature.
+//! Generated files are valid and written back unchanged by `write_into`,
+//! so that fuzz targets can check that parsing round-trips.
+
+use crate::{AccountRelation, AdsTxt, DataRecord, Variable};
+use arbitrary::{Arbitrary, Error, Result, Unstructured};
+
+const LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const PUBLISHER_ID: &[u8] = b"abcdefABCDEF0123456789-_.:pub";
+const VALUE: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789.@:/-_";
+
+/// Text of `min` to `max` characters of an ASCII alphabet
+fn text(u: &mut Unstructured<'_>, alphabet: &[u8], min: usize, max: usize) -> Result<String> {
+    let len = u.int_in_range(min..=max)?;
+    (0..len)
+        .map(|_| u.choose(alphabet).map(|&c| char::from(c)))
+        .collect()
+}
+
+impl<'a> Arbitrary<'a> for AccountRelation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary()? {
+            AccountRelation::Direct
+        } else {
+            AccountRelation::Reseller
+        })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(1))
+    }
+}
+
+impl<'a> Arbitrary<'a> for DataRecord {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let labels = u.int_in_range(1..=3)?;
+        let mut domain = String::new();
+        for _ in 0..labels {
+            domain.push_str(&text(u, ALPHANUMERIC, 1, 12)?);
+            domain.push('.');
+        }
+        domain.push_str(&text(u, LOWER, 2, 4)?);
+
+        let publisher_id = text(u, PUBLISHER_ID, 1, 24)?;
+        let relation = AccountRelation::arbitrary(u)?;
+        let cert_authority = match u.arbitrary()? {
+            true => Some(text(u, ALPHANUMERIC, 1, 16)?),
+            false => None,
+        };
+
+        DataRecord::try_new(&domain, &publisher_id, relation, cert_authority.as_deref())
+            .map_err(|_| Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Variable {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let name = match u.int_in_range(0..=5)? {
+            0 => text(u, ALPHANUMERIC, 1, 16)?,
+            known => crate::validate::KNOWN_VARIABLES[known - 1].to_string(),
+        };
+        let value = text(u, VALUE, 1, 32)?;
+        Ok(Variable::new(&name, &value))
+    }
+}
+
+impl<'a> Arbitrary<'a> for AdsTxt {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let records = u.arbitrary_iter()?.collect::<Result<Vec<DataRecord>>>()?;
+        let variables = u.arbitrary_iter()?.collect::<Result<Vec<Variable>>>()?;
+        Ok(AdsTxt { records, variables })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generating_files() {
+        let bytes: Vec<u8> = (0..4096u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let mut u = Unstructured::new(&bytes);
+        let mut generated = 0;
+
+        while !u.is_empty() {
+            let ads_txt = match AdsTxt::arbitrary(&mut u) {
+                Ok(ads_txt) => ads_txt,
+                Err(_) => break,
+            };
+            let mut text = String::new();
+            ads_txt.write_into(&mut text).unwrap();
+            assert_eq!(AdsTxt::parse(&text).unwrap(), ads_txt, "{}", text);
+            generated += ads_txt.records.len() + ads_txt.variables.len();
+        }
+        assert!(generated > 0);
+    }
+}
@@ -0,0 +1,300 @@
+//! Synthetic ads.txt files for load testing parsers, caches and crawlers
+//!
+//! Files mix well-known and made-up ad systems, DIRECT and RESELLER records,
+//! cert authority ids, comments and variables. Generation is deterministic for
+//! a given seed, so load tests are reproducible.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Ad systems which the generated records list first, with their cert
+/// authority ids
+const AD_SYSTEMS: &[(&str, &str)] = &[
+    ("google.com", "f08c47fec0942fa0"),
+    ("appnexus.com", "f5ab79cb980f11d1"),
+    ("rubiconproject.com", "0bfd66d529a55807"),
+    ("openx.com", "6a698e2ec38604c6"),
+    ("pubmatic.com", "5d62403b186f2ace"),
+    ("indexexchange.com", "50b1c356f2c5c8fc"),
+    ("sovrn.com", "fafdf38b16bf6b2b"),
+    ("triplelift.com", "6c33edb13117fd86"),
+];
+
+/// Deterministic splitmix64 generator
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+/// Generator of synthetic files, configured with `with_*` methods
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generator {
+    domain: String,
+    records: usize,
+    ad_systems: usize,
+    duplicate_rate: f64,
+    error_rate: f64,
+    comment_rate: f64,
+    reseller_rate: f64,
+    cert_authority_rate: f64,
+    variables: Vec<(String, usize)>,
+    seed: u64,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            domain: "example.com".to_string(),
+            records: 100,
+            ad_systems: 50,
+            duplicate_rate: 0.0,
+            error_rate: 0.0,
+            comment_rate: 0.02,
+            reseller_rate: 0.6,
+            cert_authority_rate: 0.7,
+            variables: vec![("contact".to_string(), 1)],
+            seed: 0,
+        }
+    }
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publisher domain of the file, used in variable values
+    pub fn with_domain(mut self, domain: &str) -> Self {
+        self.domain = domain.trim().to_string();
+        self
+    }
+
+    /// Number of record lines, including duplicates and injected errors
+    pub fn with_records(mut self, records: usize) -> Self {
+        self.records = records;
+        self
+    }
+
+    /// Number of distinct ad systems the records are spread over
+    pub fn with_ad_systems(mut self, ad_systems: usize) -> Self {
+        self.ad_systems = ad_systems.max(1);
+        self
+    }
+
+    /// Probability of a record line repeating an earlier record
+    pub fn with_duplicate_rate(mut self, rate: f64) -> Self {
+        self.duplicate_rate = rate;
+        self
+    }
+
+    /// Probability of a record line being replaced by a line which does not
+    /// parse
+    pub fn with_error_rate(mut self, rate: f64) -> Self {
+        self.error_rate = rate;
+        self
+    }
+
+    /// Probability of a comment line before each record line
+    pub fn with_comment_rate(mut self, rate: f64) -> Self {
+        self.comment_rate = rate;
+        self
+    }
+
+    pub fn with_reseller_rate(mut self, rate: f64) -> Self {
+        self.reseller_rate = rate;
+        self
+    }
+
+    pub fn with_cert_authority_rate(mut self, rate: f64) -> Self {
+        self.cert_authority_rate = rate;
+        self
+    }
+
+    /// Sets how many times the variable appears, replacing the default of one
+    /// `contact`. A count of 0 removes it.
+    pub fn with_variable(mut self, name: &str, count: usize) -> Self {
+        let name = name.trim().to_lowercase();
+        self.variables.retain(|(n, _)| *n != name);
+        if count > 0 {
+            self.variables.push((name, count));
+        }
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Generates a file, the same one every time for the same configuration
+    pub fn generate(&self) -> String {
+        let mut rng = Rng(self.seed);
+        let mut text = format!("# ads.txt file for {}\n", self.domain);
+        let mut records: Vec<String> = Vec::with_capacity(self.records);
+
+        for _ in 0..self.records {
+            if rng.chance(self.comment_rate) {
+                text.push_str("# Managed by the ad ops team\n");
+            }
+
+            let line = if rng.chance(self.error_rate) {
+                invalid_line(&mut rng)
+            } else if !records.is_empty() && rng.chance(self.duplicate_rate) {
+                records[rng.below(records.len())].clone()
+            } else {
+                let record = self.record(&mut rng);
+                records.push(record.clone());
+                record
+            };
+            text.push_str(&line);
+            text.push('\n');
+        }
+
+        for (name, count) in &self.variables {
+            for i in 0..*count {
+                text.push_str(&format!("{}={}\n", name, self.variable_value(name, i)));
+            }
+        }
+
+        text
+    }
+
+    /// Generates files for `count` publishers, `publisher<i>.com`, as
+    /// (domain, content) pairs
+    pub fn generate_corpus(&self, count: usize) -> Vec<(String, String)> {
+        (0..count)
+            .map(|i| {
+                let domain = format!("publisher{}.com", i);
+                let file = self
+                    .clone()
+                    .with_domain(&domain)
+                    .with_seed(self.seed.wrapping_add(i as u64))
+                    .generate();
+                (domain, file)
+            })
+            .collect()
+    }
+
+    fn record(&self, rng: &mut Rng) -> String {
+        let i = rng.below(self.ad_systems);
+        let (ad_system, cert_authority) = match AD_SYSTEMS.get(i) {
+            Some(&(ad_system, cert_authority)) => {
+                (ad_system.to_string(), cert_authority.to_string())
+            }
+            None => (
+                format!("adsystem{}.com", i),
+                format!("{:016x}", i as u64 * 0x9e37_79b9),
+            ),
+        };
+
+        let publisher_id = if ad_system == "google.com" {
+            format!("pub-{:016}", rng.next_u64() % 10_000_000_000_000_000)
+        } else {
+            (rng.next_u64() % 1_000_000).to_string()
+        };
+        let relation = if rng.chance(self.reseller_rate) {
+            "RESELLER"
+        } else {
+            "DIRECT"
+        };
+
+        if rng.chance(self.cert_authority_rate) {
+            format!(
+                "{}, {}, {}, {}",
+                ad_system, publisher_id, relation, cert_authority
+            )
+        } else {
+            format!("{}, {}, {}", ad_system, publisher_id, relation)
+        }
+    }
+
+    fn variable_value(&self, name: &str, i: usize) -> String {
+        match name {
+            "contact" => format!("adops{}@{}", i, self.domain),
+            "subdomain" => format!("section{}.{}", i, self.domain),
+            "ownerdomain" | "managerdomain" => self.domain.clone(),
+            "inventorypartnerdomain" => format!("partner{}.com", i),
+            _ => format!("value{}", i),
+        }
+    }
+}
+
+/// Line which is neither a record nor a variable
+fn invalid_line(rng: &mut Rng) -> String {
+    let publisher_id = rng.next_u64() % 1_000_000;
+    match rng.below(4) {
+        0 => format!("adsystem.com, {}", publisher_id),
+        1 => format!("adsystem.com, {}, DIRCT", publisher_id),
+        2 => format!("adsystem.com, {}, DIRECT, abc, extra", publisher_id),
+        _ => "<html><body>Not Found</body></html>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AdsTxt;
+
+    #[test]
+    fn generating_files() {
+        let generator = Generator::new().with_records(500).with_seed(7);
+        let text = generator.generate();
+        assert_eq!(text, generator.generate());
+        assert_ne!(text, generator.clone().with_seed(8).generate());
+
+        let (ads_txt, errors) = AdsTxt::parse_lenient(&text);
+        assert_eq!(ads_txt.records.len(), 500);
+        assert!(errors.is_empty());
+        assert_eq!(ads_txt.contacts(), vec!["adops0@example.com"]);
+        assert!(ads_txt.records.iter().any(|r| r.domain == "google.com"));
+        assert!(ads_txt
+            .records
+            .iter()
+            .any(|r| r.domain.starts_with("adsystem")));
+
+        let text = Generator::new()
+            .with_records(200)
+            .with_error_rate(0.25)
+            .with_variable("contact", 0)
+            .with_variable("subdomain", 2)
+            .generate();
+        let (ads_txt, errors) = AdsTxt::parse_lenient(&text);
+        assert!(!errors.is_empty());
+        assert_eq!(ads_txt.records.len() + errors.len(), 200);
+        assert_eq!(ads_txt.sub_domains().len(), 2);
+        assert!(ads_txt.contacts().is_empty());
+    }
+
+    #[test]
+    fn generating_duplicates() {
+        let text = Generator::new()
+            .with_records(100)
+            .with_duplicate_rate(1.0)
+            .generate();
+        let (ads_txt, _) = AdsTxt::parse_lenient(&text);
+        assert_eq!(ads_txt.records.len(), 100);
+        assert!(ads_txt.records.iter().all(|r| *r == ads_txt.records[0]));
+
+        let corpus = Generator::new().with_records(10).generate_corpus(3);
+        assert_eq!(corpus.len(), 3);
+        assert_eq!(corpus[2].0, "publisher2.com");
+        assert!(corpus[2].1.contains("adops0@publisher2.com"));
+    }
+}
@@ -0,0 +1,255 @@
+//! Supply-path graph built from many publishers' ads.txt files and,
+//! optionally, the sellers.json files of the ad systems they list
+//!
+//! For a publisher's inventory, every ads.txt record is an account on an ad
+//! system. The owner of that account, taken from the ad system's sellers.json
+//! when known and otherwise assumed to be the publisher, sells the inventory
+//! into the ad system. Routes are paths along these accounts from the
+//! publisher to an exchange.
+
+use crate::sellers_json::SellersJson;
+use crate::{AccountRelation, AdsTxt, DataRecord};
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Hop {
+    /// Business selling the inventory
+    pub from: String,
+    /// Ad system the inventory is sold into
+    pub ad_system: String,
+    pub seller_id: String,
+    pub relation: AccountRelation,
+}
+
+pub type Route = Vec<Hop>;
+
+#[derive(Debug, Clone, Default)]
+pub struct SupplyGraph {
+    records: HashMap<String, Vec<DataRecord>>,
+    /// Ad system → seller id → business domain of the seller
+    owners: HashMap<String, HashMap<String, String>>,
+}
+
+pub(crate) fn normalize_domain(domain: &str) -> String {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    match domain.strip_prefix("www.") {
+        Some(stripped) => stripped.to_string(),
+        None => domain,
+    }
+}
+
+impl SupplyGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the ads.txt file of a publisher, replacing any earlier one
+    pub fn add_ads_txt(&mut self, publisher: &str, ads_txt: &AdsTxt) {
+        self.records
+            .insert(normalize_domain(publisher), ads_txt.records.clone());
+    }
+
+    /// Adds the sellers.json file of an ad system, replacing any earlier one
+    pub fn add_sellers_json(&mut self, ad_system: &str, sellers_json: &SellersJson) {
+        let owners = sellers_json
+            .sellers
+            .iter()
+            .filter_map(|s| {
+                s.domain
+                    .as_ref()
+                    .map(|d| (s.seller_id.clone(), normalize_domain(d)))
+            })
+            .collect();
+
+        self.owners.insert(normalize_domain(ad_system), owners);
+    }
+
+    /// Publishers whose ads.txt has been added, sorted
+    pub fn publishers(&self) -> Vec<&str> {
+        let mut publishers: Vec<&str> = self.records.keys().map(|p| p.as_str()).collect();
+        publishers.sort_unstable();
+        publishers
+    }
+
+    /// Ad systems listed by any publisher, sorted
+    pub fn ad_systems(&self) -> Vec<String> {
+        let ad_systems: BTreeSet<String> = self
+            .records
+            .values()
+            .flatten()
+            .map(|r| normalize_domain(&r.domain))
+            .collect();
+        ad_systems.into_iter().collect()
+    }
+
+    /// All accounts through which the publisher's inventory is sold
+    pub fn hops(&self, publisher: &str) -> Vec<Hop> {
+        let publisher = normalize_domain(publisher);
+
+        let records = match self.records.get(&publisher) {
+            Some(records) => records,
+            None => return vec![],
+        };
+
+        records
+            .iter()
+            .map(|record| {
+                let ad_system = normalize_domain(&record.domain);
+                let from = self
+                    .owners
+                    .get(&ad_system)
+                    .and_then(|owners| owners.get(record.publisher_id.as_str()))
+                    .cloned()
+                    .unwrap_or_else(|| publisher.clone());
+
+                Hop {
+                    from,
+                    ad_system,
+                    seller_id: record.publisher_id.to_string(),
+                    relation: record.acc_relation.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// All routes with at most `max_hops` hops along which the publisher's
+    /// inventory reaches the given exchange
+    pub fn routes(&self, publisher: &str, exchange: &str, max_hops: usize) -> Vec<Route> {
+        let publisher = normalize_domain(publisher);
+        let exchange = normalize_domain(exchange);
+        let hops = self.hops(&publisher);
+
+        let mut routes = vec![];
+        let mut route = vec![];
+        let mut visited = vec![publisher.clone()];
+        Self::find_routes(
+            &hops,
+            &publisher,
+            &exchange,
+            max_hops,
+            &mut visited,
+            &mut route,
+            &mut routes,
+        );
+        routes
+    }
+
+    fn find_routes(
+        hops: &[Hop],
+        from: &str,
+        exchange: &str,
+        max_hops: usize,
+        visited: &mut Vec<String>,
+        route: &mut Route,
+        routes: &mut Vec<Route>,
+    ) {
+        if route.len() >= max_hops {
+            return;
+        }
+
+        for hop in hops.iter().filter(|h| h.from == from) {
+            route.push(hop.clone());
+
+            if hop.ad_system == exchange {
+                routes.push(route.clone());
+            } else if !visited.contains(&hop.ad_system) {
+                visited.push(hop.ad_system.clone());
+                Self::find_routes(
+                    hops,
+                    &hop.ad_system,
+                    exchange,
+                    max_hops,
+                    visited,
+                    route,
+                    routes,
+                );
+                visited.pop();
+            }
+
+            route.pop();
+        }
+    }
+
+    /// Publishers whose inventory reaches the given exchange, sorted
+    pub fn publishers_reaching(&self, exchange: &str, max_hops: usize) -> Vec<&str> {
+        self.publishers()
+            .into_iter()
+            .filter(|p| !self.routes(p, exchange, max_hops).is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sellers_json::{Seller, SellerType};
+
+    fn seller(id: &str, domain: &str) -> Seller {
+        Seller {
+            domain: Some(domain.to_string()),
+            ..Seller::new(id, SellerType::Intermediary)
+        }
+    }
+
+    #[test]
+    fn finding_routes() {
+        let mut graph = SupplyGraph::new();
+        graph.add_ads_txt(
+            "publisher.com",
+            &AdsTxt::parse(
+                r"
+                exchange.com, 1, DIRECT
+                intermediary.com, 2, DIRECT
+                exchange.com, 3, RESELLER
+                other.com, 4, RESELLER
+                ",
+            )
+            .unwrap(),
+        );
+        graph.add_ads_txt(
+            "www.other-publisher.com",
+            &AdsTxt::parse("other.com, 5, DIRECT").unwrap(),
+        );
+        graph.add_sellers_json(
+            "exchange.com",
+            &SellersJson::new(&[seller("3", "intermediary.com")]),
+        );
+
+        assert_eq!(
+            graph.publishers(),
+            vec!["other-publisher.com", "publisher.com"]
+        );
+        assert_eq!(
+            graph.ad_systems(),
+            vec!["exchange.com", "intermediary.com", "other.com"]
+        );
+
+        let routes = graph.routes("publisher.com", "exchange.com", 5);
+        let accounts: Vec<Vec<(&str, &str)>> = routes
+            .iter()
+            .map(|r| {
+                r.iter()
+                    .map(|h| (h.ad_system.as_str(), h.seller_id.as_str()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            accounts,
+            vec![
+                vec![("exchange.com", "1")],
+                vec![("intermediary.com", "2"), ("exchange.com", "3")],
+            ]
+        );
+
+        assert_eq!(graph.routes("publisher.com", "exchange.com", 1).len(), 1);
+        assert!(graph.routes("publisher.com", "unknown.com", 5).is_empty());
+        assert_eq!(
+            graph.publishers_reaching("other.com", 5),
+            vec!["other-publisher.com", "publisher.com"]
+        );
+        assert_eq!(
+            graph.publishers_reaching("exchange.com", 5),
+            vec!["publisher.com"]
+        );
+    }
+}
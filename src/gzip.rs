@@ -0,0 +1,698 @@
+//! Streaming gzip decompression (RFC 1952 over RFC 1951 deflate), so that
+//! compressed files from crawl archives are parsed without first inflating
+//! them into a string
+//!
+//! Blocks are decoded as output is read, keeping only the last `WINDOW_SIZE`
+//! bytes already read, as back-references reach no further, so memory stays
+//! bounded however much a block inflates.
+
+use std::io::{self, Read};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+
+/// Largest decompressed ads.txt file read by `AdsTxt::from_gzip_reader`
+pub const DEFAULT_MAX_OUTPUT: u64 = 64 << 20;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order in which dynamic blocks list the lengths of the code length code
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-32 of the bytes, continuing from the CRC of the bytes before them
+pub fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |crc, &b| {
+        CRC_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Whether the bytes start with the gzip magic number
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1f, 0x8b])
+}
+
+fn invalid_data<T>(message: &str) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+struct BitReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    bits: u32,
+    count: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(reader: R) -> Self {
+        BitReader {
+            reader,
+            buf: vec![0; 8 * 1024],
+            pos: 0,
+            len: 0,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    /// Next byte, ignoring any bits left of the current one
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        if self.pos == self.len {
+            self.len = loop {
+                match self.reader.read(&mut self.buf) {
+                    Ok(len) => break len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            };
+            self.pos = 0;
+            if self.len == 0 {
+                return Ok(None);
+            }
+        }
+        self.pos += 1;
+        Ok(Some(self.buf[self.pos - 1]))
+    }
+
+    fn need_byte(&mut self) -> io::Result<u8> {
+        match self.byte()? {
+            Some(b) => Ok(b),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated gzip stream",
+            )),
+        }
+    }
+
+    /// Reads up to 16 bits, least significant first
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            self.bits |= (self.need_byte()? as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.bits & ((1 << n) - 1);
+        self.bits >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary
+    fn align(&mut self) {
+        self.bits = 0;
+        self.count = 0;
+    }
+}
+
+/// Canonical Huffman code, decoded a bit at a time
+struct Huffman {
+    /// Number of codes of each length
+    counts: [u16; 16],
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn fixed() -> (Huffman, Huffman) {
+        let mut lengths = [8; 288];
+        lengths[144..256].iter_mut().for_each(|l| *l = 9);
+        lengths[256..280].iter_mut().for_each(|l| *l = 7);
+        (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+    }
+
+    fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> io::Result<usize> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as usize;
+            let count = count as usize;
+            if code < first + count {
+                return Ok(self.symbols[index + code - first] as usize);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        invalid_data("invalid Huffman code in gzip stream")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum State {
+    Header { first: bool },
+    Blocks,
+    Trailer,
+    Done,
+}
+
+/// Position within the deflate stream of a member
+enum Block {
+    /// Before the header of a block, or past the end of the stream if the
+    /// previous block was the last one
+    Start { after_last: bool },
+    /// Stored block with bytes left to copy
+    Stored { remaining: usize, last: bool },
+    Codes {
+        literals: Huffman,
+        distances: Huffman,
+        last: bool,
+    },
+}
+
+/// Reader decompressing a gzip stream from `R`, including streams of several
+/// concatenated members
+pub struct GzipDecoder<R> {
+    bits: BitReader<R>,
+    state: State,
+    block: Block,
+    /// Decompressed output, starting with the window of output already read
+    out: Vec<u8>,
+    pos: usize,
+    /// CRC-32 and size of the current member's output
+    crc: u32,
+    size: u32,
+    /// Output of all members, and the most allowed
+    total: u64,
+    max_output: u64,
+    /// Whether the stream is raw deflate data, without gzip framing
+    raw: bool,
+}
+
+impl<R: Read> GzipDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        GzipDecoder {
+            bits: BitReader::new(reader),
+            state: State::Header { first: true },
+            block: Block::Start { after_last: false },
+            out: vec![],
+            pos: 0,
+            crc: 0,
+            size: 0,
+            total: 0,
+            max_output: u64::MAX,
+            raw: false,
+        }
+    }
+
+    /// Fails reading with `InvalidData` once the stream inflates past
+    /// `max_output` bytes, unlimited by default
+    pub fn with_max_output(mut self, max_output: u64) -> Self {
+        self.max_output = max_output;
+        self
+    }
+
+    /// Decoder of a raw deflate stream, as stored in zip archives
+    #[cfg(feature = "archive")]
+    pub(crate) fn deflate(reader: R) -> Self {
+        GzipDecoder {
+            state: State::Blocks,
+            raw: true,
+            ..GzipDecoder::new(reader)
+        }
+    }
+
+    /// Reads a member header, returning false at the end of the stream
+    fn read_header(&mut self, first: bool) -> io::Result<bool> {
+        let magic = match self.bits.byte()? {
+            Some(b) => [b, self.bits.need_byte()?],
+            None if !first => return Ok(false),
+            None => return invalid_data("empty gzip stream"),
+        };
+        if !is_gzip(&magic) {
+            return invalid_data("not a gzip stream");
+        }
+        if self.bits.need_byte()? != 8 {
+            return invalid_data("unsupported gzip compression method");
+        }
+
+        let flags = self.bits.need_byte()?;
+        // Modification time, extra flags and OS
+        for _ in 0..6 {
+            self.bits.need_byte()?;
+        }
+        if flags & 0x04 != 0 {
+            for _ in 0..self.bits.bits(16)? {
+                self.bits.need_byte()?;
+            }
+        }
+        // File name and comment, zero-terminated
+        for flag in &[0x08, 0x10] {
+            if flags & flag != 0 {
+                while self.bits.need_byte()? != 0 {}
+            }
+        }
+        if flags & 0x02 != 0 {
+            self.bits.bits(16)?;
+        }
+
+        Ok(true)
+    }
+
+    fn read_trailer(&mut self) -> io::Result<()> {
+        self.bits.align();
+        let crc = self.bits.bits(16)? | self.bits.bits(16)? << 16;
+        let size = self.bits.bits(16)? | self.bits.bits(16)? << 16;
+        if crc != self.crc {
+            return invalid_data("gzip checksum mismatch");
+        }
+        if size != self.size {
+            return invalid_data("gzip size mismatch");
+        }
+
+        self.crc = 0;
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Decompresses until `len` more bytes are output or the deflate stream
+    /// ends, returning whether it ended. Only the window of output already
+    /// read is kept.
+    fn inflate(&mut self, len: usize) -> io::Result<bool> {
+        let read = self.pos.saturating_sub(WINDOW_SIZE);
+        self.out.drain(..read);
+        self.pos -= read;
+
+        let start = self.out.len();
+        let end = self.inflate_until(start + len);
+        self.crc = crc32(self.crc, &self.out[start..]);
+        self.size = self.size.wrapping_add((self.out.len() - start) as u32);
+        self.total += (self.out.len() - start) as u64;
+        if self.total > self.max_output {
+            return invalid_data("gzip stream inflates past the output limit");
+        }
+        end
+    }
+
+    fn inflate_until(&mut self, limit: usize) -> io::Result<bool> {
+        while self.out.len() < limit {
+            let block = std::mem::replace(&mut self.block, Block::Start { after_last: false });
+            self.block = match block {
+                Block::Start { after_last: true } => return Ok(true),
+                Block::Start { after_last: false } => self.block_header()?,
+                Block::Stored { remaining, last } => {
+                    let len = remaining.min(limit - self.out.len());
+                    for _ in 0..len {
+                        let b = self.bits.need_byte()?;
+                        self.out.push(b);
+                    }
+                    match remaining - len {
+                        0 => Block::Start { after_last: last },
+                        remaining => Block::Stored { remaining, last },
+                    }
+                }
+                Block::Codes {
+                    literals,
+                    distances,
+                    last,
+                } => {
+                    if self.codes(&literals, &distances, limit)? {
+                        Block::Start { after_last: last }
+                    } else {
+                        Block::Codes {
+                            literals,
+                            distances,
+                            last,
+                        }
+                    }
+                }
+            };
+        }
+        Ok(false)
+    }
+
+    fn block_header(&mut self) -> io::Result<Block> {
+        let last = self.bits.bits(1)? == 1;
+        Ok(match self.bits.bits(2)? {
+            0 => {
+                self.bits.align();
+                let len = self.bits.bits(16)?;
+                if self.bits.bits(16)? != !len & 0xffff {
+                    return invalid_data("corrupt stored block length");
+                }
+                Block::Stored {
+                    remaining: len as usize,
+                    last,
+                }
+            }
+            1 => {
+                let (literals, distances) = Huffman::fixed();
+                Block::Codes {
+                    literals,
+                    distances,
+                    last,
+                }
+            }
+            2 => {
+                let (literals, distances) = self.dynamic_codes()?;
+                Block::Codes {
+                    literals,
+                    distances,
+                    last,
+                }
+            }
+            _ => return invalid_data("invalid deflate block type"),
+        })
+    }
+
+    fn dynamic_codes(&mut self) -> io::Result<(Huffman, Huffman)> {
+        let num_literals = self.bits.bits(5)? as usize + 257;
+        let num_distances = self.bits.bits(5)? as usize + 1;
+        let num_code_lengths = self.bits.bits(4)? as usize + 4;
+        if num_literals > 286 || num_distances > 30 {
+            return invalid_data("too many codes in deflate block");
+        }
+
+        let mut lengths = [0; 19];
+        for &i in &CODE_LENGTH_ORDER[..num_code_lengths] {
+            lengths[i] = self.bits.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&lengths);
+
+        let mut lengths = vec![0; num_literals + num_distances];
+        let mut i = 0;
+        while i < lengths.len() {
+            let (length, repeat) = match code_lengths.decode(&mut self.bits)? {
+                length @ 0..=15 => (length as u8, 1),
+                16 if i > 0 => (lengths[i - 1], 3 + self.bits.bits(2)? as usize),
+                17 => (0, 3 + self.bits.bits(3)? as usize),
+                18 => (0, 11 + self.bits.bits(7)? as usize),
+                _ => return invalid_data("invalid code lengths in deflate block"),
+            };
+            if i + repeat > lengths.len() {
+                return invalid_data("invalid code lengths in deflate block");
+            }
+            lengths[i..i + repeat].iter_mut().for_each(|l| *l = length);
+            i += repeat;
+        }
+        if lengths[256] == 0 {
+            return invalid_data("missing end of block code in deflate block");
+        }
+
+        Ok((
+            Huffman::new(&lengths[..num_literals]),
+            Huffman::new(&lengths[num_literals..]),
+        ))
+    }
+
+    /// Decodes symbols until `out` reaches the limit, returning whether the
+    /// block ended. A copy may run up to 258 bytes past the limit.
+    fn codes(&mut self, literals: &Huffman, distances: &Huffman, limit: usize) -> io::Result<bool> {
+        while self.out.len() < limit {
+            let symbol = literals.decode(&mut self.bits)?;
+            if symbol < 256 {
+                self.out.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                return Ok(true);
+            }
+
+            let symbol = symbol - 257;
+            if symbol >= LENGTH_BASE.len() {
+                return invalid_data("invalid length code in deflate block");
+            }
+            let length = LENGTH_BASE[symbol] as usize
+                + self.bits.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+            let symbol = distances.decode(&mut self.bits)?;
+            if symbol >= DISTANCE_BASE.len() {
+                return invalid_data("invalid distance code in deflate block");
+            }
+            let distance = DISTANCE_BASE[symbol] as usize
+                + self.bits.bits(DISTANCE_EXTRA[symbol] as u32)? as usize;
+            if distance > self.out.len() {
+                return invalid_data("distance too far back in deflate block");
+            }
+
+            // The copy may overlap the bytes it produces
+            let from = self.out.len() - distance;
+            for i in from..from + length {
+                let b = self.out[i];
+                self.out.push(b);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<R: Read> Read for GzipDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.out.len() {
+            match self.state {
+                State::Header { first } => {
+                    self.state = if self.read_header(first)? {
+                        State::Blocks
+                    } else {
+                        State::Done
+                    }
+                }
+                State::Blocks => {
+                    if self.inflate(buf.len().max(WINDOW_SIZE))? {
+                        self.state = if self.raw {
+                            State::Done
+                        } else {
+                            State::Trailer
+                        };
+                    }
+                }
+                State::Trailer => {
+                    self.read_trailer()?;
+                    self.state = State::Header { first: false };
+                }
+                State::Done => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.out.len() - self.pos);
+        buf[..len].copy_from_slice(&self.out[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AdsTxt;
+
+    /// `greenadexchange.com, 12345, DIRECT, d75815a79\n` in a fixed Huffman block
+    const FIXED: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x4b, 0x2f, 0x4a, 0x4d, 0xcd,
+        0x4b, 0x4c, 0x49, 0xad, 0x48, 0xce, 0x48, 0xcc, 0x4b, 0x4f, 0xd5, 0x4b, 0xce, 0xcf, 0xd5,
+        0x51, 0x30, 0x34, 0x32, 0x36, 0x31, 0xd5, 0x51, 0x70, 0xf1, 0x0c, 0x72, 0x75, 0x0e, 0xd1,
+        0x51, 0x48, 0x31, 0x37, 0xb5, 0x30, 0x34, 0x4d, 0x34, 0xb7, 0xe4, 0x02, 0x00, 0x42, 0x4e,
+        0x39, 0x95, 0x2e, 0x00, 0x00, 0x00,
+    ];
+
+    /// `dynamic_text()` in a dynamic Huffman block, with the file name in
+    /// the header
+    const DYNAMIC: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x61, 0x64, 0x73, 0x2e, 0x74,
+        0x78, 0x74, 0x00, 0x7d, 0x91, 0x3b, 0x0e, 0x02, 0x31, 0x0c, 0x44, 0x7b, 0x4e, 0x91, 0x03,
+        0x04, 0x14, 0x3b, 0x76, 0x3e, 0x35, 0xa4, 0x40, 0xda, 0x2a, 0x70, 0x01, 0x58, 0x45, 0xd0,
+        0xf0, 0x69, 0x90, 0x38, 0x3e, 0x0d, 0xda, 0xb5, 0x94, 0xc8, 0xf5, 0xf3, 0x8c, 0x9e, 0xc6,
+        0xed, 0x3b, 0xdf, 0x2f, 0xcf, 0x5b, 0x73, 0xbb, 0xf9, 0xf5, 0xb0, 0xe6, 0xfd, 0xb9, 0x6e,
+        0x9d, 0x35, 0xb5, 0x9c, 0xca, 0x34, 0x95, 0xba, 0x69, 0x7f, 0x0c, 0x2b, 0xf6, 0xd1, 0x9a,
+        0xc3, 0xb1, 0x96, 0xfd, 0x79, 0xa1, 0xb8, 0xd2, 0x48, 0x1d, 0xf5, 0x2b, 0x05, 0x80, 0x41,
+        0x39, 0x89, 0x03, 0x4a, 0x5d, 0x5e, 0xa8, 0x41, 0xe2, 0x0e, 0x0b, 0x35, 0x44, 0x1c, 0xd4,
+        0x0b, 0x3b, 0xe4, 0xac, 0xe9, 0x61, 0x0e, 0x1d, 0x16, 0x72, 0xde, 0xfb, 0x41, 0xbd, 0x93,
+        0xd3, 0x38, 0x4d, 0x8f, 0x9c, 0x3a, 0x1d, 0x11, 0x0d, 0xea, 0x85, 0x1e, 0x25, 0xd0, 0xf4,
+        0x18, 0xd4, 0xed, 0x98, 0x59, 0x7f, 0x2c, 0x67, 0xd4, 0xf4, 0x02, 0xaa, 0xdb, 0x85, 0x10,
+        0xf4, 0xd7, 0x46, 0xe7, 0x97, 0xfc, 0x0f, 0x82, 0x1b, 0xf1, 0xda, 0x76, 0x02, 0x00, 0x00,
+    ];
+
+    fn dynamic_text() -> String {
+        (0..20)
+            .map(|i| {
+                let relation = if i % 3 == 0 { "RESELLER" } else { "DIRECT" };
+                format!("exchange{}.com, pub-{}, {}\n", i % 5, i * 37, relation)
+            })
+            .collect()
+    }
+
+    /// Member holding the text in a stored block
+    fn stored(text: &str) -> Vec<u8> {
+        let mut member = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff, 1];
+        let len = text.len() as u16;
+        member.extend_from_slice(&len.to_le_bytes());
+        member.extend_from_slice(&(!len).to_le_bytes());
+        member.extend_from_slice(text.as_bytes());
+        member.extend_from_slice(&crc32(0, text.as_bytes()).to_le_bytes());
+        member.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        member
+    }
+
+    /// Member of a fixed Huffman block holding `a` followed by copies of
+    /// 258 bytes back one byte, inflating about 1000:1
+    fn run_of_a(copies: usize) -> Vec<u8> {
+        let mut member = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+        let mut bit = 0;
+        let mut put = |value: u32, len: u32| {
+            for i in (0..len).rev() {
+                if bit % 8 == 0 {
+                    member.push(0);
+                }
+                *member.last_mut().unwrap() |= ((value >> i) as u8 & 1) << (bit % 8);
+                bit += 1;
+            }
+        };
+        // Last block flag, then block type 1 for fixed codes, in stream order
+        put(0b110, 3);
+        put(0x30 + b'a' as u32, 8);
+        for _ in 0..copies {
+            // Length 258, distance 1
+            put(0b1100_0101, 8);
+            put(0, 5);
+        }
+        put(0, 7);
+
+        let text = vec![b'a'; 1 + 258 * copies];
+        member.extend_from_slice(&crc32(0, &text).to_le_bytes());
+        member.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        member
+    }
+
+    fn decompress(bytes: &[u8]) -> io::Result<String> {
+        let mut text = String::new();
+        GzipDecoder::new(bytes).read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn decompressing_gzip() {
+        assert_eq!(
+            decompress(FIXED).unwrap(),
+            "greenadexchange.com, 12345, DIRECT, d75815a79\n"
+        );
+        assert_eq!(decompress(DYNAMIC).unwrap(), dynamic_text());
+
+        let mut members = stored("a.com, 1, DIRECT\n");
+        members.extend_from_slice(FIXED);
+        assert_eq!(
+            decompress(&members).unwrap(),
+            "a.com, 1, DIRECT\ngreenadexchange.com, 12345, DIRECT, d75815a79\n"
+        );
+
+        assert_eq!(crc32(0, b"123456789"), 0xcbf4_3926);
+        assert!(is_gzip(FIXED));
+        assert!(!is_gzip(b"a.com, 1, DIRECT"));
+    }
+
+    #[test]
+    fn reading_compressed_files() {
+        let dir = std::env::temp_dir();
+        let compressed = dir.join(format!("ads-txt-gzip-{}.txt.gz", std::process::id()));
+        let plain = dir.join(format!("ads-txt-gzip-{}.txt", std::process::id()));
+        std::fs::write(&compressed, DYNAMIC).unwrap();
+        std::fs::write(&plain, dynamic_text()).unwrap();
+
+        let expected = AdsTxt::parse_lenient(&dynamic_text());
+        assert_eq!(AdsTxt::from_file(&compressed).unwrap(), expected);
+        assert_eq!(AdsTxt::from_file(&plain).unwrap(), expected);
+        assert_eq!(AdsTxt::from_gzip_reader(DYNAMIC).unwrap(), expected);
+        assert!(AdsTxt::from_gzip_reader(&DYNAMIC[..100]).is_err());
+
+        std::fs::remove_file(compressed).unwrap();
+        std::fs::remove_file(plain).unwrap();
+        assert!(AdsTxt::from_file(dir.join("ads-txt-gzip-missing")).is_err());
+    }
+
+    #[test]
+    fn bounding_inflated_output() {
+        let member = run_of_a(40_000);
+        let mut decoder = GzipDecoder::new(&member[..]);
+        let mut buf = [0; 1024];
+        let mut len = 0;
+        loop {
+            match decoder.read(&mut buf).unwrap() {
+                0 => break,
+                read => len += read,
+            }
+            assert!(decoder.out.len() <= 2 * WINDOW_SIZE + 258);
+        }
+        assert_eq!(len, 1 + 258 * 40_000);
+
+        let mut text = vec![];
+        let error = GzipDecoder::new(&member[..])
+            .with_max_output(1 << 20)
+            .read_to_end(&mut text)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(text.len() <= 1 << 20);
+    }
+
+    #[test]
+    fn rejecting_corrupt_gzip() {
+        let mut corrupt = FIXED.to_vec();
+        corrupt[30] ^= 0xff;
+        assert!(decompress(&corrupt).is_err());
+
+        assert_eq!(
+            decompress(&FIXED[..40]).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            decompress(b"a.com, 1, DIRECT").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(decompress(&[]).is_err());
+    }
+}
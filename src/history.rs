@@ -0,0 +1,401 @@
+//! Successive snapshots of publishers' ads.txt files, answering what changed
+//! between two points in time
+
+use crate::diff::AdsTxtDiff;
+use crate::graph::normalize_domain;
+use crate::AdsTxt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Snapshot {
+    pub taken_at: SystemTime,
+    pub ads_txt: AdsTxt,
+}
+
+impl Snapshot {
+    pub fn new(taken_at: SystemTime, ads_txt: AdsTxt) -> Self {
+        Snapshot { taken_at, ads_txt }
+    }
+}
+
+/// Storage for snapshots, keyed by normalized publisher domain
+pub trait SnapshotStore {
+    /// Adds a snapshot taken after every stored one of the domain
+    fn save(&mut self, domain: &str, snapshot: &Snapshot) -> io::Result<()>;
+
+    /// Snapshots of the domain, oldest first
+    fn load(&mut self, domain: &str) -> io::Result<Vec<Snapshot>>;
+
+    /// Domains with at least one snapshot
+    fn domains(&mut self) -> io::Result<Vec<String>>;
+}
+
+impl<S: SnapshotStore + ?Sized> SnapshotStore for &mut S {
+    fn save(&mut self, domain: &str, snapshot: &Snapshot) -> io::Result<()> {
+        (**self).save(domain, snapshot)
+    }
+
+    fn load(&mut self, domain: &str) -> io::Result<Vec<Snapshot>> {
+        (**self).load(domain)
+    }
+
+    fn domains(&mut self) -> io::Result<Vec<String>> {
+        (**self).domains()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemorySnapshotStore {
+    snapshots: BTreeMap<String, Vec<Snapshot>>,
+}
+
+impl MemorySnapshotStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SnapshotStore for MemorySnapshotStore {
+    fn save(&mut self, domain: &str, snapshot: &Snapshot) -> io::Result<()> {
+        self.snapshots
+            .entry(domain.to_string())
+            .or_default()
+            .push(snapshot.clone());
+        Ok(())
+    }
+
+    fn load(&mut self, domain: &str) -> io::Result<Vec<Snapshot>> {
+        Ok(self.snapshots.get(domain).cloned().unwrap_or_default())
+    }
+
+    fn domains(&mut self) -> io::Result<Vec<String>> {
+        Ok(self.snapshots.keys().cloned().collect())
+    }
+}
+
+/// Stores snapshots in a directory with one `<domain>.history` file per
+/// domain. Each snapshot is appended as a `@snapshot <unix time>` line
+/// followed by the file's records and variables.
+#[derive(Debug, Clone)]
+pub struct FileSnapshotStore {
+    dir: PathBuf,
+}
+
+const HISTORY_EXTENSION: &str = ".history";
+const SNAPSHOT_PREFIX: &str = "@snapshot ";
+
+impl FileSnapshotStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        FileSnapshotStore {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, domain: &str) -> PathBuf {
+        let name: String = domain
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | '0'..='9' | '.' | '-' => c,
+                _ => '_',
+            })
+            .collect();
+        self.dir.join(name + HISTORY_EXTENSION)
+    }
+}
+
+fn invalid_data<T>(message: String) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+impl SnapshotStore for FileSnapshotStore {
+    fn save(&mut self, domain: &str, snapshot: &Snapshot) -> io::Result<()> {
+        let since_epoch = match snapshot.taken_at.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since_epoch) => since_epoch,
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "snapshot taken before the Unix epoch",
+                ))
+            }
+        };
+
+        let mut text = format!(
+            "{}{}.{:09}\n",
+            SNAPSHOT_PREFIX,
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos()
+        );
+        snapshot
+            .ads_txt
+            .write_into(&mut text)
+            .expect("writing to a String cannot fail");
+
+        fs::create_dir_all(&self.dir)?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(domain))?
+            .write_all(text.as_bytes())
+    }
+
+    fn load(&mut self, domain: &str) -> io::Result<Vec<Snapshot>> {
+        let text = match fs::read_to_string(self.path(domain)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut snapshots = vec![];
+        let mut taken_at = None;
+        let mut content = String::new();
+        for line in text.lines().chain(std::iter::once(SNAPSHOT_PREFIX)) {
+            let time = match line.strip_prefix(SNAPSHOT_PREFIX) {
+                Some(time) => time,
+                None => {
+                    content.push_str(line);
+                    content.push('\n');
+                    continue;
+                }
+            };
+
+            if let Some(taken_at) = taken_at.take() {
+                match AdsTxt::parse(&content) {
+                    Ok(ads_txt) => snapshots.push(Snapshot::new(taken_at, ads_txt)),
+                    Err(e) => {
+                        return invalid_data(format!("Invalid snapshot of {}: {}", domain, e))
+                    }
+                }
+            } else if !content.trim().is_empty() {
+                return invalid_data(format!("Invalid snapshot history of {}", domain));
+            }
+            content.clear();
+
+            if !time.is_empty() {
+                let (secs, nanos) = time.split_once('.').unwrap_or((time, "0"));
+                match (secs.parse(), nanos.parse()) {
+                    (Ok(secs), Ok(nanos)) => {
+                        taken_at = Some(SystemTime::UNIX_EPOCH + Duration::new(secs, nanos))
+                    }
+                    _ => return invalid_data(format!("Invalid snapshot time: {}", time)),
+                }
+            }
+        }
+
+        Ok(snapshots)
+    }
+
+    fn domains(&mut self) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut domains = vec![];
+        for entry in entries {
+            let name = entry?.file_name();
+            if let Some(domain) = name.to_string_lossy().strip_suffix(HISTORY_EXTENSION) {
+                domains.push(domain.to_string());
+            }
+        }
+        domains.sort_unstable();
+        Ok(domains)
+    }
+}
+
+/// Snapshot history of many domains over a store
+#[derive(Debug, Clone, Default)]
+pub struct History<S> {
+    store: S,
+}
+
+impl<S: SnapshotStore> History<S> {
+    pub fn new(store: S) -> Self {
+        History { store }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    /// Stores a snapshot of the domain's file, unless it has not changed
+    /// since the last one. Returns whether the snapshot was stored.
+    pub fn record(
+        &mut self,
+        domain: &str,
+        ads_txt: &AdsTxt,
+        taken_at: SystemTime,
+    ) -> io::Result<bool> {
+        let domain = normalize_domain(domain);
+        let snapshots = self.store.load(&domain)?;
+
+        if let Some(last) = snapshots.last() {
+            if taken_at < last.taken_at {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Snapshot of {} is older than the last one", domain),
+                ));
+            }
+            if AdsTxtDiff::between(&last.ads_txt, ads_txt).is_empty() {
+                return Ok(false);
+            }
+        }
+
+        self.store
+            .save(&domain, &Snapshot::new(taken_at, ads_txt.clone()))?;
+        Ok(true)
+    }
+
+    pub fn snapshots(&mut self, domain: &str) -> io::Result<Vec<Snapshot>> {
+        self.store.load(&normalize_domain(domain))
+    }
+
+    /// The domain's file as it was at the time, from the last snapshot taken
+    /// at or before it
+    pub fn at(&mut self, domain: &str, time: SystemTime) -> io::Result<Option<Snapshot>> {
+        let snapshots = self.snapshots(domain)?;
+        Ok(snapshots.into_iter().rev().find(|s| s.taken_at <= time))
+    }
+
+    /// Changes between the domain's file at the two times. A file without a
+    /// snapshot yet counts as empty.
+    pub fn diff(
+        &mut self,
+        domain: &str,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> io::Result<AdsTxtDiff> {
+        let snapshots = self.snapshots(domain)?;
+        let at = |time: SystemTime| {
+            snapshots
+                .iter()
+                .rev()
+                .find(|s| s.taken_at <= time)
+                .map(|s| s.ads_txt.clone())
+                .unwrap_or_else(AdsTxt::empty)
+        };
+        Ok(AdsTxtDiff::between(&at(from), &at(to)))
+    }
+
+    /// Every change to the domain's file after `from` and up to `to`, with
+    /// the time of the snapshot showing it
+    pub fn changes(
+        &mut self,
+        domain: &str,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> io::Result<Vec<(SystemTime, AdsTxtDiff)>> {
+        let snapshots = self.snapshots(domain)?;
+
+        let mut changes = vec![];
+        let mut previous = AdsTxt::empty();
+        for snapshot in snapshots.into_iter().filter(|s| s.taken_at <= to) {
+            if snapshot.taken_at > from {
+                let diff = AdsTxtDiff::between(&previous, &snapshot.ads_txt);
+                if !diff.is_empty() {
+                    changes.push((snapshot.taken_at, diff));
+                }
+            }
+            previous = snapshot.ads_txt;
+        }
+        Ok(changes)
+    }
+
+    /// Domains whose file changed between the two times
+    pub fn changed_domains(&mut self, from: SystemTime, to: SystemTime) -> io::Result<Vec<String>> {
+        let mut changed = vec![];
+        for domain in self.store.domains()? {
+            if !self.diff(&domain, from, to)?.is_empty() {
+                changed.push(domain);
+            }
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::SeatChange;
+
+    fn day(n: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000 + n * 86_400)
+    }
+
+    fn record_history<S: SnapshotStore>(history: &mut History<S>) {
+        let v1 = AdsTxt::parse("exchange.com, 1, DIRECT\ncontact=a@b.com").unwrap();
+        let v2 = AdsTxt::parse("EXCHANGE.com, 1, direct\ncontact=a@b.com").unwrap();
+        let v3 = AdsTxt::parse("exchange.com, 1, RESELLER\nother.com, 2, DIRECT").unwrap();
+
+        assert!(history.record("www.a.com", &v1, day(1)).unwrap());
+        // Cosmetic changes are not stored
+        assert!(!history.record("a.com", &v2, day(2)).unwrap());
+        assert!(history.record("a.com", &v3, day(3)).unwrap());
+        assert!(history.record("b.com", &v1, day(1)).unwrap());
+        assert!(history.record("a.com", &v1, day(0)).is_err());
+    }
+
+    fn check_history<S: SnapshotStore>(history: &mut History<S>) {
+        assert_eq!(history.snapshots("a.com").unwrap().len(), 2);
+        assert_eq!(history.at("a.com", day(0)).unwrap(), None);
+        assert_eq!(
+            history.at("a.com", day(2)).unwrap().unwrap().taken_at,
+            day(1)
+        );
+
+        let diff = history.diff("a.com", day(2), day(4)).unwrap();
+        assert_eq!(diff.seats.len(), 2);
+        assert!(matches!(diff.seats[0], SeatChange::Changed { .. }));
+        assert_eq!(diff.removed_variables.len(), 1);
+        assert!(history.diff("a.com", day(1), day(2)).unwrap().is_empty());
+        assert_eq!(
+            history.diff("a.com", day(0), day(1)).unwrap().seats.len(),
+            1
+        );
+
+        let changes = history.changes("a.com", day(0), day(4)).unwrap();
+        assert_eq!(
+            changes.iter().map(|(t, _)| *t).collect::<Vec<_>>(),
+            vec![day(1), day(3)]
+        );
+        assert_eq!(history.changes("a.com", day(1), day(2)).unwrap(), vec![]);
+
+        assert_eq!(
+            history.changed_domains(day(2), day(4)).unwrap(),
+            vec!["a.com"]
+        );
+    }
+
+    #[test]
+    fn storing_history_in_memory() {
+        let mut history = History::new(MemorySnapshotStore::new());
+        record_history(&mut history);
+        check_history(&mut history);
+    }
+
+    #[test]
+    fn storing_history_in_files() {
+        let dir = std::env::temp_dir().join(format!("ads-txt-history-{}", std::process::id()));
+        let mut history = History::new(FileSnapshotStore::new(&dir));
+        record_history(&mut history);
+        check_history(&mut history);
+
+        // Another store over the same directory sees the same history
+        let mut reopened = History::new(FileSnapshotStore::new(&dir));
+        check_history(&mut reopened);
+        assert_eq!(
+            reopened.snapshots("a.com").unwrap(),
+            history.snapshots("a.com").unwrap()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+//! Detection of homograph ad system domains, which use characters of other
+//! scripts looking like Latin letters to impersonate a major exchange, e.g.
+//! a Cyrillic `а` in `аppnexus.com`
+
+use crate::graph::normalize_domain;
+
+/// Known ad systems, whose domains and aliases are the impersonated exchanges
+const EXCHANGES: &str = include_str!("aliases.txt");
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Script {
+    /// Digits, hyphens and other characters which are not letters, which
+    /// go with any script
+    Common,
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Other,
+}
+
+fn script(c: char) -> Script {
+    match c {
+        c if !c.is_alphabetic() => Script::Common,
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => Script::Latin,
+        '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Script::Greek,
+        '\u{0400}'..='\u{052F}' => Script::Cyrillic,
+        '\u{0531}'..='\u{058F}' => Script::Armenian,
+        _ => Script::Other,
+    }
+}
+
+/// ASCII letter a lowercase character is confusable with, if any
+fn confusable(c: char) -> Option<char> {
+    let ascii = match c {
+        // Cyrillic
+        'а' => 'a',
+        'с' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ё' => 'e',
+        'һ' => 'h',
+        'і' => 'i',
+        'ј' => 'j',
+        'к' => 'k',
+        'ӏ' => 'l',
+        'о' => 'o',
+        'р' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'у' => 'y',
+        'ԝ' => 'w',
+        'х' => 'x',
+        // Greek
+        'α' => 'a',
+        'ε' => 'e',
+        'ι' => 'i',
+        'κ' => 'k',
+        'ν' => 'v',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'τ' => 't',
+        'υ' => 'u',
+        'χ' => 'x',
+        // Armenian
+        'օ' => 'o',
+        'ս' => 'u',
+        'ց' => 'g',
+        // Latin letters which are not ASCII
+        'ı' => 'i',
+        'ɑ' => 'a',
+        'ɡ' => 'g',
+        // Fullwidth forms
+        '\u{FF41}'..='\u{FF5A}' => return char::from_u32(c as u32 - 0xFEE0),
+        _ => return None,
+    };
+    Some(ascii)
+}
+
+/// The normalized domain with confusable characters replaced by the ASCII letters they
+/// look like, so that homographs of the same domain are equal
+pub fn skeleton(domain: &str) -> String {
+    normalize_domain(domain)
+        .chars()
+        .map(|c| confusable(c).unwrap_or(c))
+        .collect()
+}
+
+/// Whether a label of the domain mixes letters of different scripts
+pub fn has_mixed_scripts(domain: &str) -> bool {
+    domain.split('.').any(|label| {
+        let mut scripts = label.chars().map(script).filter(|s| *s != Script::Common);
+        match scripts.next() {
+            Some(first) => scripts.any(|s| s != first),
+            None => false,
+        }
+    })
+}
+
+/// Major exchange whose domain, or a subdomain of it, a non-ASCII domain
+/// looks like
+pub fn impersonated_exchange(domain: &str) -> Option<&'static str> {
+    if domain.is_ascii() {
+        return None;
+    }
+
+    let skeleton = skeleton(domain);
+    exchange_domains().find(|exchange| {
+        skeleton == *exchange
+            || skeleton
+                .strip_suffix(exchange)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+fn exchange_domains() -> impl Iterator<Item = &'static str> {
+    EXCHANGES
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .flat_map(|line| {
+            let mut fields = line.split(',').map(str::trim);
+            let domain = fields.next();
+            // Skip the name of the ad system
+            fields.next();
+            domain.into_iter().chain(fields)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detecting_homographs() {
+        assert_eq!(skeleton("\u{0430}ppnexus.com"), "appnexus.com");
+        assert_eq!(
+            impersonated_exchange("\u{0430}ppnexus.com"),
+            Some("appnexus.com")
+        );
+        assert_eq!(
+            impersonated_exchange("ads.g\u{043E}\u{043E}gle.com."),
+            Some("google.com")
+        );
+        assert_eq!(impersonated_exchange("\u{FF4F}penx.net"), Some("openx.net"));
+        assert_eq!(impersonated_exchange("appnexus.com"), None);
+        assert_eq!(impersonated_exchange("b\u{00FC}cher.de"), None);
+
+        assert!(has_mixed_scripts("\u{0430}ppnexus.com"));
+        assert!(has_mixed_scripts("pub\u{03BC}atic.com"));
+        assert!(!has_mixed_scripts(
+            "\u{043F}\u{0440}\u{0438}\u{043C}\u{0435}\u{0440}.\u{0440}\u{0444}"
+        ));
+        assert!(!has_mixed_scripts("b\u{00FC}cher-24.de"));
+    }
+}
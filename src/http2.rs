@@ -0,0 +1,876 @@
+//! HTTP/2 (RFC 9113) for `TcpHttpClient`, spoken on TLS connections where
+//! the server selects `h2` through ALPN. A connection carries one request at
+//! a time, like a keep-alive HTTP/1.1 connection, so that both versions are
+//! pooled the same way.
+//!
+//! Response headers are decoded with HPACK (RFC 7541); request headers are
+//! sent as literals, which needs no state shared with the server.
+
+use crate::fetch::{closed_before_response, FetchError, FetchResult, HttpResponse, Url};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Largest frame payload, SETTINGS_MAX_FRAME_SIZE left at its default
+const MAX_FRAME_SIZE: usize = 16 * 1024;
+/// Largest HPACK dynamic table, SETTINGS_HEADER_TABLE_SIZE left at its
+/// default
+const MAX_TABLE_SIZE: usize = 4096;
+/// Largest header block accepted from the server
+const MAX_HEADER_BLOCK_SIZE: usize = 64 * 1024;
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+
+/// RST_STREAM error code of a stream the server did not process
+const REFUSED_STREAM: u32 = 0x7;
+
+/// Static table of HPACK, indexed from 1
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// Code lengths of the canonical Huffman code of HPACK, of every byte then
+/// the end of string symbol
+const HUFFMAN_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 30, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6, 5, 5,
+    5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10, 13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6, 15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6,
+    6, 5, 6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28, 20, 22, 20, 20, 22, 22, 22, 23, 22,
+    23, 23, 23, 23, 23, 24, 23, 24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24, 22,
+    21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23, 21, 21, 22, 21, 23, 22, 23, 23, 20,
+    22, 22, 22, 23, 22, 22, 23, 26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25, 19,
+    21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27, 20, 24, 20, 21, 22, 21, 21, 23, 22,
+    22, 25, 25, 24, 24, 26, 23, 26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26, 30,
+];
+
+const HUFFMAN_COUNTS: [u16; 31] = huffman_counts();
+const HUFFMAN_SYMBOLS: [u16; 257] = huffman_symbols();
+
+/// Number of codes of each length
+const fn huffman_counts() -> [u16; 31] {
+    let mut counts = [0; 31];
+    let mut symbol = 0;
+    while symbol < 257 {
+        counts[HUFFMAN_LENGTHS[symbol] as usize] += 1;
+        symbol += 1;
+    }
+    counts
+}
+
+/// Symbols ordered by code, which for a canonical code is by length then
+/// symbol
+const fn huffman_symbols() -> [u16; 257] {
+    let mut symbols = [0; 257];
+    let mut i = 0;
+    let mut length = 1;
+    while length <= 30 {
+        let mut symbol = 0;
+        while symbol < 257 {
+            if HUFFMAN_LENGTHS[symbol] as usize == length {
+                symbols[i] = symbol as u16;
+                i += 1;
+            }
+            symbol += 1;
+        }
+        length += 1;
+    }
+    symbols
+}
+
+/// Decodes a Huffman-coded string, `None` if it is malformed or padded with
+/// anything but up to 7 one bits
+fn huffman_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(bytes.len() * 8 / 5);
+    let (mut code, mut first, mut index, mut length) = (0, 0, 0, 0);
+    let mut padding = true;
+
+    for byte in bytes {
+        for shift in (0..8).rev() {
+            let bit = (byte >> shift) as usize & 1;
+            code |= bit;
+            length += 1;
+            padding &= bit == 1;
+            if length > 30 {
+                return None;
+            }
+
+            let count = HUFFMAN_COUNTS[length] as usize;
+            if code < first + count {
+                match HUFFMAN_SYMBOLS[index + code - first] {
+                    256 => return None,
+                    symbol => decoded.push(symbol as u8),
+                }
+                code = 0;
+                first = 0;
+                index = 0;
+                length = 0;
+                padding = true;
+            } else {
+                index += count;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+        }
+    }
+
+    if length > 7 || !padding {
+        return None;
+    }
+    Some(decoded)
+}
+
+/// HPACK decoder, holding the dynamic table of the connection
+#[derive(Debug)]
+struct Decoder {
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Decoder {
+    fn new() -> Self {
+        Decoder {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: MAX_TABLE_SIZE,
+        }
+    }
+
+    /// Decodes a header block into its fields, in order
+    fn decode(&mut self, block: &[u8]) -> Option<Vec<(String, String)>> {
+        let mut fields = vec![];
+        let mut i = 0;
+        while i < block.len() {
+            let b = block[i];
+            if b & 0x80 != 0 {
+                let index = integer(block, &mut i, 7)?;
+                fields.push(self.field(index)?);
+            } else if b & 0x40 != 0 {
+                let field = self.literal(block, &mut i, 6)?;
+                self.insert(field.clone());
+                fields.push(field);
+            } else if b & 0x20 != 0 {
+                let max_size = integer(block, &mut i, 5)?;
+                if max_size > MAX_TABLE_SIZE {
+                    return None;
+                }
+                self.max_size = max_size;
+                self.evict();
+            } else {
+                // Without indexing, or never indexed
+                fields.push(self.literal(block, &mut i, 4)?);
+            }
+        }
+        Some(fields)
+    }
+
+    fn field(&self, index: usize) -> Option<(String, String)> {
+        let (name, value) = match index {
+            0 => return None,
+            1..=61 => STATIC_TABLE[index - 1],
+            _ => {
+                let (name, value) = self.table.get(index - 62)?;
+                (name.as_str(), value.as_str())
+            }
+        };
+        Some((name.to_string(), value.to_string()))
+    }
+
+    fn literal(&self, block: &[u8], i: &mut usize, prefix: u32) -> Option<(String, String)> {
+        let name = match integer(block, i, prefix)? {
+            0 => string(block, i)?,
+            index => self.field(index)?.0,
+        };
+        Some((name, string(block, i)?))
+    }
+
+    fn insert(&mut self, field: (String, String)) {
+        self.size += field.0.len() + field.1.len() + 32;
+        self.table.push_front(field);
+        self.evict();
+    }
+
+    /// Evicts the oldest fields until the table fits, all of them if a
+    /// field larger than the table was just inserted
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            match self.table.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + 32,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Reads an integer with an N-bit prefix at `i`, advancing past it
+fn integer(block: &[u8], i: &mut usize, prefix: u32) -> Option<usize> {
+    let max = (1 << prefix) - 1;
+    let mut value = (*block.get(*i)? & max as u8) as usize;
+    *i += 1;
+    if value < max {
+        return Some(value);
+    }
+
+    let mut shift = 0;
+    loop {
+        let b = *block.get(*i)?;
+        *i += 1;
+        if shift > 28 {
+            return None;
+        }
+        value += ((b & 0x7f) as usize) << shift;
+        shift += 7;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+fn string(block: &[u8], i: &mut usize) -> Option<String> {
+    let huffman = *block.get(*i)? & 0x80 != 0;
+    let len = integer(block, i, 7)?;
+    let bytes = block.get(*i..i.checked_add(len)?)?;
+    *i += len;
+
+    let bytes = if huffman {
+        huffman_decode(bytes)?
+    } else {
+        bytes.to_vec()
+    };
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Appends an integer with an N-bit prefix, the bits above it set to `flags`
+fn put_integer(block: &mut Vec<u8>, flags: u8, prefix: u32, value: usize) {
+    let max = (1 << prefix) - 1;
+    if value < max {
+        block.push(flags | value as u8);
+        return;
+    }
+
+    block.push(flags | max as u8);
+    let mut value = value - max;
+    while value >= 0x80 {
+        block.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    block.push(value as u8);
+}
+
+/// Appends a literal field without indexing, named by a static table index
+fn put_literal(block: &mut Vec<u8>, name_index: usize, value: &str) {
+    put_integer(block, 0, 4, name_index);
+    put_integer(block, 0, 7, value.len());
+    block.extend_from_slice(value.as_bytes());
+}
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream: u32,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Payload of a HEADERS or DATA frame without its padding and priority
+    fn content(&self) -> FetchResult<&[u8]> {
+        let mut payload = &self.payload[..];
+        let mut padding = 0;
+        if self.flags & PADDED != 0 {
+            padding = *payload
+                .first()
+                .ok_or_else(|| protocol_error("empty padded frame"))?
+                as usize;
+            payload = &payload[1..];
+        }
+        if self.kind == HEADERS && self.flags & PRIORITY != 0 {
+            payload = payload
+                .get(5..)
+                .ok_or_else(|| protocol_error("truncated priority"))?;
+        }
+        if padding > payload.len() {
+            return Err(protocol_error("padding longer than the frame"));
+        }
+        Ok(&payload[..payload.len() - padding])
+    }
+}
+
+fn protocol_error(message: &str) -> FetchError {
+    FetchError::InvalidResponse(format!("HTTP/2 protocol error: {}", message))
+}
+
+/// Reads a frame, `None` at the end of the stream
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Frame>> {
+    let mut header = [0; 9];
+    let first = loop {
+        match reader.read(&mut header[..1]) {
+            Ok(len) => break len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    };
+    if first == 0 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut header[1..])?;
+
+    let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "HTTP/2 frame larger than the maximum frame size",
+        ));
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+
+    Ok(Some(Frame {
+        kind: header[3],
+        flags: header[4],
+        stream: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff,
+        payload,
+    }))
+}
+
+fn write_frame<W: Write>(
+    writer: &mut W,
+    kind: u8,
+    flags: u8,
+    stream: u32,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(9 + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+    frame.push(kind);
+    frame.push(flags);
+    frame.extend_from_slice(&stream.to_be_bytes());
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)
+}
+
+/// Client side of an HTTP/2 connection
+#[derive(Debug)]
+pub(crate) struct Session {
+    next_stream: u32,
+    decoder: Decoder,
+    /// Whether the server still accepts new streams
+    open: bool,
+}
+
+impl Session {
+    /// Starts a session on a connection which negotiated `h2`, disabling
+    /// server push
+    pub(crate) fn start<W: Write>(writer: &mut W) -> FetchResult<Session> {
+        writer.write_all(PREFACE)?;
+        // SETTINGS_ENABLE_PUSH = 0
+        write_frame(writer, SETTINGS, 0, 0, &[0, 2, 0, 0, 0, 0])?;
+        Ok(Session {
+            next_stream: 1,
+            decoder: Decoder::new(),
+            open: true,
+        })
+    }
+
+    /// Whether another request may be sent on the connection
+    pub(crate) fn is_reusable(&self) -> bool {
+        self.open
+    }
+
+    /// Sends a GET request on a new stream and reads its response, `None` if
+    /// the connection was closed or the server refused the stream before
+    /// any of the response arrived
+    pub(crate) fn get<S: Read + Write>(
+        &mut self,
+        stream: &mut S,
+        url: &Url,
+        user_agent: &str,
+        max_body_size: usize,
+    ) -> FetchResult<Option<HttpResponse>> {
+        let id = self.next_stream;
+        if id > 0x7fff_ffff {
+            self.open = false;
+            return Ok(None);
+        }
+        self.next_stream += 2;
+
+        let result = self.exchange(stream, id, url, user_agent, max_body_size);
+        if !matches!(result, Ok(Some(_))) {
+            self.open = false;
+        }
+        result
+    }
+
+    fn exchange<S: Read + Write>(
+        &mut self,
+        stream: &mut S,
+        id: u32,
+        url: &Url,
+        user_agent: &str,
+        max_body_size: usize,
+    ) -> FetchResult<Option<HttpResponse>> {
+        match send_request(stream, id, url, user_agent) {
+            Ok(()) => {}
+            Err(e) if closed_before_response(&e) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut response: Option<HttpResponse> = None;
+        let mut block: Option<(Vec<u8>, bool)> = None;
+        let mut started = false;
+        loop {
+            let frame = match read_frame(stream) {
+                Ok(Some(frame)) => frame,
+                Ok(None) if !started => return Ok(None),
+                Err(e) if !started && closed_before_response(&e) => return Ok(None),
+                Ok(None) => {
+                    return Err(FetchError::InvalidResponse(
+                        "truncated response".to_string(),
+                    ))
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if block.is_some() && (frame.kind != CONTINUATION || frame.stream != id) {
+                return Err(protocol_error("interrupted header block"));
+            }
+
+            match frame.kind {
+                SETTINGS if frame.flags & ACK == 0 => {
+                    if frame.payload.len() % 6 != 0 {
+                        return Err(protocol_error("malformed settings"));
+                    }
+                    write_frame(stream, SETTINGS, ACK, 0, &[])?;
+                }
+                PING if frame.flags & ACK == 0 => {
+                    write_frame(stream, PING, ACK, 0, &frame.payload)?;
+                }
+                GOAWAY => {
+                    self.open = false;
+                    let last = frame
+                        .payload
+                        .get(..4)
+                        .ok_or_else(|| protocol_error("malformed goaway"))?;
+                    let last = u32::from_be_bytes([last[0], last[1], last[2], last[3]]);
+                    if last & 0x7fff_ffff < id {
+                        return Ok(None);
+                    }
+                }
+                PUSH_PROMISE => return Err(protocol_error("push promise with push disabled")),
+                RST_STREAM if frame.stream == id => {
+                    let code = frame
+                        .payload
+                        .get(..4)
+                        .ok_or_else(|| protocol_error("malformed rst_stream"))?;
+                    let code = u32::from_be_bytes([code[0], code[1], code[2], code[3]]);
+                    if code == REFUSED_STREAM && !started {
+                        return Ok(None);
+                    }
+                    return Err(FetchError::InvalidResponse(format!(
+                        "HTTP/2 stream reset with error code {}",
+                        code
+                    )));
+                }
+                HEADERS if frame.stream == id => {
+                    started = true;
+                    let end_stream = frame.flags & END_STREAM != 0;
+                    block = Some((frame.content()?.to_vec(), end_stream));
+                }
+                CONTINUATION => match &mut block {
+                    Some((fragments, _)) => fragments.extend_from_slice(&frame.payload),
+                    None => return Err(protocol_error("continuation without headers")),
+                },
+                DATA => {
+                    // Flow control windows are restored as soon as data
+                    // arrives, the body being bounded by its size limit
+                    if !frame.payload.is_empty() {
+                        let increment = (frame.payload.len() as u32).to_be_bytes();
+                        write_frame(stream, WINDOW_UPDATE, 0, 0, &increment)?;
+                        if frame.stream == id && frame.flags & END_STREAM == 0 {
+                            write_frame(stream, WINDOW_UPDATE, 0, id, &increment)?;
+                        }
+                    }
+                    if frame.stream != id {
+                        continue;
+                    }
+
+                    let body = match &mut response {
+                        Some(response) => &mut response.body,
+                        None => return Err(protocol_error("data before headers")),
+                    };
+                    let content = frame.content()?;
+                    if body.len() + content.len() > max_body_size {
+                        return Err(FetchError::OversizeBody);
+                    }
+                    body.extend_from_slice(content);
+                    if frame.flags & END_STREAM != 0 {
+                        return Ok(response);
+                    }
+                }
+                _ => {}
+            }
+
+            if let Some((fragments, _)) = &block {
+                if fragments.len() > MAX_HEADER_BLOCK_SIZE {
+                    return Err(protocol_error("header block too large"));
+                }
+            }
+            if frame.flags & END_HEADERS == 0 || !matches!(frame.kind, HEADERS | CONTINUATION) {
+                continue;
+            }
+            let (fragments, end_stream) = match block.take() {
+                Some(block) => block,
+                None => continue,
+            };
+            let fields = self
+                .decoder
+                .decode(&fragments)
+                .ok_or_else(|| protocol_error("malformed header block"))?;
+
+            if response.is_none() {
+                let status = fields
+                    .iter()
+                    .find(|(name, _)| name == ":status")
+                    .and_then(|(_, status)| status.parse::<u16>().ok())
+                    .ok_or_else(|| protocol_error("missing status"))?;
+                // Informational responses precede the final one
+                if (100..200).contains(&status) {
+                    continue;
+                }
+                response = Some(HttpResponse {
+                    status,
+                    headers: fields
+                        .into_iter()
+                        .filter(|(name, _)| !name.starts_with(':'))
+                        .collect(),
+                    body: vec![],
+                });
+            }
+            // Trailers after the body are ignored
+            if end_stream {
+                return Ok(response);
+            }
+        }
+    }
+}
+
+/// Sends the headers of a GET request, split into CONTINUATION frames if
+/// they exceed the frame size
+fn send_request<W: Write>(writer: &mut W, id: u32, url: &Url, user_agent: &str) -> io::Result<()> {
+    let authority = if url.port == 443 {
+        url.host.clone()
+    } else {
+        format!("{}:{}", url.host, url.port)
+    };
+    // :method GET and :scheme https from the static table, then :path,
+    // :authority, user-agent and accept named by it
+    let mut block = vec![0x82, 0x87];
+    put_literal(&mut block, 4, &url.path);
+    put_literal(&mut block, 1, &authority);
+    put_literal(&mut block, 58, user_agent);
+    put_literal(&mut block, 19, "text/plain, */*");
+
+    let chunks: Vec<&[u8]> = block.chunks(MAX_FRAME_SIZE).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let (kind, mut flags) = if i == 0 {
+            (HEADERS, END_STREAM)
+        } else {
+            (CONTINUATION, 0)
+        };
+        if i == chunks.len() - 1 {
+            flags |= END_HEADERS;
+        }
+        write_frame(writer, kind, flags, id, chunk)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn fields(fields: &[(&str, &str)]) -> Vec<(String, String)> {
+        fields
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn frame(kind: u8, flags: u8, stream: u32, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![];
+        write_frame(&mut frame, kind, flags, stream, payload).unwrap();
+        frame
+    }
+
+    /// Connection reading scripted server frames and recording client ones
+    struct Scripted {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Scripted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Scripted {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn decoding_huffman_strings() {
+        // RFC 7541, C.4.1
+        assert_eq!(
+            huffman_decode(&hex("f1e3c2e5f23a6ba0ab90f4ff")).unwrap(),
+            b"www.example.com"
+        );
+        assert_eq!(huffman_decode(&[]).unwrap(), b"");
+        // Padding of zero bits, then more than 7 bits of padding
+        assert_eq!(huffman_decode(&hex("f1e3c2e5f23a6ba0ab90f400")), None);
+        assert_eq!(huffman_decode(&hex("f1e3c2e5f23a6ba0ab90f4ffff")), None);
+    }
+
+    #[test]
+    fn decoding_header_blocks() {
+        // RFC 7541, C.6.1 and C.6.2, shrinking the dynamic table to 256 bytes
+        let mut decoder = Decoder::new();
+        let first = "3fe101488264025885aec3771a4b6196d07abe941054d444a8200595040b8166e082a62d1bff6e919d29ad171863c78f0b97c8e9ae82ae43d3";
+        let expected = |status| {
+            fields(&[
+                (":status", status),
+                ("cache-control", "private"),
+                ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                ("location", "https://www.example.com"),
+            ])
+        };
+        assert_eq!(decoder.decode(&hex(first)).unwrap(), expected("302"));
+        assert_eq!(decoder.size, 222);
+        assert_eq!(
+            decoder.decode(&hex("4883640effc1c0bf")).unwrap(),
+            expected("307")
+        );
+        // Adding 307 evicted 302
+        assert_eq!(decoder.table.len(), 4);
+        assert_eq!(decoder.size, 222);
+
+        assert_eq!(decoder.decode(&[0xff]), None);
+        assert_eq!(decoder.decode(&[0x80]), None);
+
+        let mut block = vec![];
+        put_literal(&mut block, 4, &format!("/{}", "a".repeat(200)));
+        assert_eq!(
+            Decoder::new().decode(&block).unwrap(),
+            fields(&[(":path", &format!("/{}", "a".repeat(200)))])
+        );
+    }
+
+    #[test]
+    fn exchanging_frames() {
+        let url = Url::parse("https://example.com/ads.txt").unwrap();
+        let mut input = frame(SETTINGS, 0, 0, &[0, 3, 0, 0, 0, 100]);
+        input.extend(frame(PING, 0, 0, b"12345678"));
+        // 200 with content-type and x-ads indexed into the dynamic table
+        let headers = hex("885f92497ca58ae819aafb50938ec415305a99567b4084f2b0e447823f5f");
+        input.extend(frame(HEADERS, END_HEADERS, 1, &headers));
+        input.extend(frame(DATA, 0, 1, b"exchange.com, "));
+        input.extend(frame(DATA, END_STREAM, 1, b"1, DIRECT\n"));
+        // The same response referring to the dynamic table, split into a
+        // continuation, with a padded body
+        input.extend(frame(HEADERS, 0, 3, &[0x88]));
+        input.extend(frame(CONTINUATION, END_HEADERS, 3, &[0xbf, 0xbe]));
+        input.extend(frame(DATA, PADDED | END_STREAM, 3, b"\x02ok\0\0"));
+        input.extend(frame(GOAWAY, 0, 0, &[0, 0, 0, 3, 0, 0, 0, 0]));
+        let mut connection = Scripted {
+            input: io::Cursor::new(input),
+            output: vec![],
+        };
+
+        let mut session = Session::start(&mut connection).unwrap();
+        let response = session
+            .get(&mut connection, &url, "bot", 1024)
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.header("content-type"),
+            Some("text/plain; charset=utf-8")
+        );
+        assert_eq!(response.body, b"exchange.com, 1, DIRECT\n");
+
+        let response = session
+            .get(&mut connection, &url, "bot", 1024)
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.header("x-ads"), Some("ok"));
+        assert_eq!(response.body, b"ok");
+        assert!(session.is_reusable());
+
+        // Refused by GOAWAY, so retried on another connection
+        assert!(session
+            .get(&mut connection, &url, "bot", 1024)
+            .unwrap()
+            .is_none());
+        assert!(!session.is_reusable());
+
+        let mut output = &connection.output[PREFACE.len()..];
+        let mut frames = vec![];
+        while let Some(frame) = read_frame(&mut output).unwrap() {
+            frames.push(frame);
+        }
+        let kinds: Vec<(u8, u8, u32)> =
+            frames.iter().map(|f| (f.kind, f.flags, f.stream)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (SETTINGS, 0, 0),
+                (HEADERS, END_STREAM | END_HEADERS, 1),
+                (SETTINGS, ACK, 0),
+                (PING, ACK, 0),
+                (WINDOW_UPDATE, 0, 0),
+                (WINDOW_UPDATE, 0, 1),
+                (WINDOW_UPDATE, 0, 0),
+                (HEADERS, END_STREAM | END_HEADERS, 3),
+                (WINDOW_UPDATE, 0, 0),
+                (HEADERS, END_STREAM | END_HEADERS, 5),
+            ]
+        );
+        assert_eq!(
+            Decoder::new().decode(&frames[1].payload).unwrap(),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/ads.txt"),
+                (":authority", "example.com"),
+                ("user-agent", "bot"),
+                ("accept", "text/plain, */*"),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejecting_bad_responses() {
+        let url = Url::parse("https://example.com:8443/ads.txt").unwrap();
+        let get = |input: Vec<u8>, max_body_size| {
+            let mut connection = Scripted {
+                input: io::Cursor::new(input),
+                output: vec![],
+            };
+            let mut session = Session::start(&mut connection).unwrap();
+            let response = session.get(&mut connection, &url, "bot", max_body_size);
+            (response, session.is_reusable())
+        };
+
+        let mut input = frame(HEADERS, END_HEADERS, 1, &[0x88]);
+        input.extend(frame(DATA, END_STREAM, 1, b"too long"));
+        assert_eq!(get(input, 4), (Err(FetchError::OversizeBody), false));
+
+        // Closed before the response, and partway through it
+        assert_eq!(get(vec![], 4), (Ok(None), false));
+        let input = frame(HEADERS, END_HEADERS, 1, &[0x88]);
+        assert!(matches!(
+            get(input, 4),
+            (Err(FetchError::InvalidResponse(_)), false)
+        ));
+
+        let input = frame(RST_STREAM, 0, 1, &REFUSED_STREAM.to_be_bytes());
+        assert_eq!(get(input, 4), (Ok(None), false));
+        let input = frame(RST_STREAM, 0, 1, &[0, 0, 0, 2]);
+        assert!(matches!(
+            get(input, 4).0,
+            Err(FetchError::InvalidResponse(_))
+        ));
+        let input = frame(DATA, END_STREAM, 1, b"ok");
+        assert!(matches!(
+            get(input, 4).0,
+            Err(FetchError::InvalidResponse(_))
+        ));
+    }
+}
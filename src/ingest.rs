@@ -0,0 +1,347 @@
+//! Streaming ingestion of raw ads.txt files into a corpus
+//!
+//! Queue consumers (e.g. for crawl results published to Kafka or SQS) push
+//! items into an `Ingest` sink one at a time, or hand over an iterator such as
+//! a channel receiver to `ingest_all`. Crawl dumps of public datasets, one
+//! file per line, are read by `TsvDump` and ingested by `import_tsv_dump`.
+
+use crate::corpus::{AdsTxtCorpus, CorpusEntry, CorpusMetadata, ShardedCorpus};
+use crate::json::JsonValue;
+use crate::{ads_txt_error, AdsTxt, AdsTxtError, Result};
+use std::io::BufRead;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IngestItem {
+    pub domain: String,
+    pub content: String,
+    pub metadata: CorpusMetadata,
+}
+
+impl IngestItem {
+    pub fn new(domain: &str, content: &str, metadata: CorpusMetadata) -> Self {
+        IngestItem {
+            domain: domain.to_string(),
+            content: content.to_string(),
+            metadata,
+        }
+    }
+}
+
+pub trait Ingest {
+    /// Adds a publisher's raw ads.txt content, replacing any earlier file,
+    /// and returns the errors of lines which could not be parsed
+    fn ingest(
+        &mut self,
+        domain: &str,
+        content: &str,
+        metadata: CorpusMetadata,
+    ) -> Result<Vec<AdsTxtError>>;
+
+    /// Ingests items until the source runs dry, returning the parse errors
+    /// of every domain which had any
+    fn ingest_all<I>(&mut self, items: I) -> Result<Vec<(String, Vec<AdsTxtError>)>>
+    where
+        I: IntoIterator<Item = IngestItem>,
+        Self: Sized,
+    {
+        let mut errors = vec![];
+        for item in items {
+            let item_errors = self.ingest(&item.domain, &item.content, item.metadata)?;
+            if !item_errors.is_empty() {
+                errors.push((item.domain, item_errors));
+            }
+        }
+        Ok(errors)
+    }
+}
+
+impl<S: Ingest + ?Sized> Ingest for &mut S {
+    fn ingest(
+        &mut self,
+        domain: &str,
+        content: &str,
+        metadata: CorpusMetadata,
+    ) -> Result<Vec<AdsTxtError>> {
+        (**self).ingest(domain, content, metadata)
+    }
+}
+
+impl Ingest for AdsTxtCorpus {
+    fn ingest(
+        &mut self,
+        domain: &str,
+        content: &str,
+        metadata: CorpusMetadata,
+    ) -> Result<Vec<AdsTxtError>> {
+        let (ads_txt, errors) = AdsTxt::parse_lenient(content);
+        self.insert(CorpusEntry::new(domain, ads_txt, metadata));
+        Ok(errors)
+    }
+}
+
+impl Ingest for ShardedCorpus {
+    fn ingest(
+        &mut self,
+        domain: &str,
+        content: &str,
+        metadata: CorpusMetadata,
+    ) -> Result<Vec<AdsTxtError>> {
+        let (ads_txt, errors) = AdsTxt::parse_lenient(content);
+        self.insert(CorpusEntry::new(domain, ads_txt, metadata));
+        Ok(errors)
+    }
+}
+
+/// Streaming reader of crawl dumps with one file per line: the domain, a
+/// tab and the file's content, optionally followed by a tab and the fetch
+/// time in Unix seconds. The content is either a JSON string or has its line
+/// breaks, tabs and backslashes escaped as `\n`, `\r`, `\t` and `\\`. A
+/// header line starting with `domain` is skipped.
+pub struct TsvDump<R> {
+    reader: R,
+    fetched_at: SystemTime,
+    line_number: usize,
+}
+
+impl<R: BufRead> TsvDump<R> {
+    /// Reads a dump, taking `fetched_at` as the fetch time of files without
+    /// one
+    pub fn new(reader: R, fetched_at: SystemTime) -> Self {
+        TsvDump {
+            reader,
+            fetched_at,
+            line_number: 0,
+        }
+    }
+
+    /// Number of the last line read
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn parse_line(&self, line: &str) -> Result<IngestItem> {
+        let mut fields = line.splitn(3, '\t');
+        let domain = fields.next().unwrap_or_default().trim();
+        let content = match fields.next() {
+            Some(content) if !domain.is_empty() => content,
+            _ => return ads_txt_error("Invalid dump line without domain and content"),
+        };
+        let content = if content.starts_with('"') {
+            match JsonValue::parse(content)?.as_str() {
+                Some(content) => content.to_string(),
+                None => return ads_txt_error("Invalid dump content"),
+            }
+        } else {
+            unescape(content)
+        };
+        let fetched_at = match fields.next().map(str::trim) {
+            None | Some("") => self.fetched_at,
+            Some(secs) => match secs.parse::<u64>() {
+                Ok(secs) => SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                Err(_) => return ads_txt_error(&format!("Invalid fetch time: {}", secs)),
+            },
+        };
+
+        let metadata = CorpusMetadata::for_content(&content, fetched_at);
+        Ok(IngestItem {
+            domain: domain.to_string(),
+            content,
+            metadata,
+        })
+    }
+
+    /// Reads the next file, failing only on IO errors
+    fn read_item(&mut self) -> std::io::Result<Option<Result<IngestItem>>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+
+            let line = line.trim_end_matches(['\n', '\r']);
+            let header = self.line_number == 1
+                && line
+                    .split('\t')
+                    .next()
+                    .is_some_and(|f| f.trim().eq_ignore_ascii_case("domain"));
+            if line.trim().is_empty() || header {
+                continue;
+            }
+
+            return Ok(Some(self.parse_line(line).map_err(|e| {
+                AdsTxtError::new(&format!("line {}: {}", self.line_number, e))
+            })));
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for TsvDump<R> {
+    /// Files of the dump, or the errors of lines which could not be read
+    type Item = Result<IngestItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_item().unwrap_or_else(|e| Some(Err(e.into())))
+    }
+}
+
+/// Decodes the escapes of a dump's content, keeping unknown ones as they are
+fn unescape(content: &str) -> String {
+    let mut decoded = String::with_capacity(content.len());
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => decoded.push('\n'),
+            Some('r') => decoded.push('\r'),
+            Some('t') => decoded.push('\t'),
+            Some('\\') => decoded.push('\\'),
+            Some(other) => {
+                decoded.push('\\');
+                decoded.push(other);
+            }
+            None => decoded.push('\\'),
+        }
+    }
+    decoded
+}
+
+/// Outcome of importing a crawl dump
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DumpImport {
+    /// Number of files ingested
+    pub files: usize,
+    /// Lines of the dump which could not be read, which are skipped
+    pub invalid_lines: Vec<AdsTxtError>,
+    /// Parse errors of every domain which had any
+    pub parse_errors: Vec<(String, Vec<AdsTxtError>)>,
+}
+
+/// Ingests every file of a TSV crawl dump, see `TsvDump`, skipping the lines
+/// which cannot be read. Fails on IO errors and errors of the sink.
+pub fn import_tsv_dump<R, S>(reader: R, mut sink: S, fetched_at: SystemTime) -> Result<DumpImport>
+where
+    R: BufRead,
+    S: Ingest,
+{
+    let mut import = DumpImport::default();
+    let mut dump = TsvDump::new(reader, fetched_at);
+    while let Some(item) = dump.read_item()? {
+        let item = match item {
+            Ok(item) => item,
+            Err(e) => {
+                import.invalid_lines.push(e);
+                continue;
+            }
+        };
+
+        let errors = sink.ingest(&item.domain, &item.content, item.metadata)?;
+        import.files += 1;
+        if !errors.is_empty() {
+            import.parse_errors.push((item.domain, errors));
+        }
+    }
+
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus::content_hash;
+    use std::sync::mpsc;
+    use std::time::SystemTime;
+
+    fn item(domain: &str, content: &str) -> IngestItem {
+        let metadata = CorpusMetadata::for_content(content, SystemTime::UNIX_EPOCH);
+        IngestItem::new(domain, content, metadata)
+    }
+
+    #[test]
+    fn ingesting_from_channel() {
+        let (sender, receiver) = mpsc::channel();
+        let producer = std::thread::spawn(move || {
+            sender
+                .send(item("a.com", "exchange.com, 1, DIRECT"))
+                .unwrap();
+            sender
+                .send(item("b.com", "exchange.com, 2, DIRECT\nbad"))
+                .unwrap();
+            sender
+                .send(item("a.com", "exchange.com, 3, DIRECT"))
+                .unwrap();
+        });
+
+        let mut corpus = AdsTxtCorpus::new();
+        let errors = corpus.ingest_all(receiver).unwrap();
+        producer.join().unwrap();
+
+        assert_eq!(
+            errors,
+            vec![(
+                "b.com".to_string(),
+                vec![AdsTxtError::new("Invalid ads.txt line: bad")]
+            )]
+        );
+        assert_eq!(corpus.len(), 2);
+        assert_eq!(
+            corpus.get("a.com").unwrap().metadata.hash,
+            content_hash("exchange.com, 3, DIRECT")
+        );
+        assert!(corpus.seat_listings("exchange.com", "1").is_empty());
+
+        let mut sharded = ShardedCorpus::new(2);
+        sharded
+            .ingest_all(vec![item("a.com", "x.com, 1, RESELLER")])
+            .unwrap();
+        assert_eq!(sharded.publishers("x.com"), vec!["a.com"]);
+    }
+
+    #[test]
+    fn importing_tsv_dumps() {
+        let dump = "domain\tcontent\tfetched_at\r\n\
+            a.com\texchange.com, 1, DIRECT\\nexchange.com, 2, RESELLER\\r\\n# tab\\there\t1700000000\r\n\
+            \n\
+            b.com\t\"exchange.com, 3, DIRECT\\nbad\"\n\
+            no-content\n\
+            c.com\texchange.com, 4, DIRECT\tyesterday\n\
+            d.com\tC:\\\\path\\x\n";
+
+        let items: Vec<Result<IngestItem>> =
+            TsvDump::new(dump.as_bytes(), SystemTime::UNIX_EPOCH).collect();
+        assert_eq!(items.len(), 5);
+        let a = items[0].as_ref().unwrap();
+        assert_eq!(
+            a.content,
+            "exchange.com, 1, DIRECT\nexchange.com, 2, RESELLER\r\n# tab\there"
+        );
+        assert_eq!(
+            a.metadata.fetched_at,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+        assert_eq!(
+            items[1].as_ref().unwrap().content,
+            "exchange.com, 3, DIRECT\nbad"
+        );
+        assert_eq!(items[4].as_ref().unwrap().content, "C:\\path\\x");
+
+        let mut corpus = AdsTxtCorpus::new();
+        let import = import_tsv_dump(dump.as_bytes(), &mut corpus, SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(import.files, 3);
+        assert_eq!(
+            import.invalid_lines,
+            vec![
+                AdsTxtError::new("line 5: Invalid dump line without domain and content"),
+                AdsTxtError::new("line 6: Invalid fetch time: yesterday"),
+            ]
+        );
+        assert_eq!(import.parse_errors.len(), 2);
+        assert_eq!(corpus.len(), 3);
+        assert_eq!(corpus.publishers("exchange.com").len(), 2);
+    }
+}
@@ -0,0 +1,217 @@
+//! String type storing short strings inline, without a heap allocation
+
+use alloc::string::String;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt::Formatter;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+
+/// Longest string stored inline, which keeps `InlineString` at 32 bytes
+pub const INLINE_CAPACITY: usize = 30;
+
+/// Immutable string stored inline up to `INLINE_CAPACITY` bytes, and on the
+/// heap beyond. Most ad system domains, publisher ids and cert authority ids
+/// fit inline.
+#[derive(Clone)]
+pub struct InlineString(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    /// Invariant: `bytes[..len]` is valid UTF-8. Only `InlineString::new`
+    /// builds this variant, copying the bytes of a `str` no longer than
+    /// `INLINE_CAPACITY`, and the bytes are never mutated afterwards.
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_CAPACITY],
+    },
+    Heap(String),
+}
+
+impl InlineString {
+    pub fn new(text: &str) -> Self {
+        if text.len() <= INLINE_CAPACITY {
+            let mut bytes = [0; INLINE_CAPACITY];
+            bytes[..text.len()].copy_from_slice(text.as_bytes());
+            InlineString(Repr::Inline {
+                len: text.len() as u8,
+                bytes,
+            })
+        } else {
+            InlineString(Repr::Heap(String::from(text)))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // SAFETY: inline bytes are valid UTF-8 by the invariant of
+            // `Repr::Inline`, so they are not checked again on every read
+            Repr::Inline { len, bytes } => unsafe {
+                core::str::from_utf8_unchecked(&bytes[..*len as usize])
+            },
+            Repr::Heap(text) => text,
+        }
+    }
+
+    /// Whether the string is stored without a heap allocation
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Inline { .. })
+    }
+}
+
+impl Default for InlineString {
+    fn default() -> Self {
+        InlineString::new("")
+    }
+}
+
+impl Deref for InlineString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for InlineString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for InlineString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for InlineString {
+    fn from(text: &str) -> Self {
+        InlineString::new(text)
+    }
+}
+
+impl From<String> for InlineString {
+    fn from(text: String) -> Self {
+        if text.len() <= INLINE_CAPACITY {
+            InlineString::new(&text)
+        } else {
+            InlineString(Repr::Heap(text))
+        }
+    }
+}
+
+impl From<InlineString> for String {
+    fn from(text: InlineString) -> Self {
+        match text.0 {
+            Repr::Heap(text) => text,
+            Repr::Inline { .. } => String::from(text.as_str()),
+        }
+    }
+}
+
+impl core::fmt::Debug for InlineString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl core::fmt::Display for InlineString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for InlineString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for InlineString {}
+
+impl PartialEq<str> for InlineString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for InlineString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for InlineString {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<InlineString> for str {
+    fn eq(&self, other: &InlineString) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<InlineString> for &str {
+    fn eq(&self, other: &InlineString) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<InlineString> for String {
+    fn eq(&self, other: &InlineString) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialOrd for InlineString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InlineString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for InlineString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn storing_strings_inline() {
+        let short = InlineString::new("greenadexchange.com");
+        assert!(short.is_inline());
+        assert_eq!(short, "greenadexchange.com");
+        assert_eq!(short.len(), 19);
+        assert_eq!(short.to_uppercase(), "GREENADEXCHANGE.COM");
+
+        let long = InlineString::from("a-very-long-ad-system-domain.example.com".to_string());
+        assert!(!long.is_inline());
+        assert_eq!(String::from(long.clone()), long.as_str());
+
+        let unicode = InlineString::new("äöü.example");
+        assert!(unicode.is_inline());
+        assert_eq!(
+            format!("{}/{:?}", unicode, unicode),
+            "äöü.example/\"äöü.example\""
+        );
+        let boundary = InlineString::new("abcdefghijklmnopqrstuvwxyz12\u{e9}");
+        assert!(boundary.is_inline());
+        assert_eq!(boundary.chars().last(), Some('\u{e9}'));
+        assert!(!InlineString::new("abcdefghijklmnopqrstuvwxyz123\u{e9}").is_inline());
+        assert!(InlineString::new("a") < InlineString::new("b"));
+        assert_eq!(core::mem::size_of::<InlineString>(), 32);
+    }
+}
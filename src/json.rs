@@ -0,0 +1,372 @@
+//! Minimal JSON reader and writer used for sellers.json and JSON exports
+
+use crate::{ads_txt_error, Result};
+use std::fmt::Formatter;
+
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    /// Numbers keep their source text so large integer ids are not rounded
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Object members in document order
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub fn parse(text: &str) -> Result<JsonValue> {
+        let mut parser = Parser::new(text);
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+
+        if parser.pos < parser.bytes.len() {
+            return parser.error("Trailing characters after JSON value");
+        }
+        Ok(value)
+    }
+
+    pub fn number<N: ToString>(n: N) -> JsonValue {
+        JsonValue::Number(n.to_string())
+    }
+
+    pub fn string(s: &str) -> JsonValue {
+        JsonValue::String(s.to_string())
+    }
+
+    /// Returns the value of the given object member
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, JsonValue)]> {
+        match self {
+            JsonValue::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == JsonValue::Null
+    }
+}
+
+/// Writes a string as a quoted JSON string literal
+pub(crate) fn write_string(f: &mut dyn std::fmt::Write, s: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl std::fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            JsonValue::Number(n) => write!(f, "{}", n),
+            JsonValue::String(s) => write_string(f, s),
+            JsonValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser {
+            text,
+            bytes: text.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        ads_txt_error(&format!("{} at offset {}", message, self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<()> {
+        if self.text[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            self.error("Invalid JSON literal")
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue> {
+        if depth > MAX_DEPTH {
+            return self.error("JSON nested too deeply");
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.expect("null").map(|_| JsonValue::Null),
+            Some(b't') => self.expect("true").map(|_| JsonValue::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| JsonValue::Bool(false)),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b'[') => self.array(depth),
+            Some(b'{') => self.object(depth),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => self.error("Unexpected character in JSON"),
+            None => self.error("Unexpected end of JSON"),
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<JsonValue> {
+        self.pos += 1;
+        let mut items = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+
+        loop {
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return self.error("Expected ',' or ']' in JSON array"),
+            }
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<JsonValue> {
+        self.pos += 1;
+        let mut members = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return self.error("Expected string key in JSON object");
+            }
+            let key = self.string()?;
+
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return self.error("Expected ':' in JSON object");
+            }
+            self.pos += 1;
+
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return self.error("Expected ',' or '}' in JSON object"),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+
+        let text = &self.text[start..self.pos];
+        if text.parse::<f64>().is_err() {
+            return self.error("Invalid JSON number");
+        }
+        Ok(JsonValue::Number(text.to_string()))
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let hex = self.text.get(self.pos..self.pos + 4);
+        match hex.and_then(|h| u32::from_str_radix(h, 16).ok()) {
+            Some(code) => {
+                self.pos += 4;
+                Ok(code)
+            }
+            None => self.error("Invalid JSON unicode escape"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut s = String::new();
+
+        loop {
+            let start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            s.push_str(&self.text[start..self.pos]);
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escape = self.peek();
+                    self.pos += 1;
+                    match escape {
+                        Some(b'"') => s.push('"'),
+                        Some(b'\\') => s.push('\\'),
+                        Some(b'/') => s.push('/'),
+                        Some(b'b') => s.push('\u{8}'),
+                        Some(b'f') => s.push('\u{c}'),
+                        Some(b'n') => s.push('\n'),
+                        Some(b'r') => s.push('\r'),
+                        Some(b't') => s.push('\t'),
+                        Some(b'u') => {
+                            let mut code = self.hex4()?;
+                            // Combine UTF-16 surrogate pairs
+                            if (0xD800..0xDC00).contains(&code)
+                                && self.text[self.pos..].starts_with("\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xD800) << 10)
+                                    + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            s.push(std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        _ => return self.error("Invalid JSON escape"),
+                    }
+                }
+                Some(_) => return self.error("Control character in JSON string"),
+                None => return self.error("Unterminated JSON string"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_json() {
+        assert_eq!(JsonValue::parse(" null "), Ok(JsonValue::Null));
+        assert_eq!(JsonValue::parse("-1.5e3").unwrap().as_f64(), Some(-1500.0));
+        assert_eq!(
+            JsonValue::parse(r#""a\"bé😀""#),
+            Ok(JsonValue::string("a\"bé😀"))
+        );
+
+        let value = JsonValue::parse(r#"{"a": [1, true, {"b": "c"}], "d": {}}"#).unwrap();
+        assert_eq!(
+            value.get("a").and_then(|a| a.as_array()).map(|a| a.len()),
+            Some(3)
+        );
+        assert_eq!(value.get("d"), Some(&JsonValue::Object(vec![])));
+        assert_eq!(value.get("e"), None);
+
+        assert!(JsonValue::parse("{\"a\": 1,}").is_err());
+        assert!(JsonValue::parse("[1] x").is_err());
+        assert!(JsonValue::parse(&"[".repeat(1000)).is_err());
+    }
+
+    #[test]
+    fn writing_json() {
+        let text = r#"{"a":[1,true,null,{"b":"c\n\"d\""}],"e":12345678901234567890}"#;
+        assert_eq!(JsonValue::parse(text).unwrap().to_string(), text);
+    }
+}
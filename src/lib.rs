@@ -1,32 +1,207 @@
-use crate::AccountRelation::{Direct, Reseller};
-use std::fmt::Formatter;
+//! Parser for ads.txt files. Without the default `std` feature only the
+//! parser and data model are built, with `#![no_std]` and `alloc`.
 
-pub type Result<T> = ::std::result::Result<T, Box<AdsTxtError>>;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+extern crate alloc;
+
+use crate::AccountRelation::{Direct, Reseller};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Formatter;
+
+#[cfg(feature = "aliases")]
+pub mod aliases;
+#[cfg(feature = "std")]
+pub mod app_ads;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod arena;
+#[cfg(feature = "std")]
+pub mod bloom;
+#[cfg(feature = "std")]
+pub mod buyers_json;
+#[cfg(feature = "std")]
+pub mod charset;
+#[cfg(feature = "std")]
+pub mod checkpoint;
+#[cfg(feature = "std")]
+pub mod corpus;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod cross_validation;
+#[cfg(feature = "std")]
+pub mod deadline;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "disk-index")]
+pub mod disk_index;
+pub mod equality;
+#[cfg(feature = "std")]
+pub mod error_report;
+#[cfg(feature = "std")]
+pub mod fetch;
+#[cfg(feature = "std")]
+pub mod findings;
+#[cfg(feature = "std")]
+pub mod format;
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
+#[cfg(feature = "gen")]
+pub mod gen;
+#[cfg(feature = "std")]
+pub mod graph;
+#[cfg(feature = "std")]
+pub mod gzip;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod homograph;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+mod http2;
+#[cfg(feature = "std")]
+pub mod ingest;
+pub mod inline_string;
+#[cfg(feature = "std")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod merge;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod normalize;
+#[cfg(feature = "std")]
+pub mod ownership;
+#[cfg(feature = "std")]
+pub mod parse_info;
+pub mod prelude;
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod provenance;
+pub mod publisher_id;
+#[cfg(feature = "std")]
+pub mod reverse_index;
+#[cfg(feature = "std")]
+pub mod robots;
+#[cfg(feature = "std")]
+pub mod schain;
+#[cfg(feature = "std")]
+pub mod sellers_json;
+#[cfg(feature = "std")]
+pub mod sellers_stream;
+#[cfg(feature = "std")]
+pub mod serialize;
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "table")]
+pub mod table;
+#[cfg(feature = "std")]
+pub mod toml;
+#[cfg(feature = "std")]
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod watch;
+
+/// Storage of the fields of `DataRecord`: `InlineString` with the
+/// `inline-strings` feature, saving an allocation per short field. It is not
+/// public, so that the feature does not change the types of the public API.
+#[cfg(feature = "inline-strings")]
+pub(crate) type FieldString = inline_string::InlineString;
+#[cfg(not(feature = "inline-strings"))]
+pub(crate) type FieldString = String;
+
+/// Result of parsing, with the unboxed `AdsTxtError` by default
+pub type Result<T, E = AdsTxtError> = ::core::result::Result<T, E>;
+
+#[derive(Debug, Clone)]
 pub struct AdsTxtError {
     message: String,
+    /// Underlying error, e.g. of the IO reading a file
+    #[cfg(feature = "std")]
+    source: Option<std::sync::Arc<dyn std::error::Error + Send + Sync>>,
 }
 
 impl AdsTxtError {
     pub fn new(message: &str) -> AdsTxtError {
         AdsTxtError {
             message: message.to_string(),
+            #[cfg(feature = "std")]
+            source: None,
+        }
+    }
+
+    /// Error with the given message, caused by `source`
+    #[cfg(feature = "std")]
+    pub fn with_source<E>(message: &str, source: E) -> AdsTxtError
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        AdsTxtError {
+            message: message.to_string(),
+            source: Some(std::sync::Arc::new(source)),
         }
     }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
-impl std::fmt::Display for AdsTxtError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+/// Errors are equal by message, regardless of their sources
+impl PartialEq for AdsTxtError {
+    fn eq(&self, other: &Self) -> bool {
+        self.message == other.message
+    }
+}
+
+impl Eq for AdsTxtError {}
+
+impl core::fmt::Display for AdsTxtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.message)
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for AdsTxtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for AdsTxtError {
+    fn from(e: std::io::Error) -> Self {
+        AdsTxtError::with_source(&format!("IO error: {}", e), e)
+    }
+}
+
 fn ads_txt_error<T>(message: &str) -> Result<T> {
-    Err(Box::new(AdsTxtError::new(message)))
+    Err(AdsTxtError::new(message))
+}
+
+/// Whether the text is a host name of at least two dot-separated labels,
+/// with an optional trailing dot
+pub fn is_valid_domain(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.');
+    domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum AccountRelation {
     Direct,
     Reseller,
@@ -34,66 +209,397 @@ pub enum AccountRelation {
 
 impl AccountRelation {
     fn parse(text: &str) -> Result<AccountRelation> {
-        let relation = text.trim().to_lowercase();
+        match Self::from_field(text) {
+            Some(relation) => Ok(relation),
+            None => ads_txt_error(&format!("Invalid account relation: {}", text)),
+        }
+    }
 
-        if &relation == "direct" {
-            Ok(Direct)
-        } else if &relation == "reseller" {
-            Ok(Reseller)
+    /// Reads the relation field of a record, case-insensitively
+    fn from_field(text: &str) -> Option<AccountRelation> {
+        let relation = text.trim();
+
+        if relation.eq_ignore_ascii_case("direct") {
+            Some(Direct)
+        } else if relation.eq_ignore_ascii_case("reseller") {
+            Some(Reseller)
         } else {
-            ads_txt_error(&format!("Invalid account relation: {}", text))
+            None
+        }
+    }
+
+    /// Spec spelling of the relation, as written in ads.txt files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direct => "DIRECT",
+            Reseller => "RESELLER",
+        }
+    }
+}
+
+impl core::str::FromStr for AccountRelation {
+    type Err = AdsTxtError;
+
+    fn from_str(s: &str) -> Result<AccountRelation> {
+        AccountRelation::parse(s)
+    }
+}
+
+/// Kind of file: ads.txt on websites, or app-ads.txt on the developer
+/// websites of mobile and CTV apps, which shares its format
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[non_exhaustive]
+pub enum FileKind {
+    #[default]
+    AdsTxt,
+    AppAdsTxt,
+}
+
+impl FileKind {
+    pub const ALL: &'static [FileKind] = &[FileKind::AdsTxt, FileKind::AppAdsTxt];
+
+    /// Name of the file, e.g. `app-ads.txt`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileKind::AdsTxt => "ads.txt",
+            FileKind::AppAdsTxt => "app-ads.txt",
         }
     }
+
+    /// Finds a kind by its `as_str` name
+    pub fn from_name(name: &str) -> Option<FileKind> {
+        FileKind::ALL
+            .iter()
+            .copied()
+            .find(|k| k.as_str() == name.trim())
+    }
+
+    /// Path of the file at the root of a host
+    pub fn path(&self) -> String {
+        format!("/{}", self.as_str())
+    }
+}
+
+/// Reason `DataRecord::validate_str` rejects a line, or `DataRecord::try_new`
+/// its fields
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum RecordIssue {
+    /// Line without three or four comma separated fields
+    FieldCount,
+    InvalidRelation,
+    /// Ad system which is not a valid domain name
+    InvalidDomain,
+    EmptyPublisherId,
+    /// Publisher id with whitespace, control characters or characters
+    /// separating fields or comments
+    InvalidPublisherId,
+    /// Cert authority id which is empty or not alphanumeric
+    InvalidCertAuthority,
+}
+
+impl RecordIssue {
+    pub const ALL: &'static [RecordIssue] = &[
+        RecordIssue::FieldCount,
+        RecordIssue::InvalidRelation,
+        RecordIssue::InvalidDomain,
+        RecordIssue::EmptyPublisherId,
+        RecordIssue::InvalidPublisherId,
+        RecordIssue::InvalidCertAuthority,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordIssue::FieldCount => "field-count",
+            RecordIssue::InvalidRelation => "invalid-relation",
+            RecordIssue::InvalidDomain => "invalid-domain",
+            RecordIssue::EmptyPublisherId => "empty-publisher-id",
+            RecordIssue::InvalidPublisherId => "invalid-publisher-id",
+            RecordIssue::InvalidCertAuthority => "invalid-cert-authority",
+        }
+    }
+
+    /// Finds an issue by its `as_str` name
+    pub fn from_name(name: &str) -> Option<RecordIssue> {
+        RecordIssue::ALL
+            .iter()
+            .copied()
+            .find(|i| i.as_str() == name.trim())
+    }
+}
+
+impl core::fmt::Display for RecordIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
+/// Record of an ads.txt file. Its fields are read through getters and
+/// written through `try_new` and the setters, which check them.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct DataRecord {
     /// Domain for which the ads configuration applies
-    pub domain: String,
+    domain: FieldString,
     /// Publisher id
-    pub publisher_id: String,
+    publisher_id: FieldString,
     /// Account relation
-    pub acc_relation: AccountRelation,
+    acc_relation: AccountRelation,
     /// Optional cert authority
-    pub cert_authority: Option<String>,
+    cert_authority: Option<FieldString>,
 }
 
 impl DataRecord {
+    /// Builds a record from trimmed fields without checking them, for
+    /// fields known to be valid. See `try_new`.
     pub fn new(
         domain: &str,
         publisher_id: &str,
         acc_relation: AccountRelation,
         cert_authority: Option<String>,
     ) -> Self {
+        // The conversion is the identity without the inline-strings feature
+        #[allow(clippy::useless_conversion)]
         Self {
-            domain: domain.trim().to_string(),
-            publisher_id: publisher_id.trim().to_string(),
+            domain: domain.trim().into(),
+            publisher_id: publisher_id.trim().into(),
             acc_relation,
-            cert_authority,
+            cert_authority: cert_authority.map(FieldString::from),
+        }
+    }
+
+    /// Builds a record, rejecting fields the parser or `validate_str` would
+    /// flag or which would not survive a round trip through the text format.
+    /// The domain is trimmed, lowercased and stripped of a trailing dot.
+    pub fn try_new(
+        domain: &str,
+        publisher_id: &str,
+        acc_relation: AccountRelation,
+        cert_authority: Option<&str>,
+    ) -> core::result::Result<Self, RecordIssue> {
+        Ok(Self::from_fields(
+            &Self::checked_domain(domain)?,
+            Self::checked_publisher_id(publisher_id)?,
+            acc_relation,
+            Self::checked_cert_authority(cert_authority)?,
+        ))
+    }
+
+    /// Ad system domain, lowercase if built by `try_new` or `set_domain`
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn publisher_id(&self) -> &str {
+        &self.publisher_id
+    }
+
+    pub fn acc_relation(&self) -> &AccountRelation {
+        &self.acc_relation
+    }
+
+    pub fn cert_authority(&self) -> Option<&str> {
+        self.cert_authority.as_deref()
+    }
+
+    /// Sets the ad system domain, normalized and checked like by `try_new`
+    pub fn set_domain(&mut self, domain: &str) -> core::result::Result<(), RecordIssue> {
+        self.domain = Self::checked_domain(domain)?.as_str().into();
+        Ok(())
+    }
+
+    /// Sets the publisher id, trimmed and checked like by `try_new`
+    pub fn set_publisher_id(
+        &mut self,
+        publisher_id: &str,
+    ) -> core::result::Result<(), RecordIssue> {
+        self.publisher_id = Self::checked_publisher_id(publisher_id)?.into();
+        Ok(())
+    }
+
+    pub fn set_acc_relation(&mut self, acc_relation: AccountRelation) {
+        self.acc_relation = acc_relation;
+    }
+
+    /// Sets or removes the cert authority id, trimmed and checked like by
+    /// `try_new`
+    pub fn set_cert_authority(
+        &mut self,
+        cert_authority: Option<&str>,
+    ) -> core::result::Result<(), RecordIssue> {
+        self.cert_authority = Self::checked_cert_authority(cert_authority)?.map(FieldString::from);
+        Ok(())
+    }
+
+    fn checked_domain(domain: &str) -> core::result::Result<String, RecordIssue> {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+        if is_valid_domain(&domain) {
+            Ok(domain)
+        } else {
+            Err(RecordIssue::InvalidDomain)
+        }
+    }
+
+    fn checked_publisher_id(publisher_id: &str) -> core::result::Result<&str, RecordIssue> {
+        let publisher_id = publisher_id.trim();
+        if publisher_id.is_empty() {
+            Err(RecordIssue::EmptyPublisherId)
+        } else if publisher_id
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, ',' | '#' | ';'))
+        {
+            Err(RecordIssue::InvalidPublisherId)
+        } else {
+            Ok(publisher_id)
+        }
+    }
+
+    fn checked_cert_authority(
+        cert_authority: Option<&str>,
+    ) -> core::result::Result<Option<&str>, RecordIssue> {
+        match cert_authority.map(str::trim) {
+            Some(c) if c.is_empty() || !c.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                Err(RecordIssue::InvalidCertAuthority)
+            }
+            cert_authority => Ok(cert_authority),
         }
     }
 
     pub fn parse(record_text: &str) -> Result<DataRecord> {
-        let fields: Vec<&str> = record_text.split(',').collect();
+        match Self::fields(record_text) {
+            Some((domain, publisher_id, relation, cert_authority)) => Ok(Self::from_fields(
+                domain,
+                publisher_id,
+                AccountRelation::parse(relation)?,
+                cert_authority,
+            )),
+            None => ads_txt_error(&format!("Invalid data record: {}", record_text)),
+        }
+    }
+
+    /// Checks a record line without allocating. Besides the checks of
+    /// `parse`, the ad system must be a valid domain and the publisher id
+    /// and cert authority id must pass the checks of `try_new`.
+    pub fn validate_str(line: &str) -> Result<(), RecordIssue> {
+        let (domain, publisher_id, relation, cert_authority) =
+            Self::fields(line).ok_or(RecordIssue::FieldCount)?;
+
+        if AccountRelation::from_field(relation).is_none() {
+            return Err(RecordIssue::InvalidRelation);
+        } else if !is_valid_domain(domain.trim()) {
+            return Err(RecordIssue::InvalidDomain);
+        }
+        Self::checked_publisher_id(publisher_id)?;
+        Self::checked_cert_authority(cert_authority)?;
+        Ok(())
+    }
+
+    /// Parses a record like `parse`, without building an error for lines
+    /// which are not records
+    fn try_parse(record_text: &str) -> Option<DataRecord> {
+        let (domain, publisher_id, acc_relation, cert_authority) =
+            Self::parsed_fields(record_text)?;
+        Some(Self::from_fields(
+            domain,
+            publisher_id,
+            acc_relation,
+            cert_authority,
+        ))
+    }
 
-        match fields.len() {
-            3 => Ok(DataRecord {
-                domain: fields[0].trim().to_string(),
-                publisher_id: fields[1].trim().to_string(),
-                acc_relation: AccountRelation::parse(fields[2])?,
-                cert_authority: None,
-            }),
-            4 => Ok(DataRecord {
-                domain: fields[0].trim().to_string(),
-                publisher_id: fields[1].trim().to_string(),
-                acc_relation: AccountRelation::parse(fields[2])?,
-                cert_authority: Some(fields[3].trim().to_string()),
-            }),
-            _ => ads_txt_error(&format!("Invalid data record: {}", record_text)),
+    /// Trimmed fields of a record like `try_parse`, borrowing the text
+    pub(crate) fn parsed_fields(
+        record_text: &str,
+    ) -> Option<(&str, &str, AccountRelation, Option<&str>)> {
+        let (domain, publisher_id, relation, cert_authority) = Self::fields(record_text)?;
+        Some((
+            domain.trim(),
+            publisher_id.trim(),
+            AccountRelation::from_field(relation)?,
+            cert_authority.map(str::trim),
+        ))
+    }
+
+    fn from_fields(
+        domain: &str,
+        publisher_id: &str,
+        acc_relation: AccountRelation,
+        cert_authority: Option<&str>,
+    ) -> Self {
+        Self {
+            domain: domain.trim().into(),
+            publisher_id: publisher_id.trim().into(),
+            acc_relation,
+            cert_authority: cert_authority.map(|c| c.trim().into()),
+        }
+    }
+
+    /// Splits a record into its three or four fields
+    fn fields(record_text: &str) -> Option<(&str, &str, &str, Option<&str>)> {
+        let mut fields = record_text.split(',');
+        match (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) {
+            (Some(domain), Some(publisher_id), Some(relation), cert_authority, None) => {
+                Some((domain, publisher_id, relation, cert_authority))
+            }
+            _ => None,
         }
     }
 }
 
+impl DataRecord {
+    /// Writes the record as an ads.txt line, without a line break and
+    /// without allocating
+    pub fn write_into<W: core::fmt::Write>(&self, out: &mut W) -> core::fmt::Result {
+        write_record(
+            out,
+            &self.domain,
+            &self.publisher_id,
+            &self.acc_relation,
+            self.cert_authority.as_deref(),
+        )
+    }
+}
+
+/// Writes the fields of a record as an ads.txt line, for the record types
+/// borrowing or sharing their fields
+pub(crate) fn write_record<W: core::fmt::Write>(
+    out: &mut W,
+    domain: &str,
+    publisher_id: &str,
+    acc_relation: &AccountRelation,
+    cert_authority: Option<&str>,
+) -> core::fmt::Result {
+    out.write_str(domain)?;
+    out.write_str(", ")?;
+    out.write_str(publisher_id)?;
+    out.write_str(", ")?;
+    out.write_str(acc_relation.as_str())?;
+    if let Some(cert_authority) = cert_authority {
+        out.write_str(", ")?;
+        out.write_str(cert_authority)?;
+    }
+    Ok(())
+}
+
+impl core::fmt::Display for DataRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.write_into(f)
+    }
+}
+
+impl core::str::FromStr for DataRecord {
+    type Err = AdsTxtError;
+
+    fn from_str(s: &str) -> Result<DataRecord> {
+        DataRecord::parse(s)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Variable {
     pub name: String,
@@ -109,19 +615,75 @@ impl Variable {
     }
 
     pub fn parse(line: &str) -> Result<Variable> {
-        let fields: Vec<&str> = line.split('=').collect();
+        match Self::try_parse(line) {
+            Some(variable) => Ok(variable),
+            None => ads_txt_error(&format!("Invalid variable record: {}", line)),
+        }
+    }
+
+    /// Parses a variable like `parse`, without building an error for lines
+    /// which are not variables
+    fn try_parse(line: &str) -> Option<Variable> {
+        let (name, value) = Self::parsed_fields(line)?;
+        Some(Variable::new(name, value))
+    }
 
-        match fields.len() {
-            2 => Ok(Variable {
-                name: fields[0].trim().to_string(),
-                value: fields[1].trim().to_string(),
-            }),
-            _ => ads_txt_error(&format!("Invalid variable record: {}", line)),
+    /// Trimmed name and value of a variable like `try_parse`, borrowing the
+    /// line
+    pub(crate) fn parsed_fields(line: &str) -> Option<(&str, &str)> {
+        match line.split_once('=') {
+            Some((name, value)) if !value.contains('=') => Some((name.trim(), value.trim())),
+            _ => None,
         }
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+impl Variable {
+    /// Writes the variable as an ads.txt line, without a line break and
+    /// without allocating
+    pub fn write_into<W: core::fmt::Write>(&self, out: &mut W) -> core::fmt::Result {
+        out.write_str(&self.name)?;
+        out.write_char('=')?;
+        out.write_str(&self.value)
+    }
+}
+
+impl core::fmt::Display for Variable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.write_into(f)
+    }
+}
+
+impl core::str::FromStr for Variable {
+    type Err = AdsTxtError;
+
+    fn from_str(s: &str) -> Result<Variable> {
+        Variable::parse(s)
+    }
+}
+
+/// Expected number of records and variables in a file, used to size the
+/// vectors of `AdsTxt` before parsing
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct CapacityHints {
+    pub records: usize,
+    pub variables: usize,
+}
+
+impl CapacityHints {
+    pub fn new(records: usize, variables: usize) -> Self {
+        CapacityHints { records, variables }
+    }
+
+    /// Cheap estimate from the line count of the text. Nearly every line of a
+    /// typical file is a record, while variables are few.
+    pub fn estimate(text: &str) -> Self {
+        let lines = text.bytes().filter(|&b| b == b'\n').count() + 1;
+        CapacityHints::new(lines, lines.min(4))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct AdsTxt {
     pub records: Vec<DataRecord>,
     pub variables: Vec<Variable>,
@@ -145,8 +707,14 @@ impl AdsTxt {
     }
 
     pub fn parse(text: &str) -> Result<AdsTxt> {
-        let mut records: Vec<DataRecord> = vec![];
-        let mut variables: Vec<Variable> = vec![];
+        Self::parse_with_capacity_hints(text, CapacityHints::estimate(text))
+    }
+
+    /// Parses ads.txt file, sizing the records and variables from the hints
+    /// instead of estimating them from the text
+    pub fn parse_with_capacity_hints(text: &str, hints: CapacityHints) -> Result<AdsTxt> {
+        let mut records: Vec<DataRecord> = Vec::with_capacity(hints.records);
+        let mut variables: Vec<Variable> = Vec::with_capacity(hints.variables);
 
         for line in text.lines() {
             let line = line.trim_start();
@@ -155,12 +723,12 @@ impl AdsTxt {
                 continue;
             }
 
-            if let Ok(record) = DataRecord::parse(line) {
+            if let Some(record) = DataRecord::try_parse(line) {
                 records.push(record);
                 continue;
             }
 
-            if let Ok(variable) = Variable::parse(line) {
+            if let Some(variable) = Variable::try_parse(line) {
                 variables.push(variable);
                 continue;
             }
@@ -171,11 +739,13 @@ impl AdsTxt {
         Ok(AdsTxt { records, variables })
     }
 
-    /// Parses ads.txt file leniently
-    pub fn parse_lenient(text: &str) -> (AdsTxt, Vec<AdsTxtError>) {
-        let mut records: Vec<DataRecord> = vec![];
-        let mut variables: Vec<Variable> = vec![];
-        let mut errors: Vec<AdsTxtError> = vec![];
+    /// Parses ads.txt file like `parse`, reading relations and normalizing
+    /// records with the normalizer
+    pub fn parse_with_normalizer<N: normalize::Normalizer>(
+        text: &str,
+        normalizer: &N,
+    ) -> Result<AdsTxt> {
+        let mut ads_txt = AdsTxt::empty();
 
         for line in text.lines() {
             let line = line.trim_start();
@@ -184,56 +754,301 @@ impl AdsTxt {
                 continue;
             }
 
-            if let Ok(record) = DataRecord::parse(line) {
-                records.push(record);
-                continue;
+            if let Some((domain, publisher_id, relation, cert_authority)) = DataRecord::fields(line)
+            {
+                if let Some(relation) = normalizer.relation(relation) {
+                    let record =
+                        DataRecord::from_fields(domain, publisher_id, relation, cert_authority);
+                    ads_txt.records.push(normalizer.record(&record));
+                    continue;
+                }
             }
 
-            if let Ok(variable) = Variable::parse(line) {
-                variables.push(variable);
+            if let Some(variable) = Variable::try_parse(line) {
+                ads_txt.variables.push(variable);
                 continue;
             }
 
-            errors.push(AdsTxtError::new(&format!("Invalid ads.txt line: {}", line)));
+            return ads_txt_error(&format!("Invalid ads.txt line: {}", line));
         }
 
-        (AdsTxt { records, variables }, errors)
+        Ok(ads_txt)
     }
 
-    pub fn values(&self, name: &str) -> Vec<String> {
-        let mut values = vec![];
+    /// Parses ads.txt file leniently
+    pub fn parse_lenient(text: &str) -> (AdsTxt, Vec<AdsTxtError>) {
+        Self::parse_lenient_each(text, |_, _| {})
+    }
+
+    /// `parse_lenient_lines` without a stop condition
+    fn parse_lenient_each(
+        text: &str,
+        parsed: impl FnMut(usize, &AdsTxt),
+    ) -> (AdsTxt, Vec<AdsTxtError>) {
+        let no_stop = |_| Ok::<_, core::convert::Infallible>(());
+        match Self::parse_lenient_lines(text, no_stop, parsed) {
+            Ok(parsed) => parsed,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Lenient parse shared by the `parse_lenient` variants. `check` is
+    /// called with the 0-based index of every line before it is parsed and
+    /// stops the parse with its error, and `parsed` with the index and the
+    /// file so far after.
+    fn parse_lenient_lines<E>(
+        text: &str,
+        mut check: impl FnMut(usize) -> core::result::Result<(), E>,
+        mut parsed: impl FnMut(usize, &AdsTxt),
+    ) -> core::result::Result<(AdsTxt, Vec<AdsTxtError>), E> {
+        let hints = CapacityHints::estimate(text);
+        let mut ads_txt = AdsTxt {
+            records: Vec::with_capacity(hints.records),
+            variables: Vec::with_capacity(hints.variables),
+        };
+        let mut errors: Vec<AdsTxtError> = vec![];
+
+        for (i, line) in text.lines().enumerate() {
+            check(i)?;
+            ads_txt.push_line_lenient(line, &mut errors);
+            parsed(i, &ads_txt);
+        }
+
+        Ok((ads_txt, errors))
+    }
+
+    /// Parses ads.txt file leniently like `parse_lenient`, giving up once the
+    /// deadline expires
+    #[cfg(feature = "std")]
+    pub fn parse_lenient_until(
+        text: &str,
+        deadline: &deadline::Deadline,
+    ) -> Result<(AdsTxt, Vec<AdsTxtError>), deadline::TimedOut> {
+        Self::parse_lenient_lines(text, |i| deadline.check(i), |_, _| {})
+    }
+
+    /// Parses ads.txt file leniently like `parse_lenient`, also returning the
+    /// time taken and the size of the file
+    #[cfg(feature = "std")]
+    pub fn parse_lenient_instrumented(
+        text: &str,
+    ) -> (AdsTxt, Vec<AdsTxtError>, parse_info::ParseInfo) {
+        let started = std::time::Instant::now();
+        let mut lines = 0;
+        let (ads_txt, errors) = Self::parse_lenient_each(text, |i, _| lines = i + 1);
+
+        let info = parse_info::ParseInfo {
+            elapsed: started.elapsed(),
+            bytes: text.len(),
+            lines,
+            records: ads_txt.records.len(),
+            variables: ads_txt.variables.len(),
+            warnings: errors.len(),
+        };
+        (ads_txt, errors, info)
+    }
+
+    /// Parses ads.txt file leniently like `parse_lenient`, also returning
+    /// the 1-based line number of each record
+    pub fn parse_lenient_with_lines(text: &str) -> (AdsTxt, Vec<usize>, Vec<AdsTxtError>) {
+        let mut lines = vec![];
+        let (ads_txt, errors) = Self::parse_lenient_each(text, |i, ads_txt| {
+            lines.resize(ads_txt.records.len(), i + 1)
+        });
+
+        (ads_txt, lines, errors)
+    }
+
+    fn push_line_lenient(&mut self, line: &str, errors: &mut Vec<AdsTxtError>) {
+        let line = line.trim_start();
+
+        if line.is_empty() || Self::is_comment(line) {
+            return;
+        }
+
+        if let Some(record) = DataRecord::try_parse(line) {
+            self.records.push(record);
+        } else if let Some(variable) = Variable::try_parse(line) {
+            self.variables.push(variable);
+        } else {
+            errors.push(AdsTxtError::new(&format!("Invalid ads.txt line: {}", line)));
+        }
+    }
 
-        for v in &self.variables {
-            if &v.name == name {
-                values.push(v.value.to_string());
+    /// Parses ads.txt file leniently one line at a time, without reading the
+    /// whole file into memory. Invalid UTF-8 is replaced rather than rejected.
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: std::io::BufRead>(mut reader: R) -> Result<(AdsTxt, Vec<AdsTxtError>)> {
+        let mut ads_txt = AdsTxt::empty();
+        let mut errors = vec![];
+        let mut line = vec![];
+
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let text = String::from_utf8_lossy(&line);
+                    ads_txt
+                        .push_line_lenient(text.trim_end_matches(&['\r', '\n'][..]), &mut errors);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    return Err(AdsTxtError::with_source(
+                        &format!("Error reading ads.txt: {}", e),
+                        e,
+                    ))
+                }
             }
         }
 
-        values
+        Ok((ads_txt, errors))
     }
 
-    pub fn sub_domains(&self) -> Vec<String> {
-        let mut sub_domains = vec![];
+    /// Parses a gzip-compressed ads.txt file leniently, decompressing it as
+    /// it is read. Files inflating past `gzip::DEFAULT_MAX_OUTPUT` bytes are
+    /// rejected; a `GzipDecoder` with another limit may be passed to
+    /// `from_reader` instead.
+    #[cfg(feature = "std")]
+    pub fn from_gzip_reader<R: std::io::Read>(reader: R) -> Result<(AdsTxt, Vec<AdsTxtError>)> {
+        let decoder = gzip::GzipDecoder::new(reader).with_max_output(gzip::DEFAULT_MAX_OUTPUT);
+        Self::from_reader(std::io::BufReader::new(decoder))
+    }
 
-        for v in &self.variables {
-            if v.name.eq_ignore_ascii_case("subdomain") {
-                sub_domains.push(v.value.to_string());
+    /// Parses an ads.txt file leniently, decompressing it if it is gzipped.
+    /// Compression is detected from the content, so `.gz` files need no
+    /// particular name.
+    #[cfg(feature = "std")]
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<(AdsTxt, Vec<AdsTxtError>)> {
+        use std::io::BufRead;
+
+        let path = path.as_ref();
+        let mut reader = match std::fs::File::open(path) {
+            Ok(file) => std::io::BufReader::new(file),
+            Err(e) => {
+                let message = format!("Error opening {}: {}", path.display(), e);
+                return Err(AdsTxtError::with_source(&message, e));
+            }
+        };
+        let compressed = match reader.fill_buf() {
+            Ok(buf) => gzip::is_gzip(buf),
+            Err(e) => {
+                let message = format!("Error reading {}: {}", path.display(), e);
+                return Err(AdsTxtError::with_source(&message, e));
             }
+        };
+
+        if compressed {
+            Self::from_gzip_reader(reader)
+        } else {
+            Self::from_reader(reader)
         }
+    }
+
+    /// Whether the file lists the seller account on the given ad system,
+    /// comparing publisher ids by the policy
+    pub fn is_authorized(
+        &self,
+        ad_system: &str,
+        publisher_id: &str,
+        policy: &publisher_id::PublisherIdPolicy,
+    ) -> bool {
+        let ad_system = ad_system.trim();
+        self.records.iter().any(|r| {
+            r.domain.eq_ignore_ascii_case(ad_system)
+                && policy.same_id(ad_system, &r.publisher_id, publisher_id)
+        })
+    }
+
+    /// Records for the seller account on the given ad system
+    pub fn seat_records(&self, domain: &str, publisher_id: &str) -> Vec<&DataRecord> {
+        self.seat_records_with_policy(
+            domain,
+            publisher_id,
+            &publisher_id::PublisherIdPolicy::default(),
+        )
+    }
+
+    /// Records for the seller account on the given ad system, comparing
+    /// publisher ids by the policy
+    pub fn seat_records_with_policy(
+        &self,
+        domain: &str,
+        publisher_id: &str,
+        policy: &publisher_id::PublisherIdPolicy,
+    ) -> Vec<&DataRecord> {
+        let domain = domain.trim();
+
+        self.records
+            .iter()
+            .filter(|r| {
+                r.domain.eq_ignore_ascii_case(domain)
+                    && policy.same_id(domain, &r.publisher_id, publisher_id)
+            })
+            .collect()
+    }
 
-        sub_domains
+    pub fn values(&self, name: &str) -> Vec<String> {
+        self.iter_values(name).map(String::from).collect()
+    }
+
+    pub fn sub_domains(&self) -> Vec<String> {
+        self.iter_sub_domains().map(String::from).collect()
     }
 
     pub fn contacts(&self) -> Vec<String> {
-        let mut sub_domains = vec![];
+        self.iter_contacts().map(String::from).collect()
+    }
 
-        for v in &self.variables {
-            if v.name.eq_ignore_ascii_case("contact") {
-                sub_domains.push(v.value.to_string());
-            }
+    /// Values of the variables with the given name, like `values` without
+    /// allocating
+    pub fn iter_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.variables
+            .iter()
+            .filter(move |v| v.name == name)
+            .map(|v| v.value.as_str())
+    }
+
+    /// Like `sub_domains` without allocating
+    pub fn iter_sub_domains(&self) -> impl Iterator<Item = &str> {
+        self.values_ignoring_case("subdomain")
+    }
+
+    /// Like `contacts` without allocating
+    pub fn iter_contacts(&self) -> impl Iterator<Item = &str> {
+        self.values_ignoring_case("contact")
+    }
+
+    pub(crate) fn values_ignoring_case<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        self.variables
+            .iter()
+            .filter(move |v| v.name.eq_ignore_ascii_case(name))
+            .map(|v| v.value.as_str())
+    }
+
+    /// Writes the file as ads.txt text, one line per record and then per
+    /// variable, without allocating
+    pub fn write_into<W: core::fmt::Write>(&self, out: &mut W) -> core::fmt::Result {
+        for record in &self.records {
+            record.write_into(out)?;
+            out.write_char('\n')?;
+        }
+        for variable in &self.variables {
+            variable.write_into(out)?;
+            out.write_char('\n')?;
         }
+        Ok(())
+    }
+}
+
+impl core::str::FromStr for AdsTxt {
+    type Err = AdsTxtError;
 
-        sub_domains
+    fn from_str(s: &str) -> Result<AdsTxt> {
+        AdsTxt::parse(s)
     }
 }
 
@@ -269,6 +1084,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parsing_with_from_str() {
+        assert_eq!("Direct".parse(), Ok(AccountRelation::Direct));
+        assert!("direct-ish".parse::<AccountRelation>().is_err());
+
+        let record: DataRecord = "greenadexchange.com, 12345, RESELLER, d75815a79"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            record,
+            DataRecord::new(
+                "greenadexchange.com",
+                "12345",
+                AccountRelation::Reseller,
+                Some("d75815a79".to_string())
+            )
+        );
+        assert!("greenadexchange.com, 12345".parse::<DataRecord>().is_err());
+
+        assert_eq!(
+            "contact=adops@example.com".parse(),
+            Ok(Variable::new("contact", "adops@example.com"))
+        );
+
+        let ads_txt: AdsTxt =
+            "# comment\ngreenadexchange.com, 12345, DIRECT\nsubdomain=a.example.com\n"
+                .parse()
+                .unwrap();
+        assert_eq!(ads_txt.records.len(), 1);
+        assert_eq!(ads_txt.sub_domains(), vec!["a.example.com"]);
+        assert_eq!(
+            "greenadexchange.com, 12345, PARTNER".parse::<AdsTxt>(),
+            AdsTxt::parse("greenadexchange.com, 12345, PARTNER")
+        );
+    }
+
     #[test]
     fn parsing_data_records() {
         assert_eq!(
@@ -293,7 +1144,13 @@ mod tests {
                 AccountRelation::Direct,
                 None
             ))
-        )
+        );
+
+        let record = DataRecord::parse("greenadexchange.com,12345 ,direct, d75815a79").unwrap();
+        assert_eq!(
+            record.to_string(),
+            "greenadexchange.com, 12345, DIRECT, d75815a79"
+        );
     }
 
     #[test]
@@ -310,6 +1167,10 @@ mod tests {
             Variable::parse("subdomain=   divisionone.example.com"),
             Ok(Variable::new("subdomain", "divisionone.example.com"))
         );
+        assert_eq!(
+            Variable::parse("contact=a=b"),
+            ads_txt_error("Invalid variable record: contact=a=b")
+        );
     }
 
     #[test]
@@ -385,7 +1246,13 @@ mod tests {
             vec!["divisionone.example.com".to_string()]
         );
 
-        assert!(ads2.unwrap().values("subdomain").is_empty());
+        let ads2 = ads2.unwrap();
+        assert!(ads2.values("subdomain").is_empty());
+        assert_eq!(
+            ads2.seat_records("OrangeExchange.com", " AB345"),
+            vec![&ads2.records[1]]
+        );
+        assert!(ads2.seat_records("orangeexchange.com", "ab345").is_empty());
     }
 
     #[test]
@@ -422,6 +1289,197 @@ mod tests {
         assert_eq!(ads2, (AdsTxt::empty(), vec![]));
     }
 
+    #[test]
+    fn validating_record_lines() {
+        assert_eq!(
+            DataRecord::validate_str(" greenadexchange.com, 12345, direct, d75815a79"),
+            Ok(())
+        );
+        assert_eq!(
+            DataRecord::validate_str("silverssp.com, 5569"),
+            Err(RecordIssue::FieldCount)
+        );
+        assert_eq!(
+            DataRecord::validate_str("silverssp.com, 5569, OTHER"),
+            Err(RecordIssue::InvalidRelation)
+        );
+        assert_eq!(
+            DataRecord::validate_str("localhost, 5569, DIRECT"),
+            Err(RecordIssue::InvalidDomain)
+        );
+        assert_eq!(
+            DataRecord::validate_str("silverssp.com, , DIRECT"),
+            Err(RecordIssue::EmptyPublisherId)
+        );
+        assert_eq!(
+            DataRecord::validate_str("silverssp.com, 55 69, DIRECT"),
+            Err(RecordIssue::InvalidPublisherId)
+        );
+        assert_eq!(
+            DataRecord::validate_str("silverssp.com, 5569;x, DIRECT"),
+            Err(RecordIssue::InvalidPublisherId)
+        );
+        assert_eq!(
+            DataRecord::validate_str("silverssp.com, 5569, DIRECT, d7-5815"),
+            Err(RecordIssue::InvalidCertAuthority)
+        );
+        assert_eq!(
+            RecordIssue::from_name("invalid-relation"),
+            Some(RecordIssue::InvalidRelation)
+        );
+    }
+
+    #[test]
+    fn building_validated_records() {
+        let record = DataRecord::try_new(
+            " GreenAdExchange.com. ",
+            " 12345 ",
+            AccountRelation::Direct,
+            Some("d75815a79"),
+        )
+        .unwrap();
+        assert_eq!(
+            record,
+            DataRecord::parse("greenadexchange.com, 12345, DIRECT, d75815a79").unwrap()
+        );
+
+        let try_new = |domain, publisher_id, cert_authority| {
+            DataRecord::try_new(
+                domain,
+                publisher_id,
+                AccountRelation::Reseller,
+                cert_authority,
+            )
+            .map(|_| ())
+        };
+        assert_eq!(
+            try_new("localhost", "1", None),
+            Err(RecordIssue::InvalidDomain)
+        );
+        assert_eq!(
+            try_new("a.com", " ", None),
+            Err(RecordIssue::EmptyPublisherId)
+        );
+        for publisher_id in ["1,2", "1 2", "1#2", "1\n2"] {
+            assert_eq!(
+                try_new("a.com", publisher_id, None),
+                Err(RecordIssue::InvalidPublisherId)
+            );
+        }
+        for cert_authority in ["", "d758-15a7", "d75815a79;x"] {
+            assert_eq!(
+                try_new("a.com", "1", Some(cert_authority)),
+                Err(RecordIssue::InvalidCertAuthority)
+            );
+        }
+
+        let mut record = record;
+        record.set_domain("Exchange.COM.").unwrap();
+        record.set_publisher_id(" pub-1 ").unwrap();
+        record.set_acc_relation(AccountRelation::Reseller);
+        record.set_cert_authority(None).unwrap();
+        assert_eq!(
+            (
+                record.domain(),
+                record.publisher_id(),
+                record.acc_relation(),
+                record.cert_authority()
+            ),
+            ("exchange.com", "pub-1", &AccountRelation::Reseller, None)
+        );
+        assert_eq!(
+            record.set_domain("not a domain"),
+            Err(RecordIssue::InvalidDomain)
+        );
+        assert_eq!(
+            record.set_publisher_id("1 2"),
+            Err(RecordIssue::InvalidPublisherId)
+        );
+        assert_eq!(
+            record.set_cert_authority(Some("")),
+            Err(RecordIssue::InvalidCertAuthority)
+        );
+        // Rejected values leave the record unchanged
+        assert_eq!(record.to_string(), "exchange.com, pub-1, RESELLER");
+    }
+
+    #[test]
+    fn writing_into_buffers() {
+        /// Fixed-size buffer, as on targets without an allocator
+        struct Buffer {
+            bytes: [u8; 64],
+            len: usize,
+        }
+
+        impl core::fmt::Write for Buffer {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                let end = self.len + s.len();
+                if end > self.bytes.len() {
+                    return Err(core::fmt::Error);
+                }
+                self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+                self.len = end;
+                Ok(())
+            }
+        }
+
+        let ads_txt = AdsTxt::parse("a.com, 1, direct, abc\ncontact=x@a.com").unwrap();
+        let mut buffer = Buffer {
+            bytes: [0; 64],
+            len: 0,
+        };
+        ads_txt.write_into(&mut buffer).unwrap();
+        assert_eq!(
+            core::str::from_utf8(&buffer.bytes[..buffer.len]),
+            Ok("a.com, 1, DIRECT, abc\ncontact=x@a.com\n")
+        );
+
+        let mut small = Buffer {
+            bytes: [0; 64],
+            len: 60,
+        };
+        assert!(ads_txt.records[0].write_into(&mut small).is_err());
+    }
+
+    #[test]
+    fn parsing_with_capacity_hints() {
+        let text = "# comment\na.com, 1, DIRECT\nb.com, 2, RESELLER\ncontact=x@a.com";
+        assert_eq!(CapacityHints::estimate(text), CapacityHints::new(4, 4));
+        assert_eq!(CapacityHints::estimate(""), CapacityHints::new(1, 1));
+
+        let ads = AdsTxt::parse_with_capacity_hints(text, CapacityHints::new(100, 0)).unwrap();
+        assert_eq!(ads, AdsTxt::parse(text).unwrap());
+        assert!(ads.records.capacity() >= 100);
+        assert!(AdsTxt::parse_with_capacity_hints("nonsense", CapacityHints::default()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn keeping_io_errors_as_sources() {
+        use std::error::Error;
+        use std::io::{BufReader, ErrorKind, Read};
+
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(ErrorKind::PermissionDenied, "denied"))
+            }
+        }
+
+        let error = AdsTxt::from_reader(BufReader::new(Failing)).unwrap_err();
+        assert_eq!(error.message(), "Error reading ads.txt: denied");
+        let source = error.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().unwrap().kind(),
+            ErrorKind::PermissionDenied
+        );
+
+        let missing = std::env::temp_dir().join("ads-txt-missing-source");
+        let error = AdsTxt::from_file(missing).unwrap_err();
+        assert!(error.source().is_some());
+    }
+
     #[test]
     fn test_subdomains_retrieval() {
         let ads_txt = r"greenadexchange.com, 12345, DIRECT, d75815a79
@@ -460,5 +1518,14 @@ mod tests {
             vec!("adops@example.com", "http://example.com/contact-u")
         );
         assert!(errors.is_empty());
+
+        // Iterating borrows the values instead of cloning them
+        let mut contacts = ads.iter_contacts();
+        assert_eq!(contacts.next(), Some("adops@example.com"));
+        assert_eq!(contacts.next(), Some("http://example.com/contact-u"));
+        assert_eq!(contacts.next(), None);
+        assert_eq!(ads.iter_values("contact").count(), 2);
+        assert_eq!(ads.iter_values("CONTACT").count(), 0);
+        assert_eq!(ads.iter_sub_domains().next(), None);
     }
 }
@@ -0,0 +1,203 @@
+//! Merging of several ads.txt files into one deduplicated file
+
+use crate::publisher_id::PublisherIdPolicy;
+use crate::{AccountRelation, AdsTxt, DataRecord, Variable};
+use std::collections::HashMap;
+
+/// How to resolve records of the same seat, i.e. ad system and publisher id,
+/// which disagree on relation or cert authority
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ConflictPolicy {
+    KeepFirst,
+    KeepLast,
+    /// Keep the first DIRECT record, or the first record if none is DIRECT
+    KeepDirect,
+    /// Keep every distinct record
+    KeepAll,
+}
+
+impl ConflictPolicy {
+    pub const ALL: &'static [ConflictPolicy] = &[
+        ConflictPolicy::KeepFirst,
+        ConflictPolicy::KeepLast,
+        ConflictPolicy::KeepDirect,
+        ConflictPolicy::KeepAll,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::KeepFirst => "keep-first",
+            ConflictPolicy::KeepLast => "keep-last",
+            ConflictPolicy::KeepDirect => "keep-direct",
+            ConflictPolicy::KeepAll => "keep-all",
+        }
+    }
+
+    /// Finds a policy by its `as_str` name
+    pub fn from_name(name: &str) -> Option<ConflictPolicy> {
+        ConflictPolicy::ALL
+            .iter()
+            .copied()
+            .find(|p| p.as_str() == name.trim())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MergeConflict {
+    pub kept: DataRecord,
+    /// Records of the same seat dropped in favour of `kept`
+    pub dropped: Vec<DataRecord>,
+}
+
+/// Merges files, keeping records in first-seen seat order followed by
+/// deduplicated variables, and returns the conflicts which were resolved
+pub fn merge<'a, I>(files: I, policy: ConflictPolicy) -> (AdsTxt, Vec<MergeConflict>)
+where
+    I: IntoIterator<Item = &'a AdsTxt>,
+{
+    merge_with_id_policy(files, policy, &PublisherIdPolicy::default())
+}
+
+/// Merges files like `merge`, identifying seats by comparing publisher ids
+/// by `id_policy`
+pub fn merge_with_id_policy<'a, I>(
+    files: I,
+    policy: ConflictPolicy,
+    id_policy: &PublisherIdPolicy,
+) -> (AdsTxt, Vec<MergeConflict>)
+where
+    I: IntoIterator<Item = &'a AdsTxt>,
+{
+    let mut seats: Vec<Vec<&DataRecord>> = vec![];
+    let mut seat_indices = HashMap::new();
+    let mut variables = vec![];
+
+    for ads_txt in files {
+        for record in &ads_txt.records {
+            let key = (
+                record.domain.to_lowercase(),
+                id_policy.key(&record.domain, &record.publisher_id),
+            );
+            let i = *seat_indices.entry(key).or_insert_with(|| {
+                seats.push(vec![]);
+                seats.len() - 1
+            });
+
+            // Only records differing in more than the domain's case conflict
+            let seat = &mut seats[i];
+            if !seat.iter().any(|r| {
+                r.acc_relation == record.acc_relation && r.cert_authority == record.cert_authority
+            }) {
+                seat.push(record);
+            }
+        }
+
+        for variable in &ads_txt.variables {
+            if !variables.iter().any(|v: &Variable| {
+                v.name.eq_ignore_ascii_case(&variable.name) && v.value == variable.value
+            }) {
+                variables.push(variable.clone());
+            }
+        }
+    }
+
+    let mut records = vec![];
+    let mut conflicts = vec![];
+
+    for seat in seats {
+        if seat.len() == 1 || policy == ConflictPolicy::KeepAll {
+            records.extend(seat.into_iter().cloned());
+            continue;
+        }
+
+        let kept = match policy {
+            ConflictPolicy::KeepLast => seat.len() - 1,
+            ConflictPolicy::KeepDirect => seat
+                .iter()
+                .position(|r| r.acc_relation == AccountRelation::Direct)
+                .unwrap_or(0),
+            _ => 0,
+        };
+
+        records.push(seat[kept].clone());
+        conflicts.push(MergeConflict {
+            kept: seat[kept].clone(),
+            dropped: seat
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != kept)
+                .map(|(_, r)| (*r).clone())
+                .collect(),
+        });
+    }
+
+    (AdsTxt { records, variables }, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publisher_id::IdComparison;
+
+    #[test]
+    fn merging_files() {
+        let a = AdsTxt::parse("a.com, 1, RESELLER\nb.com, 2, DIRECT\ncontact=x").unwrap();
+        let b = AdsTxt::parse("A.com, 1, DIRECT\nb.com, 2, DIRECT\nCONTACT=x\nc.com, 3, DIRECT")
+            .unwrap();
+        let merged = |policy| merge(vec![&a, &b], policy);
+
+        let (ads_txt, conflicts) = merged(ConflictPolicy::KeepFirst);
+        assert_eq!(
+            ads_txt,
+            AdsTxt::parse("a.com, 1, RESELLER\nb.com, 2, DIRECT\nc.com, 3, DIRECT\ncontact=x")
+                .unwrap()
+        );
+        assert_eq!(
+            conflicts,
+            vec![MergeConflict {
+                kept: a.records[0].clone(),
+                dropped: vec![b.records[0].clone()],
+            }]
+        );
+
+        let (ads_txt, _) = merged(ConflictPolicy::KeepDirect);
+        assert_eq!(ads_txt.records[0], b.records[0]);
+        let (ads_txt, _) = merged(ConflictPolicy::KeepLast);
+        assert_eq!(ads_txt.records[0], b.records[0]);
+
+        let (ads_txt, conflicts) = merged(ConflictPolicy::KeepAll);
+        assert_eq!(ads_txt.records.len(), 4);
+        assert!(conflicts.is_empty());
+
+        assert_eq!(
+            ConflictPolicy::from_name("keep-direct"),
+            Some(ConflictPolicy::KeepDirect)
+        );
+        assert_eq!(ConflictPolicy::from_name("other"), None);
+
+        let c = AdsTxt::parse(
+            "a.com, ab, DIRECT
+b.com, cd, DIRECT",
+        )
+        .unwrap();
+        let d = AdsTxt::parse(
+            "a.com, AB, DIRECT
+b.com, CD, DIRECT",
+        )
+        .unwrap();
+        let id_policy = PublisherIdPolicy::new(IdComparison::CaseInsensitive)
+            .with_override("b.com", IdComparison::Exact);
+        let (ads_txt, conflicts) =
+            merge_with_id_policy(vec![&c, &d], ConflictPolicy::KeepFirst, &id_policy);
+        assert_eq!(
+            ads_txt,
+            AdsTxt::parse(
+                "a.com, ab, DIRECT
+b.com, cd, DIRECT
+b.com, CD, DIRECT"
+            )
+            .unwrap()
+        );
+        assert!(conflicts.is_empty());
+    }
+}
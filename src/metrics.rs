@@ -0,0 +1,239 @@
+//! Operational metrics of crawls: fetch outcomes, parse durations, record
+//! counts and cache hit rates
+//!
+//! `Crawler::with_metrics` reports to any `MetricsRecorder`, e.g. an adapter
+//! to the metrics library of a deployment. `CrawlerMetrics` is a built-in
+//! recorder rendering the Prometheus text exposition format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Receiver of crawl events
+pub trait MetricsRecorder: Send + Sync {
+    /// A fetch completed with the given outcome: `fetched`,
+    /// `skipped_by_robots` or the `FetchError::class` of the failure
+    fn fetch_outcome(&self, outcome: &str);
+
+    /// A fetched file was parsed, taking `duration` and yielding `records`
+    /// records
+    fn file_parsed(&self, duration: Duration, records: usize);
+
+    /// A cache of fetched files was consulted. The crawler keeps no cache, so
+    /// this is for callers which do.
+    fn cache_lookup(&self, hit: bool);
+}
+
+/// Histogram with fixed upper bounds, counting observations cumulatively
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: Mutex<f64>,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: Mutex::new(0.0),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            if value <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut sum) = self.sum.lock() {
+            *sum += value;
+        }
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound,
+                count.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum.lock().map(|sum| *sum).unwrap_or_default();
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+const PARSE_DURATION_BOUNDS: &[f64] = &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+const RECORD_COUNT_BOUNDS: &[f64] = &[0.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0];
+
+/// Prometheus registry of crawl metrics, shared between a crawler and the
+/// endpoint serving `render`
+#[derive(Debug)]
+pub struct CrawlerMetrics {
+    fetches: Mutex<BTreeMap<String, u64>>,
+    parse_duration: Histogram,
+    records: Histogram,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+impl Default for CrawlerMetrics {
+    fn default() -> Self {
+        CrawlerMetrics {
+            fetches: Mutex::new(BTreeMap::new()),
+            parse_duration: Histogram::new(PARSE_DURATION_BOUNDS),
+            records: Histogram::new(RECORD_COUNT_BOUNDS),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+        }
+    }
+}
+
+impl CrawlerMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of fetches with the given outcome
+    pub fn fetches(&self, outcome: &str) -> u64 {
+        self.fetches
+            .lock()
+            .ok()
+            .and_then(|fetches| fetches.get(outcome).copied())
+            .unwrap_or(0)
+    }
+
+    /// Number of files parsed
+    pub fn files_parsed(&self) -> u64 {
+        self.records.count.load(Ordering::Relaxed)
+    }
+
+    /// Share of cache lookups which were hits, if there were any
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let hits = self.cache_hits.load(Ordering::Relaxed);
+        let misses = self.cache_misses.load(Ordering::Relaxed);
+        match hits + misses {
+            0 => None,
+            total => Some(hits as f64 / total as f64),
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP ads_txt_fetches_total Fetches by outcome\n");
+        out.push_str("# TYPE ads_txt_fetches_total counter\n");
+        if let Ok(fetches) = self.fetches.lock() {
+            for (outcome, count) in fetches.iter() {
+                let _ = writeln!(
+                    out,
+                    "ads_txt_fetches_total{{outcome=\"{}\"}} {}",
+                    outcome, count
+                );
+            }
+        }
+
+        self.parse_duration.render(
+            &mut out,
+            "ads_txt_parse_duration_seconds",
+            "Time taken to parse fetched files",
+        );
+        self.records
+            .render(&mut out, "ads_txt_records", "Records per fetched file");
+
+        out.push_str("# HELP ads_txt_cache_lookups_total Cache lookups by result\n");
+        out.push_str("# TYPE ads_txt_cache_lookups_total counter\n");
+        let _ = writeln!(
+            out,
+            "ads_txt_cache_lookups_total{{result=\"hit\"}} {}",
+            self.cache_hits.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "ads_txt_cache_lookups_total{{result=\"miss\"}} {}",
+            self.cache_misses.load(Ordering::Relaxed)
+        );
+
+        out
+    }
+}
+
+impl MetricsRecorder for CrawlerMetrics {
+    fn fetch_outcome(&self, outcome: &str) {
+        if let Ok(mut fetches) = self.fetches.lock() {
+            *fetches.entry(outcome.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    fn file_parsed(&self, duration: Duration, records: usize) {
+        self.parse_duration.observe(duration.as_secs_f64());
+        self.records.observe(records as f64);
+    }
+
+    fn cache_lookup(&self, hit: bool) {
+        if hit {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendering_metrics() {
+        let metrics = CrawlerMetrics::new();
+        assert_eq!(metrics.cache_hit_rate(), None);
+
+        metrics.fetch_outcome("fetched");
+        metrics.fetch_outcome("fetched");
+        metrics.fetch_outcome("not_found");
+        metrics.file_parsed(Duration::from_micros(200), 40);
+        metrics.file_parsed(Duration::from_millis(20), 2000);
+        metrics.cache_lookup(true);
+        metrics.cache_lookup(true);
+        metrics.cache_lookup(false);
+        metrics.cache_lookup(true);
+
+        assert_eq!(metrics.fetches("fetched"), 2);
+        assert_eq!(metrics.fetches("dns"), 0);
+        assert_eq!(metrics.files_parsed(), 2);
+        assert_eq!(metrics.cache_hit_rate(), Some(0.75));
+
+        let text = metrics.render();
+        for line in &[
+            "ads_txt_fetches_total{outcome=\"fetched\"} 2",
+            "ads_txt_fetches_total{outcome=\"not_found\"} 1",
+            "ads_txt_parse_duration_seconds_bucket{le=\"0.0001\"} 0",
+            "ads_txt_parse_duration_seconds_bucket{le=\"0.0005\"} 1",
+            "ads_txt_parse_duration_seconds_bucket{le=\"0.05\"} 2",
+            "ads_txt_parse_duration_seconds_count 2",
+            "ads_txt_records_bucket{le=\"50\"} 1",
+            "ads_txt_records_bucket{le=\"1000\"} 1",
+            "ads_txt_records_bucket{le=\"+Inf\"} 2",
+            "ads_txt_records_sum 2040",
+            "ads_txt_cache_lookups_total{result=\"hit\"} 3",
+            "# TYPE ads_txt_records histogram",
+        ] {
+            assert!(text.lines().any(|l| l == *line), "missing {}", line);
+        }
+    }
+}
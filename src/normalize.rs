@@ -0,0 +1,227 @@
+//! Normalization of records, pluggable for organizations with stricter or
+//! looser canonicalization rules than the default
+//!
+//! A `Normalizer` is taken by `AdsTxt::parse_with_normalizer`,
+//! `AdsTxtDiff::between_normalized` and `ReverseIndex::with_normalizer`, so
+//! the same rules apply when parsing, diffing and indexing.
+
+use crate::{AccountRelation, AdsTxt, DataRecord};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+pub trait Normalizer: core::fmt::Debug {
+    /// Canonical form of an ad system or publisher domain
+    fn domain(&self, domain: &str) -> String;
+
+    fn publisher_id(&self, publisher_id: &str) -> String {
+        publisher_id.trim().to_string()
+    }
+
+    fn cert_authority(&self, cert_authority: &str) -> String {
+        cert_authority.trim().to_lowercase()
+    }
+
+    /// Relation of the relation field of a record, `None` if it is not one
+    fn relation(&self, field: &str) -> Option<AccountRelation> {
+        AccountRelation::from_field(field)
+    }
+
+    fn record(&self, record: &DataRecord) -> DataRecord {
+        DataRecord::new(
+            &self.domain(&record.domain),
+            &self.publisher_id(&record.publisher_id),
+            record.acc_relation.clone(),
+            record
+                .cert_authority
+                .as_deref()
+                .map(|c| self.cert_authority(c)),
+        )
+    }
+
+    /// Copy of the file with its records normalized
+    fn ads_txt(&self, ads_txt: &AdsTxt) -> AdsTxt {
+        AdsTxt {
+            records: ads_txt.records.iter().map(|r| self.record(r)).collect(),
+            variables: ads_txt.variables.clone(),
+        }
+    }
+}
+
+/// Normalization used throughout the crate: domains are trimmed, lowercased
+/// and stripped of a trailing dot and `www.`, with internationalized labels
+/// in their punycode `xn--` form; cert authority ids are lowercased and
+/// relations read case-insensitively
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct DefaultNormalizer;
+
+impl Normalizer for DefaultNormalizer {
+    fn domain(&self, domain: &str) -> String {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+        let domain = domain.strip_prefix("www.").unwrap_or(&domain);
+        to_ascii(domain)
+    }
+}
+
+impl<N: Normalizer + ?Sized> Normalizer for &N {
+    fn domain(&self, domain: &str) -> String {
+        (**self).domain(domain)
+    }
+
+    fn publisher_id(&self, publisher_id: &str) -> String {
+        (**self).publisher_id(publisher_id)
+    }
+
+    fn cert_authority(&self, cert_authority: &str) -> String {
+        (**self).cert_authority(cert_authority)
+    }
+
+    fn relation(&self, field: &str) -> Option<AccountRelation> {
+        (**self).relation(field)
+    }
+
+    fn record(&self, record: &DataRecord) -> DataRecord {
+        (**self).record(record)
+    }
+
+    fn ads_txt(&self, ads_txt: &AdsTxt) -> AdsTxt {
+        (**self).ads_txt(ads_txt)
+    }
+}
+
+/// The domain with its non-ASCII labels punycode-encoded, RFC 3492. Labels
+/// which cannot be encoded are kept as they are.
+pub fn to_ascii(domain: &str) -> String {
+    if domain.is_ascii() {
+        return domain.to_string();
+    }
+
+    domain
+        .split('.')
+        .map(|label| match punycode(label) {
+            Some(encoded) if !label.is_ascii() => alloc::format!("xn--{}", encoded),
+            _ => label.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+fn punycode(label: &str) -> Option<String> {
+    let input: Vec<u32> = label.chars().map(|c| c as u32).collect();
+    let mut output: String = label.chars().filter(char::is_ascii).collect();
+    let basic = output.len() as u32;
+    if basic > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut handled = basic;
+    while (handled as usize) < input.len() {
+        let m = input.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+
+        for &c in &input {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = if k <= bias {
+                        T_MIN
+                    } else if k >= bias + T_MAX {
+                        T_MAX
+                    } else {
+                        k - bias
+                    };
+                    if q < t {
+                        break;
+                    }
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+
+    Some(output)
+}
+
+fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / DAMP } else { delta / 2 };
+    delta += delta / points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+fn digit(d: u32) -> char {
+    match d {
+        0..=25 => (b'a' + d as u8) as char,
+        _ => (b'0' + (d - 26) as u8) as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps publisher ids in upper case, e.g. for ad systems with case
+    /// insensitive ids
+    #[derive(Debug)]
+    struct UpperCaseIds;
+
+    impl Normalizer for UpperCaseIds {
+        fn domain(&self, domain: &str) -> String {
+            DefaultNormalizer.domain(domain)
+        }
+
+        fn publisher_id(&self, publisher_id: &str) -> String {
+            publisher_id.trim().to_uppercase()
+        }
+    }
+
+    #[test]
+    fn normalizing_records() {
+        assert_eq!(to_ascii("b\u{fc}cher.de"), "xn--bcher-kva.de");
+        assert_eq!(to_ascii("m\u{fc}nchen.example"), "xn--mnchen-3ya.example");
+        assert_eq!(to_ascii("\u{4f8b}\u{3048}.jp"), "xn--r8jz45g.jp");
+        assert_eq!(
+            DefaultNormalizer.domain(" WWW.B\u{dc}cher.DE. "),
+            "xn--bcher-kva.de"
+        );
+
+        let text = "B\u{fc}cher.de., ab1, direct, F08C47FEC0942FA0\ncontact=x";
+        assert_eq!(
+            AdsTxt::parse_with_normalizer(text, &DefaultNormalizer).unwrap(),
+            AdsTxt::parse("xn--bcher-kva.de, ab1, DIRECT, f08c47fec0942fa0\ncontact=x").unwrap()
+        );
+        assert_eq!(
+            UpperCaseIds.ads_txt(&AdsTxt::parse("a.com, ab1, DIRECT").unwrap()),
+            AdsTxt::parse("a.com, AB1, DIRECT").unwrap()
+        );
+        assert!(AdsTxt::parse_with_normalizer("a.com, 1, OTHER", &UpperCaseIds).is_err());
+    }
+}
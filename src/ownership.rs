@@ -0,0 +1,245 @@
+//! Resolution of the OWNERDOMAIN and MANAGERDOMAIN variables of a root
+//! domain and its subdomains
+//!
+//! A subdomain's file which does not declare a variable inherits the values
+//! in effect for the file referring to it with `subdomain=`, up to the root
+//! domain's file. A subdomain declaring other values than those it would
+//! inherit overrides them, which is reported as a conflict.
+
+use crate::fetch::SubdomainCrawl;
+use crate::AdsTxt;
+
+/// Variables resolved through inheritance
+pub const INHERITED_VARIABLES: &[&str] = &["OWNERDOMAIN", "MANAGERDOMAIN"];
+
+/// Values of a variable in effect for a file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResolvedVariable {
+    /// Variable name as in `INHERITED_VARIABLES`
+    pub name: &'static str,
+    pub values: Vec<String>,
+    /// Host whose file declares the values, the file's own host unless
+    /// inherited
+    pub declared_by: String,
+}
+
+impl ResolvedVariable {
+    /// Whether the values come from a referring file
+    pub fn is_inherited(&self, host: &str) -> bool {
+        self.declared_by != host
+    }
+}
+
+/// OWNERDOMAIN and MANAGERDOMAIN in effect for one file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResolvedOwnership {
+    pub host: String,
+    /// Host whose file refers to this one, `None` for the root domain
+    pub referrer: Option<String>,
+    pub owner_domain: Option<ResolvedVariable>,
+    pub manager_domain: Option<ResolvedVariable>,
+}
+
+impl ResolvedOwnership {
+    /// Resolved variable of a name of `INHERITED_VARIABLES`
+    pub fn variable(&self, name: &str) -> Option<&ResolvedVariable> {
+        if name.eq_ignore_ascii_case("OWNERDOMAIN") {
+            self.owner_domain.as_ref()
+        } else if name.eq_ignore_ascii_case("MANAGERDOMAIN") {
+            self.manager_domain.as_ref()
+        } else {
+            None
+        }
+    }
+
+    fn variable_mut(&mut self, name: &str) -> &mut Option<ResolvedVariable> {
+        if name == "OWNERDOMAIN" {
+            &mut self.owner_domain
+        } else {
+            &mut self.manager_domain
+        }
+    }
+}
+
+/// Subdomain declaring other values of a variable than it would inherit
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OwnershipConflict {
+    pub host: String,
+    pub declared: Vec<String>,
+    /// Values the file would inherit, and the host declaring them
+    pub inherited: ResolvedVariable,
+}
+
+impl std::fmt::Display for OwnershipConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} declares {}={}, {} declares {}",
+            self.host,
+            self.inherited.name,
+            self.declared.join(" "),
+            self.inherited.declared_by,
+            self.inherited.values.join(" ")
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct OwnershipResolution {
+    /// Resolved variables of each file, in the order given
+    pub files: Vec<ResolvedOwnership>,
+    pub conflicts: Vec<OwnershipConflict>,
+}
+
+impl OwnershipResolution {
+    pub fn get(&self, host: &str) -> Option<&ResolvedOwnership> {
+        let host = normalize_host(host);
+        self.files.iter().find(|f| f.host == host)
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim().trim_end_matches('.').to_lowercase()
+}
+
+/// Values compared ignoring case, surrounding whitespace and order
+fn comparable(values: &[String]) -> Vec<String> {
+    let mut values: Vec<String> = values
+        .iter()
+        .map(|v| v.split_whitespace().collect::<String>().to_lowercase())
+        .collect();
+    values.sort();
+    values.dedup();
+    values
+}
+
+/// Resolves the variables of the files of a root domain and its
+/// subdomains, the root domain's file first and every other file after the
+/// file referring to it, as crawled by `Crawler::fetch_with_subdomains`
+pub fn resolve<'a, I>(files: I) -> OwnershipResolution
+where
+    I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+{
+    let mut resolution = OwnershipResolution::default();
+    let mut referred: Vec<(String, Vec<String>)> = vec![];
+
+    for (host, ads_txt) in files {
+        let host = normalize_host(host);
+        // The first file referring to the host, as the crawl follows it
+        let referrer = referred
+            .iter()
+            .find(|(_, subdomains)| subdomains.contains(&host))
+            .map(|(referrer, _)| referrer.clone());
+        let parent = referrer
+            .as_deref()
+            .and_then(|r| resolution.files.iter().find(|f| f.host == r))
+            .cloned();
+
+        let mut file = ResolvedOwnership {
+            host: host.clone(),
+            referrer,
+            owner_domain: None,
+            manager_domain: None,
+        };
+        for &name in INHERITED_VARIABLES {
+            let declared: Vec<String> = ads_txt
+                .values_ignoring_case(name)
+                .map(|v| v.trim().to_string())
+                .collect();
+            let inherited = parent.as_ref().and_then(|p| p.variable(name)).cloned();
+            if declared.is_empty() {
+                *file.variable_mut(name) = inherited;
+                continue;
+            }
+
+            if let Some(inherited) = inherited {
+                if comparable(&declared) != comparable(&inherited.values) {
+                    resolution.conflicts.push(OwnershipConflict {
+                        host: host.clone(),
+                        declared: declared.clone(),
+                        inherited,
+                    });
+                }
+            }
+            *file.variable_mut(name) = Some(ResolvedVariable {
+                name,
+                values: declared,
+                declared_by: host.clone(),
+            });
+        }
+
+        referred.push((
+            host,
+            ads_txt.iter_sub_domains().map(normalize_host).collect(),
+        ));
+        resolution.files.push(file);
+    }
+
+    resolution
+}
+
+impl SubdomainCrawl {
+    /// OWNERDOMAIN and MANAGERDOMAIN in effect for each fetched file, see
+    /// `ownership::resolve`
+    pub fn ownership(&self) -> OwnershipResolution {
+        resolve(
+            self.fetched()
+                .map(|(host, fetched)| (host, &fetched.ads_txt)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolving_ownership() {
+        let root = AdsTxt::parse(
+            "a.com, 1, DIRECT\nOWNERDOMAIN=Example.com\nMANAGERDOMAIN=manager.com,US\n\
+             subdomain=news.example.com\nsubdomain=shop.example.com",
+        )
+        .unwrap();
+        let news = AdsTxt::parse("b.com, 2, DIRECT\nsubdomain=local.news.example.com").unwrap();
+        let shop =
+            AdsTxt::parse("c.com, 3, DIRECT\nownerdomain=other.com\nmanagerdomain=manager.com, us")
+                .unwrap();
+        let local = AdsTxt::parse("d.com, 4, DIRECT\nMANAGERDOMAIN=local.com").unwrap();
+
+        let resolution = resolve(vec![
+            ("Example.com", &root),
+            ("news.example.com", &news),
+            ("shop.example.com", &shop),
+            ("local.news.example.com", &local),
+        ]);
+
+        let news = resolution.get("news.example.com").unwrap();
+        assert_eq!(news.referrer.as_deref(), Some("example.com"));
+        let owner = news.owner_domain.as_ref().unwrap();
+        assert_eq!(
+            (owner.values.clone(), owner.declared_by.as_str()),
+            (vec!["Example.com".to_string()], "example.com")
+        );
+        assert!(owner.is_inherited("news.example.com"));
+
+        let local = resolution.get("local.news.example.com").unwrap();
+        assert_eq!(local.referrer.as_deref(), Some("news.example.com"));
+        assert_eq!(
+            local.owner_domain.as_ref().unwrap().declared_by,
+            "example.com"
+        );
+        assert_eq!(
+            local.manager_domain.as_ref().unwrap().declared_by,
+            "local.news.example.com"
+        );
+
+        let conflicts: Vec<String> = resolution.conflicts.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            conflicts,
+            vec![
+                "shop.example.com declares OWNERDOMAIN=other.com, example.com declares Example.com",
+                "local.news.example.com declares MANAGERDOMAIN=local.com, example.com declares manager.com,US",
+            ]
+        );
+    }
+}
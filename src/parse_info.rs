@@ -0,0 +1,84 @@
+//! Instrumentation of parses, for pipelines tracking parser performance per
+//! document without timing every call themselves
+
+use crate::json::JsonValue;
+use std::time::Duration;
+
+/// Time taken and size of a parse, as returned by
+/// `AdsTxt::parse_lenient_instrumented`
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ParseInfo {
+    pub elapsed: Duration,
+    /// Length of the text in bytes
+    pub bytes: usize,
+    pub lines: usize,
+    pub records: usize,
+    pub variables: usize,
+    /// Number of invalid lines skipped
+    pub warnings: usize,
+}
+
+impl ParseInfo {
+    /// Parsing throughput, 0 if no time was measured
+    pub fn bytes_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            (
+                "elapsed_us".to_string(),
+                JsonValue::number(self.elapsed.as_micros()),
+            ),
+            ("bytes".to_string(), JsonValue::number(self.bytes)),
+            ("lines".to_string(), JsonValue::number(self.lines)),
+            ("records".to_string(), JsonValue::number(self.records)),
+            ("variables".to_string(), JsonValue::number(self.variables)),
+            ("warnings".to_string(), JsonValue::number(self.warnings)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AdsTxt;
+
+    #[test]
+    fn instrumenting_parses() {
+        let text = "# comment\na.com, 1, DIRECT\ncontact=x\nbad\n\nb.com, 2, RESELLER";
+        let (ads_txt, errors, info) = AdsTxt::parse_lenient_instrumented(text);
+        assert_eq!((ads_txt, errors), AdsTxt::parse_lenient(text));
+        assert_eq!(
+            ParseInfo {
+                elapsed: Duration::default(),
+                ..info
+            },
+            ParseInfo {
+                elapsed: Duration::default(),
+                bytes: text.len(),
+                lines: 6,
+                records: 2,
+                variables: 1,
+                warnings: 1,
+            }
+        );
+
+        let info = ParseInfo {
+            elapsed: Duration::from_millis(500),
+            bytes: 1000,
+            ..ParseInfo::default()
+        };
+        assert_eq!(info.bytes_per_second(), 2000.0);
+        assert_eq!(ParseInfo::default().bytes_per_second(), 0.0);
+        assert_eq!(
+            info.to_json().to_string(),
+            r#"{"elapsed_us":500000,"bytes":1000,"lines":0,"records":0,"variables":0,"warnings":0}"#
+        );
+    }
+}
@@ -0,0 +1,12 @@
+//! The commonly used types, imported with `use rs_ads_txt::prelude::*;`
+
+pub use crate::{AccountRelation, AdsTxt, AdsTxtError, DataRecord, FileKind, Variable};
+
+#[cfg(feature = "std")]
+pub use crate::corpus::{AdsTxtCorpus, CorpusEntry};
+#[cfg(feature = "std")]
+pub use crate::diff::AdsTxtDiff;
+#[cfg(feature = "std")]
+pub use crate::fetch::{CrawlResult, Crawler, CrawlerConfig, FetchError, HttpClient};
+#[cfg(feature = "std")]
+pub use crate::validate::{validate, Diagnostic, Validator};
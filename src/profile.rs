@@ -0,0 +1,265 @@
+//! Spec profiles pinning the version of the ads.txt or app-ads.txt spec a
+//! file is parsed and serialized for, for partners expecting an older one
+//!
+//! Plain `AdsTxt::parse` accepts any variable and keeps every record. Parsing
+//! with a profile rejects variables the version does not define, drops the
+//! placeholder record of versions which give it meaning and accepts
+//! extension fields only where the version defines them.
+
+use crate::{ads_txt_error, AccountRelation, AdsTxt, AdsTxtError, DataRecord, FileKind, Result};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Record declaring that no seller is authorized, since ads.txt 1.1
+pub const PLACEHOLDER_DOMAIN: &str = "placeholder.example.com";
+pub const PLACEHOLDER_ID: &str = "placeholder";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[non_exhaustive]
+pub enum SpecProfile {
+    AdsTxt102,
+    #[default]
+    AdsTxt11,
+    AppAdsTxt10,
+}
+
+impl SpecProfile {
+    pub const ALL: &'static [SpecProfile] = &[
+        SpecProfile::AdsTxt102,
+        SpecProfile::AdsTxt11,
+        SpecProfile::AppAdsTxt10,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpecProfile::AdsTxt102 => "ads.txt-1.0.2",
+            SpecProfile::AdsTxt11 => "ads.txt-1.1",
+            SpecProfile::AppAdsTxt10 => "app-ads.txt-1.0",
+        }
+    }
+
+    /// Finds a profile by its `as_str` name
+    pub fn from_name(name: &str) -> Option<SpecProfile> {
+        SpecProfile::ALL
+            .iter()
+            .copied()
+            .find(|p| p.as_str() == name.trim())
+    }
+
+    pub fn file_kind(&self) -> FileKind {
+        match self {
+            SpecProfile::AdsTxt102 | SpecProfile::AdsTxt11 => FileKind::AdsTxt,
+            SpecProfile::AppAdsTxt10 => FileKind::AppAdsTxt,
+        }
+    }
+
+    /// Upper case names of the variables the version defines
+    pub fn variables(&self) -> &'static [&'static str] {
+        match self {
+            SpecProfile::AdsTxt102 => &["CONTACT", "SUBDOMAIN"],
+            SpecProfile::AdsTxt11 => &[
+                "CONTACT",
+                "SUBDOMAIN",
+                "INVENTORYPARTNERDOMAIN",
+                "OWNERDOMAIN",
+                "MANAGERDOMAIN",
+            ],
+            SpecProfile::AppAdsTxt10 => &["CONTACT"],
+        }
+    }
+
+    pub fn supports_variable(&self, name: &str) -> bool {
+        self.variables()
+            .iter()
+            .any(|v| v.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Whether the placeholder record declares that no seller is
+    /// authorized, rather than being an ordinary record
+    pub fn has_placeholder(&self) -> bool {
+        *self == SpecProfile::AdsTxt11
+    }
+
+    /// Whether records may end with extension fields after a `;`
+    pub fn supports_extension_fields(&self) -> bool {
+        *self == SpecProfile::AdsTxt11
+    }
+
+    /// The placeholder record of versions which have one
+    pub fn placeholder(&self) -> Option<DataRecord> {
+        if self.has_placeholder() {
+            Some(DataRecord::new(
+                PLACEHOLDER_DOMAIN,
+                PLACEHOLDER_ID,
+                AccountRelation::Direct,
+                Some(PLACEHOLDER_ID.into()),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the record is the placeholder of versions which have one
+    pub fn is_placeholder(&self, record: &DataRecord) -> bool {
+        self.has_placeholder()
+            && record.domain.eq_ignore_ascii_case(PLACEHOLDER_DOMAIN)
+            && record.publisher_id.eq_ignore_ascii_case(PLACEHOLDER_ID)
+    }
+
+    /// Parses a file for the version, failing on the first line which is
+    /// invalid or a variable the version does not define
+    pub fn parse(&self, text: &str) -> Result<AdsTxt> {
+        let mut ads_txt = AdsTxt::empty();
+        for line in text.lines() {
+            self.push_line(&mut ads_txt, line)?;
+        }
+        Ok(ads_txt)
+    }
+
+    /// Parses a file for the version like `parse`, skipping invalid lines
+    /// and unsupported variables with an error for each
+    pub fn parse_lenient(&self, text: &str) -> (AdsTxt, Vec<AdsTxtError>) {
+        let mut ads_txt = AdsTxt::empty();
+        let mut errors = Vec::new();
+        for line in text.lines() {
+            if let Err(e) = self.push_line(&mut ads_txt, line) {
+                errors.push(e);
+            }
+        }
+        (ads_txt, errors)
+    }
+
+    fn push_line(&self, ads_txt: &mut AdsTxt, line: &str) -> Result<()> {
+        let line = line.trim_start();
+        if line.is_empty() || AdsTxt::is_comment(line) {
+            return Ok(());
+        }
+
+        if let Some(record) = self.parse_record(line) {
+            if !self.is_placeholder(&record) {
+                ads_txt.records.push(record);
+            }
+            return Ok(());
+        }
+        if DataRecord::fields(line).is_some() {
+            return ads_txt_error(&format!("Invalid ads.txt line: {}", line));
+        }
+
+        match crate::Variable::try_parse(line) {
+            Some(variable) if self.supports_variable(&variable.name) => {
+                ads_txt.variables.push(variable);
+                Ok(())
+            }
+            Some(variable) => ads_txt_error(&format!(
+                "Variable not defined by {}: {}",
+                self, variable.name
+            )),
+            None => ads_txt_error(&format!("Invalid ads.txt line: {}", line)),
+        }
+    }
+
+    /// Parses a record, without its extension fields where the version
+    /// defines them. Other versions take a `;` for an invalid record rather
+    /// than part of its last field.
+    fn parse_record(&self, line: &str) -> Option<DataRecord> {
+        match line.split_once(';') {
+            Some((fields, _extensions)) if self.supports_extension_fields() => {
+                DataRecord::try_parse(fields)
+            }
+            Some(_) => None,
+            None => DataRecord::try_parse(line),
+        }
+    }
+
+    /// Copy of the file without the variables the version does not define
+    /// and without the placeholder record
+    pub fn restrict(&self, ads_txt: &AdsTxt) -> AdsTxt {
+        AdsTxt {
+            records: ads_txt
+                .records
+                .iter()
+                .filter(|r| !self.is_placeholder(r))
+                .cloned()
+                .collect(),
+            variables: ads_txt
+                .variables
+                .iter()
+                .filter(|v| self.supports_variable(&v.name))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Renders the file as text for the version. A file without records
+    /// gets the placeholder record of versions which have one, so that it
+    /// is not read as missing.
+    pub fn to_text(&self, ads_txt: &AdsTxt) -> String {
+        let mut restricted = self.restrict(ads_txt);
+        if restricted.records.is_empty() {
+            restricted.records.extend(self.placeholder());
+        }
+
+        let mut text = String::new();
+        restricted
+            .write_into(&mut text)
+            .expect("writing to a String cannot fail");
+        text
+    }
+}
+
+impl core::fmt::Display for SpecProfile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_with_profiles() {
+        let text = "a.com, 1, DIRECT, abc; region=eu\n\
+                    placeholder.example.com, placeholder, DIRECT, placeholder\n\
+                    contact=ads@a.com\n\
+                    OWNERDOMAIN=a.com\n";
+
+        let ads_txt = SpecProfile::AdsTxt11.parse(text).unwrap();
+        assert_eq!(
+            ads_txt,
+            AdsTxt::parse("a.com, 1, DIRECT, abc\ncontact=ads@a.com\nOWNERDOMAIN=a.com").unwrap()
+        );
+
+        assert_eq!(
+            SpecProfile::AdsTxt102.parse(text).unwrap_err().to_string(),
+            "Invalid ads.txt line: a.com, 1, DIRECT, abc; region=eu"
+        );
+        let (ads_txt, errors) = SpecProfile::AdsTxt102.parse_lenient(text);
+        assert_eq!(ads_txt.records.len(), 1);
+        assert_eq!(ads_txt.records[0].domain, "placeholder.example.com");
+        assert_eq!(
+            errors.last().unwrap().to_string(),
+            "Variable not defined by ads.txt-1.0.2: OWNERDOMAIN"
+        );
+
+        let ads_txt = AdsTxt::parse("subdomain=b.a.com\ncontact=x\nmanagerdomain=m.com").unwrap();
+        assert_eq!(
+            SpecProfile::AdsTxt102.to_text(&ads_txt),
+            "subdomain=b.a.com\ncontact=x\n"
+        );
+        assert_eq!(SpecProfile::AppAdsTxt10.to_text(&ads_txt), "contact=x\n");
+        assert_eq!(
+            SpecProfile::AdsTxt11.to_text(&ads_txt),
+            "placeholder.example.com, placeholder, DIRECT, placeholder\n\
+             subdomain=b.a.com\ncontact=x\nmanagerdomain=m.com\n"
+        );
+
+        assert_eq!(SpecProfile::default(), SpecProfile::AdsTxt11);
+        assert_eq!(
+            SpecProfile::from_name("app-ads.txt-1.0"),
+            Some(SpecProfile::AppAdsTxt10)
+        );
+        assert_eq!(SpecProfile::AppAdsTxt10.file_kind(), FileKind::AppAdsTxt);
+    }
+}
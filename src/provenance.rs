@@ -0,0 +1,364 @@
+//! Provenance of records, i.e. where and when each was found, for auditing
+//! files built from crawls and corpora
+//!
+//! `ProvenancedAdsTxt` keeps a `Provenance` beside every record of an
+//! `AdsTxt`, and carries it through diffs and the JSON and CSV exports.
+
+use crate::corpus::CorpusEntry;
+use crate::diff::{AdsTxtDiff, SeatChange};
+use crate::fetch::{FetchedAdsTxt, SubdomainCrawl};
+use crate::json::JsonValue;
+use crate::serialize::{csv_field, record_to_csv, record_to_json, variable_to_json, CSV_HEADER};
+use crate::{AdsTxt, AdsTxtError, DataRecord};
+use std::time::SystemTime;
+
+/// Where and when a record was found
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Provenance {
+    /// Domain whose file lists the record
+    pub source_domain: String,
+    /// 1-based line of the record in the file, if known
+    pub line: Option<usize>,
+    pub fetched_at: Option<SystemTime>,
+    /// Whether the file was reached through a SUBDOMAIN referral rather
+    /// than fetched for itself
+    pub via_referral: bool,
+}
+
+impl Provenance {
+    pub fn new(source_domain: &str) -> Self {
+        Provenance {
+            source_domain: source_domain.to_string(),
+            line: None,
+            fetched_at: None,
+            via_referral: false,
+        }
+    }
+
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn with_fetched_at(mut self, fetched_at: SystemTime) -> Self {
+        self.fetched_at = Some(fetched_at);
+        self
+    }
+
+    pub fn with_referral(mut self, via_referral: bool) -> Self {
+        self.via_referral = via_referral;
+        self
+    }
+
+    /// Renders the provenance as `{"source_domain", "line", "fetched_at",
+    /// "via_referral"}`, with the fetch time in Unix seconds
+    pub fn to_json(&self) -> JsonValue {
+        let number_or_null = |n: Option<u64>| n.map_or(JsonValue::Null, JsonValue::number);
+        JsonValue::Object(vec![
+            (
+                "source_domain".to_string(),
+                JsonValue::string(&self.source_domain),
+            ),
+            (
+                "line".to_string(),
+                number_or_null(self.line.map(|l| l as u64)),
+            ),
+            (
+                "fetched_at".to_string(),
+                number_or_null(self.fetched_at.and_then(unix_secs)),
+            ),
+            (
+                "via_referral".to_string(),
+                JsonValue::Bool(self.via_referral),
+            ),
+        ])
+    }
+}
+
+fn unix_secs(at: SystemTime) -> Option<u64> {
+    at.duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// File whose records each have a provenance. Records of several files,
+/// e.g. of a domain and its subdomains, can be combined in one.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProvenancedAdsTxt {
+    ads_txt: AdsTxt,
+    /// Provenance of each record of `ads_txt`, by index
+    provenance: Vec<Provenance>,
+}
+
+impl Default for ProvenancedAdsTxt {
+    fn default() -> Self {
+        ProvenancedAdsTxt {
+            ads_txt: AdsTxt::empty(),
+            provenance: vec![],
+        }
+    }
+}
+
+impl ProvenancedAdsTxt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a file leniently, giving each record the source's provenance
+    /// with the line of the record
+    pub fn parse(text: &str, source: &Provenance) -> (ProvenancedAdsTxt, Vec<AdsTxtError>) {
+        let (ads_txt, lines, errors) = AdsTxt::parse_lenient_with_lines(text);
+        let provenance = lines
+            .into_iter()
+            .map(|line| source.clone().with_line(line))
+            .collect();
+        (
+            ProvenancedAdsTxt {
+                ads_txt,
+                provenance,
+            },
+            errors,
+        )
+    }
+
+    /// Gives every record of a file the same provenance
+    pub fn from_ads_txt(ads_txt: &AdsTxt, source: &Provenance) -> Self {
+        ProvenancedAdsTxt {
+            ads_txt: ads_txt.clone(),
+            provenance: vec![source.clone(); ads_txt.records.len()],
+        }
+    }
+
+    /// Records of a fetched file with their lines and the fetch time
+    pub fn from_fetched(fetched: &FetchedAdsTxt, fetched_at: SystemTime) -> Self {
+        let source = Provenance::new(&fetched.domain).with_fetched_at(fetched_at);
+        let mut provenanced = Self::from_ads_txt(&fetched.ads_txt, &source);
+        for (provenance, line) in provenanced.provenance.iter_mut().zip(&fetched.record_lines) {
+            provenance.line = Some(*line);
+        }
+        provenanced
+    }
+
+    /// Records of every file fetched by following SUBDOMAIN referrals,
+    /// those of the subdomains marked as reached through a referral
+    pub fn from_subdomain_crawl(crawl: &SubdomainCrawl, fetched_at: SystemTime) -> Self {
+        let mut provenanced = Self::new();
+        for (i, (host, fetched)) in crawl.fetched().enumerate() {
+            let mut file = Self::from_fetched(fetched, fetched_at);
+            for provenance in &mut file.provenance {
+                provenance.source_domain = host.to_string();
+                provenance.via_referral = i > 0;
+            }
+            provenanced.extend(file);
+        }
+        provenanced
+    }
+
+    /// Records of a corpus entry, found at its fetch time
+    pub fn from_corpus_entry(entry: &CorpusEntry) -> Self {
+        let source = Provenance::new(entry.publisher()).with_fetched_at(entry.metadata.fetched_at);
+        Self::from_ads_txt(&entry.ads_txt, &source)
+    }
+
+    pub fn push(&mut self, record: DataRecord, provenance: Provenance) {
+        self.ads_txt.records.push(record);
+        self.provenance.push(provenance);
+    }
+
+    /// Adds the records and variables of another file
+    pub fn extend(&mut self, other: ProvenancedAdsTxt) {
+        self.ads_txt.records.extend(other.ads_txt.records);
+        self.ads_txt.variables.extend(other.ads_txt.variables);
+        self.provenance.extend(other.provenance);
+    }
+
+    pub fn ads_txt(&self) -> &AdsTxt {
+        &self.ads_txt
+    }
+
+    pub fn into_ads_txt(self) -> AdsTxt {
+        self.ads_txt
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&DataRecord, &Provenance)> {
+        self.ads_txt.records.iter().zip(&self.provenance)
+    }
+
+    /// Provenance of the first record equal to the given one
+    pub fn provenance_of(&self, record: &DataRecord) -> Option<&Provenance> {
+        self.iter().find(|(r, _)| *r == record).map(|(_, p)| p)
+    }
+
+    /// Seat changes between two files, with the provenance of the old and
+    /// new records of each
+    pub fn diff(old: &ProvenancedAdsTxt, new: &ProvenancedAdsTxt) -> Vec<ProvenancedChange> {
+        AdsTxtDiff::between(&old.ads_txt, &new.ads_txt)
+            .seats
+            .into_iter()
+            .map(|change| {
+                let (old_record, new_record) = match &change {
+                    SeatChange::Added(record) => (None, Some(record)),
+                    SeatChange::Removed(record) => (Some(record), None),
+                    SeatChange::Changed { old, new } => (Some(old), Some(new)),
+                };
+                ProvenancedChange {
+                    old: old_record.and_then(|r| old.provenance_of(r)).cloned(),
+                    new: new_record.and_then(|r| new.provenance_of(r)).cloned(),
+                    change,
+                }
+            })
+            .collect()
+    }
+
+    /// Renders the file like `serialize::ads_txt_to_json`, each record with a
+    /// `provenance` member
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            (
+                "records".to_string(),
+                JsonValue::Array(
+                    self.iter()
+                        .map(|(record, provenance)| {
+                            with_provenance(record_to_json(record), provenance)
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "variables".to_string(),
+                JsonValue::Array(
+                    self.ads_txt
+                        .variables
+                        .iter()
+                        .map(variable_to_json)
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+
+    /// Renders the records like `serialize::ads_txt_to_csv`, with the
+    /// provenance in further columns and the fetch time in Unix seconds
+    pub fn to_csv(&self) -> String {
+        let optional = |n: Option<u64>| n.map_or(String::new(), |n| n.to_string());
+        let mut csv = format!(
+            "{},source_domain,line,fetched_at,via_referral\n",
+            CSV_HEADER
+        );
+        for (record, provenance) in self.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                record_to_csv(record),
+                csv_field(&provenance.source_domain),
+                optional(provenance.line.map(|l| l as u64)),
+                optional(provenance.fetched_at.and_then(unix_secs)),
+                provenance.via_referral
+            ));
+        }
+        csv
+    }
+}
+
+fn with_provenance(record: JsonValue, provenance: &Provenance) -> JsonValue {
+    match record {
+        JsonValue::Object(mut members) => {
+            members.push(("provenance".to_string(), provenance.to_json()));
+            JsonValue::Object(members)
+        }
+        json => json,
+    }
+}
+
+/// Seat change with the provenance of its old and new records
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProvenancedChange {
+    pub change: SeatChange,
+    /// Provenance of the removed or changed record of the old file
+    pub old: Option<Provenance>,
+    /// Provenance of the added or changed record of the new file
+    pub new: Option<Provenance>,
+}
+
+impl ProvenancedChange {
+    /// Renders the change like `SeatChange::to_json`, with `old_provenance`
+    /// and `new_provenance` members where known
+    pub fn to_json(&self) -> JsonValue {
+        match self.change.to_json() {
+            JsonValue::Object(mut members) => {
+                for (key, provenance) in
+                    [("old_provenance", &self.old), ("new_provenance", &self.new)]
+                {
+                    if let Some(provenance) = provenance {
+                        members.push((key.to_string(), provenance.to_json()));
+                    }
+                }
+                JsonValue::Object(members)
+            }
+            json => json,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn tracking_provenance() {
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let source = Provenance::new("example.com").with_fetched_at(at);
+        let (old, errors) = ProvenancedAdsTxt::parse(
+            "# sellers\na.com, 1, DIRECT\nbad\nb.com, 2, DIRECT",
+            &source,
+        );
+        assert_eq!(errors.len(), 1);
+        let lines: Vec<Option<usize>> = old.iter().map(|(_, p)| p.line).collect();
+        assert_eq!(lines, vec![Some(2), Some(4)]);
+
+        let mut new = ProvenancedAdsTxt::from_ads_txt(
+            &AdsTxt::parse("b.com, 2, RESELLER").unwrap(),
+            &Provenance::new("games.example.com").with_referral(true),
+        );
+        new.push(
+            DataRecord::parse("c.com, 3, DIRECT").unwrap(),
+            Provenance::new("example.com").with_line(7),
+        );
+
+        let changes = ProvenancedAdsTxt::diff(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].old.as_ref().unwrap().line, Some(2));
+        assert_eq!(changes[0].new, None);
+        assert_eq!(
+            changes[1].to_json().to_string(),
+            r#"{"change":"changed","old":{"domain":"b.com","publisher_id":"2","relation":"DIRECT","cert_authority":null},"new":{"domain":"b.com","publisher_id":"2","relation":"RESELLER","cert_authority":null},"old_provenance":{"source_domain":"example.com","line":4,"fetched_at":1600000000,"via_referral":false},"new_provenance":{"source_domain":"games.example.com","line":null,"fetched_at":null,"via_referral":true}}"#
+        );
+
+        assert_eq!(
+            new.to_csv(),
+            "domain,publisher_id,relation,cert_authority,source_domain,line,fetched_at,via_referral\n\
+             b.com,2,RESELLER,,games.example.com,,,true\n\
+             c.com,3,DIRECT,,example.com,7,,false\n"
+        );
+        assert_eq!(
+            new.to_json().get("records").unwrap().as_array().unwrap()[1]
+                .get("provenance")
+                .unwrap()
+                .get("line")
+                .unwrap()
+                .as_i64(),
+            Some(7)
+        );
+
+        let entry = CorpusEntry::new(
+            "example.com",
+            old.ads_txt().clone(),
+            crate::corpus::CorpusMetadata::new(at, 0),
+        );
+        let from_corpus = ProvenancedAdsTxt::from_corpus_entry(&entry);
+        assert_eq!(
+            from_corpus.provenance_of(&old.ads_txt().records[1]),
+            Some(&source)
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Comparison of publisher ids. Ad systems differ on whether `AB12` and
+//! `ab12` are the same seller account, so the comparison is set per ad
+//! system, falling back to a default for the others.
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+/// How two publisher ids of the same ad system are compared
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum IdComparison {
+    #[default]
+    Exact,
+    /// Ids differing only in the case of ASCII letters are the same
+    CaseInsensitive,
+}
+
+impl IdComparison {
+    pub const ALL: &'static [IdComparison] = &[IdComparison::Exact, IdComparison::CaseInsensitive];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdComparison::Exact => "exact",
+            IdComparison::CaseInsensitive => "case-insensitive",
+        }
+    }
+
+    /// Finds a comparison by its `as_str` name
+    pub fn from_name(name: &str) -> Option<IdComparison> {
+        IdComparison::ALL
+            .iter()
+            .copied()
+            .find(|c| c.as_str() == name.trim())
+    }
+
+    pub fn same(&self, a: &str, b: &str) -> bool {
+        match self {
+            IdComparison::Exact => a == b,
+            IdComparison::CaseInsensitive => a.eq_ignore_ascii_case(b),
+        }
+    }
+
+    /// Form of the id which is equal for all ids which are the same, for
+    /// keying maps by seat
+    pub fn key<'a>(&self, id: &'a str) -> Cow<'a, str> {
+        match self {
+            IdComparison::CaseInsensitive if id.bytes().any(|b| b.is_ascii_uppercase()) => {
+                Cow::Owned(id.to_ascii_lowercase())
+            }
+            _ => Cow::Borrowed(id),
+        }
+    }
+}
+
+/// Comparison of publisher ids by ad system, used to identify seats when
+/// diffing, merging and checking authorization
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PublisherIdPolicy {
+    default: IdComparison,
+    /// Comparisons keyed by lowercase ad system domain
+    overrides: BTreeMap<String, IdComparison>,
+}
+
+impl PublisherIdPolicy {
+    /// Policy comparing the ids of every ad system the same way
+    pub fn new(default: IdComparison) -> Self {
+        PublisherIdPolicy {
+            default,
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Compares the ids of the ad systems without an override by the
+    /// comparison
+    pub fn with_default(mut self, default: IdComparison) -> Self {
+        self.default = default;
+        self
+    }
+
+    /// Compares the ids of the given ad system differently from the default
+    pub fn with_override(mut self, ad_system: &str, comparison: IdComparison) -> Self {
+        self.overrides.insert(ad_system_key(ad_system), comparison);
+        self
+    }
+
+    pub fn comparison(&self, ad_system: &str) -> IdComparison {
+        self.overrides
+            .get(&ad_system_key(ad_system))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Whether two ids are the same seller account of the ad system
+    pub fn same_id(&self, ad_system: &str, a: &str, b: &str) -> bool {
+        self.comparison(ad_system).same(a.trim(), b.trim())
+    }
+
+    /// Form of the id which is equal for the same seller accounts of the ad
+    /// system
+    pub fn key<'a>(&self, ad_system: &str, id: &'a str) -> Cow<'a, str> {
+        self.comparison(ad_system).key(id.trim())
+    }
+}
+
+fn ad_system_key(ad_system: &str) -> String {
+    ad_system.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comparing_publisher_ids() {
+        let policy = PublisherIdPolicy::default();
+        assert!(policy.same_id("a.com", "AB12", " AB12"));
+        assert!(!policy.same_id("a.com", "AB12", "ab12"));
+
+        let policy = PublisherIdPolicy::new(IdComparison::CaseInsensitive)
+            .with_override("Strict.com.", IdComparison::Exact);
+        assert!(policy.same_id("a.com", "AB12", "ab12"));
+        assert!(!policy.same_id("strict.com", "AB12", "ab12"));
+        assert_eq!(policy.comparison("STRICT.COM"), IdComparison::Exact);
+        assert_eq!(policy.key("a.com", "AB12"), "ab12");
+        assert_eq!(policy.key("strict.com", "AB12"), "AB12");
+
+        assert_eq!(
+            IdComparison::from_name("case-insensitive"),
+            Some(IdComparison::CaseInsensitive)
+        );
+        assert_eq!(IdComparison::from_name("fuzzy"), None);
+
+        let ads_txt =
+            crate::AdsTxt::parse("a.com, AB12, DIRECT\nstrict.com, CD34, DIRECT").unwrap();
+        assert!(ads_txt.is_authorized("A.com", "ab12", &policy));
+        assert!(!ads_txt.is_authorized("strict.com", "cd34", &policy));
+        assert!(ads_txt.is_authorized("strict.com", "CD34", &policy));
+        assert!(!ads_txt.is_authorized("a.com", "ab12", &PublisherIdPolicy::default()));
+        assert_eq!(
+            ads_txt
+                .seat_records_with_policy("a.com", "ab12", &policy)
+                .len(),
+            1
+        );
+        assert!(ads_txt.seat_records("a.com", "ab12").is_empty());
+    }
+}
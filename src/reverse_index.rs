@@ -0,0 +1,193 @@
+//! Reverse index over a corpus of ads.txt files, answering which publishers
+//! list a given ad system or seat
+
+use crate::normalize::{DefaultNormalizer, Normalizer};
+use crate::{AccountRelation, AdsTxt};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Listing {
+    pub publisher: String,
+    pub seller_id: String,
+    pub relation: AccountRelation,
+    pub cert_authority: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReverseIndex {
+    /// Ad system → publisher → records listing the ad system
+    listings: HashMap<String, BTreeMap<String, Vec<Listing>>>,
+    /// Normalizer of the domains the index is keyed and queried by
+    normalizer: Arc<dyn Normalizer + Send + Sync>,
+}
+
+impl Default for ReverseIndex {
+    fn default() -> Self {
+        Self::with_normalizer(DefaultNormalizer)
+    }
+}
+
+impl ReverseIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index normalizing publisher and ad system domains and seller ids by
+    /// the normalizer rather than by `DefaultNormalizer`
+    pub fn with_normalizer<N: Normalizer + Send + Sync + 'static>(normalizer: N) -> Self {
+        ReverseIndex {
+            listings: HashMap::new(),
+            normalizer: Arc::new(normalizer),
+        }
+    }
+
+    /// Builds the index from (publisher domain, ads.txt) pairs
+    pub fn build<'a, I>(files: I) -> ReverseIndex
+    where
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+    {
+        let mut index = ReverseIndex::new();
+        for (publisher, ads_txt) in files {
+            index.add_ads_txt(publisher, ads_txt);
+        }
+        index
+    }
+
+    /// Adds the ads.txt file of a publisher, replacing any earlier one
+    pub fn add_ads_txt(&mut self, publisher: &str, ads_txt: &AdsTxt) {
+        let publisher = self.normalizer.domain(publisher);
+        self.remove(&publisher);
+
+        for record in &ads_txt.records {
+            self.listings
+                .entry(self.normalizer.domain(&record.domain))
+                .or_default()
+                .entry(publisher.clone())
+                .or_default()
+                .push(Listing {
+                    publisher: publisher.clone(),
+                    seller_id: self.normalizer.publisher_id(&record.publisher_id),
+                    relation: record.acc_relation.clone(),
+                    cert_authority: record.cert_authority.as_deref().map(str::to_string),
+                });
+        }
+    }
+
+    /// Removes the ads.txt file of a publisher
+    pub fn remove(&mut self, publisher: &str) {
+        let publisher = self.normalizer.domain(publisher);
+        for publishers in self.listings.values_mut() {
+            publishers.remove(&publisher);
+        }
+        self.listings.retain(|_, publishers| !publishers.is_empty());
+    }
+
+    /// Ad systems listed by any publisher
+    pub fn ad_systems(&self) -> Vec<&str> {
+        let mut ad_systems: Vec<&str> = self.listings.keys().map(|a| a.as_str()).collect();
+        ad_systems.sort_unstable();
+        ad_systems
+    }
+
+    /// Publishers listing the ad system, sorted
+    pub fn publishers(&self, ad_system: &str) -> Vec<&str> {
+        match self.listings.get(&self.normalizer.domain(ad_system)) {
+            Some(publishers) => publishers.keys().map(|p| p.as_str()).collect(),
+            None => vec![],
+        }
+    }
+
+    /// Every record listing the ad system, sorted by publisher
+    pub fn listings(&self, ad_system: &str) -> Vec<&Listing> {
+        match self.listings.get(&self.normalizer.domain(ad_system)) {
+            Some(publishers) => publishers.values().flatten().collect(),
+            None => vec![],
+        }
+    }
+
+    /// Every record listing the seat on the ad system, sorted by publisher
+    pub fn seat_listings(&self, ad_system: &str, seat: &str) -> Vec<&Listing> {
+        let seat = self.normalizer.publisher_id(seat);
+        self.listings(ad_system)
+            .into_iter()
+            .filter(|l| l.seller_id == seat)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::AdsTxtDiff;
+
+    #[test]
+    fn querying_reverse_index() {
+        let a = AdsTxt::parse(
+            r"
+            exchange.com, 1, DIRECT, abc
+            Exchange.com, 2, RESELLER
+            other.com, 1, DIRECT
+            ",
+        )
+        .unwrap();
+        let b = AdsTxt::parse("exchange.com, 1, RESELLER").unwrap();
+
+        let mut index = ReverseIndex::build(vec![("www.b.com", &b), ("a.com", &a)]);
+
+        assert_eq!(index.ad_systems(), vec!["exchange.com", "other.com"]);
+        assert_eq!(index.publishers("EXCHANGE.COM"), vec!["a.com", "b.com"]);
+        assert_eq!(
+            index.seat_listings("exchange.com", "1"),
+            vec![
+                &Listing {
+                    publisher: "a.com".to_string(),
+                    seller_id: "1".to_string(),
+                    relation: AccountRelation::Direct,
+                    cert_authority: Some("abc".to_string()),
+                },
+                &Listing {
+                    publisher: "b.com".to_string(),
+                    seller_id: "1".to_string(),
+                    relation: AccountRelation::Reseller,
+                    cert_authority: None,
+                },
+            ]
+        );
+        assert_eq!(index.listings("exchange.com").len(), 3);
+        assert!(index.publishers("unknown.com").is_empty());
+
+        // Re-adding a publisher replaces its listings
+        index.add_ads_txt("a.com", &AdsTxt::parse("exchange.com, 3, DIRECT").unwrap());
+        assert_eq!(index.ad_systems(), vec!["exchange.com"]);
+        assert!(index.seat_listings("exchange.com", "2").is_empty());
+
+        index.remove("b.com");
+        assert_eq!(index.publishers("exchange.com"), vec!["a.com"]);
+
+        /// Treats the ad system domains of `.net` and `.com` as one
+        #[derive(Debug)]
+        struct SameTld;
+        impl Normalizer for SameTld {
+            fn domain(&self, domain: &str) -> String {
+                let domain = DefaultNormalizer.domain(domain);
+                match domain.strip_suffix(".net") {
+                    Some(name) => format!("{}.com", name),
+                    None => domain,
+                }
+            }
+        }
+
+        let mut index = ReverseIndex::with_normalizer(SameTld);
+        index.add_ads_txt("b\u{fc}cher.de", &b);
+        assert_eq!(index.publishers("exchange.net"), vec!["xn--bcher-kva.de"]);
+        assert_eq!(
+            AdsTxtDiff::between_normalized(
+                &b,
+                &AdsTxt::parse("Exchange.NET, 1, RESELLER").unwrap(),
+                &SameTld
+            ),
+            AdsTxtDiff::default()
+        );
+    }
+}
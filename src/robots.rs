@@ -0,0 +1,226 @@
+//! Minimal robots.txt parser following RFC 9309
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+}
+
+impl RobotsTxt {
+    /// Robots rules which allow everything, used when no robots.txt is available
+    pub fn allow_all() -> Self {
+        RobotsTxt { groups: vec![] }
+    }
+
+    /// Robots rules which disallow everything, used when robots.txt is unreachable
+    pub fn disallow_all() -> Self {
+        RobotsTxt {
+            groups: vec![Group {
+                user_agents: vec!["*".to_string()],
+                rules: vec![Rule {
+                    allow: false,
+                    pattern: "/".to_string(),
+                }],
+            }],
+        }
+    }
+
+    pub fn parse(text: &str) -> RobotsTxt {
+        let mut groups: Vec<Group> = vec![];
+        // Whether the last non-comment line was a user-agent line, so that
+        // consecutive user-agent lines are collected into the same group
+        let mut in_agents = false;
+
+        for line in text.lines() {
+            let line = match line.find('#') {
+                Some(i) => &line[..i],
+                None => line,
+            };
+
+            let (key, value) = match line.find(':') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => continue,
+            };
+
+            if key.eq_ignore_ascii_case("user-agent") {
+                if !in_agents {
+                    groups.push(Group {
+                        user_agents: vec![],
+                        rules: vec![],
+                    });
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.user_agents.push(value.to_lowercase());
+                }
+                in_agents = true;
+            } else if key.eq_ignore_ascii_case("allow") || key.eq_ignore_ascii_case("disallow") {
+                in_agents = false;
+                // Rules before any user-agent line do not belong to a group
+                if let Some(group) = groups.last_mut() {
+                    // An empty disallow rule matches nothing
+                    if !value.is_empty() {
+                        group.rules.push(Rule {
+                            allow: key.eq_ignore_ascii_case("allow"),
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+            } else {
+                in_agents = false;
+            }
+        }
+
+        RobotsTxt { groups }
+    }
+
+    /// Checks whether the given path may be crawled by the given user agent
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        let rules = self.rules_for(user_agent);
+
+        let mut best: Option<&Rule> = None;
+        for rule in rules {
+            if !pattern_matches(&rule.pattern, path) {
+                continue;
+            }
+
+            best = match best {
+                // The longest match wins, and allow wins a tie
+                Some(b)
+                    if b.pattern.len() > rule.pattern.len()
+                        || (b.pattern.len() == rule.pattern.len() && b.allow) =>
+                {
+                    Some(b)
+                }
+                _ => Some(rule),
+            };
+        }
+
+        best.is_none_or(|rule| rule.allow)
+    }
+
+    fn rules_for(&self, user_agent: &str) -> Vec<&Rule> {
+        let token = product_token(user_agent);
+
+        let matching: Vec<&Group> = self
+            .groups
+            .iter()
+            .filter(|g| g.user_agents.iter().any(|ua| ua != "*" && *ua == token))
+            .collect();
+
+        let groups = if matching.is_empty() {
+            self.groups
+                .iter()
+                .filter(|g| g.user_agents.iter().any(|ua| ua == "*"))
+                .collect()
+        } else {
+            matching
+        };
+
+        groups.into_iter().flat_map(|g| g.rules.iter()).collect()
+    }
+}
+
+/// Extracts the product token from a user agent, e.g. `adstxtbot` from `AdsTxtBot/1.0`
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+/// Matches a robots.txt path pattern, supporting `*` wildcards and a `$` end anchor
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let mut rest = path;
+
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 && anchored {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_patterns() {
+        assert!(pattern_matches("/", "/ads.txt"));
+        assert!(pattern_matches("/ads", "/ads.txt"));
+        assert!(pattern_matches("/*.txt", "/ads.txt"));
+        assert!(pattern_matches("/ads.txt$", "/ads.txt"));
+        assert!(!pattern_matches("/ads$", "/ads.txt"));
+        assert!(!pattern_matches("/private", "/ads.txt"));
+        assert!(pattern_matches("/*$", "/ads.txt"));
+    }
+
+    #[test]
+    fn parsing_robots_txt() {
+        let robots = RobotsTxt::parse(
+            r"
+            # Block everyone from ads.txt except our bot
+            User-agent: *
+            Disallow: /ads.txt
+
+            User-agent: AdsTxtBot
+            User-agent: otherbot
+            Allow: /
+            Disallow: /private
+            ",
+        );
+
+        assert!(!robots.is_allowed("SomeBot/2.0", "/ads.txt"));
+        assert!(robots.is_allowed("SomeBot/2.0", "/index.html"));
+        assert!(robots.is_allowed("AdsTxtBot/1.0", "/ads.txt"));
+        assert!(robots.is_allowed("otherbot", "/ads.txt"));
+        assert!(!robots.is_allowed("adstxtbot", "/private/x"));
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let robots = RobotsTxt::parse(
+            r"
+            User-agent: *
+            Disallow: /
+            Allow: /ads.txt
+            ",
+        );
+
+        assert!(robots.is_allowed("bot", "/ads.txt"));
+        assert!(!robots.is_allowed("bot", "/other.txt"));
+
+        assert!(RobotsTxt::allow_all().is_allowed("bot", "/ads.txt"));
+        assert!(!RobotsTxt::disallow_all().is_allowed("bot", "/ads.txt"));
+        assert!(RobotsTxt::parse("User-agent: *\nDisallow:").is_allowed("bot", "/ads.txt"));
+    }
+}
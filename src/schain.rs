@@ -0,0 +1,350 @@
+//! OpenRTB SupplyChain objects and their validation against ads.txt and
+//! sellers.json
+//!
+//! Spec - <https://github.com/InteractiveAdvertisingBureau/openrtb/blob/master/supplychainobject.md>
+
+use crate::cross_validation::{check_record, RecordVerdict};
+use crate::json::JsonValue;
+use crate::publisher_id::PublisherIdPolicy;
+use crate::sellers_json::SellersJson;
+use crate::{ads_txt_error, AdsTxt, Result};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SupplyChainNode {
+    /// Canonical domain of the ad system, as used in ads.txt
+    pub asi: String,
+    /// Seller id on the ad system, as used in ads.txt and sellers.json
+    pub sid: String,
+    /// Request id issued by this seller
+    pub rid: Option<String>,
+    pub name: Option<String>,
+    pub domain: Option<String>,
+    /// Whether this node is involved in the flow of payment
+    pub hp: bool,
+}
+
+impl SupplyChainNode {
+    pub fn new(asi: &str, sid: &str) -> Self {
+        SupplyChainNode {
+            asi: asi.trim().to_string(),
+            sid: sid.trim().to_string(),
+            rid: None,
+            name: None,
+            domain: None,
+            hp: true,
+        }
+    }
+
+    fn from_json(value: &JsonValue) -> Result<SupplyChainNode> {
+        let field = |key: &str| {
+            value
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        };
+
+        match (field("asi"), field("sid")) {
+            (Some(asi), Some(sid)) => Ok(SupplyChainNode {
+                rid: field("rid"),
+                name: field("name"),
+                domain: field("domain"),
+                hp: value.get("hp").and_then(|hp| hp.as_i64()) != Some(0),
+                ..SupplyChainNode::new(&asi, &sid)
+            }),
+            _ => ads_txt_error(&format!("Invalid supply chain node: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SupplyChain {
+    /// Whether the chain contains all nodes back to the owner of the inventory
+    pub complete: bool,
+    pub ver: String,
+    pub nodes: Vec<SupplyChainNode>,
+}
+
+impl SupplyChain {
+    pub fn new(complete: bool, nodes: &[SupplyChainNode]) -> Self {
+        SupplyChain {
+            complete,
+            ver: "1.0".to_string(),
+            nodes: nodes.to_vec(),
+        }
+    }
+
+    /// Parses a SupplyChain object in its JSON form
+    pub fn parse(text: &str) -> Result<SupplyChain> {
+        Self::from_json(&JsonValue::parse(text)?)
+    }
+
+    pub fn from_json(value: &JsonValue) -> Result<SupplyChain> {
+        let nodes = match value.get("nodes").and_then(|n| n.as_array()) {
+            Some(nodes) => nodes
+                .iter()
+                .map(SupplyChainNode::from_json)
+                .collect::<Result<Vec<_>>>()?,
+            None => return ads_txt_error("Invalid supply chain without nodes"),
+        };
+
+        Ok(SupplyChain {
+            complete: value.get("complete").and_then(|c| c.as_i64()) == Some(1),
+            ver: value
+                .get("ver")
+                .and_then(|v| v.as_str())
+                .unwrap_or("1.0")
+                .to_string(),
+            nodes,
+        })
+    }
+
+    /// Parses the compact string form, e.g. `1.0,1!exchange1.com,1234,1,,,`
+    pub fn parse_compact(text: &str) -> Result<SupplyChain> {
+        let mut parts = text.trim().split('!');
+
+        let header: Vec<&str> = parts.next().unwrap_or("").split(',').collect();
+        if header.len() != 2 {
+            return ads_txt_error(&format!("Invalid supply chain: {}", text));
+        }
+
+        let mut nodes = vec![];
+        for part in parts {
+            let fields: Vec<String> = part.split(',').map(percent_decode).collect();
+            if fields.len() < 2 || fields[0].is_empty() || fields[1].is_empty() {
+                return ads_txt_error(&format!("Invalid supply chain node: {}", part));
+            }
+
+            let optional = |i: usize| fields.get(i).filter(|f| !f.is_empty()).cloned();
+            nodes.push(SupplyChainNode {
+                rid: optional(3),
+                name: optional(4),
+                domain: optional(5),
+                hp: fields.get(2).is_none_or(|hp| hp != "0"),
+                ..SupplyChainNode::new(&fields[0], &fields[1])
+            });
+        }
+
+        Ok(SupplyChain {
+            complete: header[1] == "1",
+            ver: header[0].to_string(),
+            nodes,
+        })
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = text
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum HopVerdict {
+    Authorized,
+    /// The publisher's ads.txt does not list the node's ad system and seller id
+    NotInAdsTxt,
+    /// The node is listed in ads.txt but fails the sellers.json check
+    Unverified(RecordVerdict),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HopCheck {
+    /// Position of the node in the chain
+    pub index: usize,
+    pub node: SupplyChainNode,
+    pub verdict: HopVerdict,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SupplyChainReport {
+    /// Whether the chain claims to be complete
+    pub complete: bool,
+    pub hops: Vec<HopCheck>,
+}
+
+impl SupplyChainReport {
+    /// Returns the first hop which breaks authorization
+    pub fn broken_hop(&self) -> Option<&HopCheck> {
+        self.hops
+            .iter()
+            .find(|h| h.verdict != HopVerdict::Authorized)
+    }
+
+    /// Whether the chain is complete and every hop is authorized
+    pub fn is_authorized(&self) -> bool {
+        self.complete && !self.hops.is_empty() && self.broken_hop().is_none()
+    }
+}
+
+/// Validates every hop of a supply chain against the publisher's ads.txt and
+/// the sellers.json files keyed by ad system domain
+pub fn validate_supply_chain(
+    schain: &SupplyChain,
+    ads_txt: &AdsTxt,
+    sellers: &HashMap<String, SellersJson>,
+) -> SupplyChainReport {
+    validate_supply_chain_with_policy(schain, ads_txt, sellers, &PublisherIdPolicy::default())
+}
+
+/// Validates a supply chain like `validate_supply_chain`, matching the seller
+/// ids of nodes to publisher ids by the policy
+pub fn validate_supply_chain_with_policy(
+    schain: &SupplyChain,
+    ads_txt: &AdsTxt,
+    sellers: &HashMap<String, SellersJson>,
+    policy: &PublisherIdPolicy,
+) -> SupplyChainReport {
+    let hops = schain
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            let record = ads_txt.records.iter().find(|r| {
+                r.domain.eq_ignore_ascii_case(&node.asi)
+                    && policy.same_id(&node.asi, &r.publisher_id, &node.sid)
+            });
+
+            let verdict = match record {
+                None => HopVerdict::NotInAdsTxt,
+                Some(record) => {
+                    match check_record(record, sellers.get(&node.asi.to_lowercase())).verdict {
+                        RecordVerdict::Verified => HopVerdict::Authorized,
+                        verdict => HopVerdict::Unverified(verdict),
+                    }
+                }
+            };
+
+            HopCheck {
+                index,
+                node: node.clone(),
+                verdict,
+            }
+        })
+        .collect();
+
+    SupplyChainReport {
+        complete: schain.complete,
+        hops,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sellers_json::{Seller, SellerType};
+
+    #[test]
+    fn parsing_supply_chains() {
+        let schain = SupplyChain::parse(
+            r#"{"complete": 1, "ver": "1.0", "nodes": [
+                {"asi": "exchange1.com", "sid": "1234", "hp": 1, "rid": "bid-request-1"},
+                {"asi": "exchange2.com", "sid": "abcd", "hp": 1}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            schain,
+            SupplyChain::new(
+                true,
+                &[
+                    SupplyChainNode {
+                        rid: Some("bid-request-1".to_string()),
+                        ..SupplyChainNode::new("exchange1.com", "1234")
+                    },
+                    SupplyChainNode::new("exchange2.com", "abcd"),
+                ]
+            )
+        );
+
+        assert_eq!(
+            SupplyChain::parse_compact(
+                "1.0,1!exchange1.com,1234,1,bid-request-1,,!exchange2.com,abcd,1,,,"
+            ),
+            Ok(schain)
+        );
+        assert_eq!(
+            SupplyChain::parse_compact("1.0,0!exchange1.com,12%2C34,0,,pub%20name,"),
+            Ok(SupplyChain::new(
+                false,
+                &[SupplyChainNode {
+                    name: Some("pub name".to_string()),
+                    hp: false,
+                    ..SupplyChainNode::new("exchange1.com", "12,34")
+                }]
+            ))
+        );
+        assert!(SupplyChain::parse_compact("1.0!exchange1.com").is_err());
+    }
+
+    #[test]
+    fn validating_supply_chains() {
+        let ads_txt = AdsTxt::parse(
+            r"
+            exchange1.com, 1234, DIRECT
+            exchange2.com, abcd, RESELLER
+            ",
+        )
+        .unwrap();
+
+        let mut sellers = HashMap::new();
+        sellers.insert(
+            "exchange1.com".to_string(),
+            SellersJson::new(&[Seller::new("1234", SellerType::Publisher)]),
+        );
+        sellers.insert(
+            "exchange2.com".to_string(),
+            SellersJson::new(&[Seller::new("abcd", SellerType::Intermediary)]),
+        );
+
+        let schain = SupplyChain::new(
+            true,
+            &[
+                SupplyChainNode::new("exchange1.com", "1234"),
+                SupplyChainNode::new("exchange2.com", "abcd"),
+            ],
+        );
+        let report = validate_supply_chain(&schain, &ads_txt, &sellers);
+        assert!(report.is_authorized());
+
+        let schain = SupplyChain::new(
+            true,
+            &[
+                SupplyChainNode::new("exchange1.com", "1234"),
+                SupplyChainNode::new("exchange3.com", "xyz"),
+            ],
+        );
+        let report = validate_supply_chain(&schain, &ads_txt, &sellers);
+        assert!(!report.is_authorized());
+        let broken = report.broken_hop().unwrap();
+        assert_eq!(broken.index, 1);
+        assert_eq!(broken.verdict, HopVerdict::NotInAdsTxt);
+
+        sellers.remove("exchange2.com");
+        let schain = SupplyChain::new(false, &[SupplyChainNode::new("exchange2.com", "abcd")]);
+        let report = validate_supply_chain(&schain, &ads_txt, &sellers);
+        assert_eq!(
+            report.broken_hop().map(|h| &h.verdict),
+            Some(&HopVerdict::Unverified(RecordVerdict::SellersJsonMissing))
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! IAB sellers.json data model and parser
+//!
+//! Spec - <https://iabtechlab.com/wp-content/uploads/2019/07/Sellers.json_Final.pdf>
+
+use crate::json::JsonValue;
+use crate::{ads_txt_error, AdsTxtError, Result};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum SellerType {
+    Publisher,
+    Intermediary,
+    Both,
+}
+
+impl SellerType {
+    fn parse(text: &str) -> Result<SellerType> {
+        let seller_type = text.trim().to_lowercase();
+
+        if seller_type == "publisher" {
+            Ok(SellerType::Publisher)
+        } else if seller_type == "intermediary" {
+            Ok(SellerType::Intermediary)
+        } else if seller_type == "both" {
+            Ok(SellerType::Both)
+        } else {
+            ads_txt_error(&format!("Invalid seller type: {}", text))
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SellerType::Publisher => "PUBLISHER",
+            SellerType::Intermediary => "INTERMEDIARY",
+            SellerType::Both => "BOTH",
+        }
+    }
+}
+
+/// Identifier type of Trustworthy Accountability Group ids
+pub const TAG_ID: &str = "TAG-ID";
+
+/// Identifier type of Dun & Bradstreet numbers
+pub const DUNS: &str = "DUNS";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Identifier {
+    /// Identifier type, e.g. `TAG-ID` or `DUNS`
+    pub name: String,
+    pub value: String,
+}
+
+impl Identifier {
+    pub fn new(name: &str, value: &str) -> Self {
+        Identifier {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// Checks the identifier type, ignoring case and punctuation so that
+    /// e.g. `TAG-ID`, `tag_id` and `TAGID` are the same type
+    pub fn is_type(&self, name: &str) -> bool {
+        let normalize = |s: &str| -> String {
+            s.chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .map(|c| c.to_ascii_uppercase())
+                .collect()
+        };
+        normalize(&self.name) == normalize(name)
+    }
+
+    pub(crate) fn from_json(value: &JsonValue) -> Result<Identifier> {
+        match (string_field(value, "name"), string_field(value, "value")) {
+            (Some(name), Some(value)) => Ok(Identifier { name, value }),
+            _ => ads_txt_error(&format!("Invalid identifier: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Seller {
+    /// Id of the seller on the ad system, matching publisher ids in ads.txt
+    pub seller_id: String,
+    /// Business name, absent for confidential sellers
+    pub name: Option<String>,
+    /// Business domain, absent for confidential sellers
+    pub domain: Option<String>,
+    pub seller_type: SellerType,
+    pub is_confidential: bool,
+    /// Whether the seller passes inventory through without its own ads.txt entry
+    pub is_passthrough: bool,
+    pub comment: Option<String>,
+}
+
+impl Seller {
+    pub fn new(seller_id: &str, seller_type: SellerType) -> Self {
+        Seller {
+            seller_id: seller_id.trim().to_string(),
+            name: None,
+            domain: None,
+            seller_type,
+            is_confidential: false,
+            is_passthrough: false,
+            comment: None,
+        }
+    }
+
+    pub fn from_json(value: &JsonValue) -> Result<Seller> {
+        let seller_id = match string_field(value, "seller_id") {
+            Some(seller_id) if !seller_id.is_empty() => seller_id,
+            _ => return ads_txt_error(&format!("Invalid seller without seller_id: {}", value)),
+        };
+
+        let seller_type = match value.get("seller_type").and_then(|t| t.as_str()) {
+            Some(seller_type) => SellerType::parse(seller_type)?,
+            None => {
+                return ads_txt_error(&format!(
+                    "Invalid seller without seller_type: {}",
+                    seller_id
+                ))
+            }
+        };
+
+        Ok(Seller {
+            seller_id,
+            name: string_field(value, "name"),
+            domain: string_field(value, "domain"),
+            seller_type,
+            is_confidential: flag_field(value, "is_confidential"),
+            is_passthrough: flag_field(value, "is_passthrough"),
+            comment: string_field(value, "comment"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SellersJson {
+    pub contact_email: Option<String>,
+    pub contact_address: Option<String>,
+    pub version: String,
+    pub identifiers: Vec<Identifier>,
+    pub sellers: Vec<Seller>,
+}
+
+impl SellersJson {
+    pub fn new(sellers: &[Seller]) -> Self {
+        SellersJson {
+            contact_email: None,
+            contact_address: None,
+            version: "1.0".to_string(),
+            identifiers: vec![],
+            sellers: sellers.to_vec(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<SellersJson> {
+        let (sellers_json, errors) = Self::parse_with(text)?;
+
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(sellers_json),
+        }
+    }
+
+    /// Parses sellers.json leniently, skipping invalid sellers and identifiers.
+    /// Fails only if the document is not a JSON object with a sellers array.
+    pub fn parse_lenient(text: &str) -> Result<(SellersJson, Vec<AdsTxtError>)> {
+        Self::parse_with(text)
+    }
+
+    fn parse_with(text: &str) -> Result<(SellersJson, Vec<AdsTxtError>)> {
+        let json = JsonValue::parse(text)?;
+        let mut errors = vec![];
+
+        let sellers = match json.get("sellers").and_then(|s| s.as_array()) {
+            Some(sellers) => sellers,
+            None => return ads_txt_error("Invalid sellers.json without sellers array"),
+        };
+
+        let mut identifiers = vec![];
+        if let Some(values) = json.get("identifiers").and_then(|i| i.as_array()) {
+            for value in values {
+                match Identifier::from_json(value) {
+                    Ok(identifier) => identifiers.push(identifier),
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+
+        let mut parsed = Vec::with_capacity(sellers.len());
+        for value in sellers {
+            match Seller::from_json(value) {
+                Ok(seller) => parsed.push(seller),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let sellers_json = SellersJson {
+            contact_email: string_field(&json, "contact_email"),
+            contact_address: string_field(&json, "contact_address"),
+            version: string_field(&json, "version").unwrap_or_else(|| "1.0".to_string()),
+            identifiers,
+            sellers: parsed,
+        };
+
+        Ok((sellers_json, errors))
+    }
+
+    /// Finds the seller with the given id
+    pub fn seller(&self, seller_id: &str) -> Option<&Seller> {
+        let seller_id = seller_id.trim();
+        self.sellers.iter().find(|s| s.seller_id == seller_id)
+    }
+
+    /// Values of all identifiers of the given type, e.g. `TAG_ID`
+    pub fn identifier_values(&self, name: &str) -> Vec<&str> {
+        self.identifiers
+            .iter()
+            .filter(|i| i.is_type(name))
+            .map(|i| i.value.as_str())
+            .collect()
+    }
+
+    /// The ad system's TAG id, as expected in the cert authority field of ads.txt records
+    pub fn tag_id(&self) -> Option<&str> {
+        self.identifier_values(TAG_ID).into_iter().next()
+    }
+
+    /// Checks whether a cert authority id from ads.txt is one of the TAG ids
+    /// declared by the ad system
+    pub fn declares_tag_id(&self, cert_authority: &str) -> bool {
+        let cert_authority = cert_authority.trim();
+        self.identifier_values(TAG_ID)
+            .iter()
+            .any(|v| v.trim().eq_ignore_ascii_case(cert_authority))
+    }
+}
+
+/// Reads a string field, also accepting numbers since many files use numeric ids
+pub(crate) fn string_field(value: &JsonValue, key: &str) -> Option<String> {
+    match value.get(key) {
+        Some(JsonValue::String(s)) => Some(s.trim().to_string()),
+        Some(JsonValue::Number(n)) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+/// Reads a 0/1 flag field, also accepting booleans
+pub(crate) fn flag_field(value: &JsonValue, key: &str) -> bool {
+    match value.get(key) {
+        Some(JsonValue::Bool(b)) => *b,
+        Some(v) => v.as_i64() == Some(1),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELLERS_JSON: &str = r#"{
+        "contact_email": "adops@advertisingsystem.com",
+        "contact_address": "Advertising System Inc., 101 Main Street, New York, NY 10101",
+        "version": "1.0",
+        "identifiers": [
+            {"name": "TAG-ID", "value": "28cb65e5bbc0bd5f"}
+        ],
+        "sellers": [
+            {
+                "seller_id": "1942009976",
+                "name": "Publisher1",
+                "domain": "publisher1.com",
+                "seller_type": "PUBLISHER"
+            },
+            {
+                "seller_id": 1397604095,
+                "seller_type": "intermediary",
+                "is_confidential": 1
+            },
+            {
+                "seller_id": "1242931154",
+                "name": "Network1",
+                "domain": "network1.com",
+                "seller_type": "BOTH",
+                "is_passthrough": 1
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn parsing_sellers_json() {
+        let sellers_json = SellersJson::parse(SELLERS_JSON).unwrap();
+
+        assert_eq!(
+            sellers_json.contact_email,
+            Some("adops@advertisingsystem.com".to_string())
+        );
+        assert_eq!(
+            sellers_json.identifiers,
+            vec![Identifier::new("TAG-ID", "28cb65e5bbc0bd5f")]
+        );
+        assert_eq!(sellers_json.sellers.len(), 3);
+
+        assert_eq!(
+            sellers_json.seller("1942009976"),
+            Some(&Seller {
+                name: Some("Publisher1".to_string()),
+                domain: Some("publisher1.com".to_string()),
+                ..Seller::new("1942009976", SellerType::Publisher)
+            })
+        );
+        assert_eq!(
+            sellers_json.seller("1397604095"),
+            Some(&Seller {
+                is_confidential: true,
+                ..Seller::new("1397604095", SellerType::Intermediary)
+            })
+        );
+        assert!(sellers_json.seller("1242931154").unwrap().is_passthrough);
+        assert_eq!(sellers_json.seller("42"), None);
+    }
+
+    #[test]
+    fn looking_up_identifiers() {
+        let mut sellers_json = SellersJson::parse(SELLERS_JSON).unwrap();
+        sellers_json
+            .identifiers
+            .push(Identifier::new("duns", "123456789"));
+        sellers_json
+            .identifiers
+            .push(Identifier::new("tag_id", "f08c47fec0942fa0"));
+
+        assert_eq!(sellers_json.tag_id(), Some("28cb65e5bbc0bd5f"));
+        assert_eq!(
+            sellers_json.identifier_values(TAG_ID),
+            vec!["28cb65e5bbc0bd5f", "f08c47fec0942fa0"]
+        );
+        assert_eq!(sellers_json.identifier_values(DUNS), vec!["123456789"]);
+        assert!(sellers_json.declares_tag_id("F08C47FEC0942FA0"));
+        assert!(!sellers_json.declares_tag_id("123456789"));
+        assert_eq!(SellersJson::new(&[]).tag_id(), None);
+    }
+
+    #[test]
+    fn parsing_sellers_json_leniently() {
+        let text = r#"{"sellers": [
+            {"seller_id": "1", "seller_type": "PUBLISHER"},
+            {"seller_id": "2", "seller_type": "RESELLER"},
+            {"seller_type": "PUBLISHER"}
+        ]}"#;
+
+        assert_eq!(
+            SellersJson::parse(text),
+            ads_txt_error("Invalid seller type: RESELLER")
+        );
+
+        let (sellers_json, errors) = SellersJson::parse_lenient(text).unwrap();
+        assert_eq!(
+            sellers_json,
+            SellersJson::new(&[Seller::new("1", SellerType::Publisher)])
+        );
+        assert_eq!(errors.len(), 2);
+
+        assert!(SellersJson::parse_lenient("{}").is_err());
+        assert!(SellersJson::parse_lenient("not json").is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! Streaming sellers.json reader for files too large to load at once
+//!
+//! Only the sellers array is read. Every seller object is buffered and parsed
+//! on its own, so memory use is bounded by the largest seller rather than by
+//! the whole document.
+
+use crate::json::JsonValue;
+use crate::sellers_json::Seller;
+use crate::{ads_txt_error, Result};
+use std::io::BufRead;
+
+/// Largest seller object accepted, guarding against unbounded buffering
+const MAX_SELLER_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum State {
+    Start,
+    Sellers { first: bool },
+    Done,
+}
+
+/// Iterator over the sellers of a sellers.json document read from `R`.
+///
+/// Invalid sellers are yielded as errors and skipped. A malformed document
+/// yields one error and ends the iteration.
+pub struct SellerStream<R> {
+    reader: R,
+    state: State,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead> SellerStream<R> {
+    pub fn new(reader: R) -> Self {
+        SellerStream {
+            reader,
+            state: State::Start,
+            buf: vec![],
+        }
+    }
+
+    /// Finds the seller with the given id, only parsing sellers whose source
+    /// text contains the id. Invalid sellers are ignored.
+    pub fn find_seller(mut self, seller_id: &str) -> Result<Option<Seller>> {
+        let seller_id = seller_id.trim();
+        if seller_id.is_empty() {
+            return Ok(None);
+        }
+
+        while self.next_raw()? {
+            if !self
+                .buf
+                .windows(seller_id.len())
+                .any(|w| w == seller_id.as_bytes())
+            {
+                continue;
+            }
+
+            if let Ok(seller) = self.parse_seller() {
+                if seller.seller_id == seller_id {
+                    return Ok(Some(seller));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        match self.reader.fill_buf() {
+            Ok(buf) => Ok(buf.first().copied()),
+            Err(e) => ads_txt_error(&format!("Error reading sellers.json: {}", e)),
+        }
+    }
+
+    fn bump(&mut self) {
+        self.reader.consume(1);
+    }
+
+    fn skip_whitespace(&mut self) -> Result<Option<u8>> {
+        loop {
+            match self.peek()? {
+                Some(b) if b.is_ascii_whitespace() => self.bump(),
+                other => return Ok(other),
+            }
+        }
+    }
+
+    /// Reads the next JSON value, keeping its source text in `buf` if asked to
+    fn read_value(&mut self, keep: bool) -> Result<()> {
+        self.buf.clear();
+
+        match self.peek()? {
+            Some(b'{') | Some(b'[') | Some(b'"') => {}
+            Some(_) => {
+                // Scalars end at the next delimiter
+                while let Some(b) = self.peek()? {
+                    if b == b',' || b == b']' || b == b'}' || b.is_ascii_whitespace() {
+                        break;
+                    }
+                    if keep {
+                        self.buf.push(b);
+                    }
+                    self.bump();
+                }
+                return Ok(());
+            }
+            None => return ads_txt_error("Unexpected end of sellers.json"),
+        }
+
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        loop {
+            let b = match self.peek()? {
+                Some(b) => b,
+                None => return ads_txt_error("Unexpected end of sellers.json"),
+            };
+            self.bump();
+
+            if keep {
+                if self.buf.len() >= MAX_SELLER_SIZE {
+                    return ads_txt_error("Seller too large in sellers.json");
+                }
+                self.buf.push(b);
+            }
+
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' {
+                    escaped = true;
+                } else if b == b'"' {
+                    in_string = false;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+            } else {
+                match b {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn buffered_json(&self) -> Result<JsonValue> {
+        match std::str::from_utf8(&self.buf) {
+            Ok(text) => JsonValue::parse(text),
+            Err(_) => ads_txt_error("Invalid UTF-8 in sellers.json"),
+        }
+    }
+
+    fn parse_seller(&self) -> Result<Seller> {
+        Seller::from_json(&self.buffered_json()?)
+    }
+
+    /// Skips top level members up to the start of the sellers array
+    fn find_sellers(&mut self) -> Result<()> {
+        if self.skip_whitespace()? != Some(b'{') {
+            return ads_txt_error("Invalid sellers.json which is not a JSON object");
+        }
+        self.bump();
+
+        loop {
+            match self.skip_whitespace()? {
+                Some(b'"') => {}
+                Some(b'}') | None => {
+                    return ads_txt_error("Invalid sellers.json without sellers array")
+                }
+                Some(_) => return ads_txt_error("Expected string key in sellers.json"),
+            }
+
+            self.read_value(true)?;
+            let key = self.buffered_json()?;
+
+            if self.skip_whitespace()? != Some(b':') {
+                return ads_txt_error("Expected ':' in sellers.json");
+            }
+            self.bump();
+
+            if key.as_str() == Some("sellers") {
+                if self.skip_whitespace()? != Some(b'[') {
+                    return ads_txt_error("Invalid sellers.json without sellers array");
+                }
+                self.bump();
+                self.state = State::Sellers { first: true };
+                return Ok(());
+            }
+
+            self.skip_whitespace()?;
+            self.read_value(false)?;
+            if self.skip_whitespace()? == Some(b',') {
+                self.bump();
+            }
+        }
+    }
+
+    /// Reads the next seller's source text into `buf`
+    fn next_raw(&mut self) -> Result<bool> {
+        if self.state == State::Start {
+            if let Err(e) = self.find_sellers() {
+                self.state = State::Done;
+                return Err(e);
+            }
+        }
+
+        let first = match self.state {
+            State::Sellers { first } => first,
+            _ => return Ok(false),
+        };
+
+        let result = self.next_seller(first);
+        match result {
+            Ok(true) => self.state = State::Sellers { first: false },
+            _ => self.state = State::Done,
+        }
+        result
+    }
+
+    fn next_seller(&mut self, first: bool) -> Result<bool> {
+        match self.skip_whitespace()? {
+            Some(b']') => return Ok(false),
+            Some(b',') if !first => self.bump(),
+            _ if first => {}
+            _ => return ads_txt_error("Expected ',' or ']' in sellers array"),
+        }
+
+        self.skip_whitespace()?;
+        self.read_value(true)?;
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for SellerStream<R> {
+    type Item = Result<Seller>;
+
+    fn next(&mut self) -> Option<Result<Seller>> {
+        match self.next_raw() {
+            Ok(true) => Some(self.parse_seller()),
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sellers_json::SellerType;
+    use std::io::BufReader;
+
+    const SELLERS_JSON: &str = r#"{
+        "contact_email": "adops@advertisingsystem.com",
+        "identifiers": [{"name": "TAG-ID", "value": "28cb65e5bbc0bd5f"}],
+        "ext": {"note": "brackets ] and braces } in \"strings\""},
+        "version": 1.0,
+        "sellers": [
+            {"seller_id": "1942009976", "name": "Pub [1]", "seller_type": "PUBLISHER"},
+            {"seller_id": "2", "seller_type": "RESELLER"},
+            {"seller_id": 1397604095, "seller_type": "INTERMEDIARY", "ext": {"a": [1, {}]}}
+        ],
+        "trailing": true
+    }"#;
+
+    fn stream(text: &str) -> SellerStream<BufReader<&[u8]>> {
+        // A tiny buffer exercises values spanning buffer refills
+        SellerStream::new(BufReader::with_capacity(3, text.as_bytes()))
+    }
+
+    #[test]
+    fn streaming_sellers() {
+        let sellers: Vec<Result<Seller>> = stream(SELLERS_JSON).collect();
+
+        assert_eq!(sellers.len(), 3);
+        assert_eq!(
+            sellers[0],
+            Ok(Seller {
+                name: Some("Pub [1]".to_string()),
+                ..Seller::new("1942009976", SellerType::Publisher)
+            })
+        );
+        assert_eq!(sellers[1], ads_txt_error("Invalid seller type: RESELLER"));
+        assert_eq!(
+            sellers[2],
+            Ok(Seller::new("1397604095", SellerType::Intermediary))
+        );
+
+        assert_eq!(
+            stream(r#"{"sellers": []}"#).collect::<Vec<_>>(),
+            Vec::<Result<Seller>>::new()
+        );
+
+        let errors: Vec<Result<Seller>> = stream(r#"{"identifiers": []}"#).collect();
+        assert_eq!(
+            errors,
+            vec![ads_txt_error("Invalid sellers.json without sellers array")]
+        );
+
+        let truncated: Vec<Result<Seller>> =
+            stream(r#"{"sellers": [{"seller_id": "1", "seller_type": "BOTH"}, {"sel"#).collect();
+        assert_eq!(truncated.len(), 2);
+        assert!(truncated[0].is_ok());
+        assert_eq!(
+            truncated[1],
+            ads_txt_error("Unexpected end of sellers.json")
+        );
+    }
+
+    #[test]
+    fn finding_sellers() {
+        assert_eq!(
+            stream(SELLERS_JSON).find_seller("1397604095"),
+            Ok(Some(Seller::new("1397604095", SellerType::Intermediary)))
+        );
+        assert_eq!(stream(SELLERS_JSON).find_seller("2"), Ok(None));
+        assert_eq!(stream(SELLERS_JSON).find_seller("194200997"), Ok(None));
+        assert!(stream("[]").find_seller("1").is_err());
+    }
+}
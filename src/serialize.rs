@@ -0,0 +1,284 @@
+//! Conversions of ads.txt data to and from other formats
+
+use crate::json::JsonValue;
+use crate::{ads_txt_error, AccountRelation, AdsTxt, DataRecord, Result, Variable};
+
+/// Header of the CSV rendering of records
+pub(crate) const CSV_HEADER: &str = "domain,publisher_id,relation,cert_authority";
+
+pub fn record_to_json(record: &DataRecord) -> JsonValue {
+    JsonValue::Object(vec![
+        ("domain".to_string(), JsonValue::string(&record.domain)),
+        (
+            "publisher_id".to_string(),
+            JsonValue::string(&record.publisher_id),
+        ),
+        (
+            "relation".to_string(),
+            JsonValue::string(record.acc_relation.as_str()),
+        ),
+        (
+            "cert_authority".to_string(),
+            match &record.cert_authority {
+                Some(cert_authority) => JsonValue::string(cert_authority),
+                None => JsonValue::Null,
+            },
+        ),
+    ])
+}
+
+pub fn variable_to_json(variable: &Variable) -> JsonValue {
+    JsonValue::Object(vec![
+        ("name".to_string(), JsonValue::string(&variable.name)),
+        ("value".to_string(), JsonValue::string(&variable.value)),
+    ])
+}
+
+/// Renders a file as `{"records": [...], "variables": [...]}`
+pub fn ads_txt_to_json(ads_txt: &AdsTxt) -> JsonValue {
+    JsonValue::Object(vec![
+        (
+            "records".to_string(),
+            JsonValue::Array(ads_txt.records.iter().map(record_to_json).collect()),
+        ),
+        (
+            "variables".to_string(),
+            JsonValue::Array(ads_txt.variables.iter().map(variable_to_json).collect()),
+        ),
+    ])
+}
+
+fn json_field<'a>(json: &'a JsonValue, key: &str) -> Result<&'a str> {
+    match json.get(key).and_then(|v| v.as_str()) {
+        Some(value) => Ok(value),
+        None => ads_txt_error(&format!("Missing string field: {}", key)),
+    }
+}
+
+pub fn record_from_json(json: &JsonValue) -> Result<DataRecord> {
+    let cert_authority = match json.get("cert_authority") {
+        None | Some(JsonValue::Null) => None,
+        Some(_) => Some(json_field(json, "cert_authority")?.to_string()),
+    };
+
+    Ok(DataRecord::new(
+        json_field(json, "domain")?,
+        json_field(json, "publisher_id")?,
+        AccountRelation::parse(json_field(json, "relation")?)?,
+        cert_authority,
+    ))
+}
+
+pub fn variable_from_json(json: &JsonValue) -> Result<Variable> {
+    Ok(Variable::new(
+        json_field(json, "name")?,
+        json_field(json, "value")?,
+    ))
+}
+
+/// Reads a file rendered by `ads_txt_to_json`
+pub fn ads_txt_from_json(json: &JsonValue) -> Result<AdsTxt> {
+    let array = |key| match json.get(key) {
+        None => Ok(&[][..]),
+        Some(value) => match value.as_array() {
+            Some(items) => Ok(items),
+            None => ads_txt_error(&format!("Expected an array: {}", key)),
+        },
+    };
+
+    Ok(AdsTxt {
+        records: array("records")?
+            .iter()
+            .map(record_from_json)
+            .collect::<Result<_>>()?,
+        variables: array("variables")?
+            .iter()
+            .map(variable_from_json)
+            .collect::<Result<_>>()?,
+    })
+}
+
+/// Renders a file as one JSON object per line, records before variables
+pub fn ads_txt_to_jsonl(ads_txt: &AdsTxt) -> String {
+    let records = ads_txt.records.iter().map(record_to_json);
+    let variables = ads_txt.variables.iter().map(variable_to_json);
+
+    records
+        .chain(variables)
+        .map(|json| format!("{}\n", json))
+        .collect()
+}
+
+/// Reads a file rendered by `ads_txt_to_jsonl`, taking objects with a `name`
+/// as variables and the others as records
+pub fn ads_txt_from_jsonl(text: &str) -> Result<AdsTxt> {
+    let mut ads_txt = AdsTxt::empty();
+
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parsed = JsonValue::parse(line).and_then(|json| {
+            if json.get("name").is_some() {
+                ads_txt.variables.push(variable_from_json(&json)?);
+            } else {
+                ads_txt.records.push(record_from_json(&json)?);
+            }
+            Ok(())
+        });
+        if let Err(e) = parsed {
+            return ads_txt_error(&format!("line {}: {}", i + 1, e));
+        }
+    }
+
+    Ok(ads_txt)
+}
+
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub(crate) fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+
+    fields
+}
+
+/// Renders the records of a file as CSV with a
+/// `domain,publisher_id,relation,cert_authority` header. Variables are not
+/// rendered.
+pub fn ads_txt_to_csv(ads_txt: &AdsTxt) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+
+    for record in &ads_txt.records {
+        csv.push_str(&record_to_csv(record));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Renders a record as a CSV row, without a line break
+pub(crate) fn record_to_csv(record: &DataRecord) -> String {
+    format!(
+        "{},{},{},{}",
+        csv_field(&record.domain),
+        csv_field(&record.publisher_id),
+        record.acc_relation.as_str(),
+        csv_field(record.cert_authority.as_deref().unwrap_or(""))
+    )
+}
+
+/// Reads records rendered by `ads_txt_to_csv`, with or without the header. An
+/// empty cert authority is read as none.
+pub fn ads_txt_from_csv(text: &str) -> Result<AdsTxt> {
+    let mut records = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || (i == 0 && line.trim() == CSV_HEADER) {
+            continue;
+        }
+
+        let fields = csv_fields(line);
+        if fields.len() < 3 || fields.len() > 4 {
+            return ads_txt_error(&format!("line {}: Invalid CSV record: {}", i + 1, line));
+        }
+
+        let cert_authority = fields
+            .get(3)
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .map(str::to_string);
+        let relation = match AccountRelation::parse(&fields[2]) {
+            Ok(relation) => relation,
+            Err(e) => return ads_txt_error(&format!("line {}: {}", i + 1, e)),
+        };
+        records.push(DataRecord::new(
+            &fields[0],
+            &fields[1],
+            relation,
+            cert_authority,
+        ));
+    }
+
+    Ok(AdsTxt::new(&records, &[]))
+}
+
+/// Renders a file as ads.txt text, records before variables
+pub fn ads_txt_to_text(ads_txt: &AdsTxt) -> String {
+    let mut text = String::new();
+    ads_txt
+        .write_into(&mut text)
+        .expect("writing to a String cannot fail");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converting_to_json() {
+        let ads_txt =
+            AdsTxt::parse("a.com, 1, DIRECT, abc\nb.com, 2, reseller\ncontact=x").unwrap();
+
+        assert_eq!(
+            ads_txt_to_json(&ads_txt).to_string(),
+            r#"{"records":[{"domain":"a.com","publisher_id":"1","relation":"DIRECT","cert_authority":"abc"},{"domain":"b.com","publisher_id":"2","relation":"RESELLER","cert_authority":null}],"variables":[{"name":"contact","value":"x"}]}"#
+        );
+    }
+
+    #[test]
+    fn converting_between_formats() {
+        let ads_txt =
+            AdsTxt::parse("a.com, 1, DIRECT, abc\nb.com, \"2\", reseller\ncontact=x").unwrap();
+
+        let json = ads_txt_to_json(&ads_txt);
+        assert_eq!(ads_txt_from_json(&json), Ok(ads_txt.clone()));
+        assert!(ads_txt_from_json(&JsonValue::parse(r#"{"records": [{}]}"#).unwrap()).is_err());
+
+        let jsonl = ads_txt_to_jsonl(&ads_txt);
+        assert_eq!(jsonl.lines().count(), 3);
+        assert_eq!(ads_txt_from_jsonl(&jsonl), Ok(ads_txt.clone()));
+        assert_eq!(
+            ads_txt_from_jsonl("{}\nnot json").map_err(|e| e.to_string().starts_with("line 1")),
+            Err(true)
+        );
+
+        let csv = ads_txt_to_csv(&ads_txt);
+        assert_eq!(
+            csv,
+            "domain,publisher_id,relation,cert_authority\na.com,1,DIRECT,abc\nb.com,\"\"\"2\"\"\",RESELLER,\n"
+        );
+        assert_eq!(
+            ads_txt_from_csv(&csv),
+            Ok(AdsTxt::new(&ads_txt.records, &[]))
+        );
+        assert!(ads_txt_from_csv("a.com,1").is_err());
+        assert!(ads_txt_from_csv("a.com,1,OTHER").is_err());
+
+        assert_eq!(
+            ads_txt_to_text(&ads_txt),
+            "a.com, 1, DIRECT, abc\nb.com, \"2\", RESELLER\ncontact=x\n"
+        );
+    }
+}
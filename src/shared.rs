@@ -0,0 +1,217 @@
+//! Immutable ads.txt files which are cheap to clone and share between
+//! threads, e.g. one parsed file read by every worker of an ad server
+
+use crate::{write_record, AccountRelation, AdsTxt, DataRecord, Variable};
+use alloc::collections::BTreeMap;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Formatter;
+
+/// `AdsTxt` whose clones share the records and variables. Every field is an
+/// `Arc<str>`, with equal fields of a file sharing one allocation.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SharedAdsTxt {
+    records: Arc<[SharedRecord]>,
+    variables: Arc<[SharedVariable]>,
+}
+
+/// Record of a `SharedAdsTxt`, read through the same getters as `DataRecord`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SharedRecord {
+    domain: Arc<str>,
+    publisher_id: Arc<str>,
+    acc_relation: AccountRelation,
+    cert_authority: Option<Arc<str>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SharedVariable {
+    pub name: Arc<str>,
+    pub value: Arc<str>,
+}
+
+/// Hands out one `Arc<str>` per distinct string
+#[derive(Default)]
+struct Interner<'a> {
+    strings: BTreeMap<&'a str, Arc<str>>,
+}
+
+impl<'a> Interner<'a> {
+    fn intern(&mut self, text: &'a str) -> Arc<str> {
+        self.strings
+            .entry(text)
+            .or_insert_with(|| text.into())
+            .clone()
+    }
+}
+
+impl SharedAdsTxt {
+    pub fn records(&self) -> &[SharedRecord] {
+        &self.records
+    }
+
+    pub fn variables(&self) -> &[SharedVariable] {
+        &self.variables
+    }
+
+    /// Records for the seller account on the given ad system
+    pub fn seat_records(&self, domain: &str, publisher_id: &str) -> Vec<&SharedRecord> {
+        let domain = domain.trim();
+        let publisher_id = publisher_id.trim();
+
+        self.records
+            .iter()
+            .filter(|r| r.domain.eq_ignore_ascii_case(domain) && &*r.publisher_id == publisher_id)
+            .collect()
+    }
+
+    /// Values of the variable, sharing the file's strings
+    pub fn values(&self, name: &str) -> Vec<Arc<str>> {
+        self.variables
+            .iter()
+            .filter(|v| &*v.name == name)
+            .map(|v| v.value.clone())
+            .collect()
+    }
+
+    /// Copies the file into an `AdsTxt`
+    pub fn to_ads_txt(&self) -> AdsTxt {
+        AdsTxt {
+            records: self.records.iter().map(|r| r.to_record()).collect(),
+            variables: self
+                .variables
+                .iter()
+                .map(|v| Variable::new(&v.name, &v.value))
+                .collect(),
+        }
+    }
+}
+
+impl From<&AdsTxt> for SharedAdsTxt {
+    fn from(ads_txt: &AdsTxt) -> Self {
+        let mut interner = Interner::default();
+
+        let records: Vec<SharedRecord> = ads_txt
+            .records
+            .iter()
+            .map(|r| SharedRecord {
+                domain: interner.intern(&r.domain),
+                publisher_id: interner.intern(&r.publisher_id),
+                acc_relation: r.acc_relation.clone(),
+                cert_authority: r.cert_authority.as_deref().map(|c| interner.intern(c)),
+            })
+            .collect();
+        let variables: Vec<SharedVariable> = ads_txt
+            .variables
+            .iter()
+            .map(|v| SharedVariable {
+                name: interner.intern(&v.name),
+                value: interner.intern(&v.value),
+            })
+            .collect();
+
+        SharedAdsTxt {
+            records: records.into(),
+            variables: variables.into(),
+        }
+    }
+}
+
+impl From<AdsTxt> for SharedAdsTxt {
+    fn from(ads_txt: AdsTxt) -> Self {
+        SharedAdsTxt::from(&ads_txt)
+    }
+}
+
+impl SharedRecord {
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn publisher_id(&self) -> &str {
+        &self.publisher_id
+    }
+
+    pub fn acc_relation(&self) -> &AccountRelation {
+        &self.acc_relation
+    }
+
+    pub fn cert_authority(&self) -> Option<&str> {
+        self.cert_authority.as_deref()
+    }
+
+    /// Writes the record as an ads.txt line like `DataRecord::write_into`
+    pub fn write_into<W: core::fmt::Write>(&self, out: &mut W) -> core::fmt::Result {
+        write_record(
+            out,
+            &self.domain,
+            &self.publisher_id,
+            &self.acc_relation,
+            self.cert_authority(),
+        )
+    }
+
+    pub fn to_record(&self) -> DataRecord {
+        DataRecord::new(
+            &self.domain,
+            &self.publisher_id,
+            self.acc_relation.clone(),
+            self.cert_authority.as_deref().map(|c| c.to_string()),
+        )
+    }
+}
+
+impl core::fmt::Display for SharedRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.write_into(f)
+    }
+}
+
+impl core::fmt::Display for SharedVariable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}={}", self.name, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn sharing_ads_txt() {
+        assert_send_sync::<SharedAdsTxt>();
+
+        let ads_txt = AdsTxt::parse(
+            "greenadexchange.com, 12345, DIRECT, d75815a79\n\
+             greenadexchange.com, 67890, RESELLER, d75815a79\n\
+             contact=ads@example.com",
+        )
+        .unwrap();
+        let shared = SharedAdsTxt::from(&ads_txt);
+
+        let records = shared.records();
+        assert!(core::ptr::eq(records[0].domain(), records[1].domain()));
+        assert!(core::ptr::eq(
+            records[0].cert_authority().unwrap(),
+            records[1].cert_authority().unwrap()
+        ));
+        assert_eq!(records[0].publisher_id(), "12345");
+        assert_eq!(records[0].acc_relation(), &AccountRelation::Direct);
+        assert_eq!(
+            records[1].to_string(),
+            "greenadexchange.com, 67890, RESELLER, d75815a79"
+        );
+        assert_eq!(shared.seat_records("GreenAdExchange.com", "67890").len(), 1);
+        assert_eq!(shared.values("contact"), vec![Arc::from("ads@example.com")]);
+        assert_eq!(shared.to_ads_txt(), ads_txt);
+
+        let clone = shared.clone();
+        assert!(core::ptr::eq(clone.records(), shared.records()));
+        let contacts: Vec<String> = clone.variables().iter().map(|v| v.to_string()).collect();
+        assert_eq!(contacts, vec!["contact=ads@example.com"]);
+    }
+}
@@ -0,0 +1,545 @@
+//! Summary statistics over one or many ads.txt files
+
+use crate::json::JsonValue;
+use crate::{AccountRelation, AdsTxt};
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct AdsTxtStats {
+    pub files: usize,
+    pub records: usize,
+    pub direct: usize,
+    pub reseller: usize,
+    /// Number of records per lowercased ad system
+    pub ad_systems: HashMap<String, usize>,
+    /// Number of uses per uppercased variable name
+    pub variables: HashMap<String, usize>,
+}
+
+/// Sorts counts by descending count, then by name
+fn ranked(counts: &HashMap<String, usize>) -> Vec<(&str, usize)> {
+    let mut ranked: Vec<(&str, usize)> = counts.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    ranked
+}
+
+impl AdsTxtStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, ads_txt: &AdsTxt) {
+        self.files += 1;
+        self.records += ads_txt.records.len();
+
+        for record in &ads_txt.records {
+            match record.acc_relation {
+                AccountRelation::Direct => self.direct += 1,
+                AccountRelation::Reseller => self.reseller += 1,
+            }
+            *self
+                .ad_systems
+                .entry(record.domain.to_lowercase())
+                .or_default() += 1;
+        }
+
+        for variable in &ads_txt.variables {
+            *self
+                .variables
+                .entry(variable.name.to_uppercase())
+                .or_default() += 1;
+        }
+    }
+
+    /// The `n` ad systems with the most records
+    pub fn top_ad_systems(&self, n: usize) -> Vec<(&str, usize)> {
+        ranked(&self.ad_systems).into_iter().take(n).collect()
+    }
+
+    /// Variable names by descending use
+    pub fn top_variables(&self) -> Vec<(&str, usize)> {
+        ranked(&self.variables)
+    }
+
+    /// Renders the statistics with the `n` top ad systems
+    pub fn to_json(&self, n: usize) -> JsonValue {
+        let counts = |counts: Vec<(&str, usize)>, key: &str, count_key: &str| {
+            JsonValue::Array(
+                counts
+                    .into_iter()
+                    .map(|(name, count)| {
+                        JsonValue::Object(vec![
+                            (key.to_string(), JsonValue::string(name)),
+                            (count_key.to_string(), JsonValue::number(count)),
+                        ])
+                    })
+                    .collect(),
+            )
+        };
+
+        JsonValue::Object(vec![
+            ("files".to_string(), JsonValue::number(self.files)),
+            ("records".to_string(), JsonValue::number(self.records)),
+            ("direct".to_string(), JsonValue::number(self.direct)),
+            ("reseller".to_string(), JsonValue::number(self.reseller)),
+            (
+                "ad_systems".to_string(),
+                counts(self.top_ad_systems(n), "ad_system", "records"),
+            ),
+            (
+                "variables".to_string(),
+                counts(self.top_variables(), "name", "uses"),
+            ),
+        ])
+    }
+}
+
+/// Aggregates of one ad system across a corpus
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ExchangeStats {
+    /// Lowercased ad system domain
+    pub ad_system: String,
+    pub records: usize,
+    pub direct: usize,
+    pub reseller: usize,
+    /// Number of publishers listing the ad system
+    pub publishers: usize,
+}
+
+impl ExchangeStats {
+    /// Share of the ad system's records which are DIRECT, between 0 and 1
+    pub fn direct_share(&self) -> f64 {
+        match self.records {
+            0 => 0.0,
+            records => self.direct as f64 / records as f64,
+        }
+    }
+
+    /// Share of the ad system's records which are RESELLER, between 0 and 1
+    pub fn reseller_share(&self) -> f64 {
+        match self.records {
+            0 => 0.0,
+            records => self.reseller as f64 / records as f64,
+        }
+    }
+}
+
+/// Per-exchange aggregates of a corpus, one row per ad system sorted by
+/// descending record count, then by ad system
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ExchangeTable {
+    pub rows: Vec<ExchangeStats>,
+}
+
+impl ExchangeTable {
+    /// Aggregates (publisher domain, ads.txt) pairs
+    pub fn build<'a, I>(files: I) -> ExchangeTable
+    where
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+    {
+        let mut rows: HashMap<String, ExchangeStats> = HashMap::new();
+
+        for (_, ads_txt) in files {
+            let mut listed: Vec<&str> = vec![];
+            for record in &ads_txt.records {
+                let ad_system = record.domain.to_lowercase();
+                let row = rows
+                    .entry(ad_system.clone())
+                    .or_insert_with(|| ExchangeStats {
+                        ad_system,
+                        ..ExchangeStats::default()
+                    });
+                row.records += 1;
+                match record.acc_relation {
+                    AccountRelation::Direct => row.direct += 1,
+                    AccountRelation::Reseller => row.reseller += 1,
+                }
+                if !listed
+                    .iter()
+                    .any(|d| d.eq_ignore_ascii_case(&record.domain))
+                {
+                    listed.push(&record.domain);
+                    row.publishers += 1;
+                }
+            }
+        }
+
+        let mut rows: Vec<ExchangeStats> = rows.into_values().collect();
+        rows.sort_by(|a, b| {
+            b.records
+                .cmp(&a.records)
+                .then(a.ad_system.cmp(&b.ad_system))
+        });
+        ExchangeTable { rows }
+    }
+
+    /// Row of the ad system, if any publisher lists it
+    pub fn get(&self, ad_system: &str) -> Option<&ExchangeStats> {
+        let ad_system = ad_system.trim();
+        self.rows
+            .iter()
+            .find(|r| r.ad_system.eq_ignore_ascii_case(ad_system))
+    }
+
+    /// Renders the table as an array of row objects
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Array(
+            self.rows
+                .iter()
+                .map(|r| {
+                    JsonValue::Object(vec![
+                        ("ad_system".to_string(), JsonValue::string(&r.ad_system)),
+                        ("records".to_string(), JsonValue::number(r.records)),
+                        ("direct".to_string(), JsonValue::number(r.direct)),
+                        ("reseller".to_string(), JsonValue::number(r.reseller)),
+                        (
+                            "direct_share".to_string(),
+                            JsonValue::number(r.direct_share()),
+                        ),
+                        ("publishers".to_string(), JsonValue::number(r.publishers)),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    /// Renders one CSV row per ad system:
+    /// `ad_system,records,direct,reseller,direct_share,publishers`
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("ad_system,records,direct,reseller,direct_share,publishers\n");
+
+        for r in &self.rows {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.4},{}\n",
+                r.ad_system,
+                r.records,
+                r.direct,
+                r.reseller,
+                r.direct_share(),
+                r.publishers
+            ));
+        }
+
+        csv
+    }
+}
+
+/// Cert authority ids listed for one ad system across a corpus
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CertAuthorityStats {
+    /// Lowercased ad system domain
+    pub ad_system: String,
+    pub records: usize,
+    /// Number of records with a cert authority id
+    pub with_cert_authority: usize,
+    /// Number of records per lowercased cert authority id
+    pub values: HashMap<String, usize>,
+}
+
+impl CertAuthorityStats {
+    /// Share of the ad system's records with a cert authority id, between 0
+    /// and 1
+    pub fn coverage(&self) -> f64 {
+        match self.records {
+            0 => 0.0,
+            records => self.with_cert_authority as f64 / records as f64,
+        }
+    }
+
+    /// Cert authority ids by descending use
+    pub fn top_values(&self) -> Vec<(&str, usize)> {
+        ranked(&self.values)
+    }
+
+    /// Most used cert authority id, most likely the ad system's real one
+    pub fn dominant(&self) -> Option<&str> {
+        self.top_values().first().map(|(value, _)| *value)
+    }
+
+    /// Number of records with a cert authority id other than the dominant
+    /// one, which are most likely wrong
+    pub fn deviating(&self) -> usize {
+        let dominant = self.top_values().first().map_or(0, |(_, count)| *count);
+        self.with_cert_authority - dominant
+    }
+}
+
+/// Cert authority usage per ad system, sorted by ad system
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CertAuthorityReport {
+    pub rows: Vec<CertAuthorityStats>,
+}
+
+impl CertAuthorityReport {
+    /// Aggregates (publisher domain, ads.txt) pairs
+    pub fn build<'a, I>(files: I) -> CertAuthorityReport
+    where
+        I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+    {
+        let mut rows: HashMap<String, CertAuthorityStats> = HashMap::new();
+
+        for (_, ads_txt) in files {
+            for record in &ads_txt.records {
+                let ad_system = record.domain.to_lowercase();
+                let row = rows
+                    .entry(ad_system.clone())
+                    .or_insert_with(|| CertAuthorityStats {
+                        ad_system,
+                        ..CertAuthorityStats::default()
+                    });
+                row.records += 1;
+
+                let cert_authority = record.cert_authority.as_deref().map(str::trim);
+                if let Some(cert_authority) = cert_authority.filter(|c| !c.is_empty()) {
+                    row.with_cert_authority += 1;
+                    *row.values.entry(cert_authority.to_lowercase()).or_default() += 1;
+                }
+            }
+        }
+
+        let mut rows: Vec<CertAuthorityStats> = rows.into_values().collect();
+        rows.sort_by(|a, b| a.ad_system.cmp(&b.ad_system));
+        CertAuthorityReport { rows }
+    }
+
+    /// Row of the ad system, if any publisher lists it
+    pub fn get(&self, ad_system: &str) -> Option<&CertAuthorityStats> {
+        let ad_system = ad_system.trim();
+        self.rows
+            .iter()
+            .find(|r| r.ad_system.eq_ignore_ascii_case(ad_system))
+    }
+
+    /// Ad systems with at least `min_records` records whose ids are missing
+    /// or deviate from the dominant one for more than `max_share` of them
+    pub fn suspicious(&self, min_records: usize, max_share: f64) -> Vec<&CertAuthorityStats> {
+        self.rows
+            .iter()
+            .filter(|r| r.records >= min_records && r.records > 0)
+            .filter(|r| {
+                let wrong = r.records - r.with_cert_authority + r.deviating();
+                wrong as f64 / r.records as f64 > max_share
+            })
+            .collect()
+    }
+
+    /// Renders the report as an array of row objects with the `n` most used
+    /// ids of each ad system
+    pub fn to_json(&self, n: usize) -> JsonValue {
+        JsonValue::Array(
+            self.rows
+                .iter()
+                .map(|r| {
+                    let values = r
+                        .top_values()
+                        .into_iter()
+                        .take(n)
+                        .map(|(value, count)| {
+                            JsonValue::Object(vec![
+                                ("cert_authority".to_string(), JsonValue::string(value)),
+                                ("records".to_string(), JsonValue::number(count)),
+                            ])
+                        })
+                        .collect();
+                    JsonValue::Object(vec![
+                        ("ad_system".to_string(), JsonValue::string(&r.ad_system)),
+                        ("records".to_string(), JsonValue::number(r.records)),
+                        (
+                            "with_cert_authority".to_string(),
+                            JsonValue::number(r.with_cert_authority),
+                        ),
+                        ("values".to_string(), JsonValue::Array(values)),
+                    ])
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Publisher id listed under several ad systems
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DuplicatePublisherId {
+    pub publisher_id: String,
+    /// Lowercased ad systems listing the id, sorted
+    pub ad_systems: Vec<String>,
+    /// Publishers whose files list the id, sorted
+    pub publishers: Vec<String>,
+}
+
+/// Finds publisher ids listed under at least `min_ad_systems` ad systems by at
+/// least `min_publishers` publishers, most ad systems first. Exchanges assign
+/// their own ids, so one id under many exchanges across unrelated publishers
+/// usually means copy-pasted entries. Short numeric ids also collide by
+/// chance, which a high `min_ad_systems` filters out.
+pub fn duplicate_publisher_ids<'a, I>(
+    files: I,
+    min_ad_systems: usize,
+    min_publishers: usize,
+) -> Vec<DuplicatePublisherId>
+where
+    I: IntoIterator<Item = (&'a str, &'a AdsTxt)>,
+{
+    let mut ids: HashMap<&str, (BTreeSet<String>, BTreeSet<&str>)> = HashMap::new();
+    for (publisher, ads_txt) in files {
+        for record in &ads_txt.records {
+            let (ad_systems, publishers) = ids.entry(&record.publisher_id).or_default();
+            ad_systems.insert(record.domain.to_lowercase());
+            publishers.insert(publisher);
+        }
+    }
+
+    let mut duplicates: Vec<DuplicatePublisherId> = ids
+        .into_iter()
+        .filter(|(_, (ad_systems, publishers))| {
+            ad_systems.len() >= min_ad_systems.max(2) && publishers.len() >= min_publishers
+        })
+        .map(|(id, (ad_systems, publishers))| DuplicatePublisherId {
+            publisher_id: id.to_string(),
+            ad_systems: ad_systems.into_iter().collect(),
+            publishers: publishers.into_iter().map(str::to_string).collect(),
+        })
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.ad_systems
+            .len()
+            .cmp(&a.ad_systems.len())
+            .then_with(|| a.publisher_id.cmp(&b.publisher_id))
+    });
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collecting_stats() {
+        let mut stats = AdsTxtStats::new();
+        stats.add(
+            &AdsTxt::parse(
+                r"
+                b.com, 1, DIRECT
+                a.com, 2, RESELLER
+                B.com, 3, RESELLER
+                contact=x@example.com
+                ",
+            )
+            .unwrap(),
+        );
+        stats.add(&AdsTxt::parse("a.com, 4, DIRECT\nCONTACT=y@example.com").unwrap());
+
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.records, 4);
+        assert_eq!((stats.direct, stats.reseller), (2, 2));
+        assert_eq!(stats.top_ad_systems(5), vec![("a.com", 2), ("b.com", 2)]);
+        assert_eq!(stats.top_ad_systems(1), vec![("a.com", 2)]);
+        assert_eq!(stats.top_variables(), vec![("CONTACT", 2)]);
+        assert_eq!(
+            stats.to_json(1).to_string(),
+            r#"{"files":2,"records":4,"direct":2,"reseller":2,"ad_systems":[{"ad_system":"a.com","records":2}],"variables":[{"name":"CONTACT","uses":2}]}"#
+        );
+    }
+
+    #[test]
+    fn aggregating_exchanges() {
+        let a = AdsTxt::parse("x.com, 1, DIRECT\nX.com, 2, RESELLER\ny.com, 3, DIRECT").unwrap();
+        let b = AdsTxt::parse("x.com, 4, RESELLER\nz.com, 5, RESELLER").unwrap();
+
+        let table = ExchangeTable::build(vec![("a.com", &a), ("b.com", &b)]);
+        assert_eq!(
+            table
+                .rows
+                .iter()
+                .map(|r| (r.ad_system.as_str(), r.records, r.publishers))
+                .collect::<Vec<_>>(),
+            vec![("x.com", 3, 2), ("y.com", 1, 1), ("z.com", 1, 1)]
+        );
+        let x = table.get("X.com").unwrap();
+        assert_eq!((x.direct, x.reseller), (1, 2));
+        assert!((x.direct_share() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(table.get("y.com").unwrap().reseller_share(), 0.0);
+        assert_eq!(table.get("w.com"), None);
+
+        assert_eq!(
+            table.to_csv(),
+            "ad_system,records,direct,reseller,direct_share,publishers\n\
+             x.com,3,1,2,0.3333,2\n\
+             y.com,1,1,0,1.0000,1\n\
+             z.com,1,0,1,0.0000,1\n"
+        );
+        assert_eq!(
+            ExchangeTable::build(vec![("b.com", &b)])
+                .to_json()
+                .to_string(),
+            r#"[{"ad_system":"x.com","records":1,"direct":0,"reseller":1,"direct_share":0,"publishers":1},{"ad_system":"z.com","records":1,"direct":0,"reseller":1,"direct_share":0,"publishers":1}]"#
+        );
+    }
+
+    #[test]
+    fn collecting_cert_authority_stats() {
+        let a = AdsTxt::parse(
+            "x.com, 1, DIRECT, abc\nx.com, 2, DIRECT, ABC\nx.com, 3, DIRECT, abd\ny.com, 4, DIRECT",
+        )
+        .unwrap();
+        let b = AdsTxt::parse("x.com, 5, RESELLER\ny.com, 6, DIRECT, f00").unwrap();
+
+        let report = CertAuthorityReport::build(vec![("a.com", &a), ("b.com", &b)]);
+        let x = report.get("X.com").unwrap();
+        assert_eq!((x.records, x.with_cert_authority), (4, 3));
+        assert_eq!(x.top_values(), vec![("abc", 2), ("abd", 1)]);
+        assert_eq!(x.dominant(), Some("abc"));
+        assert_eq!(x.deviating(), 1);
+        assert!((x.coverage() - 0.75).abs() < 1e-9);
+
+        let suspicious: Vec<&str> = report
+            .suspicious(2, 0.4)
+            .iter()
+            .map(|r| r.ad_system.as_str())
+            .collect();
+        assert_eq!(suspicious, vec!["x.com", "y.com"]);
+        assert!(report.suspicious(2, 0.5).is_empty());
+        assert!(report.suspicious(5, 0.0).is_empty());
+
+        assert_eq!(
+            report.to_json(1).to_string(),
+            r#"[{"ad_system":"x.com","records":4,"with_cert_authority":3,"values":[{"cert_authority":"abc","records":2}]},{"ad_system":"y.com","records":2,"with_cert_authority":1,"values":[{"cert_authority":"f00","records":1}]}]"#
+        );
+    }
+
+    #[test]
+    fn finding_duplicate_publisher_ids() {
+        let a = AdsTxt::parse("x.com, 123456, DIRECT\ny.com, 123456, DIRECT\nz.com, 9, DIRECT")
+            .unwrap();
+        let b = AdsTxt::parse("Z.com, 123456, RESELLER\nx.com, 9, DIRECT").unwrap();
+        let c = AdsTxt::parse("x.com, 123456, DIRECT\nx.com, 7, DIRECT").unwrap();
+        let files = vec![("a.com", &a), ("b.com", &b), ("c.com", &c)];
+
+        assert_eq!(
+            duplicate_publisher_ids(files.clone(), 2, 1),
+            vec![
+                DuplicatePublisherId {
+                    publisher_id: "123456".to_string(),
+                    ad_systems: vec![
+                        "x.com".to_string(),
+                        "y.com".to_string(),
+                        "z.com".to_string()
+                    ],
+                    publishers: vec![
+                        "a.com".to_string(),
+                        "b.com".to_string(),
+                        "c.com".to_string()
+                    ],
+                },
+                DuplicatePublisherId {
+                    publisher_id: "9".to_string(),
+                    ad_systems: vec!["x.com".to_string(), "z.com".to_string()],
+                    publishers: vec!["a.com".to_string(), "b.com".to_string()],
+                },
+            ]
+        );
+        assert_eq!(duplicate_publisher_ids(files.clone(), 3, 1).len(), 1);
+        assert_eq!(duplicate_publisher_ids(files.clone(), 2, 3).len(), 1);
+        // An id is never a duplicate of itself on one ad system
+        assert_eq!(duplicate_publisher_ids(files, 0, 0).len(), 2);
+    }
+}
@@ -0,0 +1,242 @@
+//! Rendering of records and diagnostics as tables with aligned columns for
+//! terminals, optionally colored by relation and severity with ANSI escapes
+
+use crate::findings::Severity;
+use crate::validate::Diagnostic;
+use crate::{AccountRelation, AdsTxt};
+use std::fmt::Write;
+
+/// Terminal color of a cell
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Cyan,
+}
+
+impl Color {
+    fn ansi_code(&self) -> &'static str {
+        match self {
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Yellow => "33",
+            Color::Blue => "34",
+            Color::Cyan => "36",
+        }
+    }
+
+    pub fn of_relation(relation: &AccountRelation) -> Color {
+        match relation {
+            AccountRelation::Direct => Color::Green,
+            _ => Color::Cyan,
+        }
+    }
+
+    pub fn of_severity(severity: Severity) -> Color {
+        match severity {
+            Severity::Error => Color::Red,
+            Severity::Warning => Color::Yellow,
+            Severity::Info => Color::Blue,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+/// Table with a header row, whose columns are as wide as their widest cell
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+    color: bool,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Table {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: vec![],
+            color: false,
+        }
+    }
+
+    /// Colors the cells given a color with ANSI escapes, off by default for
+    /// output which is not a terminal
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Adds a row of cells, each with an optional color. Missing cells are
+    /// left empty.
+    pub fn push_row<S: Into<String>>(&mut self, cells: Vec<(S, Option<Color>)>) {
+        self.rows.push(
+            cells
+                .into_iter()
+                .map(|(text, color)| Cell {
+                    text: text.into(),
+                    color,
+                })
+                .collect(),
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let width = cell.text.chars().count();
+                match widths.get_mut(i) {
+                    Some(w) => *w = (*w).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        widths
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let widths = self.widths();
+        let header: Vec<Cell> = self
+            .headers
+            .iter()
+            .map(|h| Cell {
+                text: h.to_uppercase(),
+                color: None,
+            })
+            .collect();
+
+        for row in std::iter::once(&header).chain(&self.rows) {
+            let mut line = String::new();
+            for (i, width) in widths.iter().enumerate() {
+                let text = row.get(i).map_or("", |c| c.text.as_str());
+                if i > 0 {
+                    line.push_str("  ");
+                }
+                match row.get(i).and_then(|c| c.color).filter(|_| self.color) {
+                    Some(color) => write!(line, "\x1b[{}m{}\x1b[0m", color.ansi_code(), text)?,
+                    None => line.push_str(text),
+                }
+                // Padding after the escapes keeps colored columns aligned
+                if i + 1 < widths.len() {
+                    write!(line, "{:1$}", "", width - text.chars().count())?;
+                }
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Table of the records of a file, colored by relation
+pub fn records_table(ads_txt: &AdsTxt) -> Table {
+    let mut table = Table::new(&["domain", "publisher id", "relation", "cert authority"]);
+    for record in &ads_txt.records {
+        table.push_row(vec![
+            (record.domain.to_string(), None),
+            (record.publisher_id.to_string(), None),
+            (
+                record.acc_relation.as_str().to_string(),
+                Some(Color::of_relation(&record.acc_relation)),
+            ),
+            (
+                record
+                    .cert_authority
+                    .as_ref()
+                    .map_or(String::new(), |c| c.to_string()),
+                None,
+            ),
+        ]);
+    }
+    table
+}
+
+/// Table of the variables of a file
+pub fn variables_table(ads_txt: &AdsTxt) -> Table {
+    let mut table = Table::new(&["variable", "value"]);
+    for variable in &ads_txt.variables {
+        table.push_row(vec![
+            (variable.name.as_str(), None),
+            (variable.value.as_str(), None),
+        ]);
+    }
+    table
+}
+
+/// Table of diagnostics, colored by severity
+pub fn diagnostics_table(diagnostics: &[Diagnostic]) -> Table {
+    let mut table = Table::new(&["line", "severity", "rule", "message"]);
+    for d in diagnostics {
+        table.push_row(vec![
+            (d.line.to_string(), None),
+            (
+                d.severity.as_str().to_lowercase(),
+                Some(Color::of_severity(d.severity)),
+            ),
+            (d.rule.as_str().to_string(), None),
+            (d.message.clone(), None),
+        ]);
+    }
+    table
+}
+
+/// Renders the records of a file, then its variables if it has any
+pub fn render_ads_txt(ads_txt: &AdsTxt, color: bool) -> String {
+    let mut text = records_table(ads_txt).with_color(color).to_string();
+    if !ads_txt.variables.is_empty() {
+        text.push('\n');
+        text.push_str(&variables_table(ads_txt).to_string());
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendering_tables() {
+        let ads_txt =
+            AdsTxt::parse("exchange.com, 1, DIRECT, abc\nb.com, 23456, RESELLER\ncontact=x")
+                .unwrap();
+        assert_eq!(
+            render_ads_txt(&ads_txt, false),
+            "DOMAIN        PUBLISHER ID  RELATION  CERT AUTHORITY\n\
+             exchange.com  1             DIRECT    abc\n\
+             b.com         23456         RESELLER\n\
+             \n\
+             VARIABLE  VALUE\n\
+             contact   x\n"
+        );
+        assert_eq!(
+            render_ads_txt(&AdsTxt::parse("a.com, 1, DIRECT").unwrap(), true),
+            "DOMAIN  PUBLISHER ID  RELATION  CERT AUTHORITY\n\
+             a.com   1             \x1b[32mDIRECT\x1b[0m\n"
+        );
+
+        let diagnostics = crate::validate::validate("a.com, 1, DIRECT\na.com, 1, DIRECT\nbad");
+        assert_eq!(
+            diagnostics_table(&diagnostics).with_color(true).to_string(),
+            "LINE  SEVERITY  RULE              MESSAGE\n\
+             2     \x1b[33mwarning\x1b[0m   duplicate-record  Duplicate record: a.com, 1, DIRECT\n\
+             3     \x1b[31merror\x1b[0m     invalid-line      Invalid ads.txt line: bad\n"
+        );
+    }
+}
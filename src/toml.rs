@@ -0,0 +1,317 @@
+//! Reader for the subset of TOML used by configuration and policy files:
+//! tables, and keys with string, integer, float, boolean or array values.
+//! Arrays may span lines, with comments between their items.
+
+use crate::{AdsTxtError, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TomlValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<TomlValue>),
+}
+
+impl TomlValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TomlValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TomlValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Toml {
+    /// (table, key, value) entries in document order, the root table being ""
+    entries: Vec<(String, String, TomlValue)>,
+}
+
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    /// 1-based line of the next character
+    line: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .peek()
+            .is_some_and(|c| *c == ' ' || *c == '\t' || *c == '\r')
+        {
+            self.next();
+        }
+    }
+
+    /// Skips whitespace, line breaks and comments between array items
+    fn skip_array_space(&mut self) {
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                Some('\n') => {
+                    self.next();
+                }
+                Some('#') => while !matches!(self.next(), None | Some('\n')) {},
+                _ => return,
+            }
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Result<String, String> {
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some(c) if c == quote => return Ok(s),
+                Some('\\') if quote == '"' => match self.next() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    other => return Err(format!("Invalid escape: \\{}", other.unwrap_or(' '))),
+                },
+                Some('\n') | None => return Err("Unterminated string".to_string()),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('"') | Some('\'') => {
+                let quote = self.next().unwrap_or('"');
+                self.string(quote)
+            }
+            _ => {
+                let mut key = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+                        break;
+                    }
+                    key.push(c);
+                    self.next();
+                }
+                if key.is_empty() {
+                    return Err("Expected a key".to_string());
+                }
+                Ok(key)
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<TomlValue, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('"') | Some('\'') => {
+                let quote = self.next().unwrap_or('"');
+                self.string(quote).map(TomlValue::String)
+            }
+            Some('[') => {
+                self.next();
+                let mut items = vec![];
+                loop {
+                    self.skip_array_space();
+                    if self.chars.peek() == Some(&']') {
+                        self.next();
+                        return Ok(TomlValue::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_array_space();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(TomlValue::Array(items)),
+                        _ => return Err("Expected ',' or ']' in array".to_string()),
+                    }
+                }
+            }
+            _ => {
+                let mut literal = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if c == ',' || c == ']' || c == '#' || c.is_whitespace() {
+                        break;
+                    }
+                    literal.push(c);
+                    self.next();
+                }
+
+                let number = literal.replace('_', "");
+                if literal == "true" || literal == "false" {
+                    Ok(TomlValue::Boolean(literal == "true"))
+                } else if let Ok(i) = number.parse() {
+                    Ok(TomlValue::Integer(i))
+                } else if let Ok(f) = number.parse() {
+                    Ok(TomlValue::Float(f))
+                } else {
+                    Err(format!("Invalid value: {}", literal))
+                }
+            }
+        }
+    }
+
+    /// Accepts only whitespace or a comment up to the end of the line,
+    /// which it consumes
+    fn end(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.next() {
+            None | Some('\n') => Ok(()),
+            Some('#') => {
+                while !matches!(self.next(), None | Some('\n')) {}
+                Ok(())
+            }
+            Some(c) => Err(format!("Unexpected character: {}", c)),
+        }
+    }
+
+    /// Reads the tables and keys of a document into `entries`
+    fn document(&mut self, entries: &mut Vec<(String, String, TomlValue)>) -> Result<(), String> {
+        let mut table = String::new();
+
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                None => return Ok(()),
+                Some('\n') | Some('#') => self.end()?,
+                Some('[') => {
+                    self.next();
+                    table = self.key()?;
+                    self.skip_whitespace();
+                    if self.next() != Some(']') {
+                        return Err("Expected ']' after table name".to_string());
+                    }
+                    self.end()?;
+                }
+                Some(_) => {
+                    let key = self.key()?;
+                    self.skip_whitespace();
+                    if self.next() != Some('=') {
+                        return Err(format!("Expected '=' after {}", key));
+                    }
+                    let value = self.value()?;
+                    self.end()?;
+                    entries.push((table.clone(), key, value));
+                }
+            }
+        }
+    }
+}
+
+impl Toml {
+    pub fn parse(text: &str) -> Result<Toml> {
+        let mut toml = Toml::default();
+        let mut cursor = Cursor {
+            chars: text.chars().peekable(),
+            line: 1,
+        };
+
+        match cursor.document(&mut toml.entries) {
+            Ok(()) => Ok(toml),
+            Err(e) => Err(AdsTxtError::new(&format!("line {}: {}", cursor.line, e))),
+        }
+    }
+
+    /// Value of a key in a table, the root table being ""
+    pub fn get(&self, table: &str, key: &str) -> Option<&TomlValue> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(t, k, _)| t == table && k == key)
+            .map(|(_, _, v)| v)
+    }
+
+    /// Keys and values of a table, in document order
+    pub fn table(&self, table: &str) -> Vec<(&str, &TomlValue)> {
+        self.entries
+            .iter()
+            .filter(|(t, _, _)| t == table)
+            .map(|(_, k, v)| (k.as_str(), v))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_toml() {
+        let toml = Toml::parse(
+            r#"
+            # Shared settings
+            user-agent = "crawler \"x\"" # trailing comment
+            concurrency = 1_000
+
+            [rules]
+            duplicate-record = 'error'
+            "unknown-variable" = "off"
+            list = [1, 2.5, true, "a"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            toml.get("", "user-agent").and_then(|v| v.as_str()),
+            Some("crawler \"x\"")
+        );
+        assert_eq!(toml.get("", "concurrency"), Some(&TomlValue::Integer(1000)));
+        assert_eq!(
+            toml.table("rules")[..2],
+            [
+                ("duplicate-record", &TomlValue::String("error".to_string())),
+                ("unknown-variable", &TomlValue::String("off".to_string())),
+            ]
+        );
+        assert_eq!(
+            toml.get("rules", "list"),
+            Some(&TomlValue::Array(vec![
+                TomlValue::Integer(1),
+                TomlValue::Float(2.5),
+                TomlValue::Boolean(true),
+                TomlValue::String("a".to_string()),
+            ]))
+        );
+
+        let multi_line = Toml::parse(
+            "seats = [\n  \"a.com, 1, DIRECT\", # first\n\n  # second\n  \"b.com, 2, RESELLER\",\n]\nnext = 1",
+        )
+        .unwrap();
+        assert_eq!(
+            multi_line.get("", "seats"),
+            Some(&TomlValue::Array(vec![
+                TomlValue::String("a.com, 1, DIRECT".to_string()),
+                TomlValue::String("b.com, 2, RESELLER".to_string()),
+            ]))
+        );
+        assert_eq!(multi_line.get("", "next"), Some(&TomlValue::Integer(1)));
+
+        assert_eq!(
+            Toml::parse("a = b").unwrap_err().message(),
+            "line 1: Invalid value: b"
+        );
+        assert_eq!(
+            Toml::parse("a = [\n1,\n2 3]").unwrap_err().message(),
+            "line 3: Expected ',' or ']' in array"
+        );
+        assert!(Toml::parse("[rules").is_err());
+        assert!(Toml::parse("a = \"x").is_err());
+        assert!(Toml::parse("a = \"x\ny\"").is_err());
+        assert!(Toml::parse("a = 1 2").is_err());
+        assert!(Toml::parse("a = [1,\n2").is_err());
+    }
+}
@@ -0,0 +1,894 @@
+//! Line-level diagnostics for ads.txt files
+
+use crate::deadline::{Deadline, TimedOut};
+use crate::findings::Severity;
+use crate::homograph;
+use crate::json::JsonValue;
+use crate::publisher_id::{IdComparison, PublisherIdPolicy};
+use crate::toml::{Toml, TomlValue};
+use crate::{is_valid_domain, AdsTxtError, DataRecord, FileKind, Result, Variable};
+use std::collections::{HashMap, HashSet};
+
+/// Variable names defined by the ads.txt spec
+pub(crate) const KNOWN_VARIABLES: &[&str] = &[
+    "CONTACT",
+    "SUBDOMAIN",
+    "INVENTORYPARTNERDOMAIN",
+    "OWNERDOMAIN",
+    "MANAGERDOMAIN",
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Rule {
+    /// Line which is neither a record nor a variable
+    InvalidLine,
+    /// Ad system or subdomain which is not a valid domain name
+    InvalidDomain,
+    EmptyPublisherId,
+    DuplicateRecord,
+    /// Record differing from an earlier one only by case, whitespace within
+    /// the publisher id or a trailing dot of the domain
+    NearDuplicateRecord,
+    /// Record whose publisher id is one inserted, deleted or replaced
+    /// character away from that of an earlier record of the ad system.
+    /// Sequential ids of distinct accounts look the same, so it is only
+    /// informational.
+    PublisherIdTypo,
+    UnknownVariable,
+    /// Ad system domain mixing scripts or imitating a major exchange with
+    /// confusable characters
+    HomographDomain,
+    /// Cert authority id missing or present against the validator's
+    /// `CertAuthorityPolicy`
+    CertAuthority,
+    /// Record of an ad system the validator forbids
+    ForbiddenDomain,
+    /// Seat the validator requires which the file does not list
+    MissingSeat,
+}
+
+impl Rule {
+    pub const ALL: &'static [Rule] = &[
+        Rule::InvalidLine,
+        Rule::InvalidDomain,
+        Rule::EmptyPublisherId,
+        Rule::DuplicateRecord,
+        Rule::NearDuplicateRecord,
+        Rule::PublisherIdTypo,
+        Rule::UnknownVariable,
+        Rule::HomographDomain,
+        Rule::CertAuthority,
+        Rule::ForbiddenDomain,
+        Rule::MissingSeat,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rule::InvalidLine => "invalid-line",
+            Rule::InvalidDomain => "invalid-domain",
+            Rule::EmptyPublisherId => "empty-publisher-id",
+            Rule::DuplicateRecord => "duplicate-record",
+            Rule::NearDuplicateRecord => "near-duplicate-record",
+            Rule::PublisherIdTypo => "publisher-id-typo",
+            Rule::UnknownVariable => "unknown-variable",
+            Rule::HomographDomain => "homograph-domain",
+            Rule::CertAuthority => "cert-authority",
+            Rule::ForbiddenDomain => "forbidden-domain",
+            Rule::MissingSeat => "missing-seat",
+        }
+    }
+
+    /// Finds a rule by its `as_str` name
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL
+            .iter()
+            .copied()
+            .find(|r| r.as_str() == name.trim())
+    }
+
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Rule::InvalidLine
+            | Rule::EmptyPublisherId
+            | Rule::HomographDomain
+            | Rule::ForbiddenDomain
+            | Rule::MissingSeat => Severity::Error,
+            Rule::InvalidDomain
+            | Rule::DuplicateRecord
+            | Rule::NearDuplicateRecord
+            | Rule::UnknownVariable
+            | Rule::CertAuthority => Severity::Warning,
+            Rule::PublisherIdTypo => Severity::Info,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// 1-based line number, 0 for diagnostics of the whole file
+    pub line: usize,
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("line".to_string(), JsonValue::number(self.line)),
+            ("rule".to_string(), JsonValue::string(self.rule.as_str())),
+            (
+                "severity".to_string(),
+                JsonValue::string(self.severity.as_str()),
+            ),
+            ("message".to_string(), JsonValue::string(&self.message)),
+        ])
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}: {}: {} [{}]",
+            self.line,
+            self.severity.as_str().to_lowercase(),
+            self.message,
+            self.rule.as_str()
+        )
+    }
+}
+
+fn check_variable<F: FnMut(Rule, String)>(variable: &Variable, file: FileKind, diagnose: &mut F) {
+    let name = variable.name.to_uppercase();
+    if !KNOWN_VARIABLES.contains(&name.as_str()) {
+        diagnose(
+            Rule::UnknownVariable,
+            format!("Unknown variable: {}", variable.name),
+        );
+    } else if name == "SUBDOMAIN" && file == FileKind::AppAdsTxt {
+        // app-ads.txt applies to the apps of a developer, not to websites
+        diagnose(
+            Rule::UnknownVariable,
+            format!("Variable not supported in app-ads.txt: {}", variable.name),
+        );
+    } else if name == "SUBDOMAIN" && !is_valid_domain(&variable.value) {
+        diagnose(
+            Rule::InvalidDomain,
+            format!("Invalid subdomain: {}", variable.value),
+        );
+    }
+}
+
+/// Whether two publisher ids of the same ad system are the same one written
+/// differently, equal ignoring case and whitespace
+fn near_publisher_ids(a: &str, b: &str) -> bool {
+    let significant = |id: &str| {
+        id.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_lowercase())
+            .collect::<Vec<_>>()
+    };
+    significant(a) == significant(b)
+}
+
+/// Whether two distinct publisher ids of the same ad system may be the same
+/// one mistyped, long enough for a single inserted, deleted or replaced
+/// character to be a typo
+fn publisher_id_typo(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if short.len() < 4 || long.len() - short.len() > 1 {
+        return false;
+    }
+
+    let prefix = short.iter().zip(&long).take_while(|(x, y)| x == y).count();
+    if prefix == long.len() {
+        false
+    } else if short.len() == long.len() {
+        short[prefix + 1..] == long[prefix + 1..]
+    } else {
+        short[prefix..] == long[prefix + 1..]
+    }
+}
+
+/// Whether records of an ad system must carry a cert authority id, the
+/// TAG-ID of exchanges certified by the Trustworthy Accountability Group
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum CertAuthorityPolicy {
+    Required,
+    #[default]
+    Optional,
+    Forbidden,
+}
+
+impl CertAuthorityPolicy {
+    pub const ALL: &'static [CertAuthorityPolicy] = &[
+        CertAuthorityPolicy::Required,
+        CertAuthorityPolicy::Optional,
+        CertAuthorityPolicy::Forbidden,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CertAuthorityPolicy::Required => "required",
+            CertAuthorityPolicy::Optional => "optional",
+            CertAuthorityPolicy::Forbidden => "forbidden",
+        }
+    }
+
+    /// Finds a policy by its `as_str` name, ignoring case
+    pub fn from_name(name: &str) -> Option<CertAuthorityPolicy> {
+        CertAuthorityPolicy::ALL
+            .iter()
+            .copied()
+            .find(|p| p.as_str().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Validator with per-rule severities, allowing rules to be turned off
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Validator {
+    /// Rules whose severity differs from the default, `None` if turned off
+    overrides: Vec<(Rule, Option<Severity>)>,
+    file: FileKind,
+    cert_authority: CertAuthorityPolicy,
+    /// Policies of ad system domains differing from `cert_authority`
+    cert_authority_overrides: Vec<(String, CertAuthorityPolicy)>,
+    required_seats: Vec<DataRecord>,
+    forbidden_domains: Vec<String>,
+    publisher_ids: PublisherIdPolicy,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.set(rule, Some(severity));
+        self
+    }
+
+    pub fn without_rule(mut self, rule: Rule) -> Self {
+        self.set(rule, None);
+        self
+    }
+
+    /// Checks files of the given kind, ads.txt by default
+    pub fn with_file_kind(mut self, file: FileKind) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// Policy on the cert authority ids of all ad systems, `Optional` by
+    /// default
+    pub fn with_cert_authority_policy(mut self, policy: CertAuthorityPolicy) -> Self {
+        self.cert_authority = policy;
+        self
+    }
+
+    /// Policy on the cert authority ids of the records of an ad system,
+    /// overriding that of all ad systems
+    pub fn with_cert_authority_policy_for(
+        mut self,
+        domain: &str,
+        policy: CertAuthorityPolicy,
+    ) -> Self {
+        let domain = policy_domain(domain);
+        self.cert_authority_overrides.retain(|(d, _)| *d != domain);
+        self.cert_authority_overrides.push((domain, policy));
+        self
+    }
+
+    /// Policy on the cert authority ids of the records of an ad system
+    pub fn cert_authority_policy(&self, domain: &str) -> CertAuthorityPolicy {
+        let domain = policy_domain(domain);
+        self.cert_authority_overrides
+            .iter()
+            .find(|(d, _)| *d == domain)
+            .map_or(self.cert_authority, |(_, policy)| *policy)
+    }
+
+    /// Requires a record of the seat's ad system, publisher id and relation,
+    /// whatever its cert authority id
+    pub fn with_required_seat(mut self, seat: DataRecord) -> Self {
+        self.required_seats.push(seat);
+        self
+    }
+
+    /// Forbids records of an ad system
+    pub fn with_forbidden_domain(mut self, domain: &str) -> Self {
+        self.forbidden_domains.push(policy_domain(domain));
+        self
+    }
+
+    /// Compares publisher ids by the policy when finding duplicate records
+    /// and required seats, exactly by default
+    pub fn with_publisher_id_policy(mut self, policy: PublisherIdPolicy) -> Self {
+        self.publisher_ids = policy;
+        self
+    }
+
+    pub fn publisher_id_policy(&self) -> &PublisherIdPolicy {
+        &self.publisher_ids
+    }
+
+    /// Validator configured by a TOML policy file, as of `with_policy`
+    pub fn from_policy_str(text: &str) -> Result<Validator> {
+        Validator::new().with_policy(&Toml::parse(text)?)
+    }
+
+    /// Configures severities from a table of a TOML file mapping rule names
+    /// to `error`, `warning`, `info` or `off`
+    pub fn with_rules(mut self, config: &Toml, table: &str) -> Result<Self> {
+        for (name, value) in config.table(table) {
+            let rule = Rule::from_name(name)
+                .ok_or_else(|| AdsTxtError::new(&format!("Unknown lint rule: {}", name)))?;
+            self = match value.as_str().map(str::to_lowercase).as_deref() {
+                Some("error") => self.with_severity(rule, Severity::Error),
+                Some("warning") => self.with_severity(rule, Severity::Warning),
+                Some("info") => self.with_severity(rule, Severity::Info),
+                Some("off") => self.without_rule(rule),
+                _ => {
+                    let message = format!("Invalid severity for lint rule {}", name);
+                    return Err(AdsTxtError::new(&message));
+                }
+            };
+        }
+
+        Ok(self)
+    }
+
+    /// Configures the validator from a TOML policy: the severities of its
+    /// `[rules]` table, the seats, domains and cert authority ids its
+    /// `[policy]` tables require or forbid, and how they compare publisher
+    /// ids
+    pub fn with_policy(self, config: &Toml) -> Result<Self> {
+        let mut validator = self.with_rules(config, "rules")?;
+        let strings = |key: &str, value: &TomlValue| -> Result<Vec<String>> {
+            match value {
+                TomlValue::Array(items) => items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string))
+                    .collect::<Option<_>>(),
+                _ => None,
+            }
+            .ok_or_else(|| AdsTxtError::new(&format!("Policy {} must be an array of strings", key)))
+        };
+        let cert_authority = |value: &TomlValue| {
+            value
+                .as_str()
+                .and_then(CertAuthorityPolicy::from_name)
+                .ok_or_else(|| AdsTxtError::new("Invalid cert authority policy"))
+        };
+        let comparison = |value: &TomlValue| {
+            value
+                .as_str()
+                .and_then(IdComparison::from_name)
+                .ok_or_else(|| AdsTxtError::new("Invalid publisher id comparison"))
+        };
+
+        for (key, value) in config.table("policy") {
+            validator = match key {
+                "cert-authority" => validator.with_cert_authority_policy(cert_authority(value)?),
+                "publisher-ids" => {
+                    let policy = validator.publisher_ids.clone();
+                    validator.with_publisher_id_policy(policy.with_default(comparison(value)?))
+                }
+                "required-seats" => {
+                    strings(key, value)?.iter().try_fold(validator, |v, seat| {
+                        DataRecord::parse(seat).map(|seat| v.with_required_seat(seat))
+                    })?
+                }
+                "forbidden-domains" => strings(key, value)?
+                    .iter()
+                    .fold(validator, |v, domain| v.with_forbidden_domain(domain)),
+                _ => {
+                    let message = format!("Unknown policy setting: {}", key);
+                    return Err(AdsTxtError::new(&message));
+                }
+            };
+        }
+        for (domain, value) in config.table("policy.cert-authority") {
+            validator = validator.with_cert_authority_policy_for(domain, cert_authority(value)?);
+        }
+        for (domain, value) in config.table("policy.publisher-ids") {
+            let policy = validator.publisher_ids.clone();
+            validator = validator
+                .with_publisher_id_policy(policy.with_override(domain, comparison(value)?));
+        }
+
+        Ok(validator)
+    }
+
+    fn set(&mut self, rule: Rule, severity: Option<Severity>) {
+        self.overrides.retain(|(r, _)| *r != rule);
+        self.overrides.push((rule, severity));
+    }
+
+    /// Severity of the rule's diagnostics, `None` if the rule is turned off
+    pub fn severity(&self, rule: Rule) -> Option<Severity> {
+        match self.overrides.iter().find(|(r, _)| *r == rule) {
+            Some((_, severity)) => *severity,
+            None => Some(rule.default_severity()),
+        }
+    }
+
+    /// Checks an ads.txt file, returning diagnostics in line order
+    pub fn validate(&self, text: &str) -> Vec<Diagnostic> {
+        self.validate_until(text, &Deadline::never())
+            .expect("a deadline without a token never expires")
+    }
+
+    /// Checks an ads.txt file like `validate`, giving up once the deadline
+    /// expires
+    pub fn validate_until(
+        &self,
+        text: &str,
+        deadline: &Deadline,
+    ) -> Result<Vec<Diagnostic>, TimedOut> {
+        Ok(diagnostics(text, self, deadline)?
+            .into_iter()
+            .filter_map(|d| {
+                self.severity(d.rule)
+                    .map(|severity| Diagnostic { severity, ..d })
+            })
+            .collect())
+    }
+}
+
+/// Checks an ads.txt file with the default severities, returning diagnostics
+/// in line order
+pub fn validate(text: &str) -> Vec<Diagnostic> {
+    Validator::new().validate(text)
+}
+
+/// Ad system domain as compared by the policies of a validator
+fn policy_domain(domain: &str) -> String {
+    domain.trim().trim_end_matches('.').to_lowercase()
+}
+
+fn diagnostics(
+    text: &str,
+    validator: &Validator,
+    deadline: &Deadline,
+) -> Result<Vec<Diagnostic>, TimedOut> {
+    let mut diagnostics = vec![];
+    let mut records = HashSet::new();
+    // Line and publisher id of the records of each ad system and relation
+    let mut seats: HashMap<(String, &'static str), Vec<(usize, String)>> = HashMap::new();
+    let mut missing_seats: Vec<&DataRecord> = validator.required_seats.iter().collect();
+
+    for (i, line) in text.lines().enumerate() {
+        deadline.check(i)?;
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut diagnose = |rule: Rule, message: String| {
+            diagnostics.push(Diagnostic {
+                line: i + 1,
+                rule,
+                severity: rule.default_severity(),
+                message,
+            })
+        };
+
+        let record = match DataRecord::parse(line) {
+            Ok(record) => record,
+            Err(record_error) => {
+                match Variable::parse(line) {
+                    Ok(variable) => check_variable(&variable, validator.file, &mut diagnose),
+                    // Report why the record did not parse, e.g. an invalid relation
+                    Err(_) if line.contains(',') => {
+                        diagnose(Rule::InvalidLine, record_error.to_string())
+                    }
+                    Err(_) => diagnose(
+                        Rule::InvalidLine,
+                        format!("Invalid ads.txt line: {}", line.trim_end()),
+                    ),
+                }
+                continue;
+            }
+        };
+
+        if !is_valid_domain(&record.domain) {
+            diagnose(
+                Rule::InvalidDomain,
+                format!("Invalid ad system domain: {}", record.domain),
+            );
+        }
+        if let Some(exchange) = homograph::impersonated_exchange(&record.domain) {
+            diagnose(
+                Rule::HomographDomain,
+                format!("Ad system domain {} imitates {}", record.domain, exchange),
+            );
+        } else if homograph::has_mixed_scripts(&record.domain) {
+            diagnose(
+                Rule::HomographDomain,
+                format!("Ad system domain mixes scripts: {}", record.domain),
+            );
+        }
+        if record.publisher_id.is_empty() {
+            diagnose(Rule::EmptyPublisherId, "Empty publisher id".to_string());
+        }
+        match (
+            validator.cert_authority_policy(&record.domain),
+            &record.cert_authority,
+        ) {
+            (CertAuthorityPolicy::Required, None) => diagnose(
+                Rule::CertAuthority,
+                format!("Missing cert authority id for {}", record.domain),
+            ),
+            (CertAuthorityPolicy::Forbidden, Some(cert_authority)) => diagnose(
+                Rule::CertAuthority,
+                format!(
+                    "Cert authority id {} not allowed for {}",
+                    cert_authority, record.domain
+                ),
+            ),
+            _ => {}
+        }
+        if validator
+            .forbidden_domains
+            .contains(&policy_domain(&record.domain))
+        {
+            diagnose(
+                Rule::ForbiddenDomain,
+                format!("Forbidden ad system: {}", record.domain),
+            );
+        }
+        missing_seats.retain(|seat| {
+            policy_domain(&seat.domain) != policy_domain(&record.domain)
+                || !validator.publisher_ids.same_id(
+                    &record.domain,
+                    &seat.publisher_id,
+                    &record.publisher_id,
+                )
+                || seat.acc_relation != record.acc_relation
+        });
+
+        let key = (
+            record.domain.to_lowercase(),
+            validator
+                .publisher_ids
+                .key(&record.domain, &record.publisher_id)
+                .into_owned(),
+            record.acc_relation.as_str(),
+        );
+        if !records.insert(key) {
+            diagnose(
+                Rule::DuplicateRecord,
+                format!("Duplicate record: {}", line.trim_end()),
+            );
+            continue;
+        }
+
+        let seat = (
+            record.domain.trim_end_matches('.').to_lowercase(),
+            record.acc_relation.as_str(),
+        );
+        let listed = seats.entry(seat).or_default();
+        if let Some((near_line, _)) = listed
+            .iter()
+            .find(|(_, id)| near_publisher_ids(id, &record.publisher_id))
+        {
+            diagnose(
+                Rule::NearDuplicateRecord,
+                format!(
+                    "Near-duplicate of the record on line {}: {}",
+                    near_line,
+                    line.trim_end()
+                ),
+            );
+        } else if let Some((near_line, _)) = listed
+            .iter()
+            .find(|(_, id)| publisher_id_typo(id, &record.publisher_id))
+        {
+            diagnose(
+                Rule::PublisherIdTypo,
+                format!(
+                    "Publisher id one character away from that on line {}: {}",
+                    near_line,
+                    line.trim_end()
+                ),
+            );
+        }
+        listed.push((i + 1, record.publisher_id.to_string()));
+    }
+
+    // Seats missing from the whole file come first, on line 0
+    let missing = missing_seats.into_iter().map(|seat| Diagnostic {
+        line: 0,
+        rule: Rule::MissingSeat,
+        severity: Rule::MissingSeat.default_severity(),
+        message: format!("Missing required seat: {}", seat),
+    });
+    Ok(missing.chain(diagnostics).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validating_ads_txt() {
+        let diagnostics = validate(
+            r"# comment
+greenadexchange.com, 12345, DIRECT
+greenadexchange.com, 12345, DIRECT
+blue_exchange, XF436, RESELLER
+silverssp.com, , DIRECT
+orangeexchange.com, AB345, OTHER
+subdomain=divisionone.example.com
+subdomain=not a domain
+colour=blue
+nonsense",
+        );
+
+        let summary: Vec<(usize, Rule, Severity)> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.rule, d.severity))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (3, Rule::DuplicateRecord, Severity::Warning),
+                (4, Rule::InvalidDomain, Severity::Warning),
+                (5, Rule::EmptyPublisherId, Severity::Error),
+                (6, Rule::InvalidLine, Severity::Error),
+                (8, Rule::InvalidDomain, Severity::Warning),
+                (9, Rule::UnknownVariable, Severity::Warning),
+                (10, Rule::InvalidLine, Severity::Error),
+            ]
+        );
+        assert_eq!(
+            diagnostics[3].to_string(),
+            "line 6: error: Invalid account relation:  OTHER [invalid-line]"
+        );
+        assert_eq!(
+            diagnostics[0].to_json().to_string(),
+            r#"{"line":3,"rule":"duplicate-record","severity":"WARNING","message":"Duplicate record: greenadexchange.com, 12345, DIRECT"}"#
+        );
+        assert!(validate("a.com, 1, DIRECT\ncontact=x@a.com").is_empty());
+    }
+
+    #[test]
+    fn configuring_rules() {
+        let validator = Validator::new()
+            .with_severity(Rule::DuplicateRecord, Severity::Error)
+            .without_rule(Rule::UnknownVariable);
+        let diagnostics = validator.validate("a.com, 1, DIRECT\na.com, 1, DIRECT\ncolour=blue");
+
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                line: 2,
+                rule: Rule::DuplicateRecord,
+                severity: Severity::Error,
+                message: "Duplicate record: a.com, 1, DIRECT".to_string(),
+            }]
+        );
+        assert_eq!(validator.severity(Rule::InvalidLine), Some(Severity::Error));
+        assert_eq!(
+            Rule::from_name("unknown-variable"),
+            Some(Rule::UnknownVariable)
+        );
+        assert_eq!(Rule::from_name("other"), None);
+
+        let app_ads = Validator::new().with_file_kind(FileKind::AppAdsTxt);
+        assert_eq!(
+            app_ads.validate("subdomain=games.example.com")[0].message,
+            "Variable not supported in app-ads.txt: subdomain"
+        );
+        assert!(validate("subdomain=games.example.com").is_empty());
+    }
+
+    #[test]
+    fn flagging_near_duplicates() {
+        let diagnostics = validate(
+            "exchange.com, pub-12345, DIRECT
+Exchange.com., PUB-12345, DIRECT
+exchange.com, pub-123 45, DIRECT
+exchange.com, pub-12346, DIRECT
+exchange.com, pub-1234, DIRECT
+exchange.com, pub-12345, RESELLER
+exchange.com, 1, DIRECT
+exchange.com, 2, DIRECT
+other.com, pub-12345, DIRECT",
+        );
+        let summary: Vec<(usize, Rule, Severity)> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.rule, d.severity))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (2, Rule::NearDuplicateRecord, Severity::Warning),
+                (3, Rule::NearDuplicateRecord, Severity::Warning),
+                (4, Rule::PublisherIdTypo, Severity::Info),
+                (5, Rule::PublisherIdTypo, Severity::Info),
+            ]
+        );
+        assert_eq!(
+            diagnostics[2].message,
+            "Publisher id one character away from that on line 1: exchange.com, pub-12346, DIRECT"
+        );
+        let strict = Validator::new().without_rule(Rule::PublisherIdTypo);
+        assert_eq!(
+            strict.validate("a.com, pub-12345, DIRECT\na.com, pub-12346, DIRECT"),
+            vec![]
+        );
+
+        assert!(near_publisher_ids("Pub-1", "pub- 1"));
+        assert!(!near_publisher_ids("pub-1", "pub-2"));
+        assert!(publisher_id_typo("abcde", "abde"));
+        assert!(publisher_id_typo("abcd", "xbcd"));
+        assert!(!publisher_id_typo("abcd", "abdc"));
+        assert!(!publisher_id_typo("abc", "abd"));
+        assert!(!publisher_id_typo("abcd", "abcd"));
+        assert!(publisher_id_typo("abcd", "abcde"));
+    }
+
+    #[test]
+    fn compiling_policies() {
+        let validator = Validator::from_policy_str(
+            r#"
+            [rules]
+            duplicate-record = "off"
+            invalid-line = "Error"
+
+            [policy]
+            cert-authority = "required"
+            required-seats = [
+                "a.com, 1, DIRECT",
+                # Resold through the network
+                "c.com, 2, RESELLER",
+            ]
+            forbidden-domains = ["bad.com"]
+
+            [policy.cert-authority]
+            "b.com" = "optional"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(validator.severity(Rule::DuplicateRecord), None);
+        assert_eq!(
+            validator.cert_authority_policy("B.com."),
+            CertAuthorityPolicy::Optional
+        );
+        let rules: Vec<(usize, Rule)> = validator
+            .validate("b.com, 1, DIRECT\nc.com, 2, RESELLER, x\nbad.com, 1, DIRECT, abc")
+            .iter()
+            .map(|d| (d.line, d.rule))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![(0, Rule::MissingSeat), (3, Rule::ForbiddenDomain)]
+        );
+
+        let validator = Validator::from_policy_str(
+            r#"
+            [policy]
+            publisher-ids = "case-insensitive"
+            required-seats = ["a.com, ab, DIRECT"]
+
+            [policy.publisher-ids]
+            "b.com" = "exact"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            validator.publisher_id_policy().comparison("b.com"),
+            IdComparison::Exact
+        );
+        let rules: Vec<(usize, Rule)> = validator
+            .validate(
+                "a.com, AB, DIRECT
+a.com, ab, DIRECT
+b.com, CD, DIRECT
+b.com, cd, DIRECT",
+            )
+            .iter()
+            .map(|d| (d.line, d.rule))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![(2, Rule::DuplicateRecord), (4, Rule::NearDuplicateRecord)]
+        );
+
+        for invalid in &[
+            "[rules]\nother = \"off\"",
+            "[rules]\ninvalid-line = 1",
+            "[policy]\nrequired-seats = [\"a.com\"]",
+            "[policy]\nforbidden-domains = \"bad.com\"",
+            "[policy]\nseats = []",
+            "[policy.cert-authority]\n\"a.com\" = \"always\"",
+            "[policy]\npublisher-ids = \"fuzzy\"",
+            "[policy]\nrequired-seats = [\n\"a.com, 1, DIRECT\"",
+        ] {
+            assert!(Validator::from_policy_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn checking_domains() {
+        assert!(is_valid_domain("example.com"));
+        assert!(is_valid_domain("sub.ex-ample.co.uk."));
+        assert!(!is_valid_domain("localhost"));
+        assert!(!is_valid_domain("-bad.com"));
+        assert!(!is_valid_domain("a..com"));
+        assert!(!is_valid_domain("a b.com"));
+
+        let diagnostics: Vec<String> =
+            validate("\u{0430}ppnexus.com, 1, DIRECT\nad\u{03BD}ert.com, 2, DIRECT")
+                .iter()
+                .map(|d| d.to_string())
+                .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "line 1: error: Ad system domain \u{0430}ppnexus.com imitates appnexus.com [homograph-domain]",
+                "line 2: error: Ad system domain mixes scripts: ad\u{03BD}ert.com [homograph-domain]",
+            ]
+        );
+    }
+
+    #[test]
+    fn applying_cert_authority_policies() {
+        let text = "a.com, 1, DIRECT\nb.com, 2, DIRECT, f08c47fec0942fa0\nC.com., 3, RESELLER";
+        assert!(validate(text).is_empty());
+
+        let validator = Validator::new()
+            .with_cert_authority_policy(CertAuthorityPolicy::Required)
+            .with_cert_authority_policy_for("b.com", CertAuthorityPolicy::Forbidden)
+            .with_cert_authority_policy_for("c.com", CertAuthorityPolicy::Optional);
+        assert_eq!(
+            validator.cert_authority_policy("C.COM."),
+            CertAuthorityPolicy::Optional
+        );
+        let diagnostics: Vec<String> = validator
+            .validate(text)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "line 1: warning: Missing cert authority id for a.com [cert-authority]",
+                "line 2: warning: Cert authority id f08c47fec0942fa0 not allowed for b.com [cert-authority]",
+            ]
+        );
+        assert_eq!(
+            CertAuthorityPolicy::from_name("Required"),
+            Some(CertAuthorityPolicy::Required)
+        );
+    }
+
+    #[test]
+    fn applying_seat_policies() {
+        let validator = Validator::new()
+            .with_required_seat(DataRecord::parse("google.com, pub-1, DIRECT").unwrap())
+            .with_required_seat(DataRecord::parse("a.com, 2, RESELLER").unwrap())
+            .with_forbidden_domain("Bad.com.");
+        let diagnostics: Vec<String> = validator
+            .validate(
+                "google.com, pub-1, DIRECT, f08c47fec0942fa0\na.com, 2, DIRECT\nbad.com, 3, DIRECT",
+            )
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "line 0: error: Missing required seat: a.com, 2, RESELLER [missing-seat]",
+                "line 3: error: Forbidden ad system: bad.com [forbidden-domain]",
+            ]
+        );
+    }
+}
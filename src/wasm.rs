@@ -0,0 +1,101 @@
+//! JavaScript bindings for `wasm32-unknown-unknown` through `wasm-bindgen`,
+//! with the `wasm` feature. Results are returned as JSON text in the format
+//! of the `serialize` module and the CLI's `--format json` output.
+
+use crate::diff::AdsTxtDiff;
+use crate::json::JsonValue;
+use crate::serialize::ads_txt_to_json;
+use crate::validate::{Diagnostic, Validator};
+use crate::{AdsTxt, FileKind};
+use wasm_bindgen::prelude::*;
+
+fn file_kind(app: bool) -> FileKind {
+    if app {
+        FileKind::AppAdsTxt
+    } else {
+        FileKind::AdsTxt
+    }
+}
+
+fn diagnostics_json(diagnostics: &[Diagnostic]) -> String {
+    JsonValue::Array(diagnostics.iter().map(|d| d.to_json()).collect()).to_string()
+}
+
+/// Parses a file leniently into `{"ads_txt": {...}, "errors": [...]}`
+#[wasm_bindgen(js_name = parseAdsTxt)]
+pub fn parse_ads_txt(text: &str) -> String {
+    let (ads_txt, errors) = AdsTxt::parse_lenient(text);
+    JsonValue::Object(vec![
+        ("ads_txt".to_string(), ads_txt_to_json(&ads_txt)),
+        (
+            "errors".to_string(),
+            JsonValue::Array(
+                errors
+                    .iter()
+                    .map(|e| JsonValue::string(e.message()))
+                    .collect(),
+            ),
+        ),
+    ])
+    .to_string()
+}
+
+/// Validates an ads.txt file, or an app-ads.txt file if `app`, into an
+/// array of diagnostics
+#[wasm_bindgen(js_name = validateAdsTxt)]
+pub fn validate_ads_txt(text: &str, app: bool) -> String {
+    diagnostics_json(
+        &Validator::new()
+            .with_file_kind(file_kind(app))
+            .validate(text),
+    )
+}
+
+/// Validates a file like `validateAdsTxt` against a TOML policy, as taken by
+/// `Validator::from_policy_str`, throwing if the policy is invalid
+#[wasm_bindgen(js_name = validateAdsTxtWithPolicy)]
+pub fn validate_ads_txt_with_policy(
+    text: &str,
+    app: bool,
+    policy: &str,
+) -> Result<String, JsValue> {
+    let validator =
+        Validator::from_policy_str(policy).map_err(|e| JsValue::from_str(e.message()))?;
+    Ok(diagnostics_json(
+        &validator.with_file_kind(file_kind(app)).validate(text),
+    ))
+}
+
+/// Seats and variables added, removed or changed between two files
+#[wasm_bindgen(js_name = diffAdsTxt)]
+pub fn diff_ads_txt(old: &str, new: &str) -> String {
+    let (old, _) = AdsTxt::parse_lenient(old);
+    let (new, _) = AdsTxt::parse_lenient(new);
+    AdsTxtDiff::between(&old, &new).to_json().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calling_bindings() {
+        assert_eq!(
+            parse_ads_txt("ssp.com, 1, DIRECT\nbroken"),
+            r#"{"ads_txt":{"records":[{"domain":"ssp.com","publisher_id":"1","relation":"DIRECT","cert_authority":null}],"variables":[]},"errors":["Invalid ads.txt line: broken"]}"#
+        );
+        assert_eq!(validate_ads_txt("subdomain=a.example.com", false), "[]");
+        assert!(validate_ads_txt("subdomain=a.example.com", true).contains("unknown-variable"));
+
+        let policy = "[policy]\nforbidden-domains = [\"ssp.com\"]";
+        let diagnostics = validate_ads_txt_with_policy("ssp.com, 1, DIRECT", false, policy);
+        assert!(diagnostics.unwrap().contains("forbidden-domain"));
+
+        let diff = diff_ads_txt("ssp.com, 1, DIRECT", "ssp.com, 2, DIRECT");
+        assert!(diff.starts_with(r#"{"seats":["#));
+        assert_eq!(
+            diff_ads_txt("ssp.com, 1, DIRECT", "ssp.com, 1, DIRECT"),
+            r#"{"seats":[],"added_variables":[],"removed_variables":[]}"#
+        );
+    }
+}
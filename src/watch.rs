@@ -0,0 +1,512 @@
+//! Watching publishers' files for changes, with alerts on the changes that
+//! matter to a user, e.g. their own seat being removed
+
+use crate::diff::{AdsTxtDiff, SeatChange};
+use crate::graph::normalize_domain;
+use crate::publisher_id::PublisherIdPolicy;
+use crate::{AccountRelation, AdsTxt, DataRecord, Variable};
+use std::collections::{BTreeMap, HashMap};
+use std::time::SystemTime;
+
+/// Single change of a watched file
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Change<'a> {
+    Seat(&'a SeatChange),
+    VariableAdded(&'a Variable),
+    VariableRemoved(&'a Variable),
+}
+
+impl<'a> Change<'a> {
+    /// Changes making up the diff
+    pub fn all(diff: &'a AdsTxtDiff) -> Vec<Change<'a>> {
+        diff.seats
+            .iter()
+            .map(Change::Seat)
+            .chain(diff.added_variables.iter().map(Change::VariableAdded))
+            .chain(diff.removed_variables.iter().map(Change::VariableRemoved))
+            .collect()
+    }
+
+    /// Record listed by the new version of the file, if any
+    fn added_record(&self) -> Option<&'a DataRecord> {
+        match self {
+            Change::Seat(SeatChange::Added(record)) => Some(record),
+            Change::Seat(SeatChange::Changed { new, .. }) => Some(new),
+            _ => None,
+        }
+    }
+
+    /// Record no longer listed by the new version of the file, if any
+    fn removed_record(&self) -> Option<&'a DataRecord> {
+        match self {
+            Change::Seat(SeatChange::Removed(record)) => Some(record),
+            Change::Seat(SeatChange::Changed { old, .. }) => Some(old),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Change<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Seat(change) => write!(f, "{}", change),
+            Change::VariableAdded(variable) => write!(f, "+ {}", variable),
+            Change::VariableRemoved(variable) => write!(f, "- {}", variable),
+        }
+    }
+}
+
+/// Matches the changes of a publisher's file an alert fires on
+pub type ChangePredicate = Box<dyn Fn(&str, &Change<'_>) -> bool + Send>;
+
+/// Receives the publisher domain and the matching changes of its file
+pub type AlertCallback = Box<dyn FnMut(&str, &[Change<'_>]) + Send>;
+
+/// Handle of a registered alert, for removing it
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct AlertId(usize);
+
+/// The seat's record was removed or replaced, e.g. by one with another
+/// relation
+pub fn seat_removed(ad_system: &str, seller_id: &str) -> ChangePredicate {
+    seat_removed_with_policy(ad_system, seller_id, PublisherIdPolicy::default())
+}
+
+/// Like `seat_removed`, comparing seller ids by the policy
+pub fn seat_removed_with_policy(
+    ad_system: &str,
+    seller_id: &str,
+    policy: PublisherIdPolicy,
+) -> ChangePredicate {
+    let ad_system = ad_system.trim().to_string();
+    let seller_id = seller_id.trim().to_string();
+    Box::new(move |_, change| {
+        change.removed_record().is_some_and(|r| {
+            r.domain.eq_ignore_ascii_case(&ad_system)
+                && policy.same_id(&ad_system, &r.publisher_id, &seller_id)
+        })
+    })
+}
+
+/// A record with the relation was added, or an existing record changed to it
+pub fn relation_added(relation: AccountRelation) -> ChangePredicate {
+    Box::new(move |_, change| {
+        change
+            .added_record()
+            .is_some_and(|r| r.acc_relation == relation)
+            && change.removed_record().map(|r| &r.acc_relation) != Some(&relation)
+    })
+}
+
+/// Any seat of the ad system changed
+pub fn ad_system_changed(ad_system: &str) -> ChangePredicate {
+    let ad_system = ad_system.trim().to_string();
+    Box::new(move |_, change| {
+        change
+            .added_record()
+            .or_else(|| change.removed_record())
+            .is_some_and(|r| r.domain.eq_ignore_ascii_case(&ad_system))
+    })
+}
+
+/// A variable with the name was added or removed
+pub fn variable_changed(name: &str) -> ChangePredicate {
+    let name = name.trim().to_string();
+    Box::new(move |_, change| match change {
+        Change::VariableAdded(v) | Change::VariableRemoved(v) => v.name.eq_ignore_ascii_case(&name),
+        Change::Seat(_) => false,
+    })
+}
+
+/// Restricts the predicate to the file of one publisher
+pub fn on_domain(domain: &str, predicate: ChangePredicate) -> ChangePredicate {
+    let domain = normalize_domain(domain);
+    Box::new(move |d, change| d == domain && predicate(d, change))
+}
+
+struct Alert {
+    id: AlertId,
+    predicate: ChangePredicate,
+    callback: AlertCallback,
+}
+
+/// Latest known files of many publishers. Every update is diffed against the
+/// previous file of the domain and the changes are passed to the alerts
+/// they match.
+#[derive(Default)]
+pub struct Watcher {
+    files: HashMap<String, AdsTxt>,
+    alerts: Vec<Alert>,
+    next_id: usize,
+    publisher_ids: PublisherIdPolicy,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Identifies seats in diffs by comparing publisher ids by the policy,
+    /// exactly by default
+    pub fn with_publisher_id_policy(mut self, policy: PublisherIdPolicy) -> Self {
+        self.publisher_ids = policy;
+        self
+    }
+
+    /// Registers a callback receiving the changes of each update which match
+    /// the predicate. It is not called for updates without such changes.
+    pub fn on<F>(&mut self, predicate: ChangePredicate, callback: F) -> AlertId
+    where
+        F: FnMut(&str, &[Change<'_>]) + Send + 'static,
+    {
+        let id = AlertId(self.next_id);
+        self.next_id += 1;
+        self.alerts.push(Alert {
+            id,
+            predicate,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Unregisters the alert, returning whether it was registered
+    pub fn remove_alert(&mut self, id: AlertId) -> bool {
+        let count = self.alerts.len();
+        self.alerts.retain(|a| a.id != id);
+        self.alerts.len() < count
+    }
+
+    /// Latest known file of the domain
+    pub fn file(&self, domain: &str) -> Option<&AdsTxt> {
+        self.files.get(&normalize_domain(domain))
+    }
+
+    /// Replaces the latest known file of the domain with the update,
+    /// returning the changes. The first file of a domain is its baseline and
+    /// has no changes.
+    pub fn update(&mut self, domain: &str, ads_txt: AdsTxt) -> AdsTxtDiff {
+        let domain = normalize_domain(domain);
+        let previous = match self.files.insert(domain.clone(), ads_txt) {
+            Some(previous) => previous,
+            None => return AdsTxtDiff::default(),
+        };
+
+        let diff =
+            AdsTxtDiff::between_with_policy(&previous, &self.files[&domain], &self.publisher_ids);
+        let changes = Change::all(&diff);
+        for alert in &mut self.alerts {
+            let matching: Vec<Change<'_>> = changes
+                .iter()
+                .filter(|c| (alert.predicate)(&domain, c))
+                .copied()
+                .collect();
+            if !matching.is_empty() {
+                (alert.callback)(&domain, &matching);
+            }
+        }
+        diff
+    }
+}
+
+impl std::fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watcher")
+            .field("files", &self.files)
+            .field("alerts", &self.alerts.len())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SeatEventKind {
+    /// The publisher started listing the seat
+    Appeared(AccountRelation),
+    /// The publisher stopped listing the seat
+    Disappeared(AccountRelation),
+    RelationChanged {
+        old: AccountRelation,
+        new: AccountRelation,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SeatEvent {
+    pub domain: String,
+    pub at: SystemTime,
+    pub kind: SeatEventKind,
+}
+
+impl std::fmt::Display for SeatEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            SeatEventKind::Appeared(relation) => {
+                write!(f, "{}: appeared as {}", self.domain, relation.as_str())
+            }
+            SeatEventKind::Disappeared(relation) => {
+                write!(f, "{}: {} disappeared", self.domain, relation.as_str())
+            }
+            SeatEventKind::RelationChanged { old, new } => write!(
+                f,
+                "{}: changed from {} to {}",
+                self.domain,
+                old.as_str(),
+                new.as_str()
+            ),
+        }
+    }
+}
+
+/// Tracks one seat, an ad system and publisher id pair, across the files of
+/// monitored publishers. A file listing the seat as both DIRECT and RESELLER
+/// counts as DIRECT.
+#[derive(Debug, Clone)]
+pub struct SeatMonitor {
+    ad_system: String,
+    seller_id: String,
+    publisher_ids: PublisherIdPolicy,
+    /// Relation of the seat in the latest file of each monitored domain,
+    /// `None` before its first file
+    listings: BTreeMap<String, Option<Option<AccountRelation>>>,
+    events: Vec<SeatEvent>,
+}
+
+impl SeatMonitor {
+    pub fn new(ad_system: &str, seller_id: &str) -> Self {
+        SeatMonitor {
+            ad_system: ad_system.trim().to_lowercase(),
+            seller_id: seller_id.trim().to_string(),
+            publisher_ids: PublisherIdPolicy::default(),
+            listings: BTreeMap::new(),
+            events: vec![],
+        }
+    }
+
+    /// Compares the seat's publisher id with those of the files by the
+    /// policy, exactly by default
+    pub fn with_publisher_id_policy(mut self, policy: PublisherIdPolicy) -> Self {
+        self.publisher_ids = policy;
+        self
+    }
+
+    /// Adds publisher domains to monitor
+    pub fn with_domains<I, D>(mut self, domains: I) -> Self
+    where
+        I: IntoIterator<Item = D>,
+        D: AsRef<str>,
+    {
+        for domain in domains {
+            self.monitor(domain.as_ref());
+        }
+        self
+    }
+
+    pub fn monitor(&mut self, domain: &str) {
+        self.listings
+            .entry(normalize_domain(domain))
+            .or_insert(None);
+    }
+
+    /// Stops monitoring the domain, returning whether it was monitored
+    pub fn unmonitor(&mut self, domain: &str) -> bool {
+        self.listings.remove(&normalize_domain(domain)).is_some()
+    }
+
+    pub fn domains(&self) -> impl Iterator<Item = &str> {
+        self.listings.keys().map(String::as_str)
+    }
+
+    /// Relation of the seat in the file, if it is listed
+    fn relation(&self, ads_txt: &AdsTxt) -> Option<AccountRelation> {
+        let records =
+            ads_txt.seat_records_with_policy(&self.ad_system, &self.seller_id, &self.publisher_ids);
+        if records
+            .iter()
+            .any(|r| r.acc_relation == AccountRelation::Direct)
+        {
+            Some(AccountRelation::Direct)
+        } else {
+            records.first().map(|r| r.acc_relation.clone())
+        }
+    }
+
+    /// Checks a fetched file of a monitored domain, returning the event if
+    /// the seat changed since the domain's previous file. The first file of
+    /// a domain is its baseline. Files of other domains are ignored.
+    pub fn update(&mut self, domain: &str, ads_txt: &AdsTxt, at: SystemTime) -> Option<&SeatEvent> {
+        let domain = normalize_domain(domain);
+        let relation = self.relation(ads_txt);
+        let previous = self.listings.get_mut(&domain)?.replace(relation.clone())?;
+
+        let kind = match (previous, relation) {
+            (None, Some(relation)) => SeatEventKind::Appeared(relation),
+            (Some(relation), None) => SeatEventKind::Disappeared(relation),
+            (Some(old), Some(new)) if old != new => SeatEventKind::RelationChanged { old, new },
+            _ => return None,
+        };
+        self.events.push(SeatEvent { domain, at, kind });
+        self.events.last()
+    }
+
+    /// Every event so far, in update order
+    pub fn events(&self) -> &[SeatEvent] {
+        &self.events
+    }
+
+    /// Events since the given time
+    pub fn events_since(&self, since: SystemTime) -> impl Iterator<Item = &SeatEvent> {
+        self.events.iter().filter(move |e| e.at >= since)
+    }
+
+    /// Monitored domains whose latest file lists the seat, with its relation
+    pub fn listed_by(&self) -> Vec<(&str, &AccountRelation)> {
+        self.listings
+            .iter()
+            .filter_map(|(domain, listing)| match listing {
+                Some(Some(relation)) => Some((domain.as_str(), relation)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Monitored domains whose latest file does not list the seat, or which
+    /// have not been checked yet
+    pub fn not_listed_by(&self) -> Vec<&str> {
+        self.listings
+            .iter()
+            .filter(|(_, listing)| !matches!(listing, Some(Some(_))))
+            .map(|(domain, _)| domain.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publisher_id::IdComparison;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn alerting_on_changes() {
+        let mut watcher = Watcher::new();
+        let alerts = Arc::new(Mutex::new(vec![]));
+
+        let log = |name: &'static str| {
+            let alerts = Arc::clone(&alerts);
+            move |domain: &str, changes: &[Change<'_>]| {
+                for change in changes {
+                    alerts
+                        .lock()
+                        .unwrap()
+                        .push(format!("{} {}: {}", name, domain, change));
+                }
+            }
+        };
+        watcher.on(seat_removed("exchange.com", "1"), log("ours"));
+        let resellers = watcher.on(
+            on_domain("a.com", relation_added(AccountRelation::Reseller)),
+            log("reseller"),
+        );
+        watcher.on(variable_changed("contact"), log("contact"));
+
+        let v1 = AdsTxt::parse("exchange.com, 1, DIRECT\nother.com, 2, DIRECT").unwrap();
+        assert!(watcher.update("www.a.com", v1.clone()).is_empty());
+        assert!(watcher.update("b.com", v1).is_empty());
+        assert!(alerts.lock().unwrap().is_empty());
+
+        let v2 = AdsTxt::parse(
+            "Exchange.com, 1, RESELLER\nother.com, 2, DIRECT\nnew.com, 3, RESELLER\ncontact=x@a.com",
+        )
+        .unwrap();
+        assert_eq!(watcher.update("a.com", v2.clone()).seats.len(), 2);
+        watcher.update("b.com", v2);
+        assert_eq!(
+            *alerts.lock().unwrap(),
+            vec![
+                "ours a.com: ~ exchange.com, 1, DIRECT -> Exchange.com, 1, RESELLER",
+                "reseller a.com: ~ exchange.com, 1, DIRECT -> Exchange.com, 1, RESELLER",
+                "reseller a.com: + new.com, 3, RESELLER",
+                "contact a.com: + contact=x@a.com",
+                "ours b.com: ~ exchange.com, 1, DIRECT -> Exchange.com, 1, RESELLER",
+                "contact b.com: + contact=x@a.com",
+            ]
+        );
+
+        alerts.lock().unwrap().clear();
+        assert!(watcher.remove_alert(resellers));
+        assert!(!watcher.remove_alert(resellers));
+        let v3 = AdsTxt::parse("other.com, 2, DIRECT\nnew.com, 4, RESELLER").unwrap();
+        watcher.update("a.com", v3);
+        assert_eq!(
+            *alerts.lock().unwrap(),
+            vec![
+                "ours a.com: - Exchange.com, 1, RESELLER",
+                "contact a.com: - contact=x@a.com",
+            ]
+        );
+        assert_eq!(watcher.file("A.com").unwrap().records.len(), 2);
+    }
+
+    #[test]
+    fn monitoring_seats() {
+        let day = |n: u64| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(n * 86_400);
+        let mut monitor =
+            SeatMonitor::new("Exchange.com", "42").with_domains(["a.com", "www.b.com"]);
+        monitor.monitor("c.com");
+
+        let listed = AdsTxt::parse("exchange.com, 42, RESELLER").unwrap();
+        let both = AdsTxt::parse("exchange.com, 42, RESELLER\nEXCHANGE.com, 42, DIRECT").unwrap();
+        let other = AdsTxt::parse("exchange.com, 43, DIRECT").unwrap();
+
+        assert_eq!(monitor.update("a.com", &listed, day(1)), None);
+        assert_eq!(monitor.update("b.com", &other, day(1)), None);
+        assert_eq!(monitor.update("d.com", &other, day(1)), None);
+        assert_eq!(
+            monitor.listed_by(),
+            vec![("a.com", &AccountRelation::Reseller)]
+        );
+        assert_eq!(monitor.not_listed_by(), vec!["b.com", "c.com"]);
+
+        assert_eq!(monitor.update("a.com", &listed, day(2)), None);
+        assert_eq!(
+            monitor.update("a.com", &both, day(2)).unwrap().kind,
+            SeatEventKind::RelationChanged {
+                old: AccountRelation::Reseller,
+                new: AccountRelation::Direct
+            }
+        );
+        monitor.update("b.com", &listed, day(3));
+        monitor.update("a.com", &other, day(3));
+
+        let events: Vec<String> = monitor.events().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            events,
+            vec![
+                "a.com: changed from RESELLER to DIRECT",
+                "b.com: appeared as RESELLER",
+                "a.com: DIRECT disappeared",
+            ]
+        );
+        assert_eq!(monitor.events_since(day(3)).count(), 2);
+        assert_eq!(
+            monitor.listed_by(),
+            vec![("b.com", &AccountRelation::Reseller)]
+        );
+
+        assert!(monitor.unmonitor("B.com"));
+        assert_eq!(
+            monitor.domains().collect::<Vec<_>>(),
+            vec!["a.com", "c.com"]
+        );
+
+        let policy = PublisherIdPolicy::new(IdComparison::CaseInsensitive);
+        let mut monitor = SeatMonitor::new("exchange.com", "ab")
+            .with_publisher_id_policy(policy)
+            .with_domains(["a.com"]);
+        monitor.update("a.com", &other, day(1));
+        let listed = AdsTxt::parse("exchange.com, AB, DIRECT").unwrap();
+        assert_eq!(
+            monitor.update("a.com", &listed, day(2)).unwrap().kind,
+            SeatEventKind::Appeared(AccountRelation::Direct)
+        );
+    }
+}